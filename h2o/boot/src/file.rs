@@ -3,6 +3,8 @@
 pub mod elf;
 pub mod tar;
 
+use alloc::string::String;
+
 use uefi::{
     prelude::*,
     proto::{
@@ -40,6 +42,36 @@ pub fn init(img: Handle, syst: &SystemTable<Boot>) {
     }
 }
 
+/// Get the load options of the boot loader image, without the image path
+/// prepended by the UEFI shell.
+pub fn load_options(img: Handle, syst: &SystemTable<Boot>) -> String {
+    let local_img = syst
+        .boot_services()
+        .handle_protocol::<LoadedImage>(img)
+        .expect_success("Failed to locate loaded image protocol");
+
+    let mut buffer = alloc::vec![0; minfo::CMDLINE_MAX];
+    let options = match unsafe { &*local_img.get() }.load_options(&mut buffer) {
+        Ok(options) => options.trim(),
+        Err(err) => {
+            log::warn!("Ignoring the load options: {:?}", err);
+            return String::new();
+        }
+    };
+
+    let mut words = options.splitn(2, char::is_whitespace);
+    match words.next() {
+        Some(path)
+            if path
+                .get(path.len().saturating_sub(4)..)
+                .map_or(false, |ext| ext.eq_ignore_ascii_case(".efi")) =>
+        {
+            words.next().unwrap_or_default().trim_start().into()
+        }
+        _ => options.into(),
+    }
+}
+
 /// Load a file in the local volume.
 ///
 /// # Returns
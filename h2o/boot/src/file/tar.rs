@@ -11,14 +11,20 @@ const BLK_SIZE: usize = 1 << BLK_SHIFT;
 pub struct Files<'a>(Vec<(String, &'a [u8])>);
 
 impl<'a> Files<'a> {
-    pub fn find<S>(&self, name: S) -> &'a [u8]
+    pub fn get<S>(&self, name: S) -> Option<&'a [u8]>
     where
         S: AsRef<str>,
     {
         self.0
             .iter()
-            .find_map(|(nm, data)| (nm.starts_with(name.as_ref())).then_some(data))
-            .expect("Failed to find file")
+            .find_map(|(nm, data)| (nm.starts_with(name.as_ref())).then_some(*data))
+    }
+
+    pub fn find<S>(&self, name: S) -> &'a [u8]
+    where
+        S: AsRef<str>,
+    {
+        self.get(name).expect("Failed to find file")
     }
 }
 
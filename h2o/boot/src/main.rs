@@ -23,6 +23,7 @@ mod mem;
 mod outp;
 mod rxx;
 
+use alloc::string::String;
 use core::mem::MaybeUninit;
 
 use log::*;
@@ -97,8 +98,9 @@ fn efi_main(img: Handle, syst: SystemTable<Boot>) -> Status {
 
     outp::choose_mode(&syst, (1024, 768));
     outp::draw_logo(&syst);
+    let framebuffer = outp::framebuffer(&syst);
 
-    let (entry, pls_layout, tinit, bootfs) = {
+    let (entry, pls_layout, tinit, bootfs, cmdline) = {
         // Load the TAR archive file.
         let tar = file::load(&syst, "\\EFI\\Oceanic\\H2O.k");
         // Get the files.
@@ -120,8 +122,29 @@ fn efi_main(img: Handle, syst: SystemTable<Boot>) -> Status {
 
         let bootfs = unsafe { &*file::realloc_file(&syst, files.find("BOOT.fs")) };
 
+        // The load options take precedence over the file in the archive.
+        let cmdline = {
+            let mut cmdline = file::load_options(img, &syst);
+            if cmdline.is_empty() {
+                if let Some(data) = files.get("CMDLINE") {
+                    cmdline = String::from_utf8_lossy(data).trim().into();
+                }
+            }
+            if cmdline.len() > minfo::CMDLINE_MAX {
+                log::warn!("The kernel command line is truncated");
+                let mut len = minfo::CMDLINE_MAX;
+                while !cmdline.is_char_boundary(len) {
+                    len -= 1;
+                }
+                cmdline.truncate(len);
+            }
+            log::debug!("Kernel command line: {:?}", cmdline);
+            (!cmdline.is_empty())
+                .then(|| unsafe { &*file::realloc_file(&syst, cmdline.as_bytes()) })
+        };
+
         mem::alloc(&syst).dealloc_from_slice(tar, mem::EFI_ID_OFFSET);
-        (h2o_entry, h2o_pls_layout, tinit, bootfs)
+        (h2o_entry, h2o_pls_layout, tinit, bootfs, cmdline)
     };
 
     // Prepare the data needed for H2O.
@@ -131,14 +154,19 @@ fn efi_main(img: Handle, syst: SystemTable<Boot>) -> Status {
     // Get the EFI memory map to be parsed in the kernel. So far we cannot parse it
     // in the loader because if we make dynamic space after get the map, the map
     // will be updated immediately and the key will become invalid.
+    let efi_runtime_map = mem::map_efi_runtime(&syst);
     let (efi_mmap_paddr, mmap_buf) = mem::alloc(&syst)
         .alloc_into_slice(mmap_size_approx * 2, mem::EFI_ID_OFFSET)
         .expect("Failed to allocate memory map buffer");
-    let (_rt, mmap) = syst
+    let (rt, mmap) = syst
         .exit_boot_services(img, unsafe { &mut *mmap_buf })
         .expect_success("Failed to exit EFI boot services");
     let efi_mmap_len = mmap.len();
-    // mem::config_efi_runtime(&rt, mmap);
+    // The pointer in the system table is converted to the virtual address when
+    // switching to the virtual mode, so take the physical one beforehand.
+    let rs_phys = PAddr::new(unsafe { rt.runtime_services() } as *const _ as usize);
+    let efi_runtime =
+        efi_runtime_map.and_then(|buf| mem::config_efi_runtime(&rt, mmap, buf).then(|| rs_phys));
 
     mem::commit_mapping();
 
@@ -154,6 +182,12 @@ fn efi_main(img: Handle, syst: SystemTable<Boot>) -> Status {
             tinit_len: tinit.len(),
             bootfs_phys: paging::LAddr::new(bootfs.as_ptr() as *mut _).to_paddr(mem::EFI_ID_OFFSET),
             bootfs_len: bootfs.len(),
+            cmdline_phys: cmdline.map_or(PAddr::new(0), |cmdline| {
+                paging::LAddr::new(cmdline.as_ptr() as *mut _).to_paddr(mem::EFI_ID_OFFSET)
+            }),
+            cmdline_len: cmdline.map_or(0, |cmdline| cmdline.len()),
+            framebuffer,
+            efi_runtime,
         });
         call_kmain(entry);
     }
@@ -1,9 +1,17 @@
 use core::{arch::asm, ffi::c_void, mem::MaybeUninit, ops::Range, ptr::NonNull};
 
 use bitop_ex::BitOpEx;
-use minfo::{ID_OFFSET as KERNEL_ID_OFFSET, INITIAL_ID_SPACE, KMEM_PHYS_BASE, PF_SIZE};
+use minfo::{
+    EFI_RUNTIME_OFFSET, ID_OFFSET as KERNEL_ID_OFFSET, INITIAL_ID_SPACE, KMEM_PHYS_BASE, PF_SIZE,
+};
 use paging::PageAlloc;
-use uefi::{prelude::*, table::boot};
+use uefi::{
+    prelude::*,
+    table::{
+        boot::{self, MemoryAttribute, MemoryDescriptor, MemoryType},
+        Runtime,
+    },
+};
 
 pub const EFI_ID_OFFSET: usize = 0;
 static mut ROOT_TABLE: MaybeUninit<NonNull<paging::Table>> = MaybeUninit::uninit();
@@ -242,10 +250,71 @@ pub fn get_rsdp_smbios(syst: &SystemTable<Boot>) -> (*const c_void, *const c_voi
     )
 }
 
-// pub fn config_efi_runtime<'a>(
-//       rt: &SystemTable<Runtime>,
-//       mmap: impl ExactSizeIterator<Item = &'a boot::MemoryDescriptor>,
-// ) {
-//       for block in mmap {
-//       }
-// }
+/// Map the memory used by EFI runtime services to the kernel space with
+/// [`EFI_RUNTIME_OFFSET`], and allocate the buffer for their virtual address
+/// map, which must be done before exiting boot services.
+pub fn map_efi_runtime(syst: &SystemTable<Boot>) -> Option<&'static mut [MemoryDescriptor]> {
+    log::trace!("mem::map_efi_runtime: syst = {:?}", syst as *const _);
+
+    let mmap_size = syst.boot_services().memory_map_size();
+    let mut buffer = alloc::vec![0; mmap_size * 6 / 5];
+    let (_key, mmap) = syst
+        .boot_services()
+        .memory_map(&mut buffer)
+        .expect_success("Failed to get the memory map");
+
+    let mut count = 0;
+    for block in mmap.filter(|block| block.att.contains(MemoryAttribute::RUNTIME)) {
+        let attr = match block.ty {
+            MemoryType::RUNTIME_SERVICES_CODE => paging::Attr::KERNEL_RW,
+            MemoryType::MMIO | MemoryType::MMIO_PORT_SPACE => {
+                paging::Attr::KERNEL_RWNE | paging::Attr::CACHE_DISABLE
+            }
+            _ => paging::Attr::KERNEL_RWNE,
+        };
+        let phys = paging::PAddr::new(block.phys_start as usize);
+        let start = EFI_RUNTIME_OFFSET + *phys;
+        let end = start + ((block.page_count as usize) << paging::PAGE_SHIFT);
+        maps(syst, start.into()..end.into(), phys, attr).expect("Failed to map EFI runtime memory");
+        count += 1;
+    }
+    if count == 0 {
+        return None;
+    }
+
+    let (_, buf) = alloc(syst)
+        .alloc_into_slice(
+            count * core::mem::size_of::<MemoryDescriptor>(),
+            EFI_ID_OFFSET,
+        )
+        .expect("Failed to allocate the EFI runtime map");
+    Some(unsafe { core::slice::from_raw_parts_mut(buf.cast(), count) })
+}
+
+/// Switch EFI runtime services to the virtual mode with the mapping set up by
+/// [`map_efi_runtime`], returning whether it succeeds.
+pub fn config_efi_runtime<'a>(
+    rt: &SystemTable<Runtime>,
+    mmap: impl ExactSizeIterator<Item = &'a MemoryDescriptor>,
+    buf: &mut [MemoryDescriptor],
+) -> bool {
+    let mut len = 0;
+    for block in mmap.filter(|block| block.att.contains(MemoryAttribute::RUNTIME)) {
+        // The runtime memory is not supposed to change when exiting boot
+        // services, but don't hand over a partial map if it does.
+        let slot = match buf.get_mut(len) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        *slot = MemoryDescriptor {
+            virt_start: EFI_RUNTIME_OFFSET as u64 + block.phys_start,
+            ..*block
+        };
+        len += 1;
+    }
+
+    unsafe {
+        let rs = rt.runtime_services();
+        rs.set_virtual_address_map(&mut buf[..len]).is_ok()
+    }
+}
@@ -1,6 +1,7 @@
 use alloc::vec::Vec;
 use core::ptr::NonNull;
 
+use minfo::Framebuffer;
 use uefi::{prelude::*, proto::console::gop::*};
 
 static LOGO_FILE: &[u8] = include_bytes!("../../assets/Oceanic.500.bmp");
@@ -89,3 +90,26 @@ pub fn draw_logo(syst: &SystemTable<Boot>) {
         })
         .expect_success("Failed to draw a logo");
 }
+
+/// Get the framebuffer of the current mode to hand over to the kernel.
+pub fn framebuffer(syst: &SystemTable<Boot>) -> Option<Framebuffer> {
+    log::trace!("outp::framebuffer: syst = {:?}", syst as *const _);
+
+    let gop = unsafe { self::gop(syst).as_mut() };
+    let info = gop.current_mode_info();
+    let format = match info.pixel_format() {
+        PixelFormat::Rgb => minfo::PixelFormat::Rgb,
+        PixelFormat::Bgr => minfo::PixelFormat::Bgr,
+        _ => return None,
+    };
+    let (width, height) = info.resolution();
+    let mut fb = gop.frame_buffer();
+    Some(Framebuffer {
+        phys: paging::PAddr::new(fb.as_mut_ptr() as usize),
+        size: fb.size(),
+        width,
+        height,
+        stride: info.stride(),
+        format,
+    })
+}
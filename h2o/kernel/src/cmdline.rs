@@ -0,0 +1,127 @@
+//! The kernel command line passed from the boot loader.
+//!
+//! The command line consists of options separated by whitespace. The kernel
+//! recognizes the following ones:
+//!
+//! - `log=<level>`: the maximum level of the kernel logs, one of `error`,
+//!   `warn`, `info`, `debug` and `trace`.
+//! - `cpus=<n>`: the maximum number of CPUs to start, including the bootstrap
+//!   one.
+//! - `clock=<source>`: the clock source of the kernel, either `tsc` or `hpet`.
+//!   User tasks always read the TSC through the VDSO.
+//! - `gdb`: enable the kernel debugger on the serial port of the logger.
+//!
+//! The other options are forwarded to TINIT, which passes them to `progm` as
+//! its arguments.
+
+use core::{num::NonZeroUsize, str};
+
+use archop::Azy;
+
+static CMDLINE: Azy<Cmdline> = Azy::new(|| {
+    let kargs = crate::kargs();
+    let raw = if kargs.cmdline_len > 0 {
+        // SAFETY: The boot loader reserves the memory for the kernel.
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                *kargs.cmdline_phys.to_laddr(minfo::ID_OFFSET),
+                kargs.cmdline_len,
+            )
+        };
+        str::from_utf8(bytes).ok()
+    } else {
+        Some("")
+    };
+    match raw {
+        Some(raw) => Cmdline::parse(raw),
+        None => Cmdline {
+            invalid: Some("<non-UTF-8 command line>"),
+            ..Cmdline::parse("")
+        },
+    }
+});
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClockSource {
+    Tsc,
+    Hpet,
+}
+
+#[derive(Debug)]
+pub struct Cmdline {
+    raw: &'static str,
+    /// The first option with an invalid value, reported after the logger is
+    /// initialized.
+    invalid: Option<&'static str>,
+
+    pub log_level: log::Level,
+    pub max_cpus: Option<NonZeroUsize>,
+    pub clock: ClockSource,
+    pub gdb: bool,
+}
+
+impl Cmdline {
+    fn parse(raw: &'static str) -> Self {
+        let mut ret = Cmdline {
+            raw,
+            invalid: None,
+            log_level: log::Level::Debug,
+            max_cpus: None,
+            clock: ClockSource::Tsc,
+            gdb: false,
+        };
+        for option in raw.split_whitespace() {
+            let valid = match option.split_once('=') {
+                Some(("log", value)) => value.parse().map(|level| ret.log_level = level).is_ok(),
+                Some(("cpus", value)) => value.parse().map(|max| ret.max_cpus = Some(max)).is_ok(),
+                Some(("clock", "tsc")) => {
+                    ret.clock = ClockSource::Tsc;
+                    true
+                }
+                Some(("clock", "hpet")) => {
+                    ret.clock = ClockSource::Hpet;
+                    true
+                }
+                Some(("clock", _)) => false,
+                None if option == "gdb" => {
+                    ret.gdb = true;
+                    true
+                }
+                _ => true,
+            };
+            if !valid {
+                ret.invalid = ret.invalid.or(Some(option));
+            }
+        }
+        ret
+    }
+
+    #[inline]
+    fn is_kernel_option(option: &str) -> bool {
+        option == "gdb" || matches!(option.split_once('='), Some(("log" | "cpus" | "clock", _)))
+    }
+
+    /// The options not recognized by the kernel, which are forwarded to TINIT.
+    pub fn args(&self) -> impl Iterator<Item = &'static str> {
+        self.raw
+            .split_whitespace()
+            .filter(|option| !Self::is_kernel_option(option))
+    }
+}
+
+#[inline]
+pub fn get() -> &'static Cmdline {
+    &CMDLINE
+}
+
+/// Report the command line, which can be only done after the logger is
+/// initialized.
+pub fn init() {
+    let cmdline = get();
+    if !cmdline.raw.is_empty() {
+        log::info!("Kernel command line: {}", cmdline.raw);
+    }
+    if let Some(option) = cmdline.invalid {
+        log::warn!("Ignoring invalid kernel option {:?}", option);
+    }
+}
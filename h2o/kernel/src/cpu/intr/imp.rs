@@ -1,9 +1,9 @@
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 
 use crossbeam_queue::ArrayQueue;
 use sv_call::Feature;
 
-use super::arch::Manager;
+use super::{arch::Manager, Msi};
 use crate::{
     cpu::time::Instant,
     dev::Resource,
@@ -12,12 +12,27 @@ use crate::{
 
 const MAX_TIMES: usize = 100;
 
+/// A block of MSI vectors, deallocated when all of its interrupts are dropped.
+#[derive(Debug)]
+struct MsiBlock(Msi);
+
+impl Drop for MsiBlock {
+    fn drop(&mut self) {
+        let _ = Manager::deallocate_msi(self.0.clone());
+    }
+}
+
+#[derive(Debug)]
+enum Source {
+    Gsi { gsi: u32, level_triggered: bool },
+    Msi { block: Arc<MsiBlock>, vec: u8 },
+}
+
 #[derive(Debug)]
 pub struct Interrupt {
-    gsi: u32,
+    source: Source,
     cpu: usize,
     last_time: ArrayQueue<Instant>,
-    level_triggered: bool,
     event_data: EventData,
 }
 
@@ -27,8 +42,12 @@ impl Event for Interrupt {
     }
 
     fn wait(&self, waiter: Arc<dyn crate::sched::Waiter>) {
-        if self.level_triggered {
-            Manager::mask(self.gsi, false).unwrap();
+        if let Source::Gsi {
+            gsi,
+            level_triggered: true,
+        } = self.source
+        {
+            Manager::mask(gsi, false).unwrap();
         }
         self.wait_impl(waiter);
     }
@@ -38,10 +57,17 @@ impl Event for Interrupt {
 
         let signal = self.notify_impl(clear, set);
 
-        if self.level_triggered {
-            Manager::mask(self.gsi, true).unwrap();
+        // MSIs are always edge-triggered and acknowledged by the local APIC.
+        if let Source::Gsi {
+            gsi,
+            level_triggered,
+        } = self.source
+        {
+            if level_triggered {
+                Manager::mask(gsi, true).unwrap();
+            }
+            Manager::eoi(gsi).unwrap();
         }
-        Manager::eoi(self.gsi).unwrap();
         signal
     }
 }
@@ -56,10 +82,12 @@ impl Interrupt {
     ) -> sv_call::Result<Arc<Self>> {
         if res.magic_eq(super::gsi_resource()) && res.range().contains(&gsi) {
             Ok(Arc::try_new(Interrupt {
-                gsi,
+                source: Source::Gsi {
+                    gsi,
+                    level_triggered,
+                },
                 cpu,
                 last_time: ArrayQueue::new(MAX_TIMES),
-                level_triggered,
                 event_data: EventData::new(0),
             })?)
         } else {
@@ -67,21 +95,61 @@ impl Interrupt {
         }
     }
 
+    /// Allocate `count` MSI vectors on `cpu`, each of which is represented by
+    /// an interrupt object.
+    ///
+    /// Allocating MSIs requires the root GSI resource.
+    pub fn new_msi(res: &Resource<u32>, count: u8, cpu: usize) -> sv_call::Result<Vec<Arc<Self>>> {
+        if !res.magic_eq(super::gsi_resource()) {
+            return Err(sv_call::EPERM);
+        }
+        if count == 0 || cpu >= crate::cpu::count() {
+            return Err(sv_call::EINVAL);
+        }
+        let block = Arc::try_new(MsiBlock(Manager::allocate_msi(count, cpu)?))?;
+
+        let mut ret = Vec::with_capacity(count as usize);
+        for vec in block.0.vecs.clone().take(count as usize) {
+            let intr = Arc::try_new(Interrupt {
+                source: Source::Msi {
+                    block: Arc::clone(&block),
+                    vec,
+                },
+                cpu,
+                last_time: ArrayQueue::new(MAX_TIMES),
+                event_data: EventData::new(0),
+            })?;
+            Manager::register_msi(&block.0, vec, (handler, Arc::as_ptr(&intr) as *mut u8))?;
+            ret.push(intr);
+        }
+        Ok(ret)
+    }
+
     #[inline]
     pub fn last_time(&self) -> Option<Instant> {
         self.last_time.pop()
     }
 
+    /// The address and data to be programmed into the device for an MSI.
     #[inline]
-    pub fn gsi(&self) -> u32 {
-        self.gsi
+    pub fn msi_target(&self) -> Option<(u32, u32)> {
+        match self.source {
+            Source::Gsi { .. } => None,
+            Source::Msi { ref block, vec } => Some((
+                block.0.target_address,
+                block.0.target_data + u32::from(vec - block.0.vecs.start),
+            )),
+        }
     }
 }
 
 impl Drop for Interrupt {
     fn drop(&mut self) {
         self.cancel();
-        let _ = Manager::deregister(self.gsi, self.cpu);
+        let _ = match self.source {
+            Source::Gsi { gsi, .. } => Manager::deregister(gsi, self.cpu),
+            Source::Msi { ref block, vec } => Manager::deregister_msi(&block.0, vec),
+        };
     }
 }
 
@@ -99,7 +167,10 @@ fn handler(arg: *mut u8) {
 mod syscall {
     use alloc::sync::Arc;
 
-    use sv_call::{res::IntrConfig, *};
+    use sv_call::{
+        res::{IntrConfig, MsiVector},
+        *,
+    };
 
     use super::*;
     use crate::{
@@ -138,6 +209,42 @@ mod syscall {
         SCHED.with_current(|cur| unsafe { cur.space().handles().insert_raw(intr, Some(event)) })
     }
 
+    #[syscall]
+    fn intr_msi(res: Handle, cpu: u32, vecs: UserPtr<Out, MsiVector>, count: usize) -> Result {
+        let count = u8::try_from(count).map_err(|_| EINVAL)?;
+        vecs.check_slice(count as usize)?;
+
+        let intrs = SCHED.with_current(|cur| {
+            let res = cur.space().handles().get::<Resource<u32>>(res)?;
+            Interrupt::new_msi(&res, count, cpu as usize)
+        })?;
+
+        SCHED.with_current(|cur| {
+            let handles = cur.space().handles();
+            let mut out = Vec::with_capacity(intrs.len());
+            for intr in intrs {
+                let (address, data) = intr.msi_target().unwrap();
+                let event = Arc::downgrade(&intr) as _;
+                match handles.insert_raw(intr, Some(event)) {
+                    Ok(hdl) => out.push(MsiVector {
+                        intr: hdl,
+                        address,
+                        data,
+                    }),
+                    Err(err) => {
+                        out.iter()
+                            .for_each(|vec| drop(handles.remove::<Interrupt>(vec.intr)));
+                        return Err(err);
+                    }
+                }
+            }
+            vecs.write_slice(&out).inspect_err(|_| {
+                out.iter()
+                    .for_each(|vec| drop(handles.remove::<Interrupt>(vec.intr)))
+            })
+        })
+    }
+
     #[syscall]
     fn intr_query(hdl: Handle, last_time: UserPtr<Out, u128>) -> Result {
         hdl.check_null()?;
@@ -6,7 +6,7 @@ use core::{
     time::Duration,
 };
 
-pub use self::timer::{tick as timer_tick, Timer};
+pub use self::timer::{tick as timer_tick, Timer, TimerEvent};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(transparent)]
@@ -129,4 +129,11 @@ mod syscall {
         ptr.write(unsafe { super::Instant::now().raw() })?;
         Ok(())
     }
+
+    #[syscall]
+    fn time_utc(ptr: UserPtr<Out, time::UtcTime>) -> Result {
+        ptr.check()?;
+        let time = crate::dev::rtc::utc_time().ok_or(ESPRT)?;
+        ptr.write(time)
+    }
 }
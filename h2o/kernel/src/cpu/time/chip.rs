@@ -3,12 +3,27 @@ use core::sync::atomic::Ordering::Release;
 use archop::Azy;
 
 use super::Instant;
-use crate::{cpu::arch::tsc::TSC_CLOCK, dev::hpet::HPET_CLOCK};
+use crate::{cmdline::ClockSource, cpu::arch::tsc::TSC_CLOCK, dev::hpet::HPET_CLOCK};
 
 pub static CLOCK: Azy<&'static dyn ClockChip> = Azy::new(|| {
-    let ret: &crate::cpu::arch::tsc::TscClock = &TSC_CLOCK;
+    // The TSC is always calibrated for the VDSO, which stops the HPET, so it
+    // must be done before the HPET is chosen.
+    let tsc: &crate::cpu::arch::tsc::TscClock = &TSC_CLOCK;
+    let ret: &'static dyn ClockChip = match crate::cmdline::get().clock {
+        ClockSource::Tsc => tsc,
+        ClockSource::Hpet => match HPET_CLOCK.as_ref() {
+            Some(hpet) => {
+                hpet.start();
+                hpet
+            }
+            None => {
+                log::warn!("No HPET available, falling back to the TSC");
+                tsc
+            }
+        },
+    };
     crate::logger::HAS_TIME.store(true, Release);
-    ret as _
+    ret
 });
 
 static CALIB_CLOCK: Azy<&'static dyn CalibrationClock> =
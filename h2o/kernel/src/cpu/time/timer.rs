@@ -9,6 +9,7 @@ use core::{
 use spin::RwLock;
 use sv_call::ipc::SIG_TIMER;
 
+pub use self::syscall::TimerEvent;
 use super::Instant;
 use crate::sched::{ipc::Arsc, task, Event, PREEMPT, SCHED};
 
@@ -203,7 +204,7 @@ mod syscall {
     };
 
     #[derive(Debug, Default)]
-    struct TimerEvent {
+    pub struct TimerEvent {
         event_data: EventData,
         timer: Mutex<Option<Arsc<Timer>>>,
     }
@@ -230,7 +231,7 @@ mod syscall {
 
     unsafe impl DefaultFeature for TimerEvent {
         fn default_features() -> sv_call::Feature {
-            Feature::SEND | Feature::SYNC | Feature::WAIT | Feature::WRITE
+            Feature::SEND | Feature::SYNC | Feature::WAIT | Feature::WRITE | Feature::DUPLICATE
         }
     }
 
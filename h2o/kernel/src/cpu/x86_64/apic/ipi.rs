@@ -210,3 +210,40 @@ pub unsafe fn task_migrate(cpu: usize) {
         None => log::warn!("CPU #{} not present", cpu),
     };
 }
+
+/// Stop all the other CPUs with NMIs for the kernel debugger.
+///
+/// # Safety
+///
+/// This function must be called only by the kernel debugger after the Local
+/// APIC of the current CPU is initialized.
+pub unsafe fn stop_others() {
+    lapic(|lapic| lapic.send_ipi(0, DelivMode::Nmi, Shorthand::Others, 0));
+}
+
+/// Notify `cpu` to handle the pending TLB shootdown requests in its inbox.
+///
+/// Returns `false` if the CPU is not present and thus will never respond.
+///
+/// # Safety
+///
+/// This function must be called only by the initiator of a TLB shootdown.
+pub unsafe fn tlb_shootdown(cpu: usize) -> bool {
+    match PREEMPT.scope(|| super::LAPIC_ID.read().get(&cpu).copied()) {
+        Some(id) => {
+            lapic(|lapic| {
+                lapic.send_ipi(
+                    intr::def::ApicVec::IpiTlbShootdown as u8,
+                    DelivMode::Fixed,
+                    Shorthand::None,
+                    id,
+                )
+            });
+            true
+        }
+        None => {
+            log::warn!("CPU #{} not present", cpu);
+            false
+        }
+    }
+}
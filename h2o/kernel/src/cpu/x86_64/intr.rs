@@ -6,7 +6,10 @@ use core::{
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-use archop::Azy;
+use archop::{
+    reg::{dr6, rflags},
+    Azy,
+};
 use array_macro::array;
 use collection_ex::RangeMap;
 use spin::Mutex;
@@ -154,7 +157,10 @@ impl Manager {
         let apic_id = *LAPIC_ID.read().get(&cpu).ok_or(sv_call::EINVAL)?;
 
         let start = PREEMPT.scope(|| {
-            manager.map.lock().allocate_with(
+            // The vectors of multiple messages differ only in the lowest
+            // bits of the data, so the first one is aligned to their number.
+            manager.map.lock().allocate_aligned_with(
+                num_vec,
                 num_vec,
                 |_| {
                     manager.count.fetch_add(num_vec as usize, Ordering::SeqCst);
@@ -172,9 +178,40 @@ impl Manager {
         })
     }
 
+    pub fn register_msi(msi: &Msi, vec: u8, handler: (IntrHandler, *mut u8)) -> sv_call::Result {
+        if !msi.vecs.contains(&vec) {
+            return Err(sv_call::EINVAL);
+        }
+        let manager = MANAGER.get(msi.cpu).ok_or(sv_call::ENODEV)?;
+        PREEMPT.scope(|| match &mut *manager.slots[vec as usize].lock() {
+            Some(_) => Err(sv_call::EEXIST),
+            slot => {
+                *slot = Some(handler);
+                Ok(())
+            }
+        })
+    }
+
+    pub fn deregister_msi(msi: &Msi, vec: u8) -> sv_call::Result {
+        if !msi.vecs.contains(&vec) {
+            return Err(sv_call::EINVAL);
+        }
+        let manager = MANAGER.get(msi.cpu).ok_or(sv_call::ENODEV)?;
+        PREEMPT.scope(|| manager.slots[vec as usize].lock().take());
+        Ok(())
+    }
+
     pub fn deallocate_msi(msi: Msi) -> sv_call::Result {
         let manager = MANAGER.get(msi.cpu).ok_or(sv_call::ENODEV)?;
-        PREEMPT.scope(|| manager.map.lock().remove(msi.vecs.start));
+        PREEMPT.scope(|| {
+            let mut lock = manager.map.lock();
+            for vec in msi.vecs.clone() {
+                *manager.slots[vec as usize].lock() = None;
+            }
+            if lock.remove(msi.vecs.start).is_some() {
+                manager.count.fetch_sub(msi.vecs.len(), Ordering::SeqCst);
+            }
+        });
         Ok(())
     }
 }
@@ -205,9 +242,52 @@ unsafe fn exception(frame_ptr: *mut Frame, vec: def::ExVec) {
         return;
     }
 
+    if vec == Nmi {
+        crate::gdb::handle_exception(frame, vec);
+        return;
+    }
+
+    let from_user = frame.cs == USR_CODE_X64.into_val().into();
+    if vec == Debug {
+        let status = dr6::read();
+        dr6::write(dr6::CLEAR);
+        // Data breakpoints can be hit by the kernel when it accesses user
+        // memory on behalf of the task, and are simply ignored. Single steps
+        // in the kernel are requested by the kernel debugger.
+        if !from_user {
+            if status & dr6::BS != 0 {
+                crate::gdb::handle_exception(frame, vec);
+            }
+            return;
+        }
+        let _ = SCHED.with_current(|cur| {
+            cur.debug_regs_mut().set_status(status);
+            Ok(())
+        });
+        // Single-stepping is requested one instruction at a time.
+        if status & dr6::BS != 0 {
+            frame.rflags &= !rflags::TF;
+        }
+    }
+
     match SCHED.with_current(|cur| Ok(cur.tid().ty())) {
-        Ok(task::Type::User) if frame.cs == USR_CODE_X64.into_val().into() => {
-            if !task::dispatch_exception(frame, vec) {
+        Ok(task::Type::User) if from_user => {
+            if task::dispatch_exception(frame, vec) {
+                // Instruction breakpoints are faults, so they must be skipped
+                // once to make progress.
+                let hit_instruction = SCHED
+                    .with_current(|cur| Ok(vec == Debug && cur.debug_regs().hit_instruction()));
+                if matches!(hit_instruction, Ok(true)) {
+                    frame.rflags |= rflags::RF;
+                }
+                // Give the debugger a chance to suspend the task before it
+                // goes on.
+                SCHED.handle_signal();
+            } else {
+                // The task may have been woken up from waiting for the handler
+                // because it's killed.
+                SCHED.handle_signal();
+
                 #[cfg(debug_assertions)]
                 {
                     let _ = SCHED.with_current(|cur| {
@@ -224,22 +304,31 @@ unsafe fn exception(frame_ptr: *mut Frame, vec: def::ExVec) {
                     });
                 }
                 // Kill the fucking task.
-                SCHED.exit_current(sv_call::EFAULT.into_retval(), true)
+                let info = task::exception_exit_info(frame, vec);
+                SCHED.exit_current(info, true)
             }
             // unreachable!()
         }
         _ => {}
     }
 
-    // No more available remedies. Die.
-    log::error!("{:?} in the kernel", vec);
+    // Report the fault before the debugger takes over, which may never resume.
+    // Breakpoints are requests for the debugger rather than faults.
+    if vec != Breakpoint || !crate::gdb::enabled() {
+        log::error!("{:?} in the kernel", vec);
+
+        frame.dump(if vec == PageFault {
+            PageFaultErrCode::FMT
+        } else {
+            Frame::ERRC
+        });
+    }
 
-    frame.dump(if vec == PageFault {
-        PageFaultErrCode::FMT
-    } else {
-        Frame::ERRC
-    });
+    if crate::gdb::handle_exception(frame, vec) {
+        return;
+    }
 
+    // No more available remedies. Die.
     archop::halt_loop(Some(false));
 }
 
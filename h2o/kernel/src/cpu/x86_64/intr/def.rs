@@ -62,6 +62,7 @@ pub enum ApicVec {
     Timer = 0x20,
     Error = 0x21,
     IpiTaskMigrate = 0x22,
+    IpiTlbShootdown = 0x23,
     Spurious = 0xFF,
 }
 
@@ -131,7 +132,7 @@ pub static IDT_INIT: &[IdtInit] = &[
     single_ent!(ExVec::DivideBy0, div_0, 0, 0),
     single_ent!(ExVec::Debug, debug, 0, 0),
     single_ent!(ExVec::Nmi, nmi, 0, 0),
-    single_ent!(ExVec::Breakpoint, breakpoint, 0, 0),
+    single_ent!(ExVec::Breakpoint, breakpoint, 0, 3),
     single_ent!(ExVec::Overflow, overflow, 0, 3),
     single_ent!(ExVec::Bound, bound, 0, 0),
     single_ent!(ExVec::InvalidOp, invalid_op, 0, 0),
@@ -151,6 +152,7 @@ pub static IDT_INIT: &[IdtInit] = &[
     single_ent!(ApicVec::Timer, lapic_timer, 0, 0),
     single_ent!(ApicVec::Error, lapic_error, 0, 0),
     single_ent!(ApicVec::IpiTaskMigrate, lapic_ipi_task_migrate, 0, 0),
+    single_ent!(ApicVec::IpiTlbShootdown, lapic_ipi_tlb_shootdown, 0, 0),
     single_ent!(ApicVec::Spurious, lapic_spurious, 0, 0),
     // All other allocable interrupts
     Multiple(repeat::repeat! {"&[" for i in 0x40..0xFF {
@@ -194,6 +196,10 @@ hdl!(lapic_ipi_task_migrate, |_frame| {
     crate::sched::task_migrate_handler();
 });
 
+hdl!(lapic_ipi_tlb_shootdown, |_frame| {
+    crate::mem::space::tlb_shootdown_handler();
+});
+
 hdl!(lapic_spurious, |_frame| {
     crate::cpu::arch::apic::spurious_handler();
 });
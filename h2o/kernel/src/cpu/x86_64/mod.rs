@@ -154,7 +154,12 @@ pub unsafe fn init() {
             .processor_info
             .as_ref()
             .expect("Failed to get LAPIC data");
-        apic::ipi::start_cpus(&lapic_data.application_processors)
+        let aps = &lapic_data.application_processors;
+        let aps = match crate::cmdline::get().max_cpus {
+            Some(max) => &aps[..aps.len().min(max.get() - 1)],
+            None => aps,
+        };
+        apic::ipi::start_cpus(aps)
     };
     CPU_COUNT.store(cnt + 1, Ordering::SeqCst);
     intr::init();
@@ -1,5 +1,7 @@
 pub mod acpi;
+mod efi;
 mod res;
+pub mod rtc;
 
 cfg_if::cfg_if! {
     if #[cfg(target_arch = "x86_64")] {
@@ -21,6 +23,10 @@ static PIO_RESOURCE: Azy<Arc<Resource<u16>>> = Azy::new(|| {
         ret.allocate(crate::logger::COM_LOG..(crate::logger::COM_LOG + 1))
             .expect("Failed to reserve debug port"),
     );
+    core::mem::forget(
+        ret.allocate(cmos::PORTS)
+            .expect("Failed to reserve CMOS ports"),
+    );
     ret
 });
 
@@ -35,9 +41,30 @@ pub fn pio_resource() -> &'static Arc<Resource<u16>> {
 #[inline]
 pub unsafe fn init() {
     Azy::force(&PIO_RESOURCE);
+    rtc::test();
     unsafe { x86_64::init_intr_chip() };
 }
 
+/// Reset or power off the platform with EFI runtime services, or with ACPI if
+/// they fail or are not available. Returns only if both fail.
+fn reset(ty: u32) {
+    use uefi::table::runtime::ResetType;
+
+    let efi_ty = match ty {
+        sv_call::res::RESET_COLD => ResetType::Cold,
+        sv_call::res::RESET_WARM => ResetType::Warm,
+        _ => ResetType::Shutdown,
+    };
+    log::info!("Resetting the platform ({:?})", efi_ty);
+    efi::reset(efi_ty);
+    if ty == sv_call::res::RESET_SHUTDOWN {
+        acpi::shutdown();
+    } else {
+        acpi::reset();
+    }
+    log::warn!("Failed to reset the platform");
+}
+
 mod syscall {
     use bitvec::bitvec;
     use sv_call::*;
@@ -45,6 +72,25 @@ mod syscall {
     use super::*;
     use crate::{cpu::arch::KERNEL_GS, sched::SCHED};
 
+    #[syscall]
+    fn sys_reset(res: Handle, ty: u32) -> Result {
+        use sv_call::res::{RESET_COLD, RESET_SHUTDOWN, RESET_WARM};
+        if !matches!(ty, RESET_COLD | RESET_WARM | RESET_SHUTDOWN) {
+            return Err(EINVAL);
+        }
+        // Only the holder of the whole memory resource controls the platform.
+        SCHED.with_current(|cur| {
+            let res = cur.space().handles().get::<Resource<usize>>(res)?;
+            if res.magic_eq(mem_resource()) && res.parent().is_none() {
+                Ok(())
+            } else {
+                Err(EPERM)
+            }
+        })?;
+        reset(ty);
+        Err(ESPRT)
+    }
+
     #[syscall]
     fn pio_acq(res: Handle, base: u16, size: u16) -> Result {
         SCHED.with_current(|cur| {
@@ -58,7 +104,7 @@ mod syscall {
             {
                 drop(res);
                 let io_bitmap = cur.io_bitmap_mut().get_or_insert_with(|| bitvec![1; 65536]);
-                io_bitmap[(base as usize)..(size as usize)].fill(false);
+                io_bitmap[(base as usize)..(base as usize + size as usize)].fill(false);
                 unsafe { KERNEL_GS.update_tss_io_bitmap(cur.io_bitmap_mut().as_deref()) };
                 Ok(())
             } else {
@@ -77,7 +123,7 @@ mod syscall {
             {
                 drop(res);
                 if let Some(io_bitmap) = cur.io_bitmap_mut() {
-                    io_bitmap[(base as usize)..(size as usize)].fill(true);
+                    io_bitmap[(base as usize)..(base as usize + size as usize)].fill(true);
                 }
                 unsafe { KERNEL_GS.update_tss_io_bitmap(cur.io_bitmap_mut().as_deref()) };
                 Ok(())
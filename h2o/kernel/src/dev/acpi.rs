@@ -1,4 +1,10 @@
-use archop::Azy;
+use core::time::Duration;
+
+use acpi::platform::address::{AddressSpace, GenericAddress};
+use archop::{
+    io::{Io, Port},
+    Azy,
+};
 use paging::PAddr;
 
 #[derive(Debug, Clone)]
@@ -37,3 +43,182 @@ pub fn tables() -> &'static acpi::AcpiTables<Handler> {
 pub fn platform_info() -> &'static acpi::PlatformInfo {
     &PLATFORM_INFO
 }
+
+/// Get the ECAM window of PCI segment group 0 as `(base, bus_start, bus_end)`,
+/// or `None` if the platform has no MCFG table.
+pub fn pci_ecam() -> Option<(usize, u8, u8)> {
+    let regions = acpi::mcfg::PciConfigRegions::new(tables()).ok()?;
+    let mut buses = (0..=u8::MAX).filter(|&bus| regions.physical_address(0, bus, 0, 0).is_some());
+    let bus_start = buses.next()?;
+    let bus_end = buses.last().unwrap_or(bus_start);
+    let base = regions.physical_address(0, bus_start, 0, 0)?;
+    Some((base as usize, bus_start, bus_end))
+}
+
+pub fn fadt() -> Option<acpi::PhysicalMapping<Handler, acpi::fadt::Fadt>> {
+    unsafe { tables().get_sdt::<acpi::fadt::Fadt>(acpi::sdt::Signature::FADT) }
+        .ok()
+        .flatten()
+}
+
+/// Read from a register described by a generic address, returning `None` if
+/// the address space is not supported.
+fn read_register(reg: &GenericAddress) -> Option<u64> {
+    Some(match reg.address_space {
+        AddressSpace::SystemIo => {
+            let port = reg.address as u16;
+            // SAFETY: The port is given by the firmware.
+            unsafe {
+                match reg.bit_width {
+                    16 => Port::<u16>::new(port).read().into(),
+                    32 => Port::<u32>::new(port).read().into(),
+                    _ => Port::<u8>::new(port).read().into(),
+                }
+            }
+        }
+        AddressSpace::SystemMemory => {
+            let ptr = *PAddr::new(reg.address as usize).to_laddr(minfo::ID_OFFSET);
+            // SAFETY: The address is given by the firmware.
+            unsafe {
+                match reg.bit_width {
+                    16 => ptr.cast::<u16>().read_volatile().into(),
+                    32 => ptr.cast::<u32>().read_volatile().into(),
+                    64 => ptr.cast::<u64>().read_volatile(),
+                    _ => ptr.read_volatile().into(),
+                }
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// Write to a register described by a generic address, returning whether the
+/// address space is supported.
+fn write_register(reg: &GenericAddress, value: u64) -> bool {
+    match reg.address_space {
+        AddressSpace::SystemIo => {
+            let port = reg.address as u16;
+            // SAFETY: The port is given by the firmware.
+            unsafe {
+                match reg.bit_width {
+                    16 => Port::<u16>::new(port).write(value as u16),
+                    32 => Port::<u32>::new(port).write(value as u32),
+                    _ => Port::<u8>::new(port).write(value as u8),
+                }
+            }
+        }
+        AddressSpace::SystemMemory => {
+            let ptr = *PAddr::new(reg.address as usize).to_laddr(minfo::ID_OFFSET);
+            // SAFETY: The address is given by the firmware.
+            unsafe {
+                match reg.bit_width {
+                    16 => ptr.cast::<u16>().write_volatile(value as u16),
+                    32 => ptr.cast::<u32>().write_volatile(value as u32),
+                    64 => ptr.cast::<u64>().write_volatile(value),
+                    _ => ptr.write_volatile(value as u8),
+                }
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Reset the platform with the reset register in the FADT, returning only if
+/// it is not supported.
+pub fn reset() {
+    if let Some(fadt) = fadt() {
+        if let Ok(reg) = fadt.reset_register() {
+            if fadt.supports_system_reset_via_fadt()
+                && write_register(&reg, fadt.reset_value.into())
+            {
+                crate::cpu::time::delay(Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+/// Find the sleep types of the S5 state (soft off) in the DSDT.
+///
+/// Instead of evaluating the AML, the package of `\_S5` is matched directly,
+/// which is like `Name (_S5, Package () { SLP_TYPa, SLP_TYPb, ... })`.
+fn s5_sleep_types() -> Option<(u16, u16)> {
+    const NAME_OP: u8 = 0x08;
+    const PACKAGE_OP: u8 = 0x12;
+    const ZERO_OP: u8 = 0x00;
+    const ONE_OP: u8 = 0x01;
+    const BYTE_PREFIX: u8 = 0x0a;
+
+    let dsdt = tables().dsdt.as_ref()?;
+    // SAFETY: The table is given by the firmware.
+    let aml = unsafe {
+        core::slice::from_raw_parts(
+            *PAddr::new(dsdt.address).to_laddr(minfo::ID_OFFSET),
+            dsdt.length as usize,
+        )
+    };
+
+    let pos = aml.windows(4).position(|name| name == b"_S5_")?;
+    let name_op = match aml[..pos] {
+        [.., op, b'\\'] | [.., op] => op,
+        [] => return None,
+    };
+    if name_op != NAME_OP {
+        return None;
+    }
+
+    let mut rest = aml.get((pos + 4)..)?;
+    if *rest.first()? != PACKAGE_OP {
+        return None;
+    }
+    // Skip the package length, whose size is encoded in its first byte, and
+    // the number of elements.
+    let pkg_len_size = usize::from(*rest.get(1)? >> 6) + 1;
+    rest = rest.get((1 + pkg_len_size + 1)..)?;
+
+    let mut next = || {
+        let (value, len) = match *rest.first()? {
+            ZERO_OP => (0, 1),
+            ONE_OP => (1, 1),
+            BYTE_PREFIX => (u16::from(*rest.get(1)?), 2),
+            _ => return None,
+        };
+        rest = &rest[len..];
+        Some(value)
+    };
+    Some((next()?, next()?))
+}
+
+/// Enter the S5 state with the PM1 control registers, returning only if it
+/// fails.
+pub fn shutdown() {
+    const SLP_TYP_SHIFT: u32 = 10;
+    const SLP_TYP_MASK: u64 = 0b111 << SLP_TYP_SHIFT;
+    const SLP_EN: u64 = 1 << 13;
+
+    // The other bits such as `SCI_EN` must be preserved.
+    let enter = |reg: &GenericAddress, typ: u16| match read_register(reg) {
+        Some(value) => {
+            let typ = (u64::from(typ) << SLP_TYP_SHIFT) & SLP_TYP_MASK;
+            let value = (value & !SLP_TYP_MASK) | typ;
+            write_register(reg, value | SLP_EN)
+        }
+        None => false,
+    };
+
+    let (fadt, (typ_a, typ_b)) = match fadt().zip(s5_sleep_types()) {
+        Some(ret) => ret,
+        None => return,
+    };
+    let pm1a = match fadt.pm1a_control_block() {
+        Ok(reg) => reg,
+        Err(_) => return,
+    };
+    if !enter(&pm1a, typ_a) {
+        return;
+    }
+    if let Ok(Some(pm1b)) = fadt.pm1b_control_block() {
+        enter(&pm1b, typ_b);
+    }
+    crate::cpu::time::delay(Duration::from_secs(1));
+}
@@ -0,0 +1,52 @@
+//! EFI runtime services, switched to the virtual mode by the boot loader with
+//! their memory mapped at [`minfo::EFI_RUNTIME_OFFSET`].
+
+use archop::Azy;
+use spin::Mutex;
+use sv_call::time::UtcTime;
+use uefi::{
+    table::runtime::{ResetType, RuntimeServices},
+    Status,
+};
+
+use super::rtc::DateTime;
+use crate::sched::PREEMPT;
+
+/// The services are not reentrant, so the calls must be serialized.
+static RUNTIME: Azy<Option<Mutex<&'static RuntimeServices>>> = Azy::new(|| {
+    let phys = crate::kargs().efi_runtime?;
+    // SAFETY: The table lives in the runtime memory reserved by the firmware.
+    let rs = unsafe { &*phys.to_laddr(minfo::ID_OFFSET).cast::<RuntimeServices>() };
+    Some(Mutex::new(rs))
+});
+
+/// Get the wall-clock time from the firmware.
+pub fn utc_time() -> Option<UtcTime> {
+    let rs = RUNTIME.as_ref()?;
+    let time = PREEMPT.scope(|| rs.lock().get_time()).ok()?.log();
+    let local = DateTime {
+        year: time.year(),
+        month: time.month(),
+        day: time.day(),
+        hour: time.hour(),
+        minute: time.minute(),
+        second: time.second(),
+    }
+    .unix_secs()?;
+    // The time zone is the offset from UTC in minutes. A time without it is
+    // regarded as UTC.
+    let offset = i64::from(time.time_zone().unwrap_or(0)) * 60;
+    Some(UtcTime {
+        secs: u64::try_from(local as i64 - offset).ok()?,
+        nanos: time.nanosecond(),
+    })
+}
+
+/// Reset the platform through the firmware, returning only if the services
+/// are not available.
+pub fn reset(ty: ResetType) {
+    if let Some(rs) = RUNTIME.as_ref() {
+        let _pree = PREEMPT.lock();
+        rs.lock().reset(ty, Status::SUCCESS, None)
+    }
+}
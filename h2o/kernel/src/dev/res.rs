@@ -5,28 +5,34 @@ use collection_ex::RangeMap;
 use spin::Mutex;
 use sv_call::Feature;
 
-use crate::sched::{task::hdl::DefaultFeature, PREEMPT};
+use crate::sched::{
+    task::hdl::{DefaultFeature, Koid},
+    PREEMPT,
+};
 
 pub struct Resource<T: Ord + Copy> {
+    koid: Koid,
     magic: u64,
     range: Range<T>,
     map: Mutex<RangeMap<T, ()>>,
-    parent: Option<Weak<Resource<T>>>,
+    parent: Option<(Weak<Resource<T>>, Koid)>,
 }
 
 impl<T: Ord + Copy> Resource<T> {
     #[inline]
-    fn new(magic: u64, range: Range<T>, parent: Weak<Resource<T>>) -> Arc<Self> {
+    fn new(magic: u64, range: Range<T>, parent: &Arc<Resource<T>>) -> Arc<Self> {
         Arc::new(Resource {
+            koid: Koid::new(),
             magic,
             range: range.clone(),
             map: Mutex::new(RangeMap::new(range)),
-            parent: Some(parent),
+            parent: Some((Arc::downgrade(parent), parent.koid)),
         })
     }
 
     pub fn new_root(magic: u64, range: Range<T>) -> Arc<Self> {
         Arc::new(Resource {
+            koid: Koid::new(),
             magic,
             range: range.clone(),
             map: Mutex::new(RangeMap::new(range)),
@@ -39,14 +45,29 @@ impl<T: Ord + Copy> Resource<T> {
         self.range.clone()
     }
 
+    #[inline]
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<&Weak<Resource<T>>> {
+        self.parent.as_ref().map(|(parent, _)| parent)
+    }
+
+    #[inline]
+    pub fn parent_koid(&self) -> Option<Koid> {
+        self.parent.as_ref().map(|&(_, koid)| koid)
+    }
+
     #[must_use]
     pub fn allocate(self: &Arc<Self>, range: Range<T>) -> Option<Arc<Self>> {
-        if self.parent.as_ref().map_or(true, |p| p.strong_count() >= 1) {
+        if self.parent().map_or(true, |p| p.strong_count() >= 1) {
             PREEMPT.scope(|| {
                 let mut map = self.map.lock();
                 map.try_insert_with(
                     range.clone(),
-                    || Ok::<_, ()>(((), Self::new(self.magic, range, Arc::downgrade(self)))),
+                    || Ok::<_, ()>(((), Self::new(self.magic, range, self))),
                     (),
                 )
                 .ok()
@@ -65,7 +86,7 @@ impl<T: Ord + Copy> Resource<T> {
 
 impl<T: Ord + Copy> Drop for Resource<T> {
     fn drop(&mut self) {
-        if let Some(parent) = self.parent.as_ref().and_then(Weak::upgrade) {
+        if let Some(parent) = self.parent().and_then(Weak::upgrade) {
             let _ = PREEMPT.scope(|| parent.map.lock().remove(self.range.start));
         }
     }
@@ -73,7 +94,7 @@ impl<T: Ord + Copy> Drop for Resource<T> {
 
 unsafe impl<T: Ord + Copy + Send + Sync + Any> DefaultFeature for Resource<T> {
     fn default_features() -> Feature {
-        Feature::SEND | Feature::SYNC | Feature::READ | Feature::WRITE
+        Feature::SEND | Feature::SYNC | Feature::READ | Feature::WRITE | Feature::DUPLICATE
     }
 }
 
@@ -0,0 +1,117 @@
+//! The wall-clock time of the platform.
+
+use sv_call::time::UtcTime;
+
+/// A calendar date and time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    fn days_in_month(&self) -> u8 {
+        let leap = self.year % 4 == 0 && (self.year % 100 != 0 || self.year % 400 == 0);
+        match self.month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// The number of days from 1970-01-01 to the date in the proleptic
+    /// Gregorian calendar.
+    fn days_from_epoch(&self) -> i64 {
+        let (month, day) = (i64::from(self.month), i64::from(self.day));
+        // Count the years from March so that the leap day comes last.
+        let year = i64::from(self.year) - i64::from(month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    /// The number of seconds from the UNIX epoch, or `None` if the date is
+    /// invalid or before the epoch.
+    pub fn unix_secs(&self) -> Option<u64> {
+        let valid = (1..=12).contains(&self.month)
+            && (1..=self.days_in_month()).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60;
+        if !valid {
+            return None;
+        }
+        let days = u64::try_from(self.days_from_epoch()).ok()?;
+        let secs =
+            u64::from(self.hour) * 3600 + u64::from(self.minute) * 60 + u64::from(self.second);
+        Some(days * 86400 + secs)
+    }
+}
+
+/// Check the conversion of dates at boot time.
+pub(super) fn test() {
+    let date = |year, month, day| DateTime {
+        year,
+        month,
+        day,
+        hour: 0,
+        minute: 0,
+        second: 0,
+    };
+
+    assert_eq!(date(1970, 1, 1).days_from_epoch(), 0);
+    assert_eq!(date(1970, 1, 1).unix_secs(), Some(0));
+    assert_eq!(date(1969, 12, 31).days_from_epoch(), -1);
+    assert_eq!(date(1969, 12, 31).unix_secs(), None);
+
+    // Leap days, including the skipped one of 2100.
+    assert_eq!(date(2000, 2, 29).days_from_epoch(), 11016);
+    assert_eq!(date(2000, 3, 1).days_from_epoch(), 11017);
+    assert_eq!(date(2100, 2, 28).days_from_epoch(), 47540);
+    assert_eq!(date(2100, 3, 1).days_from_epoch(), 47541);
+    let time = DateTime {
+        hour: 12,
+        minute: 34,
+        second: 56,
+        ..date(2000, 2, 29)
+    };
+    assert_eq!(time.unix_secs(), Some(951827696));
+
+    // Invalid dates.
+    for date in [
+        date(2000, 0, 1),
+        date(2000, 13, 1),
+        date(2000, 1, 0),
+        date(2000, 1, 32),
+        date(2000, 4, 31),
+        date(2001, 2, 29),
+        date(2100, 2, 29),
+        DateTime {
+            hour: 24,
+            ..date(2000, 1, 1)
+        },
+        DateTime {
+            minute: 60,
+            ..date(2000, 1, 1)
+        },
+        DateTime {
+            second: 60,
+            ..date(2000, 1, 1)
+        },
+    ] {
+        assert_eq!(date.unix_secs(), None, "{date:?}");
+    }
+}
+
+/// Get the wall-clock time, from EFI runtime services if available, or from
+/// the CMOS clock otherwise.
+pub fn utc_time() -> Option<UtcTime> {
+    super::efi::utc_time().or_else(super::cmos::utc_time)
+}
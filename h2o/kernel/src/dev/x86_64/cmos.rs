@@ -0,0 +1,116 @@
+//! The real-time clock in the CMOS, used when EFI runtime services are not
+//! available.
+
+use core::ops::Range;
+
+use archop::io::{Io, Port};
+use spin::Mutex;
+use sv_call::time::UtcTime;
+
+use crate::{dev::rtc::DateTime, sched::PREEMPT};
+
+/// The index and data ports.
+pub const PORTS: Range<u16> = 0x70..0x72;
+
+const REG_SECOND: u8 = 0x00;
+const REG_MINUTE: u8 = 0x02;
+const REG_HOUR: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0a;
+const REG_STATUS_B: u8 = 0x0b;
+
+/// The clock is being updated (in status A).
+const UPDATING: u8 = 0x80;
+/// The hours are in the 24-hour format (in status B).
+const HOUR_24: u8 = 0x02;
+/// The values are in binary instead of BCD (in status B).
+const BINARY: u8 = 0x04;
+/// The afternoon in the 12-hour format (in the hour register).
+const HOUR_PM: u8 = 0x80;
+
+// SAFETY: The ports are present and reserved from the port I/O resource.
+static CMOS: Mutex<Port<u8>> = Mutex::new(unsafe { Port::new(PORTS.start) });
+
+/// The register of the century given by the FADT, or 0 if absent.
+fn century_reg() -> u8 {
+    crate::dev::acpi::fadt().map_or(0, |fadt| fadt.century)
+}
+
+unsafe fn read(port: &mut Port<u8>, reg: u8) -> u8 {
+    port.write(reg);
+    port.read_offset(1)
+}
+
+/// Read the raw registers, waiting for the clock not being updated.
+unsafe fn read_all(port: &mut Port<u8>, century: u8) -> [u8; 7] {
+    while read(port, REG_STATUS_A) & UPDATING != 0 {
+        core::hint::spin_loop();
+    }
+    [
+        read(port, REG_SECOND),
+        read(port, REG_MINUTE),
+        read(port, REG_HOUR),
+        read(port, REG_DAY),
+        read(port, REG_MONTH),
+        read(port, REG_YEAR),
+        if century != 0 { read(port, century) } else { 0 },
+    ]
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0xf)
+}
+
+/// Get the time from the clock, which is assumed to be in UTC.
+pub fn utc_time() -> Option<UtcTime> {
+    let century = century_reg();
+    let (regs, status) = PREEMPT.scope(|| {
+        let mut port = CMOS.lock();
+        // SAFETY: The registers are valid.
+        unsafe {
+            // Read until getting the same values twice in case of an update
+            // in the middle.
+            let mut regs = read_all(&mut port, century);
+            loop {
+                let again = read_all(&mut port, century);
+                if again == regs {
+                    break (regs, read(&mut port, REG_STATUS_B));
+                }
+                regs = again;
+            }
+        }
+    });
+
+    let [second, minute, hour, day, month, year, century] = regs;
+    let pm = hour & HOUR_PM != 0;
+    let convert = |value: u8| {
+        if status & BINARY != 0 {
+            value
+        } else {
+            from_bcd(value)
+        }
+    };
+    let mut hour = convert(hour & !HOUR_PM);
+    if status & HOUR_24 == 0 {
+        hour = match (hour, pm) {
+            (12, false) => 0,
+            (12, true) => 12,
+            (hour, true) => hour + 12,
+            (hour, false) => hour,
+        };
+    }
+    let century = if century != 0 { convert(century) } else { 20 };
+
+    let secs = DateTime {
+        year: u16::from(century) * 100 + u16::from(convert(year)),
+        month: convert(month),
+        day: convert(day),
+        hour,
+        minute: convert(minute),
+        second: convert(second),
+    }
+    .unix_secs()?;
+    Some(UtcTime { secs, nanos: 0 })
+}
@@ -190,4 +190,10 @@ impl HpetClock {
             sft,
         })
     }
+
+    /// Keep the counter running for the clock, since calibrations stop it
+    /// when finished.
+    pub fn start(&self) {
+        self.hpet.write().enable(true);
+    }
 }
@@ -0,0 +1,629 @@
+//! The kernel debugger, a stub of the GDB remote serial protocol on the COM
+//! port used by the logger.
+//!
+//! The debugger is disabled unless the `gdb` kernel option is given. It's
+//! then entered on panics, kernel faults, breakpoints and single steps, on
+//! NMIs while GDB is connected, and when GDB sends an interrupt (`Ctrl-C`).
+//! Connect to it with `target remote` on the other end of the serial port.
+//!
+//! While the debugger runs, all the other CPUs are parked in their NMI
+//! handlers, so it must neither allocate memory nor take any lock that they
+//! may hold. The only exception is the task list, which briefly takes the read
+//! locks of the task map.
+
+mod rsp;
+
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "x86_64")] {
+        #[path = "gdb/x86_64.rs"]
+        mod arch;
+        pub use self::arch::breakpoint;
+    }
+}
+
+use core::{
+    fmt::{self, Write},
+    hint, ptr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering::*},
+    time::Duration,
+};
+
+use archop::Azy;
+use paging::{LAddr, PAGE_MASK, PAGE_SIZE};
+use spin::Mutex;
+
+use self::rsp::{decode_hex, parse_hex, Reply, Rsp};
+use crate::{
+    cpu::{
+        arch::{
+            apic::{ipi, Polarity, TriggerMode},
+            intr::{ExVec, Manager},
+        },
+        intr::IsaIrq,
+        time::Instant,
+    },
+    logger::{serial::Output, COM_LOG},
+    sched::{
+        task::{self, ctx::arch::Frame},
+        SCHED,
+    },
+};
+
+const NO_OWNER: usize = usize::MAX;
+const MAX_BREAKPOINTS: usize = 64;
+/// How long to wait for the other CPUs to be parked.
+const PARK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The error code of inaccessible memory (`EFAULT`).
+const ERR_FAULT: u8 = 14;
+/// The error code of malformed or unsupported arguments (`EINVAL`).
+const ERR_INVALID: u8 = 22;
+/// The error code of nonexistent threads (`ESRCH`).
+const ERR_NO_THREAD: u8 = 3;
+
+/// The CPU running the debugger.
+static OWNER: AtomicUsize = AtomicUsize::new(NO_OWNER);
+/// The number of CPUs parked by the debugger.
+static PARKED: AtomicUsize = AtomicUsize::new(0);
+/// Whether GDB is connected and thus waiting for stop replies.
+static ATTACHED: AtomicBool = AtomicBool::new(false);
+/// Whether the debugger is entered by an interrupt request from GDB.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+static STUB: Azy<Mutex<Stub>> = Azy::new(|| {
+    // SAFETY: The port is present and shared only with the logger.
+    let port = unsafe { Output::new(COM_LOG) };
+    Mutex::new(Stub {
+        rsp: Rsp::new(port),
+        breakpoints: [None; MAX_BREAKPOINTS],
+    })
+});
+
+#[derive(Debug, Clone, Copy)]
+struct Breakpoint {
+    addr: u64,
+    orig: u8,
+}
+
+struct Stub {
+    rsp: Rsp,
+    breakpoints: [Option<Breakpoint>; MAX_BREAKPOINTS],
+}
+
+/// Why the debugger is entered.
+#[derive(Debug, Clone, Copy)]
+struct Stop {
+    signal: u8,
+    tid: Option<u64>,
+    swbreak: bool,
+}
+
+impl Stop {
+    fn write(&self, reply: &mut Reply) -> fmt::Result {
+        write!(reply, "T{:02x}", self.signal)?;
+        if let Some(tid) = self.tid {
+            write!(reply, "thread:{tid:x};")?;
+        }
+        if self.swbreak {
+            write!(reply, "swbreak:;")?;
+        }
+        Ok(())
+    }
+}
+
+enum Action {
+    Stay,
+    Resume,
+    Detach,
+}
+
+fn current_tid() -> Option<u64> {
+    SCHED.with_current(|cur| Ok(cur.tid().raw())).ok()
+}
+
+fn thread_alive(raw: u64) -> bool {
+    let mut ret = false;
+    task::for_each_task(|tid| ret |= tid.raw() == raw);
+    ret
+}
+
+/// Call `func` on the pieces of `len` bytes of memory at `addr` in the current
+/// address space, accessed through the identity mapping of the physical
+/// memory so that read-only pages can be written as well.
+///
+/// Nothing is accessed if any of the pages is not mapped.
+fn access_memory<F>(addr: u64, len: usize, mut func: F) -> Result<(), u8>
+where
+    F: FnMut(*mut u8, usize, usize) -> Result<(), u8>,
+{
+    let end = addr.checked_add(len as u64).ok_or(ERR_FAULT)?;
+    let pieces = || {
+        let mut cur = addr;
+        core::iter::from_fn(move || {
+            (cur < end).then(|| {
+                let piece = (PAGE_SIZE - (cur as usize & PAGE_MASK)).min((end - cur) as usize);
+                let ret = (cur, piece);
+                cur += piece as u64;
+                ret
+            })
+        })
+    };
+    let translate = |virt: u64| {
+        let virt = LAddr::from(virt);
+        if !archop::canonical(virt) {
+            return None;
+        }
+        // SAFETY: The other CPUs are stopped.
+        let (phys, _) = unsafe { crate::mem::space::query_current(virt) }?;
+        Some(*phys.to_laddr(minfo::ID_OFFSET))
+    };
+
+    if pieces().any(|(virt, _)| translate(virt).is_none()) {
+        return Err(ERR_FAULT);
+    }
+    for (virt, piece) in pieces() {
+        let ptr = translate(virt).ok_or(ERR_FAULT)?;
+        func(ptr, (virt - addr) as usize, piece)?;
+    }
+    Ok(())
+}
+
+fn read_memory(addr: u64, buf: &mut [u8]) -> Result<(), u8> {
+    access_memory(addr, buf.len(), |ptr, offset, len| {
+        // SAFETY: The memory is mapped.
+        unsafe { ptr::copy_nonoverlapping(ptr, buf[offset..].as_mut_ptr(), len) };
+        Ok(())
+    })
+}
+
+fn write_memory(addr: u64, data: &[u8]) -> Result<(), u8> {
+    access_memory(addr, data.len(), |ptr, offset, len| {
+        // SAFETY: The memory is mapped.
+        unsafe { ptr::copy_nonoverlapping(data[offset..].as_ptr(), ptr, len) };
+        Ok(())
+    })
+}
+
+fn split(s: &[u8], sep: u8) -> Option<(&[u8], &[u8])> {
+    let index = s.iter().position(|&c| c == sep)?;
+    Some((&s[..index], &s[(index + 1)..]))
+}
+
+/// Parse the `addr,len` arguments of memory packets.
+fn parse_range(s: &[u8]) -> Option<(u64, usize)> {
+    let (addr, len) = split(s, b',')?;
+    Some((parse_hex(addr)?, parse_hex(len)? as usize))
+}
+
+/// Writes a window of the text formatted into it to a reply, counting the
+/// total length.
+struct Window<'a, 'b> {
+    reply: Option<&'a mut Reply<'b>>,
+    skip: usize,
+    left: usize,
+    total: usize,
+}
+
+impl Write for Window<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &c in s.as_bytes() {
+            self.total += 1;
+            if self.skip > 0 {
+                self.skip -= 1;
+            } else if self.left > 0 {
+                if let Some(reply) = self.reply.as_deref_mut() {
+                    reply.push(&[c])?;
+                }
+                self.left -= 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_xml_escaped(out: &mut impl Write, s: &str) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '&' => out.write_str("&amp;")?,
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            '"' => out.write_str("&quot;")?,
+            '\'' => out.write_str("&apos;")?,
+            c => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// The thread list in the format of `qXfer:threads:read`.
+fn write_threads(out: &mut Window) -> fmt::Result {
+    out.write_str("<?xml version=\"1.0\"?><threads>")?;
+    let mut res = Ok(());
+    task::for_each_task(|tid| {
+        if res.is_ok() {
+            res = (|| {
+                write!(out, "<thread id=\"{:x}\" name=\"", tid.raw())?;
+                write_xml_escaped(out, tid.name())?;
+                out.write_str("\"/>")
+            })();
+        }
+    });
+    res?;
+    out.write_str("</threads>")
+}
+
+fn xfer_threads(args: &[u8], reply: &mut Reply) -> fmt::Result {
+    let (offset, len) = match parse_range(args) {
+        Some(range) => range,
+        None => {
+            reply.error(ERR_INVALID);
+            return Ok(());
+        }
+    };
+
+    let mut counter = Window {
+        reply: None,
+        skip: 0,
+        left: 0,
+        total: 0,
+    };
+    write_threads(&mut counter)?;
+    let offset = (offset as usize).min(counter.total);
+    // Every byte may be escaped into 2.
+    let len = len.min((reply.room() - 1) / 2);
+    let last = offset + len >= counter.total;
+
+    reply.push(if last { b"l" } else { b"m" })?;
+    write_threads(&mut Window {
+        reply: Some(reply),
+        skip: offset,
+        left: len,
+        total: 0,
+    })
+}
+
+impl Stub {
+    /// Run the debugger until GDB resumes the execution.
+    fn run(&mut self, frame: &mut Frame, vec: ExVec) {
+        arch::set_single_step(frame, false);
+
+        let swbreak = vec == ExVec::Breakpoint
+            && self
+                .breakpoints
+                .iter()
+                .flatten()
+                .any(|bp| bp.addr == frame.rip - arch::BREAKPOINT_LEN);
+        if swbreak {
+            arch::rewind_breakpoint(frame);
+        }
+        let stop = Stop {
+            signal: if INTERRUPTED.swap(false, SeqCst) {
+                arch::signal(ExVec::Nmi)
+            } else {
+                arch::signal(vec)
+            },
+            tid: current_tid(),
+            swbreak,
+        };
+
+        // Unsolicited stop replies would confuse a GDB that has just
+        // connected, which asks for the reason with `?` instead.
+        if ATTACHED.load(SeqCst) {
+            self.rsp.notify(|reply| {
+                let _ = stop.write(reply);
+            });
+        }
+
+        let Stub { rsp, breakpoints } = self;
+        loop {
+            let action = rsp.exchange(|req, reply| {
+                ATTACHED.store(true, SeqCst);
+                handle(req, reply, frame, breakpoints, &stop)
+            });
+            match action {
+                Action::Stay => {}
+                Action::Resume => break,
+                Action::Detach => {
+                    ATTACHED.store(false, SeqCst);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn set_breakpoint(
+    breakpoints: &mut [Option<Breakpoint>; MAX_BREAKPOINTS],
+    addr: u64,
+) -> Result<(), u8> {
+    if breakpoints.iter().flatten().any(|bp| bp.addr == addr) {
+        return Ok(());
+    }
+    let slot = breakpoints
+        .iter_mut()
+        .find(|slot| slot.is_none())
+        .ok_or(ERR_INVALID)?;
+
+    let mut orig = [0];
+    read_memory(addr, &mut orig)?;
+    write_memory(addr, &[arch::BREAKPOINT_INSN])?;
+    *slot = Some(Breakpoint {
+        addr,
+        orig: orig[0],
+    });
+    Ok(())
+}
+
+fn remove_breakpoint(
+    breakpoints: &mut [Option<Breakpoint>; MAX_BREAKPOINTS],
+    addr: u64,
+) -> Result<(), u8> {
+    let slot = breakpoints
+        .iter_mut()
+        .find(|slot| matches!(slot, Some(bp) if bp.addr == addr));
+    match slot.and_then(Option::take) {
+        Some(bp) => write_memory(bp.addr, &[bp.orig]),
+        None => Ok(()),
+    }
+}
+
+fn clear_breakpoints(breakpoints: &mut [Option<Breakpoint>; MAX_BREAKPOINTS]) {
+    for bp in breakpoints.iter_mut().filter_map(Option::take) {
+        let _ = write_memory(bp.addr, &[bp.orig]);
+    }
+}
+
+fn handle(
+    req: &[u8],
+    reply: &mut Reply,
+    frame: &mut Frame,
+    breakpoints: &mut [Option<Breakpoint>; MAX_BREAKPOINTS],
+    stop: &Stop,
+) -> Action {
+    let res = (|| {
+        let (&cmd, args) = match req.split_first() {
+            Some(split) => split,
+            None => return Ok(Action::Stay),
+        };
+        match cmd {
+            b'?' => {
+                let _ = stop.write(reply);
+            }
+
+            b'g' => {
+                let mut buf = [0; arch::REGS_SIZE];
+                let mut offset = 0;
+                for index in 0..arch::NR_REGS {
+                    offset +=
+                        arch::read_reg(frame, index, &mut buf[offset..]).ok_or(ERR_INVALID)?;
+                }
+                let _ = reply.hex(&buf);
+            }
+            b'G' => {
+                let mut buf = [0; arch::REGS_SIZE];
+                let len = decode_hex(args, &mut buf).ok_or(ERR_INVALID)?;
+                let mut offset = 0;
+                for index in 0..arch::NR_REGS {
+                    if offset + arch::reg_size(index) > len {
+                        break;
+                    }
+                    offset +=
+                        arch::write_reg(frame, index, &buf[offset..len]).ok_or(ERR_INVALID)?;
+                }
+                let _ = reply.push(b"OK");
+            }
+            b'p' => {
+                let index = parse_hex(args).ok_or(ERR_INVALID)? as usize;
+                let mut buf = [0; 8];
+                let len = arch::read_reg(frame, index, &mut buf).ok_or(ERR_INVALID)?;
+                let _ = reply.hex(&buf[..len]);
+            }
+            b'P' => {
+                let (index, value) = split(args, b'=').ok_or(ERR_INVALID)?;
+                let index = parse_hex(index).ok_or(ERR_INVALID)? as usize;
+                let mut buf = [0; 8];
+                let len = decode_hex(value, &mut buf).ok_or(ERR_INVALID)?;
+                arch::write_reg(frame, index, &buf[..len]).ok_or(ERR_INVALID)?;
+                let _ = reply.push(b"OK");
+            }
+
+            b'm' => {
+                let (addr, len) = parse_range(args).ok_or(ERR_INVALID)?;
+                let len = len.min(reply.room() / 2);
+                access_memory(addr, len, |ptr, _, len| {
+                    // SAFETY: The memory is mapped.
+                    let data = unsafe { core::slice::from_raw_parts(ptr, len) };
+                    reply.hex(data).map_err(|_| ERR_INVALID)
+                })?;
+            }
+            b'M' => {
+                let (range, data) = split(args, b':').ok_or(ERR_INVALID)?;
+                let (addr, len) = parse_range(range).ok_or(ERR_INVALID)?;
+                if data.len() != len * 2 {
+                    return Err(ERR_INVALID);
+                }
+                access_memory(addr, len, |ptr, offset, len| {
+                    // SAFETY: The memory is mapped.
+                    let buf = unsafe { core::slice::from_raw_parts_mut(ptr, len) };
+                    decode_hex(&data[(offset * 2)..((offset + len) * 2)], buf)
+                        .map(drop)
+                        .ok_or(ERR_INVALID)
+                })?;
+                let _ = reply.push(b"OK");
+            }
+
+            b'c' | b's' => {
+                if !args.is_empty() {
+                    frame.rip = parse_hex(args).ok_or(ERR_INVALID)?;
+                }
+                arch::set_single_step(frame, cmd == b's');
+                reply.suppress();
+                return Ok(Action::Resume);
+            }
+            b'D' => {
+                clear_breakpoints(breakpoints);
+                let _ = reply.push(b"OK");
+                return Ok(Action::Detach);
+            }
+            b'k' => {
+                clear_breakpoints(breakpoints);
+                reply.suppress();
+                return Ok(Action::Detach);
+            }
+
+            b'Z' | b'z' => {
+                let (ty, args) = split(args, b',').ok_or(ERR_INVALID)?;
+                // Only software breakpoints are supported, and GDB emulates
+                // the other kinds with them or single steps.
+                if ty != b"0" {
+                    return Ok(Action::Stay);
+                }
+                let (addr, _kind) = parse_range(args).ok_or(ERR_INVALID)?;
+                if cmd == b'Z' {
+                    set_breakpoint(breakpoints, addr)?;
+                } else {
+                    remove_breakpoint(breakpoints, addr)?;
+                }
+                let _ = reply.push(b"OK");
+            }
+
+            // All the threads are stopped, so we don't need to distinguish
+            // them in operations.
+            b'H' => {
+                let _ = reply.push(b"OK");
+            }
+            b'T' => {
+                let raw = parse_hex(args).ok_or(ERR_INVALID)?;
+                if !thread_alive(raw) {
+                    return Err(ERR_NO_THREAD);
+                }
+                let _ = reply.push(b"OK");
+            }
+
+            b'q' => {
+                if args.starts_with(b"Supported") {
+                    let _ = write!(
+                        reply,
+                        "PacketSize={:x};qXfer:threads:read+;swbreak+",
+                        rsp::PACKET_SIZE
+                    );
+                } else if args == b"Attached" {
+                    let _ = reply.push(b"1");
+                } else if args == b"C" {
+                    if let Some(tid) = stop.tid {
+                        let _ = write!(reply, "QC{tid:x}");
+                    }
+                } else if let Some(args) = args.strip_prefix(b"Xfer:threads:read::") {
+                    let _ = xfer_threads(args, reply);
+                }
+            }
+
+            _ => {}
+        }
+        Ok(Action::Stay)
+    })();
+
+    res.unwrap_or_else(|code| {
+        reply.error(code);
+        Action::Stay
+    })
+}
+
+/// Park the current CPU until the debugger running on another CPU resumes.
+fn park() {
+    PARKED.fetch_add(1, SeqCst);
+    while OWNER.load(SeqCst) != NO_OWNER {
+        hint::spin_loop();
+    }
+    PARKED.fetch_sub(1, SeqCst);
+}
+
+/// Enter the debugger for the exception, returning whether the execution can
+/// be resumed.
+///
+/// # Safety
+///
+/// This function must be called only from the exception handlers with the
+/// frame of the exception.
+pub unsafe fn handle_exception(frame: &mut Frame, vec: ExVec) -> bool {
+    if !enabled() {
+        return false;
+    }
+    // NMIs not sent by the debugger are left alone unless GDB is connected.
+    if vec == ExVec::Nmi && OWNER.load(SeqCst) == NO_OWNER && !ATTACHED.load(SeqCst) {
+        return false;
+    }
+
+    let cpu = crate::cpu::id();
+    match OWNER.compare_exchange(NO_OWNER, cpu, SeqCst, SeqCst) {
+        Ok(_) => {}
+        // Exceptions in the debugger itself can't be debugged.
+        Err(owner) if owner == cpu => return false,
+        Err(_) if vec == ExVec::Nmi => {
+            park();
+            return true;
+        }
+        Err(_) => {
+            // Another CPU entered the debugger just now, and our NMI is on its
+            // way. Wait for it and try again after the debugger resumes.
+            while OWNER.load(SeqCst) != NO_OWNER {
+                hint::spin_loop();
+            }
+            return handle_exception(frame, vec);
+        }
+    }
+
+    let count = crate::cpu::count();
+    if count > 1 {
+        ipi::stop_others();
+        let start = Instant::now();
+        while PARKED.load(SeqCst) < count - 1 && start.elapsed() < PARK_TIMEOUT {
+            hint::spin_loop();
+        }
+    }
+
+    // Take the lock forcibly in case of the interrupt handler on this CPU being
+    // interrupted.
+    let stub = &*STUB;
+    if stub.is_locked() {
+        stub.force_unlock();
+    }
+    stub.lock().run(frame, vec);
+
+    OWNER.store(NO_OWNER, SeqCst);
+    matches!(vec, ExVec::Debug | ExVec::Breakpoint | ExVec::Nmi)
+}
+
+fn serial_handler(_: *mut u8) {
+    let interrupted = STUB
+        .try_lock()
+        .map_or(false, |mut stub| stub.rsp.interrupted());
+    if interrupted {
+        INTERRUPTED.store(true, SeqCst);
+        breakpoint();
+    }
+}
+
+/// Whether the debugger is enabled with the `gdb` kernel option.
+#[inline]
+pub fn enabled() -> bool {
+    crate::cmdline::get().gdb
+}
+
+/// Listen to the interrupt requests from GDB on the serial port if the
+/// debugger is enabled.
+pub fn init() {
+    if !enabled() {
+        return;
+    }
+    log::info!("Kernel debugger enabled on the serial port");
+    let gsi = crate::dev::ioapic::gsi_from_isa(IsaIrq::Serial1);
+    let res = Manager::config(gsi, TriggerMode::Edge, Polarity::High)
+        .and_then(|_| Manager::register(gsi, 0, (serial_handler, ptr::null_mut())))
+        .and_then(|_| Manager::mask(gsi, false));
+    match res {
+        // SAFETY: The port is initialized.
+        Ok(()) => unsafe { STUB.lock().rsp.port().enable_recv_intr() },
+        Err(err) => log::warn!("Failed to listen to the kernel debugger: {:?}", err),
+    }
+}
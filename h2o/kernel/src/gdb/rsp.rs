@@ -0,0 +1,241 @@
+//! The packet layer of the GDB remote serial protocol.
+//!
+//! Packets look like `$<data>#<checksum>`, where the checksum is the sum of the
+//! data bytes modulo 256 in 2 hex digits. Every packet is acknowledged by the
+//! receiver with `+`, or `-` to request a retransmission.
+
+use core::fmt;
+
+use crate::logger::serial::Output;
+
+/// The maximum size of packets, which is also told to GDB.
+pub const PACKET_SIZE: usize = 0x1000;
+/// The byte sent by GDB out of packets to interrupt the target.
+const INTERRUPT: u8 = 0x03;
+
+pub struct Rsp {
+    port: Output,
+    input: [u8; PACKET_SIZE],
+    output: [u8; PACKET_SIZE],
+}
+
+impl Rsp {
+    pub fn new(port: Output) -> Self {
+        Rsp {
+            port,
+            input: [0; PACKET_SIZE],
+            output: [0; PACKET_SIZE],
+        }
+    }
+
+    #[inline]
+    pub fn port(&mut self) -> &mut Output {
+        &mut self.port
+    }
+
+    /// Drain the received bytes, returning whether GDB wants to interrupt the
+    /// target.
+    pub fn interrupted(&mut self) -> bool {
+        let mut ret = false;
+        // SAFETY: The port is initialized.
+        while unsafe { self.port.has_data() } {
+            ret |= unsafe { self.port.in_char() } == INTERRUPT;
+        }
+        ret
+    }
+
+    fn getc(&mut self) -> u8 {
+        // SAFETY: The port is initialized.
+        unsafe { self.port.in_char() }
+    }
+
+    fn putc(&mut self, c: u8) {
+        // SAFETY: The port is initialized.
+        unsafe { self.port.out_char(c) }
+    }
+
+    /// Receive a packet into the input buffer, returning its length.
+    fn recv(&mut self) -> usize {
+        loop {
+            while self.getc() != b'$' {}
+
+            let mut len = 0;
+            let mut sum = 0u8;
+            let mut valid = true;
+            loop {
+                match self.getc() {
+                    b'#' => break,
+                    c => {
+                        sum = sum.wrapping_add(c);
+                        match self.input.get_mut(len) {
+                            Some(slot) => *slot = c,
+                            None => valid = false,
+                        }
+                        len += 1;
+                    }
+                }
+            }
+            let checksum = [self.getc(), self.getc()];
+
+            if valid && parse_hex(&checksum) == Some(u64::from(sum)) {
+                self.putc(b'+');
+                break len;
+            }
+            self.putc(b'-');
+        }
+    }
+
+    /// Send the first `len` bytes of the output buffer as a packet, until GDB
+    /// acknowledges it.
+    fn send(&mut self, len: usize) {
+        loop {
+            let mut sum = 0u8;
+            self.putc(b'$');
+            for i in 0..len {
+                let c = self.output[i];
+                sum = sum.wrapping_add(c);
+                self.putc(c);
+            }
+            self.putc(b'#');
+            let [hi, lo] = hex_digits(sum);
+            self.putc(hi);
+            self.putc(lo);
+
+            loop {
+                match self.getc() {
+                    b'+' => return,
+                    b'-' => break,
+                    // GDB may interrupt the target before it sees the packet.
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Send a packet built by `func`.
+    pub fn notify<F>(&mut self, func: F)
+    where
+        F: FnOnce(&mut Reply),
+    {
+        let mut reply = Reply::new(&mut self.output);
+        func(&mut reply);
+        let len = reply.len;
+        self.send(len);
+    }
+
+    /// Receive a request, and send the reply built by `func` with it.
+    pub fn exchange<F, R>(&mut self, func: F) -> R
+    where
+        F: FnOnce(&[u8], &mut Reply) -> R,
+    {
+        let len = self.recv();
+        let mut reply = Reply::new(&mut self.output);
+        let ret = func(&self.input[..len], &mut reply);
+        if !reply.suppressed {
+            let len = reply.len;
+            self.send(len);
+        }
+        ret
+    }
+}
+
+/// The data of a packet being sent, escaped as needed.
+pub struct Reply<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    suppressed: bool,
+}
+
+impl<'a> Reply<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Reply {
+            buf,
+            len: 0,
+            suppressed: false,
+        }
+    }
+
+    /// The number of bytes that can be still appended without escaping.
+    #[inline]
+    pub fn room(&self) -> usize {
+        self.buf.len() - self.len
+    }
+
+    fn push_raw(&mut self, c: u8) -> fmt::Result {
+        let slot = self.buf.get_mut(self.len).ok_or(fmt::Error)?;
+        *slot = c;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Append `bytes`, escaping the ones that have special meanings.
+    pub fn push(&mut self, bytes: &[u8]) -> fmt::Result {
+        for &c in bytes {
+            if matches!(c, b'$' | b'#' | b'}' | b'*') {
+                self.push_raw(b'}')?;
+                self.push_raw(c ^ 0x20)?;
+            } else {
+                self.push_raw(c)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Append `bytes` in hex.
+    pub fn hex(&mut self, bytes: &[u8]) -> fmt::Result {
+        for &c in bytes {
+            let [hi, lo] = hex_digits(c);
+            self.push_raw(hi)?;
+            self.push_raw(lo)?;
+        }
+        Ok(())
+    }
+
+    /// Don't send the reply at all, for the requests that GDB doesn't expect
+    /// an immediate answer to.
+    #[inline]
+    pub fn suppress(&mut self) {
+        self.suppressed = true;
+    }
+
+    /// Replace the reply with an error code.
+    pub fn error(&mut self, code: u8) {
+        self.len = 0;
+        let _ = self.push_raw(b'E');
+        let _ = self.hex(&[code]);
+    }
+}
+
+impl fmt::Write for Reply<'_> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes())
+    }
+}
+
+fn hex_digits(c: u8) -> [u8; 2] {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    [DIGITS[(c >> 4) as usize], DIGITS[(c & 0xf) as usize]]
+}
+
+/// Parse a big-endian hex number, such as an address.
+pub fn parse_hex(s: &[u8]) -> Option<u64> {
+    if s.is_empty() || s.len() > 16 {
+        return None;
+    }
+    s.iter().try_fold(0, |acc, &c| {
+        let digit = (c as char).to_digit(16)?;
+        Some((acc << 4) | u64::from(digit))
+    })
+}
+
+/// Decode hex bytes into `buf`, returning the number of bytes.
+pub fn decode_hex(s: &[u8], buf: &mut [u8]) -> Option<usize> {
+    if s.len() % 2 != 0 || s.len() / 2 > buf.len() {
+        return None;
+    }
+    for (pair, byte) in s.chunks_exact(2).zip(buf.iter_mut()) {
+        *byte = parse_hex(pair)? as u8;
+    }
+    Some(s.len() / 2)
+}
@@ -0,0 +1,129 @@
+use archop::reg::rflags;
+use sv_call::task::ctx::Gpr;
+
+use crate::{cpu::arch::intr::ExVec, sched::task::ctx::arch::Frame};
+
+/// The instruction planted for software breakpoints (`int3`).
+pub const BREAKPOINT_INSN: u8 = 0xcc;
+/// The length of the trap instruction, by which the instruction pointer is
+/// rewound after a software breakpoint is hit.
+pub const BREAKPOINT_LEN: u64 = 1;
+
+/// The number of registers in GDB's `amd64` layout that we report, from
+/// `rax` to `gs`. The floating-point and vector registers are left
+/// unavailable.
+pub const NR_REGS: usize = 24;
+/// The index of the first 32-bit register (`eflags`).
+const FIRST_SHORT: usize = 17;
+/// The size of a `g` packet in bytes.
+pub const REGS_SIZE: usize = FIRST_SHORT * 8 + (NR_REGS - FIRST_SHORT) * 4;
+
+#[inline]
+pub fn reg_size(index: usize) -> usize {
+    if index < FIRST_SHORT {
+        8
+    } else {
+        4
+    }
+}
+
+fn reg_mut(gpr: &mut Gpr, index: usize) -> Option<&mut u64> {
+    Some(match index {
+        0 => &mut gpr.rax,
+        1 => &mut gpr.rbx,
+        2 => &mut gpr.rcx,
+        3 => &mut gpr.rdx,
+        4 => &mut gpr.rsi,
+        5 => &mut gpr.rdi,
+        6 => &mut gpr.rbp,
+        7 => &mut gpr.rsp,
+        8 => &mut gpr.r8,
+        9 => &mut gpr.r9,
+        10 => &mut gpr.r10,
+        11 => &mut gpr.r11,
+        12 => &mut gpr.r12,
+        13 => &mut gpr.r13,
+        14 => &mut gpr.r14,
+        15 => &mut gpr.r15,
+        16 => &mut gpr.rip,
+        17 => &mut gpr.rflags,
+        _ => return None,
+    })
+}
+
+/// Read the register at `index` into `buf`, returning its size.
+pub fn read_reg(frame: &Frame, index: usize, buf: &mut [u8]) -> Option<usize> {
+    let mut gpr = frame.debug_get();
+    let value = match index {
+        18 => frame.cs,
+        19 => frame.ss,
+        // The data segment registers are not used in long mode.
+        20..=23 => 0,
+        _ => *reg_mut(&mut gpr, index)?,
+    };
+    let size = reg_size(index);
+    buf.get_mut(..size)?
+        .copy_from_slice(&value.to_le_bytes()[..size]);
+    Some(size)
+}
+
+/// Write the register at `index` from `buf`, returning its size.
+///
+/// The writes to segment registers and the privileged flags are ignored.
+pub fn write_reg(frame: &mut Frame, index: usize, buf: &[u8]) -> Option<usize> {
+    let size = reg_size(index);
+    let mut bytes = [0; 8];
+    bytes[..size].copy_from_slice(buf.get(..size)?);
+    let value = u64::from_le_bytes(bytes);
+
+    let mut gpr = frame.debug_get();
+    if let Some(reg) = reg_mut(&mut gpr, index) {
+        *reg = value;
+        frame.debug_set(&gpr).ok()?;
+    } else if index >= NR_REGS {
+        return None;
+    }
+    Some(size)
+}
+
+/// Enable or disable single-stepping on return from the exception.
+#[inline]
+pub fn set_single_step(frame: &mut Frame, enabled: bool) {
+    if enabled {
+        frame.rflags |= rflags::TF;
+    } else {
+        frame.rflags &= !rflags::TF;
+    }
+}
+
+/// Point the instruction pointer back to the breakpoint that is just hit.
+#[inline]
+pub fn rewind_breakpoint(frame: &mut Frame) {
+    frame.rip -= BREAKPOINT_LEN;
+}
+
+/// The POSIX signal number reported to GDB for the exception.
+pub fn signal(vec: ExVec) -> u8 {
+    use ExVec::*;
+    const SIGINT: u8 = 2;
+    const SIGILL: u8 = 4;
+    const SIGTRAP: u8 = 5;
+    const SIGBUS: u8 = 7;
+    const SIGFPE: u8 = 8;
+    const SIGSEGV: u8 = 11;
+    match vec {
+        Debug | Breakpoint => SIGTRAP,
+        Nmi => SIGINT,
+        DivideBy0 | Overflow | FloatPoint | SimdExcep => SIGFPE,
+        InvalidOp => SIGILL,
+        Alignment => SIGBUS,
+        _ => SIGSEGV,
+    }
+}
+
+/// Trap into the kernel debugger.
+#[inline(always)]
+pub fn breakpoint() {
+    // SAFETY: The exception is handled by the kernel debugger.
+    unsafe { core::arch::asm!("int3") };
+}
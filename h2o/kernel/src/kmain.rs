@@ -31,8 +31,10 @@
 #![feature(unsize)]
 #![feature(vec_into_raw_parts)]
 
+mod cmdline;
 pub mod cpu;
 pub mod dev;
+mod gdb;
 mod logger;
 mod mem;
 mod rxx;
@@ -60,15 +62,18 @@ pub extern "C" fn kmain() {
     }
 
     // SAFETY: Everything is uninitialized.
-    unsafe { logger::init(log::Level::Debug) };
+    unsafe { logger::init(cmdline::get().log_level) };
     log::info!("Starting the kernel");
+    cmdline::init();
 
     mem::init();
+    logger::init_console();
     sched::task::init_early();
 
     unsafe { cpu::arch::init() };
 
     unsafe { dev::init() };
+    gdb::init();
 
     sched::init();
 
@@ -1,5 +1,6 @@
+mod console;
 pub mod flags;
-mod serial;
+pub mod serial;
 
 use core::{
     fmt::*,
@@ -27,19 +28,38 @@ impl core::fmt::Display for OptionU32Display {
 pub static HAS_TIME: AtomicBool = AtomicBool::new(false);
 
 struct Logger {
-    output: Mutex<serial::Output>,
+    output: Mutex<Output>,
     level: log::Level,
 }
 
 impl Logger {
     pub fn new(level: log::Level) -> Logger {
         Logger {
-            output: Mutex::new(unsafe { serial::Output::new(COM_LOG) }),
+            output: Mutex::new(Output {
+                serial: unsafe { serial::Output::new(COM_LOG) },
+                console: None,
+            }),
             level,
         }
     }
 }
 
+/// The serial port, and the framebuffer console once it's available.
+struct Output {
+    serial: serial::Output,
+    console: Option<console::Console>,
+}
+
+impl Write for Output {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.serial.write_str(s)?;
+        if let Some(console) = &mut self.console {
+            console.write_str(s)?;
+        }
+        Ok(())
+    }
+}
+
 impl log::Log for Logger {
     #[inline]
     fn enabled(&self, metadata: &log::Metadata) -> bool {
@@ -93,6 +113,23 @@ pub unsafe fn init(max_level: log::Level) {
     log::set_max_level(max_level.to_level_filter());
 }
 
+/// Mirror the logs to the framebuffer from the boot loader, if any, which
+/// needs the kernel space to be initialized.
+pub fn init_console() {
+    let fb = match crate::kargs().framebuffer {
+        Some(ref fb) => fb,
+        None => return,
+    };
+    match console::Console::new(fb) {
+        Ok(console) => {
+            let _pree = PREEMPT.lock();
+            let logger = unsafe { LOGGER.assume_init_ref() };
+            logger.output.lock().console = Some(console);
+        }
+        Err(err) => log::warn!("Failed to set up the framebuffer console: {:?}", err),
+    }
+}
+
 mod syscall {
     use core::fmt::Write;
 
@@ -0,0 +1,232 @@
+//! The text console on the framebuffer set up by the boot loader, to which the
+//! logs are mirrored so that they can be seen without a serial port.
+//!
+//! The framebuffer is mapped write-combining, so reading it back is very slow.
+//! The pixels are drawn into a shadow buffer in RAM instead, which is scrolled
+//! in place and whose changed scan lines are copied to the framebuffer in bulk.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::{convert::Infallible, fmt, ops::Range, ptr};
+
+use bitop_ex::BitOpEx;
+use embedded_graphics::{
+    mono_font::{ascii::FONT_8X13, MonoFont, MonoTextStyle, MonoTextStyleBuilder},
+    pixelcolor::{Rgb888, RgbColor},
+    prelude::*,
+    text::{Baseline, Text},
+};
+use minfo::{Framebuffer, PixelFormat};
+
+use crate::mem::space::{self, Flags, Phys, PhysTrait};
+
+const FONT: &MonoFont = &FONT_8X13;
+const TAB_WIDTH: usize = 4;
+
+const FOREGROUND: Rgb888 = Rgb888::new(0xcc, 0xcc, 0xcc);
+const BACKGROUND: Rgb888 = Rgb888::BLACK;
+
+/// The pixels of the framebuffer.
+struct Screen {
+    base: *mut u32,
+    width: usize,
+    height: usize,
+    stride: usize,
+    format: PixelFormat,
+    /// The pixels on the screen, scan line by scan line without padding.
+    shadow: Vec<u32>,
+    /// The scan lines changed in `shadow` but not yet copied.
+    dirty: Range<usize>,
+}
+
+impl Screen {
+    fn pixel(&self, color: Rgb888) -> u32 {
+        let (r, g, b) = (color.r() as u32, color.g() as u32, color.b() as u32);
+        match self.format {
+            PixelFormat::Rgb => r | (g << 8) | (b << 16),
+            PixelFormat::Bgr => b | (g << 8) | (r << 16),
+        }
+    }
+
+    fn mark_dirty(&mut self, lines: Range<usize>) {
+        self.dirty = if self.dirty.is_empty() {
+            lines
+        } else {
+            self.dirty.start.min(lines.start)..self.dirty.end.max(lines.end)
+        };
+    }
+
+    /// Fill the scan lines from `start` to `end` with the background color.
+    fn clear_lines(&mut self, start: usize, end: usize) {
+        let end = end.min(self.height);
+        let pixel = self.pixel(BACKGROUND);
+        self.shadow[start * self.width..end * self.width].fill(pixel);
+        self.mark_dirty(start..end);
+    }
+
+    /// Move the screen up by `lines` scan lines and clear the ones uncovered.
+    fn scroll(&mut self, lines: usize) {
+        let lines = lines.min(self.height);
+        self.shadow.copy_within(lines * self.width.., 0);
+        self.clear_lines(self.height - lines, self.height);
+        self.mark_dirty(0..self.height);
+    }
+
+    /// Copy the changed scan lines to the framebuffer.
+    fn flush(&mut self) {
+        for y in self.dirty.clone() {
+            let src = &self.shadow[y * self.width..][..self.width];
+            // SAFETY: The scan line is inside the framebuffer.
+            unsafe {
+                ptr::copy_nonoverlapping(src.as_ptr(), self.base.add(y * self.stride), src.len())
+            };
+        }
+        self.dirty = 0..0;
+    }
+}
+
+impl OriginDimensions for Screen {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+impl DrawTarget for Screen {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (x, y) = match (usize::try_from(point.x), usize::try_from(point.y)) {
+                (Ok(x), Ok(y)) if x < self.width && y < self.height => (x, y),
+                _ => continue,
+            };
+            self.shadow[y * self.width + x] = self.pixel(color);
+            self.mark_dirty(y..y + 1);
+        }
+        Ok(())
+    }
+}
+
+pub struct Console {
+    screen: Screen,
+    style: MonoTextStyle<'static, Rgb888>,
+    columns: usize,
+    rows: usize,
+    column: usize,
+    row: usize,
+    _phys: Arc<Phys>,
+}
+
+// [`Console`] lives in the kernel space and is always accessed under the lock
+// of the logger.
+unsafe impl Send for Console {}
+
+impl Console {
+    /// Map the framebuffer and clear the screen.
+    pub fn new(fb: &Framebuffer) -> sv_call::Result<Console> {
+        let char_size = FONT.character_size;
+        if fb.width < char_size.width as usize
+            || fb.height < char_size.height as usize
+            || fb.stride < fb.width
+            || fb.stride * fb.height * 4 > fb.size
+        {
+            return Err(sv_call::EINVAL);
+        }
+
+        let phys = space::new_phys(fb.phys, fb.size.round_up_bit(paging::PAGE_SHIFT))?;
+        let addr = space::KRL.map(
+            None,
+            Arc::clone(&phys),
+            0,
+            space::page_aligned(phys.len()),
+            Flags::READABLE | Flags::WRITABLE | Flags::WRITE_COMBINING,
+        )?;
+
+        let mut shadow = Vec::new();
+        shadow
+            .try_reserve_exact(fb.width * fb.height)
+            .map_err(|_| sv_call::ENOMEM)?;
+        shadow.resize(fb.width * fb.height, 0);
+
+        let mut screen = Screen {
+            base: addr.cast(),
+            width: fb.width,
+            height: fb.height,
+            stride: fb.stride,
+            format: fb.format,
+            shadow,
+            dirty: 0..0,
+        };
+        screen.clear_lines(0, fb.height);
+        screen.flush();
+        Ok(Console {
+            columns: fb.width / char_size.width as usize,
+            rows: fb.height / char_size.height as usize,
+            screen,
+            style: MonoTextStyleBuilder::new()
+                .font(FONT)
+                .text_color(FOREGROUND)
+                .background_color(BACKGROUND)
+                .build(),
+            column: 0,
+            row: 0,
+            _phys: phys,
+        })
+    }
+
+    fn new_line(&mut self) {
+        self.column = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+            return;
+        }
+
+        self.screen.scroll(FONT.character_size.height as usize);
+    }
+
+    fn draw_cell(&mut self, column: usize, row: usize, c: char) {
+        let char_size = FONT.character_size;
+        let position = Point::new(
+            (column * char_size.width as usize) as i32,
+            (row * char_size.height as usize) as i32,
+        );
+        let mut buf = [0; 4];
+        let _ = Text::with_baseline(c.encode_utf8(&mut buf), position, self.style, Baseline::Top)
+            .draw(&mut self.screen);
+    }
+
+    fn put_char(&mut self, c: char) {
+        match c {
+            '\n' => return self.new_line(),
+            '\r' => {
+                self.column = 0;
+                return;
+            }
+            '\t' => {
+                let next = (self.column / TAB_WIDTH + 1) * TAB_WIDTH;
+                while self.column < next.min(self.columns) {
+                    self.put_char(' ');
+                }
+                return;
+            }
+            _ => {}
+        }
+        if self.column >= self.columns {
+            self.new_line();
+        }
+
+        self.draw_cell(self.column, self.row, c);
+        self.column += 1;
+    }
+}
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.chars().for_each(|c| self.put_char(c));
+        self.screen.flush();
+        Ok(())
+    }
+}
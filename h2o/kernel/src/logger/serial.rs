@@ -28,23 +28,30 @@ impl Output {
 }
 
 impl Output {
-    // unsafe fn has_data(&self) -> bool {
-    //       (self.0.read_offset(5) & 1) != 0
-    // }
+    /// Whether a byte has been received.
+    pub unsafe fn has_data(&self) -> bool {
+        (self.0.read_offset(5) & 1) != 0
+    }
 
     unsafe fn buf_full(&self) -> bool {
         (self.0.read_offset(5) & 0x20) == 0
     }
 
-    // unsafe fn in_char(&self) -> u8 {
-    //       while has_data() {
-    //             core::hint::spin_loop();
-    //       }
-    //       self.0.read()
-    // }
+    /// Receive a byte from the serial port, waiting until there is one.
+    pub unsafe fn in_char(&mut self) -> u8 {
+        while !self.has_data() {
+            hint::spin_loop();
+        }
+        self.0.read_offset(0)
+    }
+
+    /// Raise interrupts when bytes are received.
+    pub unsafe fn enable_recv_intr(&mut self) {
+        self.0.write_offset(1, 0x01);
+    }
 
     /// Output a character byte to the serial port for logging.
-    unsafe fn out_char(&mut self, c: u8) {
+    pub unsafe fn out_char(&mut self, c: u8) {
         self.flush();
         self.0.write(c);
     }
@@ -4,13 +4,14 @@
 //! higher level, especially for large objects like APIC.
 
 mod phys;
+mod tlb;
 mod virt;
 
 cfg_if::cfg_if! {
     if #[cfg(target_arch = "x86_64")] {
         #[path = "space/x86_64/mod.rs"]
         mod arch;
-        pub use self::arch::{page_fault, ErrCode as PageFaultErrCode};
+        pub use self::arch::{page_fault, query_current, ErrCode as PageFaultErrCode};
     }
 }
 
@@ -28,8 +29,11 @@ use spin::Mutex;
 pub use sv_call::mem::Flags;
 use sv_call::mem::PhysOptions;
 
-pub use self::{arch::init_pgc, phys::*, virt::*};
-use crate::sched::{task, PREEMPT};
+pub use self::{arch::init_pgc, phys::*, tlb::tlb_shootdown_handler, virt::*};
+use crate::{
+    cpu::CpuMask,
+    sched::{task, PREEMPT},
+};
 
 type ArchSpace = arch::Space;
 
@@ -77,6 +81,8 @@ pub struct Space {
     arch: ArchSpace,
     root: Arc<Virt>,
     vdso: Mutex<Option<LAddr>>,
+    /// The CPUs that may hold cached translations of this space.
+    cpus: Mutex<CpuMask>,
 }
 
 unsafe impl Send for Space {}
@@ -89,6 +95,7 @@ impl Space {
             arch: ArchSpace::new(),
             root: Virt::new_root(ty, Weak::clone(me)),
             vdso: Mutex::new(None),
+            cpus: Mutex::new(CpuMask::ZERO),
         }))
     }
 
@@ -96,6 +103,16 @@ impl Space {
         &self.root
     }
 
+    /// Invalidate the translations in `batch` on other CPUs that have the space
+    /// loaded.
+    ///
+    /// The kernel half is shared among all the spaces, so the kernel space
+    /// never drops a CPU from its set once loaded.
+    fn flush_tlb(&self, batch: tlb::Batch) {
+        let targets = PREEMPT.scope(|| *self.cpus.lock());
+        tlb::shootdown(targets, batch);
+    }
+
     pub fn assert_mapped(&self, base: LAddr, len: usize) {
         PREEMPT.scope(|| {
             for offset in (0..len).step_by(paging::PAGE_SIZE) {
@@ -145,6 +162,10 @@ pub(crate) unsafe fn unmap(ptr: NonNull<u8>) -> sv_call::Result {
         ret.map_or(Err(sv_call::ENOENT), |child| {
             let end = child.end(base);
             let _ = KRL.arch.unmaps(base..end);
+
+            let mut batch = tlb::Batch::new();
+            batch.push(base..end);
+            KRL.flush_tlb(batch);
             Ok(())
         })
     })
@@ -173,7 +194,9 @@ pub fn init_stack(virt: &Arc<Virt>, size: usize) -> sv_call::Result<LAddr> {
 ///
 /// The function must be called only once from each application CPU.
 pub unsafe fn init() {
+    unsafe { arch::init_pat() };
     let space = Arc::clone(&KRL);
+    PREEMPT.scope(|| space.cpus.lock().set(unsafe { crate::cpu::id() }, true));
     unsafe { space.arch.load() };
     CURRENT = Some(space);
 }
@@ -213,6 +236,18 @@ where
     })
 }
 
+/// Try to resolve a page fault at `addr` in the current space by committing
+/// the page of a lazy mapping.
+///
+/// `blocking` must be false if the fault is raised from the kernel, which may
+/// be holding the locks needed here.
+pub(crate) fn handle_fault(addr: LAddr, access: Flags, blocking: bool) -> bool {
+    PREEMPT.scope(|| match unsafe { CURRENT.as_ref() } {
+        Some(space) => space.root.commit_fault(space, addr, access, blocking),
+        None => false,
+    })
+}
+
 /// Set the current memory space of the current CPU.
 ///
 /// # Safety
@@ -221,8 +256,17 @@ where
 pub unsafe fn set_current(space: Arc<Space>) -> Arc<Space> {
     PREEMPT.scope(|| {
         if !Arc::ptr_eq(current(), &space) {
+            let cpu = crate::cpu::id();
+            // Publish the CPU before loading the space, so that any later
+            // shootdown of the space won't miss us.
+            space.cpus.lock().set(cpu, true);
             space.arch.load();
-            CURRENT.replace(space).expect("No current space available")
+
+            let old = CURRENT.replace(space).expect("No current space available");
+            if !Arc::ptr_eq(&old, &KRL) {
+                old.cpus.lock().set(cpu, false);
+            }
+            old
         } else {
             space
         }
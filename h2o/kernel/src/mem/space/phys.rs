@@ -6,12 +6,19 @@ use alloc::{
     vec::Vec,
 };
 
+use bitop_ex::BitOpEx;
 use enum_dispatch::enum_dispatch;
-use paging::PAddr;
-use sv_call::{mem::PhysOptions, Feature, Result, EPERM};
+use paging::{PAddr, PAGE_SHIFT};
+use sv_call::{mem::PhysOptions, Feature, Result, EINVAL, EPERM};
 
 use crate::{
-    sched::{task::hdl::DefaultFeature, Event},
+    sched::{
+        task::{
+            hdl::{DefaultFeature, Koid},
+            Charge, Job, Limit,
+        },
+        Event,
+    },
     syscall::{In, Out, UserPtr},
 };
 
@@ -38,11 +45,48 @@ pub trait PhysTrait {
 
     fn len(&self) -> usize;
 
+    /// Pin the pages of the range in place and return their physical ranges.
+    ///
+    /// The pages are always private to the object, even if `write` is false,
+    /// so that they can be handed to devices or mapped writable.
     fn pin(&self, offset: usize, len: usize, write: bool) -> Result<Vec<(PAddr, usize)>>;
 
+    /// The same as [`PhysTrait::pin`], but fails with `EAGAIN` instead of
+    /// spinning if the object is locked.
+    fn try_pin(&self, offset: usize, len: usize, write: bool) -> Result<Vec<(PAddr, usize)>> {
+        self.pin(offset, len, write)
+    }
+
     fn unpin(&self, offset: usize, len: usize);
 
-    fn create_sub(&self, offset: usize, len: usize, copy: bool) -> Result<Arc<Phys>>;
+    fn koid(&self) -> Koid;
+
+    /// The object this one is created from with [`PhysTrait::create_sub`].
+    fn parent(&self) -> Option<Koid>;
+
+    /// Whether every mapping of the object bypasses the CPU caches.
+    fn uncached(&self) -> bool {
+        false
+    }
+
+    /// Whether the object shares some of its pages with others, which are to
+    /// be copied on the first write.
+    fn copy_on_write(&self) -> bool {
+        false
+    }
+
+    /// Create a sub-object of the range, sharing its memory or copying it.
+    ///
+    /// A copy is charged to `job`, the job of the caller, rather than to the
+    /// job of this object, so that it can't be used to get past the limit of
+    /// the caller.
+    fn create_sub(
+        &self,
+        offset: usize,
+        len: usize,
+        copy: bool,
+        job: &Arc<Job>,
+    ) -> Result<Arc<Phys>>;
 
     fn base(&self) -> PAddr;
 
@@ -91,6 +135,9 @@ unsafe impl DefaultFeature for Phys {
             | Feature::WRITE
             | Feature::EXECUTE
             | Feature::WAIT
+            | Feature::DUPLICATE
+            | Feature::MAP
+            | Feature::DERIVE
     }
 }
 
@@ -103,13 +150,97 @@ pub fn new_phys(base: PAddr, size: usize) -> Result<Arc<Phys>> {
 ///
 /// Returns error if the heap memory is exhausted or the size is zero.
 pub fn allocate_phys(size: usize, options: PhysOptions, contiguous: bool) -> Result<Arc<Phys>> {
+    allocate_impl(size, options, contiguous, None)
+}
+
+/// The same as [`allocate_phys`], but the memory is charged to `job` until the
+/// object is dropped.
+pub fn allocate_phys_in(
+    job: &Arc<Job>,
+    size: usize,
+    options: PhysOptions,
+    contiguous: bool,
+) -> Result<Arc<Phys>> {
+    let charge = job.charge(Limit::Memory, size.round_up_bit(PAGE_SHIFT))?;
+    allocate_impl(size, options, contiguous, Some(charge))
+}
+
+fn allocate_impl(
+    size: usize,
+    options: PhysOptions,
+    contiguous: bool,
+    charge: Option<Charge>,
+) -> Result<Arc<Phys>> {
     let resizable = options.contains(PhysOptions::RESIZABLE);
+    let contiguous = contiguous || options.contains(PhysOptions::CONTIGUOUS);
     Ok(Arc::try_new(if contiguous {
         if resizable {
             return Err(EPERM);
         }
-        Phys::from(Cont::allocate(size, options.contains(PhysOptions::ZEROED))?)
+        Phys::from(Cont::allocate(size, options, charge)?)
     } else {
-        Phys::from(Ext::new(size))
+        if options.intersects(PhysOptions::BELOW_4G | PhysOptions::UNCACHED) {
+            return Err(EINVAL);
+        }
+        Phys::from(Ext::new(size, charge))
     })?)
 }
+
+/// A range of a physical object pinned for device access, which is unpinned
+/// when dropped.
+#[derive(Debug)]
+pub struct Pinned {
+    koid: Koid,
+    phys: Arc<Phys>,
+    offset: usize,
+    len: usize,
+    ranges: Vec<(PAddr, usize)>,
+}
+
+impl Pinned {
+    pub fn new(phys: Arc<Phys>, offset: usize, len: usize, write: bool) -> Result<Self> {
+        let pages = phys.pin(offset, len, write)?;
+        // Merge the adjacent pages so that the list is as short as possible.
+        let mut ranges = Vec::<(PAddr, usize)>::new();
+        for (base, size) in pages {
+            match ranges.last_mut() {
+                Some((last, last_size)) if **last + *last_size == *base => *last_size += size,
+                _ => ranges.push((base, size)),
+            }
+        }
+        Ok(Pinned {
+            koid: Koid::new(),
+            phys,
+            offset,
+            len,
+            ranges,
+        })
+    }
+
+    #[inline]
+    pub fn ranges(&self) -> &[(PAddr, usize)] {
+        &self.ranges
+    }
+
+    #[inline]
+    pub fn phys(&self) -> &Arc<Phys> {
+        &self.phys
+    }
+
+    #[inline]
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+}
+
+impl Drop for Pinned {
+    fn drop(&mut self) {
+        self.phys.unpin(self.offset, self.len);
+    }
+}
+
+unsafe impl DefaultFeature for Pinned {
+    fn default_features() -> Feature {
+        Feature::SEND | Feature::SYNC | Feature::READ | Feature::DUPLICATE
+    }
+}
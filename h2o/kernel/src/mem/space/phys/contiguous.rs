@@ -10,38 +10,63 @@ use core::{
 
 use bitop_ex::BitOpEx;
 use paging::{LAddr, PAddr, PAGE_SHIFT, PAGE_SIZE};
-use sv_call::{Result, EPERM};
+use pmm::PfType;
+use sv_call::{mem::PhysOptions, Result, EPERM};
 
 use super::PhysTrait;
 use crate::{
-    sched::{Arsc, BasicEvent, Event},
+    sched::{
+        task::{hdl::Koid, Charge, Job, Limit},
+        Arsc, BasicEvent, Event, PREEMPT,
+    },
     syscall::{In, Out, UserPtr},
 };
 
+/// Where the memory of a physical object comes from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Source {
+    /// Memory not owned by the kernel, such as MMIO regions.
+    Manual,
+    /// The kernel heap.
+    Heap,
+    /// The page frames below 4GB.
+    Low,
+}
+
 #[derive(Debug)]
 struct PhysInner {
-    from_allocator: bool,
+    source: Source,
     base: PAddr,
     size: usize,
+    uncached: bool,
+    /// The memory charged to the job that allocated it.
+    charge: Option<Charge>,
 }
 
 impl PhysInner {
-    unsafe fn new_manual(from_allocator: bool, base: PAddr, size: usize) -> PhysInner {
+    unsafe fn new_manual(source: Source, base: PAddr, size: usize, uncached: bool) -> PhysInner {
         PhysInner {
-            from_allocator,
+            source,
             base,
             size,
+            uncached,
+            charge: None,
         }
     }
 }
 
 impl Drop for PhysInner {
     fn drop(&mut self) {
-        if self.from_allocator {
-            let ptr = unsafe { self.base.to_laddr(minfo::ID_OFFSET).as_non_null_unchecked() };
-            let layout =
-                unsafe { Layout::from_size_align_unchecked(self.size, PAGE_SIZE) }.pad_to_align();
-            unsafe { Global.deallocate(ptr, layout) };
+        match self.source {
+            Source::Manual => {}
+            Source::Heap => {
+                let ptr = unsafe { self.base.to_laddr(minfo::ID_OFFSET).as_non_null_unchecked() };
+                let layout = unsafe { Layout::from_size_align_unchecked(self.size, PAGE_SIZE) }
+                    .pad_to_align();
+                unsafe { Global.deallocate(ptr, layout) };
+            }
+            Source::Low => PREEMPT
+                .scope(|| unsafe { pmm::dealloc_pages_exact(self.size >> PAGE_SHIFT, self.base) }),
         }
     }
 }
@@ -51,6 +76,9 @@ pub struct Phys {
     offset: usize,
     len: usize,
     inner: Arsc<PhysInner>,
+    koid: Koid,
+    /// See [`PhysTrait::parent`].
+    parent: Option<Koid>,
 }
 
 impl From<Arsc<PhysInner>> for Phys {
@@ -59,6 +87,8 @@ impl From<Arsc<PhysInner>> for Phys {
             offset: 0,
             len: inner.size,
             inner,
+            koid: Koid::new(),
+            parent: None,
         }
     }
 }
@@ -66,38 +96,53 @@ impl From<Arsc<PhysInner>> for Phys {
 impl Phys {
     #[inline]
     pub fn new(base: PAddr, size: usize) -> Result<Self> {
-        unsafe { Arsc::try_new(PhysInner::new_manual(false, base, size)) }
+        unsafe { Arsc::try_new(PhysInner::new_manual(Source::Manual, base, size, false)) }
             .map_err(sv_call::Error::from)
             .map(Self::from)
     }
 
+    /// Allocate the memory from the kernel heap, or from the page frames
+    /// below 4GB if `PhysOptions::BELOW_4G` is specified. The memory is
+    /// returned to the job of `charge` along with the object.
+    ///
     /// # Errors
     ///
-    /// Returns error if the heap memory is exhausted or the size is zero.
-    pub fn allocate(size: usize, zeroed: bool) -> Result<Self> {
+    /// Returns error if the memory is exhausted or the size is zero.
+    pub fn allocate(size: usize, options: PhysOptions, charge: Option<Charge>) -> Result<Self> {
         if size == 0 {
             return Err(sv_call::ENOMEM);
         }
         let size = size.round_up_bit(PAGE_SHIFT);
+        let zeroed = options.contains(PhysOptions::ZEROED);
 
         let mut inner = Arsc::try_new_uninit()?;
-        let layout = unsafe { Layout::from_size_align_unchecked(size, PAGE_SIZE) }.pad_to_align();
-        let mem = if zeroed {
-            Global.allocate_zeroed(layout)
+        let (source, base) = if options.contains(PhysOptions::BELOW_4G) {
+            let base = PREEMPT
+                .scope(|| pmm::alloc_pages_exact(size >> PAGE_SHIFT, Some(PfType::Low)))
+                .ok_or(sv_call::ENOMEM)?;
+            if zeroed {
+                unsafe { base.to_laddr(minfo::ID_OFFSET).write_bytes(0, size) };
+            }
+            (Source::Low, base)
         } else {
-            Global.allocate(layout)
+            let layout =
+                unsafe { Layout::from_size_align_unchecked(size, PAGE_SIZE) }.pad_to_align();
+            let mem = if zeroed {
+                Global.allocate_zeroed(layout)
+            } else {
+                Global.allocate(layout)
+            };
+            let ptr = mem.map_err(sv_call::Error::from)?;
+            (Source::Heap, LAddr::from(ptr).to_paddr(minfo::ID_OFFSET))
         };
 
-        mem.map(|ptr| unsafe {
-            Arsc::get_mut_unchecked(&mut inner).write(PhysInner::new_manual(
-                true,
-                LAddr::from(ptr).to_paddr(minfo::ID_OFFSET),
-                size,
-            ));
+        let uncached = options.contains(PhysOptions::UNCACHED);
+        let mut value = unsafe { PhysInner::new_manual(source, base, size, uncached) };
+        value.charge = charge;
+        Ok(Self::from(unsafe {
+            Arsc::get_mut_unchecked(&mut inner).write(value);
             Arsc::assume_init(inner)
-        })
-        .map_err(sv_call::Error::from)
-        .map(Self::from)
+        }))
     }
 
     fn raw(&self) -> *mut u8 {
@@ -130,7 +175,25 @@ impl PhysTrait for Phys {
 
     fn unpin(&self, _: usize, _: usize) {}
 
-    fn create_sub(&self, offset: usize, len: usize, copy: bool) -> Result<Arc<super::Phys>> {
+    fn uncached(&self) -> bool {
+        self.inner.uncached
+    }
+
+    fn koid(&self) -> Koid {
+        self.koid
+    }
+
+    fn parent(&self) -> Option<Koid> {
+        self.parent
+    }
+
+    fn create_sub(
+        &self,
+        offset: usize,
+        len: usize,
+        copy: bool,
+        job: &Arc<Job>,
+    ) -> Result<Arc<super::Phys>> {
         if offset.contains_bit(PAGE_SHIFT) || len.contains_bit(PAGE_SHIFT) {
             return Err(sv_call::EALIGN);
         }
@@ -139,8 +202,12 @@ impl PhysTrait for Phys {
         let end = new_offset.wrapping_add(len);
         if self.offset <= new_offset && new_offset < end && end <= self.offset + self.len {
             let mut ret = Arc::try_new_uninit()?;
-            let phys = if copy {
-                let child = Self::allocate(len, true)?;
+            let mut phys = if copy {
+                let charge = job.charge(Limit::Memory, len)?;
+                // Contiguous memory can be written through any of its mappings
+                // without faulting, so it can't be shared copy-on-write. The
+                // copy overwrites every byte, so don't zero it beforehand.
+                let child = Self::allocate(len, PhysOptions::empty(), Some(charge))?;
                 let dst = child.raw();
                 unsafe {
                     let src = self.raw().add(offset);
@@ -152,8 +219,11 @@ impl PhysTrait for Phys {
                     offset: new_offset,
                     len,
                     inner: Arsc::clone(&self.inner),
+                    koid: Koid::new(),
+                    parent: None,
                 }
             };
+            phys.parent = Some(self.koid);
             Arc::get_mut(&mut ret).unwrap().write(phys.into());
             Ok(unsafe { ret.assume_init() })
         } else {
@@ -23,7 +23,10 @@ use sv_call::{
 
 use super::PhysTrait;
 use crate::{
-    sched::{Arsc, BasicEvent, Event, PREEMPT},
+    sched::{
+        task::{hdl::Koid, Charge, Job, Limit},
+        Arsc, BasicEvent, Event, PREEMPT,
+    },
     syscall::{In, Out, UserPtr},
 };
 
@@ -162,6 +165,8 @@ struct PageList {
     pages: BTreeMap<usize, PageNode>,
     count: usize,
     pin_count: usize,
+    /// The pages of `count` charged to the job that allocated the object.
+    charge: Option<Charge>,
 }
 
 #[derive(Debug)]
@@ -169,6 +174,9 @@ pub struct Phys {
     event: Arc<BasicEvent>,
     len: AtomicUsize,
     list: Mutex<PageList>,
+    koid: Koid,
+    /// See [`PhysTrait::parent`].
+    parent: Option<Koid>,
 }
 
 impl PageList {
@@ -198,6 +206,12 @@ impl PageList {
             if parent_index < self.parent_end {
                 return match list.commit_impl(parent_index, write) {
                     Ok(Commit::Ref(base)) => Ok(Commit::Ref(base)),
+                    // Keep the page from the grandparent and hand out a copy of
+                    // it if a private one is requested.
+                    Ok(Commit::Insert(page)) if self.branch => {
+                        let node = ent.insert(PageNode::new(page));
+                        node.get_from_branch(write).map(|(ret, _)| ret)
+                    }
                     Ok(Commit::Insert(page)) => {
                         let base = page.base;
                         ent.insert(PageNode::new(page));
@@ -247,12 +261,14 @@ impl PageList {
         Ok(())
     }
 
-    fn create_sub(&mut self, offset: usize, len: usize) -> Result<Phys, Error> {
+    fn create_sub(&mut self, offset: usize, len: usize, job: &Arc<Job>) -> Result<Phys, Error> {
         if self.pin_count > 0 {
             return Err(Error::Pinned);
         }
         let start = offset >> PAGE_SHIFT;
         let end = (offset + len).div_ceil_bit(PAGE_SHIFT);
+        let size = (end - start) << PAGE_SHIFT;
+        let charge = job.charge(Limit::Memory, size).map_err(Error::Other)?;
 
         let branch = {
             let mut branch = Arsc::try_new_uninit().map_err(|_| Error::Alloc)?;
@@ -269,7 +285,10 @@ impl PageList {
                         pages: mem::take(&mut self.pages),
                         count: self.count,
                         pin_count: self.pin_count,
+                        charge: None,
                     }),
+                    koid: Koid::new(),
+                    parent: None,
                 });
                 Arsc::assume_init(branch)
             }
@@ -286,7 +305,10 @@ impl PageList {
                 pages: BTreeMap::new(),
                 count: end - start,
                 pin_count: 0,
+                charge: Some(charge),
             }),
+            koid: Koid::new(),
+            parent: None,
         };
 
         self.parent = Some(branch);
@@ -296,30 +318,26 @@ impl PageList {
         Ok(sub)
     }
 
-    fn pin_impl(&mut self, index: usize, write: bool) -> Result<(), Error> {
+    fn pin_impl(&mut self, index: usize) -> Result<(), Error> {
         assert!(index < self.count, "Out of range");
-        if let Some(node) = self.pages.get_mut(&index) {
-            if node.pin_count >= isize::MAX as usize || self.pin_count >= isize::MAX as usize {
-                return Err(Error::MaxPinCount);
-            }
-            node.pin_count += 1;
-            self.pin_count += 1;
-        } else if write {
-            let parent = self.parent.clone().expect("Uncommitted page");
-            let parent_index = self.parent_start + index;
-            assert!(parent_index < self.parent_end, "Out of range");
-            let mut list = parent.list.try_lock().ok_or(Error::WouldBlock)?;
-            list.pin_impl(parent_index, write)?
+        let node = self.pages.get_mut(&index).expect("Uncommitted page");
+        if node.pin_count >= isize::MAX as usize || self.pin_count >= isize::MAX as usize {
+            return Err(Error::MaxPinCount);
         }
+        node.pin_count += 1;
+        self.pin_count += 1;
         Ok(())
     }
 
-    fn pin(&mut self, start: usize, end: usize, write: bool) -> Result<Vec<(PAddr, usize)>, Error> {
+    /// Pin the pages in the range, always committing private ones. Otherwise
+    /// the zero page or a page of the parent could be handed out unpinned,
+    /// e.g. to a device doing DMA.
+    fn pin(&mut self, start: usize, end: usize) -> Result<Vec<(PAddr, usize)>, Error> {
         let bases = (start..end)
-            .map(|index| self.commit(index, write).map(|base| (base, PAGE_SIZE)))
+            .map(|index| self.commit(index, true).map(|base| (base, PAGE_SIZE)))
             .collect::<Result<Vec<_>, _>>()?;
         for index in start..end {
-            if let Err(err) = self.pin_impl(index, write) {
+            if let Err(err) = self.pin_impl(index) {
                 for index in start..index {
                     self.unpin_impl(index);
                 }
@@ -329,6 +347,17 @@ impl PageList {
         Ok(bases)
     }
 
+    /// Commit the page at `index` for an access without the lock held, pinning
+    /// it if it's owned by the list. Returns whether it's pinned.
+    fn hold(&mut self, index: usize, write: bool) -> Result<(PAddr, bool), Error> {
+        let base = self.commit(index, write)?;
+        let pinned = self.pages.contains_key(&index);
+        if pinned {
+            self.pin_impl(index)?;
+        }
+        Ok((base, pinned))
+    }
+
     fn unpin_impl(&mut self, index: usize) {
         assert!(index < self.count, "Out of range");
         if let Some(node) = self.pages.get_mut(&index) {
@@ -347,10 +376,14 @@ impl PageList {
         if self.pin_count > 0 {
             return Err(Error::Pinned);
         }
-        if new_count < self.count {
-            for index in self.count..new_count {
-                let _ = self.decommit(index);
-            }
+        // Drop the pages out of range before releasing their charge.
+        for index in new_count..self.count {
+            let _ = self.decommit(index);
+        }
+        if let Some(ref mut charge) = self.charge {
+            charge
+                .resize(new_count << PAGE_SHIFT)
+                .map_err(Error::Other)?;
         }
         self.count = new_count;
 
@@ -359,7 +392,7 @@ impl PageList {
 }
 
 impl Phys {
-    pub fn new(len: usize) -> Self {
+    pub fn new(len: usize, charge: Option<Charge>) -> Self {
         Phys {
             event: BasicEvent::new(0),
             len: AtomicUsize::new(len),
@@ -371,7 +404,10 @@ impl Phys {
                 pages: BTreeMap::new(),
                 count: len.div_ceil_bit(PAGE_SHIFT),
                 pin_count: 0,
+                charge,
             }),
+            koid: Koid::new(),
+            parent: None,
         }
     }
 
@@ -380,22 +416,32 @@ impl Phys {
         let pos = pos.min(self_len);
         let len = (self_len - pos).min(len);
 
-        let mut list = self.list.try_lock().ok_or(Error::WouldBlock)?;
         let mut read_len = 0;
 
         let start = pos >> PAGE_SHIFT;
         let end = (pos + len).div_ceil_bit(PAGE_SHIFT);
         let mut pos_in_page = pos - (start << PAGE_SHIFT);
-        for base in (start..end).map(|index| list.commit(index, false)) {
-            match base {
-                Ok(base) => unsafe {
+        for index in start..end {
+            // The lock is not held while copying to user space, whose pages may
+            // be lazily mapped from this object.
+            let held = self
+                .list
+                .try_lock()
+                .ok_or(Error::WouldBlock)?
+                .hold(index, false);
+            match held {
+                Ok((base, pinned)) => unsafe {
                     let src = base.to_laddr(minfo::ID_OFFSET);
                     let src = LAddr::from(src.val() + pos_in_page);
-                    let len = (len - read_len).min(PAGE_SIZE);
+                    let len = (len - read_len).min(PAGE_SIZE - pos_in_page);
 
                     let buffer = UserPtr::<Out>::new(buffer.as_ptr().add(read_len));
                     let src = slice::from_raw_parts(*src, len);
-                    buffer.write_slice(src).map_err(Error::Other)?;
+                    let res = buffer.write_slice(src);
+                    if pinned {
+                        self.release(index);
+                    }
+                    res.map_err(Error::Other)?;
 
                     read_len += len;
                     pos_in_page = 0;
@@ -411,21 +457,30 @@ impl Phys {
         let pos = pos.min(self_len);
         let len = (self_len - pos).min(len);
 
-        let mut list = self.list.try_lock().ok_or(Error::WouldBlock)?;
         let mut written_len = 0;
 
         let start = pos >> PAGE_SHIFT;
         let end = (pos + len).div_ceil_bit(PAGE_SHIFT);
         let mut pos_in_page = pos - (start << PAGE_SHIFT);
-        for base in (start..end).map(|index| list.commit(index, true)) {
-            match base {
-                Ok(base) => unsafe {
+        for index in start..end {
+            // The same as in `read`.
+            let held = self
+                .list
+                .try_lock()
+                .ok_or(Error::WouldBlock)?
+                .hold(index, true);
+            match held {
+                Ok((base, pinned)) => unsafe {
                     let src = base.to_laddr(minfo::ID_OFFSET);
                     let src = LAddr::from(src.val() + pos_in_page);
-                    let len = (len - written_len).min(PAGE_SIZE);
+                    let len = (len - written_len).min(PAGE_SIZE - pos_in_page);
 
                     let buffer = UserPtr::<In>::new(buffer.as_ptr().add(written_len));
-                    buffer.read_slice(*src, len).map_err(Error::Other)?;
+                    let res = buffer.read_slice(*src, len);
+                    if pinned {
+                        self.release(index);
+                    }
+                    res.map_err(Error::Other)?;
 
                     written_len += len;
                     pos_in_page = 0;
@@ -436,6 +491,11 @@ impl Phys {
         Ok(written_len)
     }
 
+    /// Release the page held for an access in `read` or `write`.
+    fn release(&self, index: usize) {
+        PREEMPT.scope(|| self.list.lock().unpin_impl(index));
+    }
+
     // pub fn commit(&self, start: usize, end: usize, write: bool) -> Result<(),
     // Error> {     let mut list =
     // self.list.try_lock().ok_or(Error::WouldBlock)?;     (start..end).
@@ -451,11 +511,11 @@ impl Phys {
     //     Ok(())
     // }
 
-    pub fn create_sub(&self, offset: usize, len: usize) -> Result<Phys, Error> {
+    pub fn create_sub(&self, offset: usize, len: usize, job: &Arc<Job>) -> Result<Phys, Error> {
         self.list
             .try_lock()
             .ok_or(Error::WouldBlock)?
-            .create_sub(offset, len)
+            .create_sub(offset, len, job)
     }
 
     pub fn resize(&self, new_len: usize) -> Result<(), Error> {
@@ -481,10 +541,22 @@ impl PhysTrait for Phys {
     }
 
     #[inline]
-    fn pin(&self, offset: usize, len: usize, write: bool) -> sv_call::Result<Vec<(PAddr, usize)>> {
+    fn pin(&self, offset: usize, len: usize, _: bool) -> sv_call::Result<Vec<(PAddr, usize)>> {
         let start = offset >> PAGE_SHIFT;
         let end = (offset + len).div_ceil_bit(PAGE_SHIFT);
-        let ret = PREEMPT.scope(|| self.list.lock().pin(start, end, write))?;
+        let ret = PREEMPT.scope(|| self.list.lock().pin(start, end))?;
+        self.event.notify(0, SIG_READ | SIG_WRITE);
+        Ok(ret)
+    }
+
+    #[inline]
+    fn try_pin(&self, offset: usize, len: usize, _: bool) -> sv_call::Result<Vec<(PAddr, usize)>> {
+        let start = offset >> PAGE_SHIFT;
+        let end = (offset + len).div_ceil_bit(PAGE_SHIFT);
+        let ret = PREEMPT.scope(|| {
+            let mut list = self.list.try_lock().ok_or(Error::WouldBlock)?;
+            list.pin(start, end)
+        })?;
         self.event.notify(0, SIG_READ | SIG_WRITE);
         Ok(ret)
     }
@@ -497,18 +569,35 @@ impl PhysTrait for Phys {
         self.event.notify(0, SIG_READ | SIG_WRITE);
     }
 
+    #[inline]
+    fn koid(&self) -> Koid {
+        self.koid
+    }
+
+    #[inline]
+    fn parent(&self) -> Option<Koid> {
+        self.parent
+    }
+
+    #[inline]
+    fn copy_on_write(&self) -> bool {
+        PREEMPT.scope(|| self.list.lock().parent.is_some())
+    }
+
     fn create_sub(
         &self,
         offset: usize,
         len: usize,
         copy: bool,
+        job: &Arc<Job>,
     ) -> sv_call::Result<Arc<super::Phys>> {
         if !copy {
             return Err(EPERM);
         }
         let mut ret = Arc::try_new_uninit()?;
         let sub = Arc::get_mut(&mut ret).unwrap();
-        let value = self.create_sub(offset, len)?;
+        let mut value = self.create_sub(offset, len, job)?;
+        value.parent = Some(self.koid);
         self.event.notify(0, SIG_READ | SIG_WRITE);
         sub.write(value.into());
         Ok(unsafe { ret.assume_init() })
@@ -0,0 +1,134 @@
+//! # TLB shootdown.
+//!
+//! The paging routines only invalidate the translations cached by the current
+//! CPU. When a mapping is removed or downgraded, every other CPU that may have
+//! the space loaded receives the batch of affected ranges in its inbox along
+//! with an IPI, and the initiator spins until all of them have acknowledged
+//! it. Only after that may the backing frames be recycled.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    hint,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering::*},
+};
+
+use archop::Azy;
+use bitop_ex::BitOpEx;
+use crossbeam_queue::SegQueue;
+use paging::{LAddr, PAGE_SHIFT};
+
+use super::arch;
+use crate::{cpu::CpuMask, sched::PREEMPT};
+
+/// Invalidating more pages than this costs more than flushing the whole TLB.
+const MAX_INVLPG: usize = 32;
+
+static INBOX: Azy<Vec<SegQueue<Arc<Request>>>> = Azy::new(|| {
+    let count = crate::cpu::count();
+    core::iter::repeat_with(SegQueue::new).take(count).collect()
+});
+
+/// A batch of virtual ranges whose cached translations are to be invalidated.
+#[derive(Debug, Default)]
+pub(super) struct Batch {
+    ranges: Vec<Range<LAddr>>,
+    pages: usize,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, range: Range<LAddr>) {
+        if range.start < range.end {
+            let len = range.end.val() - range.start.val();
+            self.pages += len.round_up_bit(PAGE_SHIFT) >> PAGE_SHIFT;
+            if !self.flushes_all() {
+                self.ranges.push(range);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages == 0
+    }
+
+    fn flushes_all(&self) -> bool {
+        self.pages > MAX_INVLPG
+    }
+
+    /// # Safety
+    ///
+    /// The caller must ensure that the current CPU is not in the middle of
+    /// modifying the page tables covered by the batch.
+    unsafe fn invalidate(&self) {
+        if self.flushes_all() {
+            arch::flush_all();
+        } else {
+            for range in &self.ranges {
+                arch::invalidate(range.clone());
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Request {
+    batch: Batch,
+    pending: AtomicUsize,
+}
+
+/// Invalidate the translations in `batch` on every CPU in `targets` except
+/// the current one, and wait until all of them have done so.
+///
+/// The current CPU is expected to have already invalidated its own entries
+/// while modifying the page tables.
+pub(super) fn shootdown(mut targets: CpuMask, batch: Batch) {
+    if batch.is_empty() {
+        return;
+    }
+
+    PREEMPT.scope(|| {
+        targets.set(unsafe { crate::cpu::id() }, false);
+        let count = targets.count_ones();
+        if count == 0 {
+            return;
+        }
+
+        let req = Arc::new(Request {
+            batch,
+            pending: AtomicUsize::new(count),
+        });
+        for cpu in targets.iter_ones() {
+            INBOX[cpu].push(Arc::clone(&req));
+            if !unsafe { crate::cpu::arch::apic::ipi::tlb_shootdown(cpu) } {
+                req.pending.fetch_sub(1, Release);
+            }
+        }
+
+        while req.pending.load(Acquire) > 0 {
+            // Interrupts are disabled here, so serve the requests sent to us
+            // meanwhile, or 2 CPUs shooting down each other would deadlock.
+            unsafe { handle_requests() };
+            hint::spin_loop();
+        }
+    })
+}
+
+unsafe fn handle_requests() {
+    let inbox = &INBOX[crate::cpu::id()];
+    while let Some(req) = inbox.pop() {
+        req.batch.invalidate();
+        req.pending.fetch_sub(1, Release);
+    }
+}
+
+/// # Safety
+///
+/// This function must be called only in TLB-shootdown IPI handlers.
+pub unsafe fn tlb_shootdown_handler() {
+    crate::cpu::arch::apic::lapic(|lapic| lapic.eoi());
+    handle_requests();
+}
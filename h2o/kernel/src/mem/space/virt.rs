@@ -1,6 +1,7 @@
 use alloc::{
     collections::BTreeMap,
     sync::{Arc, Weak},
+    vec::Vec,
 };
 use core::{alloc::Layout, mem, ops::Range};
 
@@ -9,12 +10,15 @@ use paging::{LAddr, PAGE_SHIFT, PAGE_SIZE};
 use spin::Mutex;
 use sv_call::{error::*, mem::Flags, Feature, Result};
 
-use super::{paging_error, ty_to_range, Phys, Space};
+use super::{paging_error, tlb, ty_to_range, Phys, Space};
 use crate::{
     mem::space::PhysTrait,
     sched::{
         task,
-        task::{hdl::DefaultFeature, VDSO},
+        task::{
+            hdl::{DefaultFeature, Koid},
+            VDSO,
+        },
         PREEMPT,
     },
 };
@@ -23,6 +27,15 @@ use crate::{
 pub(super) enum Child {
     Virt(Arc<Virt>),
     Phys(Arc<Phys>, Flags, usize, usize),
+    /// A mapping whose pages are committed on first access. `flags` is its
+    /// current protection, which never exceeds `max`.
+    Lazy {
+        phys: Arc<Phys>,
+        max: Flags,
+        flags: Flags,
+        offset: usize,
+        len: usize,
+    },
 }
 
 impl Child {
@@ -30,6 +43,7 @@ impl Child {
         match self {
             Child::Virt(virt) => virt.len(),
             Child::Phys(.., len) => *len,
+            Child::Lazy { len, .. } => *len,
         }
     }
 
@@ -43,11 +57,13 @@ type ChildMap = BTreeMap<LAddr, Child>;
 #[derive(Debug)]
 pub struct Virt {
     ty: task::Type,
+    koid: Koid,
 
     range: Range<LAddr>,
     pub(super) space: Weak<Space>,
 
     parent: Weak<Virt>,
+    parent_koid: Option<Koid>,
     pub(super) children: Mutex<ChildMap>,
 }
 
@@ -59,9 +75,11 @@ impl Virt {
         let range = ty_to_range(ty);
         Arc::new(Virt {
             ty,
+            koid: Koid::new(),
             range: LAddr::from(range.start)..LAddr::from(range.end),
             space,
             parent: Weak::new(),
+            parent_koid: None,
             children: Mutex::new(BTreeMap::new()),
         })
     }
@@ -70,6 +88,16 @@ impl Virt {
         &self.range
     }
 
+    #[inline]
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+
+    #[inline]
+    pub fn parent_koid(&self) -> Option<Koid> {
+        self.parent_koid
+    }
+
     pub fn len(&self) -> usize {
         self.range.end.val() - self.range.start.val()
     }
@@ -89,9 +117,11 @@ impl Virt {
 
         let child = Arc::try_new(Virt {
             ty: self.ty,
+            koid: Koid::new(),
             range,
             space: Weak::clone(&self.space),
             parent: Arc::downgrade(self),
+            parent_koid: Some(self.koid),
             children: Mutex::new(BTreeMap::new()),
         })?;
         let ret = Arc::downgrade(&child);
@@ -129,6 +159,11 @@ impl Virt {
         if phys_offset.contains_bit(PAGE_SHIFT) {
             return Err(EALIGN);
         }
+        let flags = if phys.uncached() {
+            flags | Flags::UNCACHED
+        } else {
+            flags
+        };
         let phys_end = phys_offset.wrapping_add(layout.size());
         if !(phys_offset < phys_end && phys_end <= phys.len().round_up_bit(PAGE_SHIFT)) {
             return Err(ERANGE);
@@ -149,6 +184,22 @@ impl Virt {
         let virt = find_range(&children, &self.range, offset, layout)?;
         let base = virt.start;
 
+        // Writable mappings of shared pages are committed lazily as well, so
+        // that a page is only copied on its first write fault.
+        if flags.contains(Flags::LAZY) || (flags.contains(Flags::WRITABLE) && phys.copy_on_write())
+        {
+            let flags = flags - Flags::LAZY;
+            let child = Child::Lazy {
+                phys,
+                max: flags,
+                flags,
+                offset: phys_offset,
+                len: layout.size(),
+            };
+            let _ = children.insert(base, child);
+            return Ok(base);
+        }
+
         {
             let mut end = base;
             let phys = phys.pin(phys_offset, layout.size(), flags.contains(Flags::WRITABLE))?;
@@ -158,6 +209,11 @@ impl Virt {
                 if let Err(err) = space.arch.maps(virt, phys_base, flags) {
                     if base < end {
                         let _ = space.arch.unmaps(base..end);
+                        drop(children);
+
+                        let mut batch = tlb::Batch::new();
+                        batch.push(base..end);
+                        space.flush_tlb(batch);
                     }
                     return Err(paging_error(err));
                 }
@@ -183,7 +239,7 @@ impl Virt {
         }
 
         let _pree = PREEMPT.lock();
-        let children = self.children.lock();
+        let mut children = self.children.lock();
         let space = self.space.upgrade().ok_or(EKILLED)?;
 
         let vdso = *space.vdso.lock();
@@ -200,21 +256,62 @@ impl Virt {
             }
             match child {
                 Child::Virt(_) => return Err(EINVAL),
-                Child::Phys(_, f, ..) if flags.intersects(!*f) => {
+                Child::Phys(_, f, ..) | Child::Lazy { max: f, .. } if flags.intersects(!*f) => {
                     return Err(EPERM);
                 }
                 _ => {}
             }
         }
 
+        let mut batch = tlb::Batch::new();
+        let mut unpins = Vec::new();
+        let mut ret = Ok(());
         for (&base, child) in children
-            .range(start..)
+            .range_mut(start..)
             .take_while(|(&base, child)| child.end(base) <= end)
         {
-            { space.arch.reprotect(base..child.end(base), flags) }.map_err(paging_error)?;
+            let child_end = child.end(base);
+            // The cacheability of mappings is fixed once they are created.
+            let flags = match &*child {
+                Child::Phys(_, f, ..) | Child::Lazy { max: f, .. } => {
+                    let cache = Flags::UNCACHED | Flags::WRITE_COMBINING;
+                    (flags - cache) | (*f & cache)
+                }
+                _ => flags,
+            };
+            match child {
+                Child::Lazy {
+                    phys,
+                    flags: cur,
+                    offset,
+                    ..
+                } => {
+                    // The committed pages may be shared with others, so simply
+                    // drop them and let them fault in again with the new flags.
+                    committed_pages(&space, base..child_end, |page| {
+                        let phys_offset = *offset + (page.val() - base.val());
+                        unpins.push((Arc::clone(phys), phys_offset, PAGE_SIZE));
+                    });
+                    ret = space.arch.unmaps(base..child_end).map(drop);
+                    *cur = flags;
+                }
+                _ => ret = space.arch.reprotect(base..child_end, flags),
+            }
+            if ret.is_err() {
+                break;
+            }
+            batch.push(base..child_end);
         }
+        // Other CPUs may be spinning on the lock with interrupts disabled, and
+        // they must be able to acknowledge the shootdown.
+        drop(children);
+        space.flush_tlb(batch);
 
-        Ok(())
+        for (phys, offset, len) in unpins {
+            phys.unpin(offset, len);
+        }
+
+        ret.map_err(paging_error)
     }
 
     pub fn unmap(&self, base: LAddr, len: usize, drop_child: bool) -> Result {
@@ -251,17 +348,104 @@ impl Virt {
         children.append(&mut prefix);
         drop(children);
 
-        let mut ret = Ok(None);
-        for (base, child) in mid {
-            let end = child.end(base);
-            if let Child::Phys(phys, _, offset, len) = child {
-                phys.unpin(offset, len);
-                let r = space.arch.unmaps(base..end);
-                ret = ret.and(r.map_err(paging_error));
+        unmap_children(&space, &mid).map_err(paging_error)
+    }
+
+    /// Commit the page at `addr` in a lazy mapping on first access.
+    ///
+    /// Returns `false` if the access is not permitted or cannot be resolved,
+    /// so that the fault should be handled by others. If `blocking` is
+    /// false, no lock will be waited for, which is required when the fault
+    /// comes from the kernel itself.
+    pub(super) fn commit_fault(
+        &self,
+        space: &Space,
+        addr: LAddr,
+        access: Flags,
+        blocking: bool,
+    ) -> bool {
+        if !self.range.contains(&addr) {
+            return false;
+        }
+
+        let children = if blocking {
+            self.children.lock()
+        } else {
+            match self.children.try_lock() {
+                Some(children) => children,
+                None => return false,
+            }
+        };
+        let (base, child) = match children.range(..=addr).next_back() {
+            Some((&base, child)) if addr < child.end(base) => (base, child),
+            _ => return false,
+        };
+
+        let (phys, flags, offset) = match child {
+            Child::Virt(virt) => {
+                let virt = Arc::clone(virt);
+                drop(children);
+                return virt.commit_fault(space, addr, access, blocking);
             }
+            Child::Phys(..) => return false,
+            Child::Lazy {
+                phys,
+                flags,
+                offset,
+                ..
+            } => (phys, *flags, *offset),
+        };
+        if access.intersects(!flags) {
+            return false;
         }
 
-        ret.map(|_| {})
+        let page = LAddr::from(addr.val().round_down_bit(PAGE_SHIFT));
+        let page_end = LAddr::from(page.val() + PAGE_SIZE);
+        let phys_offset = offset + (page.val() - base.val());
+        let write = access.contains(Flags::WRITABLE);
+
+        match space.arch.query(page) {
+            // Another CPU has resolved the fault before us.
+            Ok((_, cur)) if !access.intersects(!cur) => return true,
+            // The page is mapped with fewer rights than the mapping, e.g. by a
+            // `reprotect` in progress, so drop it and let the access fault
+            // again. The TLB is not flushed with the lock held, for the same
+            // reason as in `reprotect`.
+            Ok(_) => {
+                let _ = space.arch.unmaps(page..page_end);
+                let phys = Arc::clone(phys);
+                drop(children);
+
+                let mut batch = tlb::Batch::new();
+                batch.push(page..page_end);
+                space.flush_tlb(batch);
+                phys.unpin(phys_offset, PAGE_SIZE);
+                return true;
+            }
+            Err(_) => {}
+        }
+
+        let pinned = if blocking {
+            phys.pin(phys_offset, PAGE_SIZE, write)
+        } else {
+            phys.try_pin(phys_offset, PAGE_SIZE, write)
+        };
+        let phys_base = match pinned.as_deref() {
+            Ok(&[(phys_base, _)]) => phys_base,
+            Ok(_) => {
+                phys.unpin(phys_offset, PAGE_SIZE);
+                return false;
+            }
+            Err(_) => return false,
+        };
+
+        // Pinned pages are always private, so they can be mapped with all the
+        // rights of the mapping even if they are only read.
+        if space.arch.maps(page..page_end, phys_base, flags).is_err() {
+            phys.unpin(phys_offset, PAGE_SIZE);
+            return false;
+        }
+        true
     }
 }
 
@@ -269,19 +453,20 @@ impl Drop for Virt {
     fn drop(&mut self) {
         let children = mem::take(self.children.get_mut());
         if let Some(space) = self.space.upgrade() {
-            for (base, child) in children {
-                let end = child.end(base);
-                if let Child::Phys(..) = child {
-                    let _ = PREEMPT.scope(|| space.arch.unmaps(base..end));
-                }
-            }
+            let _ = PREEMPT.scope(|| unmap_children(&space, &children));
         }
     }
 }
 
 unsafe impl DefaultFeature for Weak<Virt> {
     fn default_features() -> Feature {
-        Feature::SYNC | Feature::READ | Feature::WRITE | Feature::EXECUTE
+        Feature::SYNC
+            | Feature::READ
+            | Feature::WRITE
+            | Feature::EXECUTE
+            | Feature::DUPLICATE
+            | Feature::MAP
+            | Feature::DERIVE
     }
 }
 
@@ -315,6 +500,43 @@ fn check_layout(layout: Layout) -> Result<Layout> {
     Ok(layout.pad_to_align())
 }
 
+/// Unmap the mappings in `children` and release their pins.
+fn unmap_children(space: &Space, children: &ChildMap) -> core::result::Result<(), paging::Error> {
+    let mut batch = tlb::Batch::new();
+    let mut unpins = Vec::new();
+    let mut ret = Ok(());
+    for (&base, child) in children {
+        let end = child.end(base);
+        match child {
+            Child::Virt(_) => continue,
+            Child::Phys(phys, _, offset, len) => unpins.push((phys, *offset, *len)),
+            Child::Lazy { phys, offset, .. } => committed_pages(space, base..end, |page| {
+                unpins.push((phys, *offset + (page.val() - base.val()), PAGE_SIZE))
+            }),
+        }
+        ret = ret.and(space.arch.unmaps(base..end).map(drop));
+        batch.push(base..end);
+    }
+    // The pages must not be released until no CPU can access them.
+    space.flush_tlb(batch);
+
+    for (phys, offset, len) in unpins {
+        phys.unpin(offset, len);
+    }
+    ret
+}
+
+/// Call `func` with the base of every page in `range` that is present in the
+/// page tables.
+fn committed_pages(space: &Space, range: Range<LAddr>, mut func: impl FnMut(LAddr)) {
+    for addr in (range.start.val()..range.end.val()).step_by(PAGE_SIZE) {
+        let page = LAddr::from(addr);
+        if space.arch.query(page).is_ok() {
+            func(page);
+        }
+    }
+}
+
 fn check_vdso(vdso: Option<LAddr>, base: LAddr, end: LAddr) -> bool {
     let vdso_size = VDSO.1.len();
 
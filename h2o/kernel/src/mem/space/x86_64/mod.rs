@@ -4,12 +4,12 @@
 //! and the methods of x86_64 paging.
 
 use alloc::{alloc::Global, boxed::Box};
-use core::{alloc::Allocator, ops::Range};
+use core::{alloc::Allocator, arch::asm, ops::Range};
 
 use archop::Azy;
 use canary::Canary;
 use minfo::KERNEL_ALLOCABLE_RANGE;
-use paging::{Attr, LAddr, Level, PAddr, Table};
+use paging::{Attr, LAddr, Level, PAddr, Table, PAGE_SIZE};
 use spin::Mutex;
 
 use super::Flags;
@@ -50,6 +50,20 @@ static KERNEL_ROOT: Azy<(Box<Table>, u64)> = Azy::new(|| {
     (table, cr3)
 });
 
+/// Program the page attribute table of the current CPU.
+///
+/// The entries keep their power-on values except for the 4th one, which is
+/// selected by the `PAT` bit alone and made write-combining, so that the
+/// mappings without the bit are not affected.
+///
+/// # Safety
+///
+/// The function must be called before any page is mapped with the `PAT` bit.
+pub unsafe fn init_pat() {
+    const PAT: u64 = 0x0007_0401_0007_0406;
+    archop::msr::write(archop::msr::CR_PAT, PAT);
+}
+
 pub fn init_pgc() -> u64 {
     KERNEL_ROOT.1
 }
@@ -66,10 +80,13 @@ impl Space {
     #[inline]
     fn flags_to_pg_attr(flags: Flags) -> paging::Attr {
         let uncached = flags.contains(Flags::UNCACHED);
+        // See `init_pat` for the entry selected.
+        let write_combining = flags.contains(Flags::WRITE_COMBINING) && !uncached;
         paging::Attr::builder()
             .writable(flags.contains(Flags::WRITABLE))
             .user_access(flags.contains(Flags::USER_ACCESS))
             .executable(flags.contains(Flags::EXECUTABLE))
+            .pat(write_combining)
             .cache(uncached, uncached)
             .build()
     }
@@ -182,6 +199,51 @@ impl Space {
     }
 }
 
+/// Translate `virt` with the page tables loaded in the current CPU, without
+/// taking any lock.
+///
+/// # Safety
+///
+/// The caller must ensure that the page tables are not being modified, such as
+/// when all the other CPUs are stopped.
+pub unsafe fn query_current(virt: LAddr) -> Option<(PAddr, Flags)> {
+    let cr3 = PAddr::new(archop::reg::cr3::read() as usize & !paging::PAGE_MASK);
+    let root_table = &*cr3.to_laddr(minfo::ID_OFFSET).cast::<Table>();
+    paging::query(root_table, virt, minfo::ID_OFFSET)
+        .ok()
+        .map(|(phys, attr)| (phys, Space::pg_attr_to_flags(attr)))
+}
+
+/// Invalidate the translations of `virt` cached by the current CPU.
+///
+/// # Safety
+///
+/// The caller must ensure that the page tables of `virt` are not being modified
+/// by the current CPU.
+pub(super) unsafe fn invalidate(virt: Range<LAddr>) {
+    for addr in (virt.start.val()..virt.end.val()).step_by(PAGE_SIZE) {
+        asm!("invlpg [{}]", in(reg) addr);
+    }
+}
+
+/// Flush all the translations cached by the current CPU, including the global
+/// ones.
+///
+/// # Safety
+///
+/// The same as [`invalidate`].
+pub(super) unsafe fn flush_all() {
+    use archop::reg::{cr3, cr4};
+
+    let flags = cr4::read();
+    if flags & cr4::PGE != 0 {
+        cr4::write(flags & !cr4::PGE);
+        cr4::write(flags);
+    } else {
+        cr3::write(cr3::read());
+    }
+}
+
 impl PartialEq for Space {
     fn eq(&self, other: &Self) -> bool {
         self.cr3 == other.cr3
@@ -224,6 +286,20 @@ bitflags::bitflags! {
 
 impl ErrCode {
     pub const FMT: &'static str = "P WR US RSVD ID PK SS - - - - - - - - SGX";
+
+    fn access(self) -> Flags {
+        let mut flags = Flags::READABLE;
+        if self.contains(ErrCode::WRITE) {
+            flags |= Flags::WRITABLE;
+        }
+        if self.contains(ErrCode::USER_ACCESS) {
+            flags |= Flags::USER_ACCESS;
+        }
+        if self.contains(ErrCode::EXECUTING) {
+            flags |= Flags::EXECUTABLE;
+        }
+        flags
+    }
 }
 
 pub unsafe fn page_fault(frame: &mut Frame, errc: u64) -> bool {
@@ -232,14 +308,27 @@ pub unsafe fn page_fault(frame: &mut Frame, errc: u64) -> bool {
     match ErrCode::from_bits(errc) {
         // So far neither has been supported.
         Some(code) if !code.contains(ErrCode::PROT_KEY | ErrCode::SHADOW_STACK) => {
+            // Faults from user mode can't be holding any kernel lock, and
+            // neither can the kernel copying from or to user space, so both of
+            // them may wait for the pages of lazy mappings to be committed.
+            let user_copy = || {
+                (minfo::USER_BASE..minfo::USER_END).contains(&(addr as usize))
+                    && matches!(
+                        SCHED.with_current(|cur| Ok(cur.kstack().in_user_copy())),
+                        Ok(true)
+                    )
+            };
+            let blocking = code.contains(ErrCode::USER_ACCESS) || user_copy();
+            if super::handle_fault(LAddr::from(addr as usize), code.access(), blocking) {
+                return true;
+            }
+
             if SCHED
                 .with_current(|cur| cur.kstack_mut().pf_resume(frame, errc, addr))
                 .is_ok()
             {
                 return true;
             }
-
-            // TODO: Add some handling code.
         }
         _ => {}
     }
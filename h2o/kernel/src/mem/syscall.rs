@@ -7,7 +7,7 @@ use core::{alloc::Layout, ptr};
 use bitop_ex::BitOpEx;
 use paging::LAddr;
 use sv_call::{
-    mem::{Flags, IoVec, MemInfo, PhysOptions, VirtMapInfo},
+    mem::{Flags, IoVec, MemInfo, PhysOptions, PhysRange, VirtMapInfo},
     *,
 };
 
@@ -17,7 +17,7 @@ use crate::{
     mem::space::PhysTrait,
     sched::{
         task::{
-            hdl::{DefaultFeature, Ref},
+            hdl::{DefaultFeature, HandleMap, Ref},
             Space as TaskSpace, VDSO,
         },
         PREEMPT, SCHED,
@@ -46,9 +46,23 @@ fn features_to_flags(feat: Feature) -> Flags {
     flags
 }
 
+fn get_virt(
+    handles: &HandleMap,
+    hdl: Handle,
+    require: Feature,
+) -> Result<(Feature, Arc<space::Virt>)> {
+    let virt = handles.get::<Weak<space::Virt>>(hdl)?;
+    let feat = virt.features();
+    if !feat.contains(require) {
+        return Err(EPERM);
+    }
+    Ok((feat, virt.upgrade().ok_or(EKILLED)?))
+}
+
 #[syscall]
 fn phys_alloc(size: usize, options: PhysOptions) -> Result<Handle> {
-    let phys = PREEMPT.scope(|| space::allocate_phys(size, options, false))?;
+    let job = SCHED.with_current(|cur| Ok(Arc::clone(cur.space().job())))?;
+    let phys = PREEMPT.scope(|| space::allocate_phys_in(&job, size, options, false))?;
     SCHED.with_current(|cur| {
         let event = phys.event();
         cur.space().handles().insert_raw(phys, Some(event))
@@ -170,11 +184,12 @@ fn phys_writev(
 #[syscall]
 fn phys_sub(hdl: Handle, offset: usize, len: usize, copy: bool) -> Result<Handle> {
     let (feat, phys) = phys_check(hdl, offset, len)?;
-    if !feat.contains(Feature::READ) {
+    if !feat.contains(Feature::READ | Feature::DERIVE) {
         return Err(EPERM);
     }
 
-    let sub = phys.create_sub(offset, len, copy)?;
+    let job = SCHED.with_current(|cur| Ok(Arc::clone(cur.space().job())))?;
+    let sub = phys.create_sub(offset, len, copy, &job)?;
     SCHED.with_current(|cur| {
         let handles = cur.space().handles();
         let event = sub.event();
@@ -207,10 +222,60 @@ fn phys_resize(hdl: Handle, new_len: usize, zeroed: bool) -> Result {
 }
 
 #[syscall]
-fn space_new(root_virt: UserPtr<Out, Handle>) -> Result<Handle> {
+fn phys_pin(res: Handle, hdl: Handle, offset: usize, len: usize, write: bool) -> Result<Handle> {
+    if offset.contains_bit(paging::PAGE_MASK) || len == 0 {
+        return Err(EINVAL);
+    }
+    let (feat, phys) = phys_check(hdl, offset, len)?;
+    let require = if write {
+        Feature::READ | Feature::WRITE | Feature::MAP
+    } else {
+        Feature::READ | Feature::MAP
+    };
+    if !feat.contains(require) {
+        return Err(EPERM);
+    }
+    if offset + len > phys.len() {
+        return Err(ERANGE);
+    }
+
+    SCHED.with_current(|cur| {
+        // Physical addresses are only revealed to the holders of the memory
+        // resource.
+        let res = cur.space().handles().get::<Resource<usize>>(res)?;
+        if !res.magic_eq(super::mem_resource()) {
+            return Err(EPERM);
+        }
+        drop(res);
+
+        let pinned = space::Pinned::new(phys, offset, len, write)?;
+        cur.space().handles().insert(pinned, None)
+    })
+}
+
+#[syscall]
+fn pinned_ranges(hdl: Handle, ranges: UserPtr<Out, PhysRange>, count: usize) -> Result<usize> {
+    hdl.check_null()?;
+    ranges.check_slice(count)?;
+    SCHED.with_current(|cur| {
+        let pinned = cur.space().handles().get::<space::Pinned>(hdl)?;
+        let list = pinned
+            .ranges()
+            .iter()
+            .take(count)
+            .map(|&(base, len)| PhysRange { base: *base, len })
+            .collect::<Vec<_>>();
+        ranges.write_slice(&list)?;
+        Ok(pinned.ranges().len())
+    })
+}
+
+#[syscall]
+fn space_new(job: Handle, root_virt: UserPtr<Out, Handle>) -> Result<Handle> {
     root_virt.check()?;
     SCHED.with_current(|cur| {
-        let space = TaskSpace::new()?;
+        let job = cur.space().job_of(job, Feature::WRITE)?;
+        let space = TaskSpace::new(&job)?;
         let virt = Arc::downgrade(space.mem().root());
         let ret = cur.space().handles().insert_raw(space, None)?;
         let virt = unsafe {
@@ -229,14 +294,13 @@ fn space_new(root_virt: UserPtr<Out, Handle>) -> Result<Handle> {
 fn virt_alloc(hdl: Handle, offset: usize, size: usize, align: usize) -> Result<Handle> {
     hdl.check_null()?;
     SCHED.with_current(|cur| {
-        let virt_obj = cur.space().handles().get::<Weak<space::Virt>>(hdl)?;
-        let virt = virt_obj.upgrade().ok_or(EKILLED)?;
-        drop(virt_obj);
+        let (feat, virt) = get_virt(cur.space().handles(), hdl, Feature::DERIVE)?;
         let sub = virt.allocate(
             (offset != usize::MAX).then_some(offset),
             Layout::from_size_align(size, align)?,
         )?;
-        cur.space().handles().insert(sub, None)
+        // SAFETY: Sub-regions have no more rights than their parents.
+        unsafe { cur.space().handles().insert_unchecked(sub, feat, None) }
     })
 }
 
@@ -258,8 +322,7 @@ fn virt_info(hdl: Handle, size: UserPtr<Out, usize>) -> Result<*mut u8> {
 fn virt_drop(hdl: Handle) -> Result {
     hdl.check_null()?;
     SCHED.with_current(|cur| {
-        let virt = cur.space().handles().get::<Weak<space::Virt>>(hdl)?;
-        let virt = virt.upgrade().ok_or(EKILLED)?;
+        let (_, virt) = get_virt(cur.space().handles(), hdl, Feature::WRITE)?;
         virt.destroy()
     })
 }
@@ -270,12 +333,16 @@ fn virt_map(hdl: Handle, mi_ptr: UserPtr<InOut, VirtMapInfo>) -> Result<*mut u8>
     let mi = unsafe { mi_ptr.read() }?;
     let flags = check_flags(mi.flags)?;
     SCHED.with_current(|cur| {
-        let virt_obj = cur.space().handles().get::<Weak<space::Virt>>(hdl)?;
-        let virt = virt_obj.upgrade().ok_or(EKILLED)?;
-        drop(virt_obj);
+        let (feat, virt) = get_virt(cur.space().handles(), hdl, Feature::MAP)?;
+        let perm = flags - Flags::LAZY - Flags::UNCACHED - Flags::WRITE_COMBINING;
+        if perm.intersects(!features_to_flags(feat)) {
+            return Err(EPERM);
+        }
         let phys = cur.space().handles().remove::<space::Phys>(mi.phys)?;
         let offset = (mi.offset != usize::MAX).then_some(mi.offset);
-        if flags.intersects(!features_to_flags(phys.features())) {
+        if !phys.features().contains(Feature::MAP)
+            || perm.intersects(!features_to_flags(phys.features()))
+        {
             return Err(EPERM);
         }
 
@@ -296,8 +363,11 @@ fn virt_reprot(hdl: Handle, base: UserPtr<In>, len: usize, flags: Flags) -> Resu
     base.check()?;
     let flags = check_flags(flags)?;
     SCHED.with_current(|cur| {
-        let virt = cur.space().handles().get::<Weak<space::Virt>>(hdl)?;
-        let virt = virt.upgrade().ok_or(EKILLED)?;
+        let (feat, virt) = get_virt(cur.space().handles(), hdl, Feature::MAP)?;
+        let perm = flags - Flags::LAZY - Flags::UNCACHED - Flags::WRITE_COMBINING;
+        if perm.intersects(!features_to_flags(feat)) {
+            return Err(EPERM);
+        }
         virt.reprotect(LAddr::new(base.as_ptr()), len, flags)
     })
 }
@@ -307,8 +377,7 @@ fn virt_unmap(hdl: Handle, base: UserPtr<In>, len: usize, drop_child: bool) -> R
     hdl.check_null()?;
     base.check()?;
     SCHED.with_current(|cur| {
-        let virt = cur.space().handles().get::<Weak<space::Virt>>(hdl)?;
-        let virt = virt.upgrade().ok_or(EKILLED)?;
+        let (_, virt) = get_virt(cur.space().handles(), hdl, Feature::MAP)?;
         virt.unmap(LAddr::new(base.as_ptr()), len, drop_child)
     })
 }
@@ -338,7 +407,8 @@ fn phys_acq(res: Handle, addr: usize, size: usize) -> Result<Handle> {
 
         if addr == 0 {
             drop(res);
-            let phys = space::allocate_phys(size, PhysOptions::ZEROED, true)?;
+            let job = cur.space().job();
+            let phys = space::allocate_phys_in(job, size, PhysOptions::ZEROED, true)?;
             return unsafe { cur.space().handles().insert_raw(phys, None) };
         }
 
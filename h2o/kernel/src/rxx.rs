@@ -1,6 +1,9 @@
 #[panic_handler]
 fn panic_handler(info: &core::panic::PanicInfo) -> ! {
     log::error!("CPU #{} {}", unsafe { crate::cpu::id() }, info);
+    if crate::gdb::enabled() {
+        crate::gdb::breakpoint();
+    }
     unsafe { archop::halt_loop(Some(true)) }
 }
 
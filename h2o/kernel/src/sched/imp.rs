@@ -4,10 +4,12 @@ pub mod waiter;
 
 use alloc::vec::Vec;
 use core::{
+    array,
     assert_matches::assert_matches,
     cell::UnsafeCell,
+    cmp::Ordering,
     hint,
-    sync::atomic::{AtomicU64, Ordering::*},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering::*},
     time::Duration,
 };
 
@@ -15,15 +17,23 @@ use archop::{Azy, PreemptState, PreemptStateGuard};
 use canary::Canary;
 use crossbeam_queue::SegQueue;
 use deque::{Injector, Steal, Worker};
+use sv_call::task::{ExitInfo, EXIT_KILLED};
 
-use super::{ipc::Arsc, task};
+use super::{
+    ipc::Arsc,
+    task::{self, Priority},
+};
 use crate::cpu::{
     time::{Instant, Timer},
-    Lazy,
+    CpuMask, Lazy,
 };
 
 pub(super) const MIN_TIME_GRAN: Duration = Duration::from_millis(30);
 const WAKE_TIME_GRAN: Duration = Duration::from_millis(1);
+/// Realtime tasks may only run for [`RT_BUDGET`] in every [`RT_PERIOD`] while
+/// other tasks are ready, so that a spinning one can't starve the system.
+const RT_PERIOD: Duration = Duration::from_secs(1);
+const RT_BUDGET: Duration = Duration::from_millis(950);
 
 static SCHED_INFO: Azy<Vec<SchedInfo>> = Azy::new(|| {
     let count = crate::cpu::count();
@@ -37,69 +47,189 @@ pub static SCHED: Lazy<Scheduler> = Lazy::new(|| Scheduler {
     canary: Canary::new(),
     cpu: unsafe { crate::cpu::id() },
     current: UnsafeCell::new(None),
-    run_queue: Worker::new_fifo(),
+    run_queue: array::from_fn(|_| Worker::new_fifo()),
+    rt_budget: UnsafeCell::new(RtBudget {
+        // SAFETY: The period only needs to end before the first check.
+        period_end: unsafe { Instant::from_raw(0) },
+        last: unsafe { Instant::from_raw(0) },
+        used: Duration::ZERO,
+    }),
 });
 
 #[thread_local]
 pub static PREEMPT: PreemptState = PreemptState::new();
 
+/// The per-CPU scheduling information visible to other CPUs, indexed by
+/// priorities.
 #[derive(Default)]
 struct SchedInfo {
-    migration_queue: Injector<task::Ready>,
-    expected_runtime: AtomicU64,
+    migration_queue: [Injector<task::Ready>; Priority::COUNT],
+    expected_runtime: [AtomicU64; Priority::COUNT],
+    /// Set if some tasks on the CPU may have lost their affinity to it.
+    evicting: AtomicBool,
 }
 
 impl SchedInfo {
-    fn expected_runtime(&self) -> u64 {
-        self.expected_runtime.load(Acquire)
+    /// The expected runtime of the tasks that a task of `priority` has to
+    /// compete with, i.e. those of the same or higher priorities.
+    fn expected_runtime(&self, priority: Priority) -> u64 {
+        self.expected_runtime[priority as usize..]
+            .iter()
+            .map(|runtime| runtime.load(Acquire))
+            .sum()
+    }
+
+    fn add_runtime(&self, task: &task::Ready) {
+        self.expected_runtime[task.priority as usize]
+            .fetch_add(task.time_slice.as_micros() as u64, Release);
+    }
+
+    fn sub_runtime(&self, task: &task::Ready) {
+        self.expected_runtime[task.priority as usize]
+            .fetch_sub(task.time_slice.as_micros() as u64, Release);
+    }
+}
+
+/// The CPU time used by the realtime tasks on a CPU in the current period.
+struct RtBudget {
+    period_end: Instant,
+    /// The time until which the runtime is accounted.
+    last: Instant,
+    used: Duration,
+}
+
+impl RtBudget {
+    fn roll(&mut self, cur_time: Instant) {
+        if cur_time >= self.period_end {
+            self.period_end = cur_time + RT_PERIOD;
+            self.used = Duration::ZERO;
+        }
+    }
+
+    /// Account the runtime of the current realtime task started at
+    /// `start_time`.
+    fn charge(&mut self, start_time: Instant, cur_time: Instant) {
+        self.roll(cur_time);
+        let since = start_time.max(self.last);
+        self.used += cur_time.saturating_duration_since(since);
+        self.last = cur_time;
+    }
+
+    fn throttled(&mut self, cur_time: Instant) -> bool {
+        self.roll(cur_time);
+        self.used >= RT_BUDGET
     }
 }
 
 pub struct Scheduler {
     canary: Canary<Scheduler>,
     cpu: usize,
-    run_queue: Worker<task::Ready>,
+    run_queue: [Worker<task::Ready>; Priority::COUNT],
     current: UnsafeCell<Option<task::Ready>>,
+    rt_budget: UnsafeCell<RtBudget>,
 }
 
 impl Scheduler {
     pub fn unblock(&self, task: impl task::IntoReady, preempt: bool) {
         self.canary.assert();
 
-        let time_slice = MIN_TIME_GRAN;
+        let priority = task.priority();
         let affinity = task.affinity();
-        let cpu = select_cpu(&affinity, self.cpu, task.last_cpu()).expect("Zero affinity");
-        let task = task::IntoReady::into_ready(task, cpu, time_slice);
+        let cpu =
+            select_cpu(&affinity, self.cpu, task.last_cpu(), priority).expect("Zero affinity");
+        let task = task::IntoReady::into_ready(task, cpu, priority);
 
         log::trace!("Unblocking task {:?}, P{}", task.tid.raw(), PREEMPT.raw());
         if cpu == self.cpu {
             self.enqueue(task, PREEMPT.lock(), preempt);
         } else {
-            SCHED_INFO[cpu].migration_queue.push(task);
-            unsafe { crate::cpu::arch::apic::ipi::task_migrate(cpu) };
+            migrate_to(cpu, task);
         }
     }
 
     fn enqueue(&self, task: task::Ready, pree: PreemptStateGuard, preempt: bool) {
-        SCHED_INFO[self.cpu]
-            .expected_runtime
-            .fetch_add(task.time_slice.as_micros() as u64, Release);
+        SCHED_INFO[self.cpu].add_runtime(&task);
         // SAFETY: We have `pree`, which means preemption is disabled.
         match unsafe { &*self.current.get() } {
-            Some(ref cur) if preempt && Self::should_preempt(cur, &task) => {
+            Some(ref cur)
+                if preempt
+                    && task.tid.affinity()[self.cpu]
+                    && self.should_preempt(cur, &task, Instant::now()) =>
+            {
                 log::trace!(
                     "Preempting to task {:?}, P{}",
                     task.tid.raw(),
                     PREEMPT.raw(),
                 );
-                let _ = self.schedule_impl(Instant::now(), pree, Some(task), |mut task| {
-                    task.running_state = task::RunningState::NOT_RUNNING;
-                    self.run_queue.push(task);
+                let _ = self.schedule_impl(Instant::now(), pree, Some(task), |task| {
+                    self.requeue(task);
                     Ok(())
                 });
             }
-            _ => self.run_queue.push(task),
+            _ => self.push(task),
+        }
+    }
+
+    /// Push the task to the run queue, or send it to another CPU if it's no
+    /// longer allowed to run on this one.
+    fn push(&self, task: task::Ready) {
+        let affinity = task.tid.affinity();
+        if affinity[self.cpu] {
+            self.run_queue[task.priority as usize].push(task)
+        } else {
+            SCHED_INFO[self.cpu].sub_runtime(&task);
+            let cpu = select_cpu(&affinity, self.cpu, None, task.priority).expect("Zero affinity");
+            log::trace!("Evicting task {:?} to CPU #{cpu}", task.tid.raw());
+            migrate_to(cpu, task);
+        }
+    }
+
+    /// Pop the next task of the highest priority, or of the highest one below
+    /// realtime if realtime tasks have used up their budget.
+    #[inline]
+    fn pop(&self, cur_time: Instant) -> Option<task::Ready> {
+        if self.rt_throttled(cur_time) {
+            let (others, rt) = self.run_queue.split_at(Priority::Realtime as usize);
+            others
+                .iter()
+                .rev()
+                .find_map(Worker::pop)
+                .or_else(|| rt[0].pop())
+        } else {
+            self.run_queue.iter().rev().find_map(Worker::pop)
+        }
+    }
+
+    #[inline]
+    fn rt_throttled(&self, cur_time: Instant) -> bool {
+        // SAFETY: The budget is only accessed with preemption disabled.
+        PREEMPT.scope(|| unsafe { (*self.rt_budget.get()).throttled(cur_time) })
+    }
+
+    /// Check if there's any ready task that can take the place of a task of
+    /// `priority`.
+    #[inline]
+    fn has_ready(&self, priority: Priority) -> bool {
+        self.run_queue[priority as usize..]
+            .iter()
+            .any(|queue| !queue.is_empty())
+    }
+
+    /// Put the previously running task back to the run queue, picking up the
+    /// change of its priority if any.
+    fn requeue(&self, mut task: task::Ready) {
+        task.running_state = task::RunningState::NOT_RUNNING;
+
+        let priority = task.tid.priority();
+        if priority != task.priority {
+            let info = &SCHED_INFO[self.cpu];
+            info.sub_runtime(&task);
+            task.priority = priority;
+            task.time_slice = priority.time_slice();
+            info.add_runtime(&task);
         }
+
+        self.push(task)
     }
 
     #[inline]
@@ -142,31 +272,74 @@ impl Scheduler {
         );
 
         if let Some(current) = unsafe { &*self.current() } {
-            SCHED_INFO[self.cpu]
-                .expected_runtime
-                .fetch_sub(current.time_slice.as_micros() as u64, Release);
+            SCHED_INFO[self.cpu].sub_runtime(current);
         }
 
-        self.schedule_impl(Instant::now(), pree, None, |task| {
+        let timer = self.schedule_impl(Instant::now(), pree, None, |task| {
+            let tid = task::Tid::clone(&task.tid);
             let blocked = task::Ready::block(task, block_desc);
             let timer = Timer::activate(duration, blocked)?;
             if let Some(wq) = wq {
                 wq.push(Arsc::clone(&timer));
             }
             drop(guard);
+            // Don't stay blocked if the task is killed in the meantime.
+            if !tid.set_blocker(&timer) {
+                timer.cancel(false);
+            }
             Ok(timer)
-        })
+        })?;
+
+        // The task may be woken up early to be killed.
+        if SCHED.with_current(|cur| Ok(cur.tid.is_killed()))? {
+            return Err(sv_call::EKILLED);
+        }
+        Ok(timer)
+    }
+
+    /// Send away the tasks that have lost their affinity to this CPU, including
+    /// the current one.
+    fn evict(&self, pree: PreemptStateGuard) {
+        if !SCHED_INFO[self.cpu].evicting.swap(false, AcqRel) {
+            return;
+        }
+
+        for queue in &self.run_queue {
+            // Rotate the whole FIFO queue once so that the order is kept.
+            for _ in 0..queue.len() {
+                match queue.pop() {
+                    Some(task) => self.push(task),
+                    None => break,
+                }
+            }
+        }
+
+        // SAFETY: We have `pree`, which means preemption is disabled.
+        match unsafe { &mut *self.current.get() } {
+            Some(cur) if !cur.tid.affinity()[self.cpu] => {
+                cur.running_state = task::RunningState::NEED_RESCHED;
+                let ret = self.schedule(Instant::now(), pree);
+                assert_matches!(ret, Ok(()) | Err(sv_call::ENOENT));
+            }
+            _ => {}
+        }
     }
 
     #[inline]
-    fn should_preempt(cur: &task::Ready, task: &task::Ready) -> bool {
-        cur.runtime > task.runtime + WAKE_TIME_GRAN
+    fn should_preempt(&self, cur: &task::Ready, task: &task::Ready, cur_time: Instant) -> bool {
+        match task.priority.cmp(&cur.priority) {
+            Ordering::Greater => {
+                task.priority != Priority::Realtime || !self.rt_throttled(cur_time)
+            }
+            Ordering::Less => false,
+            Ordering::Equal => cur.runtime > task.runtime + WAKE_TIME_GRAN,
+        }
     }
 
     /// # Panics
     ///
     /// Panics if the scheduler unexpectedly returns.
-    pub fn exit_current(&self, retval: usize, kill_all: bool) -> ! {
+    pub fn exit_current(&self, info: ExitInfo, kill_all: bool) -> ! {
         self.canary.assert();
         let pree = PREEMPT.lock();
 
@@ -178,9 +351,7 @@ impl Scheduler {
         );
 
         if let Some(current) = unsafe { &*self.current() } {
-            SCHED_INFO[self.cpu]
-                .expected_runtime
-                .fetch_sub(current.time_slice.as_micros() as u64, Release);
+            SCHED_INFO[self.cpu].sub_runtime(current);
 
             if kill_all {
                 current.space().try_stop(&current.tid);
@@ -188,7 +359,7 @@ impl Scheduler {
         }
 
         let _ = self.schedule_impl(Instant::now(), pree, None, |task| {
-            task::Ready::exit(task, retval);
+            task::Ready::exit(task, info);
             Ok(())
         });
         unreachable!("Dead task");
@@ -214,6 +385,12 @@ impl Scheduler {
         }
     }
 
+    /// Handle the pending signal of the current task immediately, instead of
+    /// waiting for the next tick.
+    pub fn handle_signal(&self) {
+        drop(self.check_signal(Instant::now(), PREEMPT.lock()));
+    }
+
     fn check_signal<'a>(
         &'a self,
         cur_time: Instant,
@@ -251,9 +428,7 @@ impl Scheduler {
             Some(task::Signal::Suspend(slot)) => {
                 log::trace!("Suspending task {:?}, P{}", cur.tid.raw(), PREEMPT.raw());
 
-                SCHED_INFO[self.cpu]
-                    .expected_runtime
-                    .fetch_sub(cur.time_slice.as_micros() as u64, Release);
+                SCHED_INFO[self.cpu].sub_runtime(cur);
 
                 let ret = self.schedule_impl(cur_time, pree, None, |task| {
                     *slot.lock() = Some(task::Ready::block(task, "task_ctl_suspend"));
@@ -268,11 +443,14 @@ impl Scheduler {
     }
 
     fn kill(&self, cur: &task::Ready, cur_time: Instant, pree: PreemptStateGuard) -> ! {
-        SCHED_INFO[self.cpu]
-            .expected_runtime
-            .fetch_sub(cur.time_slice.as_micros() as u64, Release);
+        SCHED_INFO[self.cpu].sub_runtime(cur);
         let _ = self.schedule_impl(cur_time, pree, None, |task| {
-            task::Ready::exit(task, sv_call::EKILLED.into_retval());
+            let info = ExitInfo {
+                reason: EXIT_KILLED,
+                retval: sv_call::EKILLED.into_retval(),
+                ..Default::default()
+            };
+            task::Ready::exit(task, info);
             Ok(())
         });
         unreachable!("Dead task");
@@ -281,10 +459,9 @@ impl Scheduler {
     unsafe fn update(&self, cur_time: Instant) -> bool {
         self.canary.assert();
 
-        let sole = self.run_queue.is_empty();
         let cur = match *self.current.get() {
             Some(ref mut task) => task,
-            None => return !sole,
+            None => return self.has_ready(Priority::Idle),
         };
         log::trace!("Updating task {:?}'s timer slice", cur.tid.raw());
 
@@ -296,12 +473,25 @@ impl Scheduler {
                 // debug_assert!(cur_time > start_time);
                 let runtime_delta = cur_time.saturating_duration_since(start_time);
                 cur.runtime += runtime_delta;
-                if cur.time_slice < runtime_delta && !sole {
-                    cur.running_state = task::RunningState::NEED_RESCHED;
-                    true
+
+                let resched = if cur.priority == Priority::Realtime {
+                    let budget = &mut *self.rt_budget.get();
+                    budget.charge(start_time, cur_time);
+                    // Give way to the other tasks once the budget is used up.
+                    let others = &self.run_queue[..Priority::Realtime as usize];
+                    (budget.throttled(cur_time) && others.iter().any(|queue| !queue.is_empty()))
+                        || (cur.time_slice < runtime_delta && self.has_ready(cur.priority))
                 } else {
-                    false
+                    // Give way to the realtime tasks once the budget is
+                    // refilled.
+                    (cur.time_slice < runtime_delta && self.has_ready(cur.priority))
+                        || (!self.run_queue[Priority::Realtime as usize].is_empty()
+                            && !self.rt_throttled(cur_time))
+                };
+                if resched {
+                    cur.running_state = task::RunningState::NEED_RESCHED;
                 }
+                resched
             }
             _ => {
                 assert!(cur.running_state.needs_resched(), "Not running");
@@ -318,10 +508,9 @@ impl Scheduler {
             log::trace!("Scheduling task {:?}, P{}", cur.tid.raw(), PREEMPT.raw());
         }
 
-        self.schedule_impl(cur_time, pree, None, |mut task| {
+        self.schedule_impl(cur_time, pree, None, |task| {
             debug_assert!(task.running_state.needs_resched());
-            task.running_state = task::RunningState::NOT_RUNNING;
-            self.run_queue.push(task);
+            self.requeue(task);
             Ok(())
         })
     }
@@ -340,7 +529,7 @@ impl Scheduler {
 
         let mut next = match next {
             Some(next) => next,
-            None => match self.run_queue.pop() {
+            None => match self.pop(cur_time) {
                 Some(task) => task,
                 None => return Err(sv_call::ENOENT),
             },
@@ -369,21 +558,42 @@ impl Scheduler {
     }
 }
 
+fn migrate_to(cpu: usize, task: task::Ready) {
+    SCHED_INFO[cpu].migration_queue[task.priority as usize].push(task);
+    unsafe { crate::cpu::arch::apic::ipi::task_migrate(cpu) };
+}
+
+/// Move the tasks that are no longer allowed to run on `cpus` to the others.
+pub(super) fn evict(cpus: CpuMask) {
+    let pree = PREEMPT.lock();
+    for cpu in cpus.iter_ones() {
+        SCHED_INFO[cpu].evicting.store(true, Release);
+        if cpu != SCHED.cpu {
+            unsafe { crate::cpu::arch::apic::ipi::task_migrate(cpu) };
+        }
+    }
+    // The current task may be switched out here, so leave this CPU to the last.
+    if cpus[SCHED.cpu] {
+        SCHED.evict(pree);
+    }
+}
+
 fn select_cpu(
-    affinity: &crate::cpu::CpuMask,
+    affinity: &CpuMask,
     cur_cpu: usize,
     last_cpu: Option<usize>,
+    priority: Priority,
 ) -> Option<usize> {
     let mut iter = affinity.iter_ones();
     let mut ret = iter.next()?;
-    let mut rret = SCHED_INFO[ret].expected_runtime();
+    let mut rret = SCHED_INFO[ret].expected_runtime(priority);
 
     if ret == cur_cpu && rret == 0 {
         return Some(ret);
     }
 
     for b in iter {
-        let rb = SCHED_INFO[b].expected_runtime();
+        let rb = SCHED_INFO[b].expected_runtime(priority);
         if b == cur_cpu && rb == 0 {
             return Some(b);
         }
@@ -434,15 +644,21 @@ pub unsafe fn task_migrate_handler() {
     crate::cpu::arch::apic::lapic(|lapic| lapic.eoi());
 
     const MAX_TRIAL: usize = 50;
-    for _ in 0..MAX_TRIAL {
-        match SCHED_INFO[SCHED.cpu].migration_queue.steal() {
-            Steal::Empty => break,
-            Steal::Retry => hint::spin_loop(),
-            Steal::Success(task) => {
-                log::trace!("Migrating task {:?}, P{}", task.tid.raw(), PREEMPT.raw());
-                let pree = PREEMPT.lock();
-                SCHED.enqueue(task, pree, true);
+    // Take in the tasks of higher priorities first so that they won't wait for
+    // the less urgent ones to be enqueued.
+    for queue in SCHED_INFO[SCHED.cpu].migration_queue.iter().rev() {
+        for _ in 0..MAX_TRIAL {
+            match queue.steal() {
+                Steal::Empty => break,
+                Steal::Retry => hint::spin_loop(),
+                Steal::Success(task) => {
+                    log::trace!("Migrating task {:?}, P{}", task.tid.raw(), PREEMPT.raw());
+                    let pree = PREEMPT.lock();
+                    SCHED.enqueue(task, pree, true);
+                }
             }
         }
     }
+
+    SCHED.evict(PREEMPT.lock());
 }
@@ -20,7 +20,11 @@ use sv_call::{
 use super::PREEMPT;
 use crate::{
     cpu::arch::apic::TriggerMode,
-    sched::{task::hdl::DefaultFeature, wait::WaitObject, BasicEvent, Event, Waiter, WaiterData},
+    sched::{
+        task::hdl::{DefaultFeature, Koid},
+        wait::WaitObject,
+        BasicEvent, Event, Waiter, WaiterData,
+    },
 };
 
 #[derive(Debug)]
@@ -131,6 +135,7 @@ struct Ready {
 
 #[derive(Debug)]
 pub struct Dispatcher {
+    koid: Koid,
     next_key: AtomicUsize,
     event: Arc<BasicEvent>,
 
@@ -142,6 +147,7 @@ pub struct Dispatcher {
 impl Dispatcher {
     pub fn new(capacity: usize) -> Result<Arc<Self>> {
         Ok(Arc::try_new(Dispatcher {
+            koid: Koid::new(),
             next_key: AtomicUsize::new(1),
             event: BasicEvent::new(0),
 
@@ -151,6 +157,11 @@ impl Dispatcher {
         })?)
     }
 
+    #[inline]
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+
     pub fn event(&self) -> Weak<dyn Event> {
         Arc::downgrade(&self.event) as _
     }
@@ -265,6 +276,11 @@ impl Waiter for Dispatcher {
 unsafe impl DefaultFeature for Dispatcher {
     #[inline]
     fn default_features() -> Feature {
-        Feature::SEND | Feature::SYNC | Feature::READ | Feature::WRITE | Feature::WAIT
+        Feature::SEND
+            | Feature::SYNC
+            | Feature::READ
+            | Feature::WRITE
+            | Feature::WAIT
+            | Feature::DUPLICATE
     }
 }
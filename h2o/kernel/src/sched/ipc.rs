@@ -11,19 +11,20 @@ use core::{
 };
 
 use collection_ex::{CHashMap, FnvHasher};
-pub use sv_call::ipc::{SIG_GENERIC, SIG_READ, SIG_TIMER, SIG_WRITE};
+pub use sv_call::ipc::{SIG_GENERIC, SIG_PEER_CLOSED, SIG_READ, SIG_TIMER, SIG_WRITE};
 
 pub use self::{
     arsc::Arsc,
     channel::{Channel, Packet},
 };
-use super::PREEMPT;
+use super::{task::hdl::Koid, PREEMPT};
 use crate::cpu::arch::apic::TriggerMode;
 
 type BH = BuildHasherDefault<FnvHasher>;
 
 #[derive(Debug, Default)]
 pub struct EventData {
+    koid: Koid,
     waiters: CHashMap<usize, Arc<dyn Waiter>, BH>,
     signal: AtomicUsize,
 }
@@ -31,11 +32,17 @@ pub struct EventData {
 impl EventData {
     pub fn new(init_signal: usize) -> Self {
         EventData {
+            koid: Koid::new(),
             waiters: Default::default(),
             signal: AtomicUsize::new(init_signal),
         }
     }
 
+    #[inline]
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+
     #[inline]
     pub fn waiters(&self) -> &CHashMap<usize, Arc<dyn Waiter>, BH> {
         &self.waiters
@@ -27,6 +27,6 @@ impl Event for BasicEvent {
 unsafe impl DefaultFeature for BasicEvent {
     #[inline]
     fn default_features() -> sv_call::Feature {
-        Feature::SEND | Feature::SYNC | Feature::WAIT | Feature::EXECUTE
+        Feature::SEND | Feature::SYNC | Feature::WAIT | Feature::EXECUTE | Feature::DUPLICATE
     }
 }
@@ -1,32 +1,74 @@
 mod syscall;
 
 use alloc::{
+    collections::BTreeMap,
     sync::{Arc, Weak},
     vec::Vec,
 };
 use core::{
     mem,
-    sync::atomic::{AtomicU64, Ordering::SeqCst},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering::SeqCst},
+    time::Duration,
 };
 
 use bytes::Bytes;
 use crossbeam_queue::SegQueue;
 use spin::Mutex;
-use sv_call::Feature;
+use sv_call::{
+    ipc::{DEFAULT_CAPACITY, MAX_CAPACITY},
+    Feature,
+};
 
-use super::{Event, SIG_READ};
-use crate::sched::{
-    task::hdl::{self, DefaultFeature},
-    BasicEvent, PREEMPT, SCHED,
+use super::{Event, SIG_PEER_CLOSED, SIG_READ, SIG_WRITE};
+use crate::{
+    mem::space::Phys,
+    sched::{
+        task::hdl::{self, DefaultFeature, Koid},
+        wait::WaitObject,
+        BasicEvent, PREEMPT, SCHED,
+    },
+    syscall::{In, Out, UserPtr},
 };
 
-const MAX_QUEUE_SIZE: usize = 2048;
+/// The inline data of a packet, copied into the kernel heap.
+///
+/// Larger data is carried out of line in the pages of a physical object,
+/// which is shared copy-on-write instead of being copied. See
+/// [`Packet::with_phys`].
+#[derive(Debug, Default)]
+pub struct Buffer(Bytes);
+
+#[allow(clippy::len_without_is_empty)]
+impl Buffer {
+    /// Copy the data from userspace.
+    pub fn from_user(data: UserPtr<In>, len: usize) -> sv_call::Result<Self> {
+        let mut buf = Vec::<u8>::with_capacity(len);
+        unsafe {
+            data.read_slice(buf.as_mut_ptr(), len)?;
+            buf.set_len(len);
+        }
+        Ok(Buffer(buf.into()))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Copy the data out to userspace, whose buffer must be large enough.
+    #[inline]
+    pub fn copy_to_user(&self, out: UserPtr<Out>) -> sv_call::Result {
+        out.write_slice(&self.0)
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct Packet {
     id: usize,
     objects: Vec<hdl::Ref>,
-    buffer: Bytes,
+    buffer: Buffer,
+    /// The out-of-line data following the buffer, and its length.
+    phys: Option<(Arc<Phys>, usize)>,
 }
 
 unsafe impl Send for Packet {}
@@ -34,22 +76,37 @@ unsafe impl Sync for Packet {}
 
 impl Packet {
     pub fn new(id: usize, objects: Vec<hdl::Ref>, data: &[u8]) -> Self {
-        let buffer = Bytes::copy_from_slice(data);
+        let buffer = Buffer(Bytes::copy_from_slice(data));
+        Self::with_buffer(id, objects, buffer)
+    }
+
+    #[inline]
+    pub fn with_buffer(id: usize, objects: Vec<hdl::Ref>, buffer: Buffer) -> Self {
         Packet {
             id,
             objects,
             buffer,
+            phys: None,
         }
     }
 
+    /// Carry the data of `phys` out of line, which must be a copy-on-write
+    /// share of the sender's object so that the sender can't modify it any
+    /// more.
+    #[inline]
+    pub fn with_phys(mut self, phys: Option<(Arc<Phys>, usize)>) -> Self {
+        self.phys = phys;
+        self
+    }
+
     #[inline]
-    pub fn buffer(&self) -> &[u8] {
+    pub fn buffer(&self) -> &Buffer {
         &self.buffer
     }
 
     #[inline]
-    pub fn buffer_mut(&mut self) -> &mut Bytes {
-        &mut self.buffer
+    pub fn data(&self) -> &[u8] {
+        &self.buffer.0
     }
 
     #[inline]
@@ -58,20 +115,122 @@ impl Packet {
     }
 }
 
+#[derive(Debug)]
+enum Reply {
+    Pending,
+    Ready(sv_call::Result<Packet>),
+    /// The caller has stopped waiting, e.g. timed out.
+    Abandoned,
+}
+
+/// A task blocked in [`Channel::call`] for the reply to its packet.
+#[derive(Debug)]
+struct Caller {
+    reply: Mutex<Reply>,
+    wo: WaitObject,
+}
+
+impl Caller {
+    fn new() -> Self {
+        Caller {
+            reply: Mutex::new(Reply::Pending),
+            wo: WaitObject::new(),
+        }
+    }
+
+    /// Hand the reply to the caller and wake it up, or give it back if the
+    /// caller has abandoned the call.
+    fn wake(&self, reply: sv_call::Result<Packet>) -> Option<sv_call::Result<Packet>> {
+        let ret = PREEMPT.scope(|| {
+            let mut slot = self.reply.lock();
+            match *slot {
+                Reply::Pending => {
+                    *slot = Reply::Ready(reply);
+                    None
+                }
+                _ => Some(reply),
+            }
+        });
+        if ret.is_none() {
+            self.wo.notify(0, false);
+        }
+        ret
+    }
+}
+
 #[derive(Debug)]
 struct ChannelSide {
+    koid: Koid,
     msgs: SegQueue<Packet>,
+    /// The total size of the buffers in `msgs`.
+    msg_size: AtomicUsize,
     event: Arc<BasicEvent>,
+    /// The maximum number of packets in `msgs`.
+    capacity: usize,
+    /// Serializes the updates of `SIG_WRITE` of the writer to `msgs`.
+    write_signal: Mutex<()>,
+    /// The pending calls on this side indexed by their packet IDs, or `None`
+    /// if the peer is closed.
+    callers: Mutex<Option<BTreeMap<usize, Arc<Caller>>>>,
 }
 
-impl Default for ChannelSide {
-    #[inline]
-    fn default() -> Self {
+impl ChannelSide {
+    fn new(capacity: usize) -> Self {
         ChannelSide {
+            koid: Koid::new(),
             msgs: SegQueue::new(),
-            event: BasicEvent::new(0),
+            msg_size: AtomicUsize::new(0),
+            event: BasicEvent::new(SIG_WRITE),
+            capacity,
+            write_signal: Mutex::new(()),
+            callers: Mutex::new(Some(BTreeMap::new())),
         }
     }
+
+    /// Assert or clear `SIG_WRITE` of the peer according to whether there's
+    /// space left in the queue.
+    fn update_writer(&self, writer: &BasicEvent) {
+        PREEMPT.scope(|| {
+            let _guard = self.write_signal.lock();
+            if self.msgs.len() < self.capacity {
+                writer.notify(0, SIG_WRITE);
+            } else {
+                let signal = writer.notify(SIG_WRITE, 0);
+                // Closed peers must keep the signal asserted.
+                if signal & SIG_PEER_CLOSED != 0 {
+                    writer.notify(0, SIG_WRITE);
+                }
+            }
+        });
+    }
+
+    /// Clear `SIG_READ` if there's nothing left to receive.
+    fn update_reader(&self) {
+        if !self.msgs.is_empty() {
+            return;
+        }
+        let signal = self.event.notify(SIG_READ, 0);
+        // Recheck for packets sent or the peer closed in the meantime.
+        if !self.msgs.is_empty() || signal & SIG_PEER_CLOSED != 0 {
+            self.event.notify(0, SIG_READ);
+        }
+    }
+
+    fn push(&self, packet: Packet) {
+        self.msg_size.fetch_add(packet.buffer().len(), SeqCst);
+        self.msgs.push(packet);
+    }
+
+    fn pop(&self) -> Option<Packet> {
+        let packet = self.msgs.pop()?;
+        self.msg_size.fetch_sub(packet.buffer().len(), SeqCst);
+        Some(packet)
+    }
+
+    #[inline]
+    fn is_peer_closed(&self) -> bool {
+        self.event.event_data().signal().load(SeqCst) & SIG_PEER_CLOSED != 0
+    }
 }
 
 #[derive(Debug)]
@@ -79,29 +238,50 @@ pub struct Channel {
     peer_id: u64,
     me: Arc<ChannelSide>,
     peer: Weak<ChannelSide>,
+    /// Recorded apart from `peer` so that it's still known after the peer is
+    /// dropped.
+    peer_koid: Koid,
     head: Mutex<Option<Packet>>,
 }
 
 impl Channel {
+    #[inline]
     pub fn new() -> (Self, Self) {
+        Self::with_capacity(DEFAULT_CAPACITY).unwrap()
+    }
+
+    /// Create a pair of channels, each of which can hold at most `capacity`
+    /// packets not yet received, or `DEFAULT_CAPACITY` if it's 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the capacity is larger than `MAX_CAPACITY`.
+    pub fn with_capacity(capacity: usize) -> sv_call::Result<(Self, Self)> {
+        let capacity = match capacity {
+            0 => DEFAULT_CAPACITY,
+            c if c > MAX_CAPACITY => return Err(sv_call::EINVAL),
+            c => c,
+        };
         static PEER_ID: AtomicU64 = AtomicU64::new(0);
         let peer_id = PEER_ID.fetch_add(1, SeqCst);
 
-        let q1 = Arc::new(ChannelSide::default());
-        let q2 = Arc::new(ChannelSide::default());
+        let q1 = Arc::new(ChannelSide::new(capacity));
+        let q2 = Arc::new(ChannelSide::new(capacity));
         let c1 = Channel {
             peer_id,
             me: Arc::clone(&q1),
             peer: Arc::downgrade(&q2),
+            peer_koid: q2.koid,
             head: Mutex::new(None),
         };
         let c2 = Channel {
             peer_id,
             me: q2,
             peer: Arc::downgrade(&q1),
+            peer_koid: q1.koid,
             head: Mutex::new(None),
         };
-        (c1, c2)
+        Ok((c1, c2))
     }
 
     #[inline]
@@ -114,18 +294,46 @@ impl Channel {
         &self.me.event
     }
 
+    /// The IDs of this side and its peer.
+    #[inline]
+    pub fn koids(&self) -> (Koid, Koid) {
+        (self.me.koid, self.peer_koid)
+    }
+
+    /// The total size of the buffers of the packets not yet received.
+    pub fn pending_size(&self) -> usize {
+        let head = PREEMPT.scope(|| self.head.lock().as_ref().map_or(0, |p| p.buffer().len()));
+        head + self.me.msg_size.load(SeqCst)
+    }
+
     /// # Errors
     ///
     /// Returns error if the peer is closed or if the channel is full.
     pub fn send(&self, msg: &mut Packet) -> sv_call::Result {
         let peer = self.peer.upgrade().ok_or(sv_call::EPIPE)?;
-        if peer.msgs.len() >= MAX_QUEUE_SIZE {
+
+        // Replies to pending calls are handed to the callers directly.
+        let caller = PREEMPT.scope(|| {
+            let mut callers = peer.callers.lock();
+            callers.as_mut().and_then(|callers| callers.remove(&msg.id))
+        });
+        if let Some(caller) = caller {
+            match caller.wake(Ok(mem::take(msg))) {
+                None => return Ok(()),
+                // The caller is gone, so queue the reply as usual.
+                Some(reply) => *msg = reply?,
+            }
+        }
+
+        let ret = if peer.msgs.len() >= peer.capacity {
             Err(sv_call::ENOSPC)
         } else {
-            peer.msgs.push(mem::take(msg));
+            peer.push(mem::take(msg));
             peer.event.notify(0, SIG_READ);
             Ok(())
-        }
+        };
+        peer.update_writer(&self.me.event);
+        ret
     }
 
     /// # Errors
@@ -142,12 +350,17 @@ impl Channel {
         let packet = match head.take() {
             Some(packet) => packet,
             None => {
-                let err = if self.peer.strong_count() > 0 {
+                let peer = self.peer.upgrade();
+                let err = if peer.is_some() && !self.me.is_peer_closed() {
                     sv_call::ENOENT
                 } else {
                     sv_call::EPIPE
                 };
-                self.me.msgs.pop().ok_or(err)?
+                let packet = self.me.pop().ok_or(err)?;
+                if let Some(peer) = peer {
+                    self.me.update_writer(&peer.event);
+                }
+                packet
             }
         };
 
@@ -156,6 +369,88 @@ impl Channel {
         let ret = if buffer_size > *buffer_cap || handle_count > *handle_cap {
             *head = Some(packet);
             Err(sv_call::EBUFFER)
+        } else {
+            self.me.update_reader();
+            Ok(packet)
+        };
+        *buffer_cap = buffer_size;
+        *handle_cap = handle_count;
+        ret
+    }
+
+    /// Send a packet and wait for the reply with the same ID from the peer,
+    /// which skips the queue and returns to the caller directly.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the packet can't be sent, if another call with the
+    /// same ID is pending, if the peer is closed before it replies, or if the
+    /// wait times out. If the buffers are too small for the reply, it's left in
+    /// the channel to be received later.
+    pub fn call(
+        &self,
+        msg: &mut Packet,
+        timeout: Duration,
+        buffer_cap: &mut usize,
+        handle_cap: &mut usize,
+    ) -> sv_call::Result<Packet> {
+        let id = msg.id;
+        let caller = Arc::try_new(Caller::new())?;
+        PREEMPT.scope(|| {
+            let mut callers = self.me.callers.lock();
+            let callers = callers.as_mut().ok_or(sv_call::EPIPE)?;
+            if callers.contains_key(&id) {
+                return Err(sv_call::EEXIST);
+            }
+            callers.insert(id, Arc::clone(&caller));
+            Ok(())
+        })?;
+        let remove = || {
+            PREEMPT.scope(|| {
+                if let Some(callers) = self.me.callers.lock().as_mut() {
+                    callers.remove(&id);
+                }
+            })
+        };
+
+        if let Err(err) = self.send(msg) {
+            remove();
+            return Err(err);
+        }
+
+        let ret = {
+            let pree = PREEMPT.lock();
+            let reply = caller.reply.lock();
+            if matches!(*reply, Reply::Pending) {
+                caller.wo.wait((reply, pree), timeout, "Channel::call")
+            } else {
+                Ok(())
+            }
+        };
+        remove();
+
+        // A reply racing with the timeout will be queued by the sender.
+        let reply = PREEMPT.scope(|| mem::replace(&mut *caller.reply.lock(), Reply::Abandoned));
+        let packet = match reply {
+            Reply::Ready(reply) => reply?,
+            _ => return Err(ret.err().unwrap_or(sv_call::ETIME)),
+        };
+
+        let buffer_size = packet.buffer().len();
+        let handle_count = packet.object_count();
+        let ret = if buffer_size > *buffer_cap || handle_count > *handle_cap {
+            PREEMPT.scope(|| {
+                let mut head = self.head.lock();
+                match *head {
+                    None => *head = Some(packet),
+                    Some(_) => self.me.push(packet),
+                }
+            });
+            if let Some(peer) = self.peer.upgrade() {
+                self.me.update_writer(&peer.event);
+            }
+            self.me.event.notify(0, SIG_READ);
+            Err(sv_call::EBUFFER)
         } else {
             Ok(packet)
         };
@@ -174,7 +469,15 @@ unsafe impl DefaultFeature for Channel {
 impl Drop for Channel {
     fn drop(&mut self) {
         if let Some(peer) = self.peer.upgrade() {
+            // Wake up the waiters for reading and writing first so that they
+            // can see `EPIPE`, and then cancel the rest.
+            peer.event.notify(0, SIG_READ | SIG_WRITE | SIG_PEER_CLOSED);
             peer.event.cancel();
+
+            let callers = PREEMPT.scope(|| peer.callers.lock().take());
+            for caller in callers.into_iter().flat_map(BTreeMap::into_values) {
+                let _ = caller.wake(Err(sv_call::EPIPE));
+            }
         }
     }
 }
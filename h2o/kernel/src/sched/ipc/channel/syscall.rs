@@ -1,5 +1,8 @@
-use core::slice;
+use alloc::vec;
+use core::{mem, slice};
 
+use bitop_ex::BitOpEx;
+use paging::PAGE_SHIFT;
 use sv_call::{
     ipc::{RawPacket, MAX_BUFFER_SIZE, MAX_HANDLE_COUNT},
     *,
@@ -7,16 +10,17 @@ use sv_call::{
 
 use super::*;
 use crate::{
-    sched::SIG_READ,
+    mem::space::PhysTrait,
+    sched::task::Space,
     syscall::{In, InOut, Out, UserPtr},
 };
 
 #[syscall]
-fn chan_new(p1: UserPtr<Out, Handle>, p2: UserPtr<Out, Handle>) -> Result {
+fn chan_new(capacity: usize, p1: UserPtr<Out, Handle>, p2: UserPtr<Out, Handle>) -> Result {
     p1.check()?;
     p2.check()?;
+    let (c1, c2) = Channel::with_capacity(capacity)?;
     SCHED.with_current(|cur| {
-        let (c1, c2) = Channel::new();
         let map = cur.space().handles();
         let e1 = Arc::downgrade(&c1.me.event) as _;
         let e2 = Arc::downgrade(&c2.me.event) as _;
@@ -29,9 +33,14 @@ fn chan_new(p1: UserPtr<Out, Handle>, p2: UserPtr<Out, Handle>) -> Result {
     })
 }
 
-fn chan_send_impl<F, R>(hdl: Handle, packet: UserPtr<In, RawPacket>, send: F) -> Result<R>
+fn chan_send_impl<F, R>(
+    hdl: Handle,
+    packet: UserPtr<In, RawPacket>,
+    require: Feature,
+    send: F,
+) -> Result<R>
 where
-    F: FnOnce(&Channel, &mut Packet) -> Result<R>,
+    F: FnOnce(&Arc<Channel>, &mut Packet) -> Result<R>,
 {
     hdl.check_null()?;
 
@@ -46,44 +55,75 @@ where
     if handles.contains(&hdl) {
         return Err(EPERM);
     }
-    let buffer = unsafe { slice::from_raw_parts(packet.buffer, packet.buffer_size) };
+    let buffer = Buffer::from_user(UserPtr::new(packet.buffer), packet.buffer_size)?;
 
     SCHED.with_current(|cur| {
         let map = cur.space().handles();
         let obj = map.get::<Channel>(hdl)?;
-        if !obj.features().contains(Feature::WRITE) {
+        if !obj.features().contains(require) {
             return Err(EPERM);
         }
         let channel = Arc::clone(&obj);
         drop(obj);
 
+        let phys = share_phys(cur.space(), packet.phys, packet.phys_len)?;
         let objects = map.send(handles, &channel)?;
-        let mut packet = Packet::new(packet.id, objects, buffer);
+        let mut packet = Packet::with_buffer(packet.id, objects, buffer).with_phys(phys);
         send(&channel, &mut packet)
     })
 }
 
+/// Share the first `len` bytes of the physical object copy-on-write to carry
+/// them out of line. The shared copy is charged to the job of the sender until
+/// the packet is dropped or received.
+fn share_phys(space: &Space, hdl: Handle, len: usize) -> Result<Option<(Arc<Phys>, usize)>> {
+    if hdl.is_null() {
+        return Ok(None);
+    }
+    let phys = space.handles().get::<Phys>(hdl)?;
+    if !phys.features().contains(Feature::READ) {
+        return Err(EPERM);
+    }
+    if len > phys.len() {
+        return Err(ERANGE);
+    }
+    // Contiguous objects can only be copied in whole pages.
+    let sub_len = match **phys {
+        Phys::Cont(_) => len.round_up_bit(PAGE_SHIFT),
+        Phys::Ext(_) => len,
+    };
+    let ret = phys.create_sub(0, sub_len, true, space.job())?;
+    Ok(Some((ret, len)))
+}
+
 #[inline]
 fn read_raw(packet_ptr: UserPtr<In, RawPacket>) -> Result<RawPacket> {
-    let raw = unsafe { packet_ptr.read()? };
+    let mut raw = unsafe { packet_ptr.read()? };
     UserPtr::<Out, Handle>::new(raw.handles).check_slice(raw.handle_cap)?;
     UserPtr::<Out>::new(raw.buffer).check_slice(raw.buffer_cap)?;
 
+    raw.phys = Handle::NULL;
+    raw.phys_len = 0;
     Ok(raw)
 }
 
 #[inline]
-fn receive_handles<E: ?Sized + Event>(
+fn receive_handles(
     res: Result<Packet>,
     map: &crate::sched::task::hdl::HandleMap,
     raw: &mut RawPacket,
-    event: Arc<E>,
 ) -> Result<Packet> {
     match res {
         Ok(mut packet) => {
             let handles = unsafe { slice::from_raw_parts_mut(raw.handles, raw.handle_cap) };
             map.receive(&mut packet.objects, handles);
-            event.notify(SIG_READ, 0);
+
+            if let Some((phys, len)) = packet.phys.take() {
+                raw.phys_len = len;
+                let event = phys.event();
+                let obj: hdl::Ref = hdl::Ref::from_raw(phys, Some(event))?;
+                map.receive(&mut vec![obj], slice::from_mut(&mut raw.phys));
+            }
             Ok(packet)
         }
         Err(e) => Err(e),
@@ -96,10 +136,9 @@ fn write_raw_with_rest_of_packet(
     mut raw: RawPacket,
     res: Result<Packet>,
 ) -> Result {
-    let ret = res.map(|packet| unsafe {
+    let ret = res.and_then(|packet| {
         raw.id = packet.id;
-        raw.buffer
-            .copy_from_nonoverlapping(packet.buffer().as_ptr(), packet.buffer().len());
+        packet.buffer().copy_to_user(UserPtr::new(raw.buffer))
     });
 
     unsafe { packet_ptr.write(raw) }?;
@@ -108,7 +147,9 @@ fn write_raw_with_rest_of_packet(
 
 #[syscall]
 fn chan_send(hdl: Handle, packet: UserPtr<In, RawPacket>) -> Result {
-    chan_send_impl(hdl, packet, |channel, packet| channel.send(packet))
+    chan_send_impl(hdl, packet, Feature::WRITE, |channel, packet| {
+        channel.send(packet)
+    })
 }
 
 #[syscall]
@@ -127,10 +168,53 @@ fn chan_recv(hdl: Handle, packet_ptr: UserPtr<InOut, RawPacket>) -> Result {
         raw.buffer_size = raw.buffer_cap;
         raw.handle_count = raw.handle_cap;
         let res = channel.receive(&mut raw.buffer_size, &mut raw.handle_count);
-        let event = (**channel).event().clone();
         drop(channel);
-        receive_handles(res, map, &mut raw, event)
+        receive_handles(res, map, &mut raw)
     });
 
     write_raw_with_rest_of_packet(packet_ptr.out(), raw, res)
 }
+
+#[syscall]
+fn chan_call(hdl: Handle, packet_ptr: UserPtr<InOut, RawPacket>, timeout_us: u64) -> Result {
+    let mut raw = read_raw(packet_ptr.r#in())?;
+    // Replies are matched by IDs.
+    if raw.id == 0 {
+        return Err(EINVAL);
+    }
+
+    // Block outside the scope of the current task.
+    let (channel, mut packet) = chan_send_impl(
+        hdl,
+        packet_ptr.r#in(),
+        Feature::READ | Feature::WRITE,
+        |channel, packet| Ok((Arc::clone(channel), mem::take(packet))),
+    )?;
+
+    raw.buffer_size = raw.buffer_cap;
+    raw.handle_count = raw.handle_cap;
+    let timeout = crate::cpu::time::from_us(timeout_us);
+    let res = channel.call(
+        &mut packet,
+        timeout,
+        &mut raw.buffer_size,
+        &mut raw.handle_count,
+    );
+    drop(channel);
+
+    let res = match res {
+        Ok(packet) => {
+            SCHED.with_current(|cur| receive_handles(Ok(packet), cur.space().handles(), &mut raw))
+        }
+        Err(EBUFFER) => Err(EBUFFER),
+        Err(err) => {
+            // The handles have been taken from the caller along with the
+            // request, so tell it that none of them is left.
+            raw.buffer_size = 0;
+            raw.handle_count = 0;
+            Err(err)
+        }
+    };
+
+    write_raw_with_rest_of_packet(packet_ptr.out(), raw, res)
+}
@@ -4,6 +4,7 @@ mod elf;
 mod excep;
 pub mod hdl;
 mod idle;
+mod job;
 mod sig;
 mod sm;
 mod space;
@@ -11,13 +12,22 @@ mod syscall;
 mod tid;
 
 use alloc::{format, string::String, sync::Arc};
+use core::time::Duration;
 
 use paging::LAddr;
 
 #[cfg(target_arch = "x86_64")]
 pub use self::ctx::arch::{DEFAULT_STACK_LAYOUT, DEFAULT_STACK_SIZE};
 use self::elf::from_elf;
-pub use self::{boot::VDSO, excep::dispatch_exception, sig::Signal, sm::*, space::Space, tid::Tid};
+pub use self::{
+    boot::VDSO,
+    excep::{dispatch_exception, exception_exit_info},
+    job::{Charge, Job, Limit},
+    sig::Signal,
+    sm::*,
+    space::Space,
+    tid::{for_each_task, Tid},
+};
 use super::{ipc::Channel, Arsc, PREEMPT};
 use crate::cpu::{CpuMask, Lazy};
 
@@ -42,6 +52,76 @@ impl Type {
     }
 }
 
+/// The scheduling class of a task.
+///
+/// A ready task always runs before those of lower priorities, while tasks of
+/// the same priority share the CPU time by their runtime. The exception is that
+/// realtime tasks leave a small part of each period to the others, so that a
+/// spinning one can't starve the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Idle,
+    Normal,
+    Realtime,
+}
+
+impl Priority {
+    pub const COUNT: usize = 3;
+
+    #[inline]
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            sv_call::task::TASK_PRIO_NORMAL => Some(Self::Normal),
+            sv_call::task::TASK_PRIO_REALTIME => Some(Self::Realtime),
+            sv_call::task::TASK_PRIO_IDLE => Some(Self::Idle),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn raw(self) -> u32 {
+        match self {
+            Self::Idle => sv_call::task::TASK_PRIO_IDLE,
+            Self::Normal => sv_call::task::TASK_PRIO_NORMAL,
+            Self::Realtime => sv_call::task::TASK_PRIO_REALTIME,
+        }
+    }
+
+    /// The length of the time slice given to a task each time it becomes
+    /// ready.
+    ///
+    /// Realtime tasks take shorter turns among themselves to reduce their
+    /// latencies, and idle tasks are only run when nothing else is.
+    #[inline]
+    pub fn time_slice(self) -> Duration {
+        match self {
+            Self::Idle => super::imp::MIN_TIME_GRAN * 2,
+            Self::Normal => super::imp::MIN_TIME_GRAN,
+            Self::Realtime => super::imp::MIN_TIME_GRAN / 3,
+        }
+    }
+
+    /// Check whether the current task can grant the expected priority.
+    ///
+    /// Realtime priorities hold most of the CPU time, so they are only granted
+    /// on explicit requests from kernel tasks or from the privileged processes
+    /// in the root job, and never inherited.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the expected priority is realtime while the current
+    /// task is neither a kernel task nor in the root job.
+    #[inline]
+    pub fn pass(this: Self, cur: &TaskInfo) -> sv_call::Result<Priority> {
+        match this {
+            Self::Realtime if cur.ty() == Type::User && cur.job().parent().is_some() => {
+                Err(sv_call::EPERM)
+            }
+            _ => Ok(this),
+        }
+    }
+}
+
 #[inline(never)]
 pub(super) fn init() {
     Lazy::force(&idle::CTX_DROPPER);
@@ -65,22 +145,35 @@ fn exec_inner(
     name: Option<String>,
     ty: Option<Type>,
     affinity: Option<CpuMask>,
+    priority: Priority,
     space: Arc<Space>,
     init_chan: sv_call::Handle,
     s: &Starter,
 ) -> sv_call::Result<Init> {
     let ty = Type::pass(ty, cur.ty())?;
+    let priority = Priority::pass(priority, &cur)?;
+    let job = space.job();
+    let affinity = match affinity {
+        Some(affinity) => job.restrict(affinity)?,
+        // Tasks moved into other jobs fall back to the affinities of those.
+        None => job.restrict(cur.affinity()).unwrap_or_else(|_| job.affinity()),
+    };
+    let charge = charge_task(&space)?;
     let ti = TaskInfo::builder()
         .from(cur.downgrade())
         .excep_chan(Arsc::try_new(Default::default())?)
         .name(name.unwrap_or(format!("{}.func{}", cur.name(), archop::rand::get())))
         .ty(ty)
-        .affinity(affinity.unwrap_or_else(|| cur.affinity()))
+        .job(Arc::clone(job))
+        .space(Arc::downgrade(&space))
+        .affinity(affinity)
+        .priority(priority)
         .build()
         .unwrap();
 
     let tid = tid::allocate(ti).map_err(|_| sv_call::EBUSY)?;
     space.set_main(&tid);
+    space.add_task(&tid);
 
     let entry = ctx::Entry {
         entry: s.entry,
@@ -90,20 +183,30 @@ fn exec_inner(
     let kstack = ctx::Kstack::new(Some(entry), ty);
     let ext_frame = ctx::ExtFrame::zeroed();
 
-    let init = Init::new(tid, space, kstack, ext_frame);
+    let init = Init::new(tid, space, kstack, ext_frame, Some(charge));
 
     Ok(init)
 }
 
+/// Count a new task in the job of `space`, which must not be killed.
+fn charge_task(space: &Space) -> sv_call::Result<Charge> {
+    if space.is_killed() {
+        return Err(sv_call::EKILLED);
+    }
+    space.job().charge(Limit::Tasks, 1)
+}
+
 #[inline]
 fn exec(
     name: Option<String>,
+    affinity: Option<CpuMask>,
+    priority: Priority,
     space: Arc<Space>,
     init_chan: sv_call::Handle,
     starter: &Starter,
 ) -> sv_call::Result<(Init, sv_call::Handle)> {
     let cur = super::SCHED.with_current(|cur| Ok(cur.tid().clone()))?;
-    let init = exec_inner(cur, name, None, None, space, init_chan, starter)?;
+    let init = exec_inner(cur, name, None, affinity, priority, space, init_chan, starter)?;
     super::SCHED.with_current(|cur| {
         let event = Arc::downgrade(&init.tid().event) as _;
         let handle = cur
@@ -122,23 +225,30 @@ fn create(
     let cur = super::SCHED.with_current(|cur| Ok(cur.tid().clone()))?;
 
     let ty = cur.ty();
+    let job = space.job();
+    let affinity = job.restrict(cur.affinity()).unwrap_or_else(|_| job.affinity());
+    let charge = charge_task(&space)?;
     let ti = TaskInfo::builder()
         .from(cur.downgrade())
         .excep_chan(Arsc::try_new(Default::default())?)
         .name(name.unwrap_or(format!("{}.func{}", cur.name(), archop::rand::get())))
         .ty(ty)
-        .affinity(cur.affinity())
+        .job(Arc::clone(job))
+        .space(Arc::downgrade(&space))
+        .affinity(affinity)
+        .priority(Priority::Normal)
         .build()
         .unwrap();
 
     let tid = tid::allocate(ti).map_err(|_| sv_call::EBUSY)?;
     space.set_main(&tid);
+    space.add_task(&tid);
 
     let mut kstack = ctx::Kstack::new(None, ty);
     kstack.task_frame_mut().set_args(init_chan.raw() as _, 0);
     let ext_frame = ctx::ExtFrame::zeroed();
 
-    let init = Init::new(tid, space, kstack, ext_frame);
+    let init = Init::new(tid, space, kstack, ext_frame, Some(charge));
 
     super::SCHED.with_current(|cur| {
         let event = Arc::downgrade(&init.tid().event) as _;
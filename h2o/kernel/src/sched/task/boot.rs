@@ -39,8 +39,17 @@ pub static BOOTFS: Azy<(Flags, Arc<Phys>)> = Azy::new(|| {
     )
 });
 
+pub static FRAMEBUFFER: Azy<Option<(Flags, Arc<Phys>)>> = Azy::new(|| {
+    let fb = crate::kargs().framebuffer?;
+    let phys = space::new_phys(fb.phys, fb.size.round_up_bit(paging::PAGE_SHIFT))
+        .inspect_err(|err| log::warn!("Failed to create framebuffer object: {:?}", err))
+        .ok()?;
+    Some((Flags::READABLE | Flags::WRITABLE | Flags::USER_ACCESS, phys))
+});
+
 fn flags_to_feat(flags: Flags) -> Feature {
-    let mut feat = Feature::SEND | Feature::SYNC;
+    let mut feat =
+        Feature::SEND | Feature::SYNC | Feature::DUPLICATE | Feature::MAP | Feature::DERIVE;
     if flags.contains(Flags::READABLE) {
         feat |= Feature::READ
     }
@@ -99,7 +108,7 @@ pub fn setup() {
                 .expect("Failed to create boot FS reference"),
         );
     }
-    let space = super::Space::new().expect("Failed to create space");
+    let space = super::Space::new(super::Job::root()).expect("Failed to create space");
     unsafe {
         objects.push(
             hdl::Ref::try_new_unchecked(
@@ -109,14 +118,46 @@ pub fn setup() {
             )
             .expect("Failed to create root virt"),
         );
+
+        if let Some((flags, ref phys)) = *FRAMEBUFFER {
+            objects.push(
+                hdl::Ref::from_raw_unchecked(Arc::clone(phys), flags_to_feat(flags), None)
+                    .expect("Failed to create framebuffer reference"),
+            );
+        }
     }
 
     let buf = {
+        let (pci_ecam, pci_bus_start, pci_bus_end) =
+            crate::dev::acpi::pci_ecam().unwrap_or_default();
+        let fb = crate::kargs().framebuffer.filter(|_| FRAMEBUFFER.is_some());
         let targs = Targs {
             rsdp: *crate::kargs().rsdp,
             smbios: *crate::kargs().smbios,
+            pci_ecam,
+            pci_bus_start: pci_bus_start as usize,
+            pci_bus_end: pci_bus_end as usize,
+            fb_width: fb.map_or(0, |fb| fb.width),
+            fb_height: fb.map_or(0, |fb| fb.height),
+            fb_stride: fb.map_or(0, |fb| fb.stride),
+            fb_format: fb.map_or(0, |fb| fb.format as usize),
         };
-        unsafe { mem::transmute::<_, [u8; mem::size_of::<Targs>()]>(targs) }
+        let mut buf =
+            Vec::from(unsafe { mem::transmute::<_, [u8; mem::size_of::<Targs>()]>(targs) });
+        let mut len = 0;
+        for arg in crate::cmdline::get().args() {
+            len += arg.len() + 1;
+            if len > targs::ARGS_MAX {
+                log::warn!(
+                    "Too many arguments for TINIT, ignoring {:?} and the rest",
+                    arg
+                );
+                break;
+            }
+            buf.extend_from_slice(arg.as_bytes());
+            buf.push(0);
+        }
+        buf
     };
 
     let (me, chan) = Channel::new();
@@ -135,6 +176,9 @@ pub fn setup() {
         space,
         String::from("TINIT"),
         crate::cpu::all_mask(),
+        // TINIT is in the root job, so it can still grant realtime priorities
+        // to the others explicitly.
+        Priority::Normal,
         chan,
     )
     .expect("Failed to initialize TINIT");
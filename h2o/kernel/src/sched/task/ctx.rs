@@ -124,6 +124,13 @@ impl Kstack {
         &mut self.pf_resume
     }
 
+    /// Whether the task is copying from or to user space, whose page faults
+    /// can be recovered from.
+    #[inline]
+    pub fn in_user_copy(&self) -> bool {
+        self.pf_resume.is_some()
+    }
+
     #[cfg(target_arch = "x86_64")]
     pub unsafe fn pf_resume(
         &mut self,
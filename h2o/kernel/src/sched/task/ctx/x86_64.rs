@@ -1,6 +1,7 @@
 use alloc::sync::Arc;
-use core::{alloc::Layout, mem::size_of};
+use core::{alloc::Layout, cell::Cell, mem::size_of};
 
+use archop::reg::{dr0, dr1, dr2, dr3, dr6, dr7, NR_BREAKPOINT};
 use paging::LAddr;
 use sv_call::call::Syscall;
 
@@ -246,6 +247,115 @@ impl Frame {
     }
 }
 
+/// Whether the debug registers of the current CPU hold the breakpoints of some
+/// task, so that they must be disabled when switching to tasks without any.
+#[thread_local]
+static BREAKPOINTS_LOADED: Cell<bool> = Cell::new(false);
+
+/// The hardware breakpoints of a task, loaded into the debug registers when the
+/// task is switched to.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DebugRegs {
+    addr: [u64; NR_BREAKPOINT],
+    status: u64,
+    control: u64,
+}
+
+impl DebugRegs {
+    /// The local enable bits, and the condition and length fields.
+    const CONTROL_MASK: u64 = 0b0101_0101 | 0xFFFF_0000;
+    /// The breakpoint hit bits and the single-step bit.
+    const STATUS_MASK: u64 = dr6::B0 | dr6::B1 | dr6::B2 | dr6::B3 | dr6::BS;
+
+    pub fn get(&self) -> sv_call::task::ctx::DebugRegs {
+        sv_call::task::ctx::DebugRegs {
+            addr: self.addr,
+            status: self.status,
+            control: self.control,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns error if any enabled breakpoint is out of the user space,
+    /// unaligned, or watching data or I/O ports, or if global breakpoints are
+    /// enabled.
+    pub fn set(&mut self, regs: &sv_call::task::ctx::DebugRegs) -> sv_call::Result {
+        if regs.control & !Self::CONTROL_MASK != 0 {
+            return Err(sv_call::EPERM);
+        }
+        for (index, &addr) in regs.addr.iter().enumerate() {
+            if regs.control & dr7::L[index] == 0 {
+                continue;
+            }
+            let cond = (regs.control & dr7::RW[index]) >> (16 + index * 4);
+            let len = match (regs.control & dr7::LEN[index]) >> (18 + index * 4) {
+                0b00 => 1,
+                0b01 => 2,
+                0b10 => 8,
+                _ => 4,
+            };
+            // I/O breakpoints are not for user tasks, and instruction ones
+            // can't span more than 1 byte.
+            if cond == 0b10 || (cond == 0b00 && len != 1) {
+                return Err(sv_call::EINVAL);
+            }
+            // A data breakpoint hit by `mov ss` or `pop ss` is delayed to the
+            // first instruction of the kernel entry following it, before the
+            // GS base is swapped (CVE-2018-8897). #DB has no stack or GS check
+            // of its own to survive that, so don't allow them at all.
+            if cond != 0b00 {
+                return Err(sv_call::EPERM);
+            }
+            let addr = addr as usize;
+            if !(minfo::USER_BASE..minfo::USER_END).contains(&addr) {
+                return Err(sv_call::EPERM);
+            }
+            if addr & (len - 1) != 0 {
+                return Err(sv_call::EALIGN);
+            }
+        }
+        *self = DebugRegs {
+            addr: regs.addr,
+            status: regs.status & Self::STATUS_MASK,
+            control: regs.control,
+        };
+        Ok(())
+    }
+
+    /// Record the status of the debug exception just raised by the task.
+    #[inline]
+    pub fn set_status(&mut self, status: u64) {
+        self.status = status & Self::STATUS_MASK;
+    }
+
+    /// Whether the last debug exception is raised by an instruction
+    /// breakpoint, which is a fault and would be raised again on return
+    /// unless the resume flag is set.
+    pub fn hit_instruction(&self) -> bool {
+        (0..NR_BREAKPOINT)
+            .any(|index| self.status & (1 << index) != 0 && self.control & dr7::RW[index] == 0)
+    }
+
+    /// # Safety
+    ///
+    /// The function must be called only when switching to the task.
+    pub unsafe fn load(&self) {
+        if self.control == 0 {
+            if BREAKPOINTS_LOADED.replace(false) {
+                dr7::write(0);
+            }
+            return;
+        }
+        dr0::write(self.addr[0]);
+        dr1::write(self.addr[1]);
+        dr2::write(self.addr[2]);
+        dr3::write(self.addr[3]);
+        dr7::write(self.control);
+        BREAKPOINTS_LOADED.set(true);
+    }
+}
+
 /// # Safety
 ///
 /// This function must be called only by assembly stubs.
@@ -269,6 +379,7 @@ pub(super) unsafe extern "C" fn switch_finishing(pree_value: usize, pree_flags:
         KERNEL_GS.update_tss_io_bitmap(cur.io_bitmap.as_deref());
         crate::mem::space::set_current(Arc::clone(cur.space.mem()));
         cur.ext_frame.load();
+        cur.debug_regs.load();
         if !cpu::arch::in_intr() && cur.tid.ty() == task::Type::Kernel {
             KERNEL_GS.load();
         }
@@ -144,6 +144,7 @@ pub fn from_elf(
     space: Arc<super::Space>,
     name: String,
     affinity: CpuMask,
+    priority: Priority,
     init_chan: hdl::Ref,
 ) -> sv_call::Result<Init> {
     let file = Elf::parse(image)
@@ -174,6 +175,7 @@ pub fn from_elf(
         Some(name),
         Some(Type::User),
         Some(affinity),
+        priority,
         space,
         init_chan,
         &starter,
@@ -6,8 +6,10 @@ use core::{
 };
 
 use archop::reg::cr2;
-use bytes::Buf;
-use sv_call::task::excep::{Exception, ExceptionResult, EXRES_CODE_RECOVERED};
+use sv_call::task::{
+    excep::{Exception, ExceptionResult, EXRES_CODE_RECOVERED},
+    ExitInfo, EXIT_EXCEPTION,
+};
 
 use super::ctx::x86_64::Frame;
 use crate::{
@@ -15,6 +17,31 @@ use crate::{
     sched::{ipc::Packet, PREEMPT, SCHED, SIG_READ},
 };
 
+fn exception(frame: &Frame, vec: ExVec) -> Exception {
+    Exception {
+        vec: vec as u8,
+        errc: unsafe { frame.errc_vec },
+        cr2: match vec {
+            ExVec::PageFault => cr2::read(),
+            _ => 0,
+        },
+    }
+}
+
+/// The exit record of a task killed by an exception that no handler recovered
+/// from.
+pub fn exception_exit_info(frame: &Frame, vec: ExVec) -> ExitInfo {
+    let excep = exception(frame, vec);
+    ExitInfo {
+        reason: EXIT_EXCEPTION,
+        vec: excep.vec.into(),
+        retval: sv_call::EFAULT.into_retval(),
+        errc: excep.errc,
+        fault_addr: excep.cr2,
+        ..Default::default()
+    }
+}
+
 pub fn dispatch_exception(frame: &mut Frame, vec: ExVec) -> bool {
     let slot = match SCHED.with_current(|cur| Ok(cur.tid.excep_chan())) {
         Ok(slot) => slot,
@@ -26,16 +53,7 @@ pub fn dispatch_exception(frame: &mut Frame, vec: ExVec) -> bool {
         _ => return false,
     };
 
-    let data: [u8; mem::size_of::<Exception>()] = unsafe {
-        mem::transmute(Exception {
-            vec: vec as u8,
-            errc: unsafe { frame.errc_vec },
-            cr2: match vec {
-                ExVec::PageFault => cr2::read(),
-                _ => 0,
-            },
-        })
-    };
+    let data: [u8; mem::size_of::<Exception>()] = unsafe { mem::transmute(exception(frame, vec)) };
 
     let mut excep = Packet::new(0, Default::default(), &data);
     if excep_chan.send(&mut excep).is_err() {
@@ -58,18 +76,22 @@ pub fn dispatch_exception(frame: &mut Frame, vec: ExVec) -> bool {
 
     #[allow(const_item_mutation)]
     let ret = match excep_chan.receive(&mut usize::MAX, &mut usize::MAX) {
-        Ok(mut res) => {
-            let mut data = MaybeUninit::<ExceptionResult>::uninit();
-            res.buffer_mut().copy_to_slice(unsafe {
-                slice::from_raw_parts_mut(
-                    data.as_mut_ptr().cast(),
-                    mem::size_of::<ExceptionResult>(),
-                )
-            });
+        Ok(res) => match res.data() {
+            buf if buf.len() == mem::size_of::<ExceptionResult>() => {
+                let mut data = MaybeUninit::<ExceptionResult>::uninit();
+                unsafe {
+                    slice::from_raw_parts_mut(
+                        data.as_mut_ptr().cast(),
+                        mem::size_of::<ExceptionResult>(),
+                    )
+                }
+                .copy_from_slice(buf);
 
-            let res = unsafe { data.assume_init() };
-            Some(res.code == EXRES_CODE_RECOVERED)
-        }
+                let res = unsafe { data.assume_init() };
+                Some(res.code == EXRES_CODE_RECOVERED)
+            }
+            _ => Some(false),
+        },
         Err(err) => match err {
             sv_call::EPIPE => None,
             _ => Some(false),
@@ -1,3 +1,4 @@
+mod info;
 mod node;
 
 use alloc::{
@@ -16,7 +17,11 @@ use core::{
 use collection_ex::{CHashMap, FnvHasher};
 use sv_call::{Feature, Result, EINVAL, ETYPE};
 
-pub use self::node::{Ref, MAX_HANDLE_COUNT};
+pub use self::{
+    info::{object_info, Koid},
+    node::{Ref, MAX_HANDLE_COUNT},
+};
+use super::{Job, Limit};
 use crate::sched::{ipc::Channel, Event, PREEMPT};
 
 type BH = BuildHasherDefault<FnvHasher>;
@@ -57,6 +62,8 @@ pub struct HandleMap {
     list: CHashMap<u32, Ref, BH>,
     mix: u32,
     next_id: AtomicU32,
+    /// The job the handles are charged to.
+    job: Option<Arc<Job>>,
 }
 
 impl HandleMap {
@@ -66,6 +73,23 @@ impl HandleMap {
             list: CHashMap::default(),
             mix: archop::rand::get() as u32,
             next_id: AtomicU32::new(1),
+            job: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_job(job: Arc<Job>) -> Self {
+        HandleMap {
+            list: CHashMap::default(),
+            mix: archop::rand::get() as u32,
+            next_id: AtomicU32::new(1),
+            job: Some(job),
+        }
+    }
+
+    fn release(&self, count: usize) {
+        if let Some(ref job) = self.job {
+            job.release(Limit::Handles, count);
         }
     }
 
@@ -103,6 +127,16 @@ impl HandleMap {
         })
     }
 
+    /// Collect the handles in the map.
+    pub fn list(&self) -> Vec<sv_call::Handle> {
+        PREEMPT.scope(|| {
+            let mut ret = Vec::with_capacity(self.list.len());
+            self.list
+                .for_each(|key, _| ret.push(sv_call::Handle::new(key ^ self.mix)));
+            ret
+        })
+    }
+
     #[inline]
     pub fn clone_ref(&self, handle: sv_call::Handle) -> Result<sv_call::Handle> {
         let old = self.get_ref(handle)?;
@@ -113,10 +147,18 @@ impl HandleMap {
 
     #[inline]
     pub fn insert_ref(&self, value: Ref) -> Result<sv_call::Handle> {
+        if let Some(ref job) = self.job {
+            job.acquire(Limit::Handles, 1)?;
+        }
+        Ok(self.insert_charged(value))
+    }
+
+    /// Insert an object whose handle has already been charged to the job.
+    fn insert_charged(&self, value: Ref) -> sv_call::Handle {
         let key = self.next_id.fetch_add(1, SeqCst);
         let old = PREEMPT.scope(|| self.list.insert(key, value));
         assert!(old.is_none());
-        Ok(sv_call::Handle::new(key ^ self.mix))
+        sv_call::Handle::new(key ^ self.mix)
     }
 
     #[inline]
@@ -170,7 +212,9 @@ impl HandleMap {
     #[inline]
     pub fn remove_ref(&self, handle: sv_call::Handle) -> Result<Ref> {
         let key = self.decode(handle);
-        PREEMPT.scope(|| self.list.remove(&key).ok_or(EINVAL))
+        let obj = PREEMPT.scope(|| self.list.remove(&key).ok_or(EINVAL))?;
+        self.release(1);
+        Ok(obj)
     }
 
     pub fn remove<T: Send + Sync + Any>(&self, handle: sv_call::Handle) -> Result<Ref<T>> {
@@ -178,12 +222,13 @@ impl HandleMap {
         let res = self
             .list
             .try_remove(&key, |obj| if obj.is::<T>() { Ok(()) } else { Err(ETYPE) });
-        res.map_err(|err| err.unwrap_or(EINVAL))
-            .map(|obj| obj.downcast().unwrap())
+        let obj = res.map_err(|err| err.unwrap_or(EINVAL))?;
+        self.release(1);
+        Ok(obj.downcast().unwrap())
     }
 
-    fn merge(&self, objects: Vec<Ref>) -> impl Iterator<Item = Result<sv_call::Handle>> + '_ {
-        objects.into_iter().map(|obj| self.insert_ref(obj))
+    fn merge(&self, objects: Vec<Ref>) -> impl Iterator<Item = sv_call::Handle> + '_ {
+        objects.into_iter().map(|obj| self.insert_charged(obj))
     }
 
     fn split(&self, handles: &[sv_call::Handle], src: &Channel) -> Result<Vec<Ref>> {
@@ -205,6 +250,7 @@ impl HandleMap {
                 }
             }
         }
+        self.release(result.len());
         Ok(result)
     }
 
@@ -215,13 +261,27 @@ impl HandleMap {
         PREEMPT.scope(|| self.split(handles, src))
     }
 
+    /// Insert the objects received from a channel. They are charged to the job
+    /// regardless of its limit since the packet can't be put back.
     #[inline]
     pub fn receive(&self, other: &mut Vec<Ref>, handles: &mut [sv_call::Handle]) {
-        PREEMPT.scope(|| {
+        let count = PREEMPT.scope(|| {
+            let mut count = 0;
             for (hdl, obj) in handles.iter_mut().zip(self.merge(mem::take(other))) {
-                *hdl = obj.unwrap();
+                *hdl = obj;
+                count += 1;
             }
-        })
+            count
+        });
+        if let Some(ref job) = self.job {
+            job.acquire_force(Limit::Handles, count);
+        }
+    }
+}
+
+impl Drop for HandleMap {
+    fn drop(&mut self) {
+        self.release(self.list.len());
     }
 }
 
@@ -237,7 +297,7 @@ mod syscall {
 
     use crate::{
         sched::SCHED,
-        syscall::{InOut, UserPtr},
+        syscall::{InOut, Out, UserPtr},
     };
 
     #[syscall]
@@ -257,6 +317,25 @@ mod syscall {
         ret
     }
 
+    #[syscall]
+    fn obj_info(hdl: Handle, info: UserPtr<Out, obj::ObjInfo>) -> Result {
+        hdl.check_null()?;
+        info.check()?;
+        let ret = SCHED.with_current(|cur| {
+            let obj = cur.space().handles().get_ref(hdl)?;
+            Ok(super::object_info(&obj))
+        })?;
+        info.write(ret)
+    }
+
+    #[syscall]
+    fn obj_list(handles: UserPtr<Out, Handle>, count: usize) -> Result<usize> {
+        handles.check_slice(count)?;
+        let list = SCHED.with_current(|cur| Ok(cur.space().handles().list()))?;
+        handles.write_slice(&list[..count.min(list.len())])?;
+        Ok(list.len())
+    }
+
     #[syscall]
     fn obj_drop(hdl: Handle) -> Result {
         hdl.check_null()?;
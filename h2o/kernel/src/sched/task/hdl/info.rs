@@ -0,0 +1,103 @@
+use alloc::sync::{Arc, Weak};
+use core::{
+    any::Any,
+    num::NonZeroU64,
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+};
+
+use sv_call::{obj::ObjInfo, *};
+
+use super::Ref;
+use crate::{
+    cpu::{intr::Interrupt, time::TimerEvent},
+    dev::Resource,
+    mem::space::{Phys, PhysTrait, Pinned, Virt},
+    sched::{
+        ipc::Channel,
+        task::{syscall::SuspendToken, Job, Space, Tid},
+        BasicEvent, Dispatcher, Event,
+    },
+};
+
+/// The ID of a kernel object, allocated from a counter when the object is
+/// created and never reused until reboot.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Koid(NonZeroU64);
+
+impl Koid {
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Koid(NonZeroU64::new(NEXT.fetch_add(1, Relaxed)).expect("Koid overflow"))
+    }
+
+    #[inline]
+    pub fn raw(self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl Default for Koid {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Collect the information of the object referred to by `obj`.
+pub fn object_info(obj: &Ref) -> ObjInfo {
+    // SAFETY: We only read the properties of the object.
+    let any: &Arc<dyn Any + Send + Sync> = unsafe { obj.deref_unchecked() };
+
+    // (type, ID, ID of the related object, memory usage)
+    let (ty, koid, related, mem_usage) = if let Some(chan) = any.downcast_ref::<Channel>() {
+        let (me, peer) = chan.koids();
+        (SV_CHANNEL, Some(me), Some(peer), chan.pending_size())
+    } else if let Some(phys) = any.downcast_ref::<Phys>() {
+        (SV_PHYS, Some(phys.koid()), phys.parent(), phys.len())
+    } else if let Some(pinned) = any.downcast_ref::<Pinned>() {
+        let len = pinned.ranges().iter().map(|&(_, len)| len).sum();
+        let phys = pinned.phys().koid();
+        (SV_PINNED, Some(pinned.koid()), Some(phys), len)
+    } else if let Some(virt) = any.downcast_ref::<Weak<Virt>>() {
+        match virt.upgrade() {
+            Some(virt) => (SV_VIRT, Some(virt.koid()), virt.parent_koid(), virt.len()),
+            None => (SV_VIRT, None, None, 0),
+        }
+    } else if let Some(tid) = any.downcast_ref::<Tid>() {
+        (SV_TASK, Some(tid.koid()), None, 0)
+    } else if let Some(token) = any.downcast_ref::<SuspendToken>() {
+        let tid = token.tid().koid();
+        (SV_SUSPENDTOKEN, Some(token.koid()), Some(tid), 0)
+    } else if let Some(space) = any.downcast_ref::<Space>() {
+        (SV_SPACE, Some(space.koid()), None, 0)
+    } else if let Some(job) = any.downcast_ref::<Job>() {
+        let parent = job.parent().map(|parent| parent.koid());
+        (SV_JOB, Some(job.koid()), parent, job.usage().memory)
+    } else if let Some(event) = any.downcast_ref::<BasicEvent>() {
+        (SV_EVENT, Some(event.event_data().koid()), None, 0)
+    } else if let Some(disp) = any.downcast_ref::<Dispatcher>() {
+        (SV_DISPATCHER, Some(disp.koid()), None, 0)
+    } else if let Some(timer) = any.downcast_ref::<TimerEvent>() {
+        (SV_TIMER, Some(timer.event_data().koid()), None, 0)
+    } else if let Some(intr) = any.downcast_ref::<Interrupt>() {
+        (SV_INTERRUPT, Some(intr.event_data().koid()), None, 0)
+    } else if let Some(res) = any.downcast_ref::<Resource<usize>>() {
+        (SV_MEMRES, Some(res.koid()), res.parent_koid(), 0)
+    } else if let Some(res) = any.downcast_ref::<Resource<u16>>() {
+        (SV_PIORES, Some(res.koid()), res.parent_koid(), 0)
+    } else if let Some(res) = any.downcast_ref::<Resource<u32>>() {
+        (SV_GSIRES, Some(res.koid()), res.parent_koid(), 0)
+    } else if any.is::<u64>() {
+        (SV_INTEGER, None, None, 0)
+    } else {
+        (usize::MAX, None, None, 0)
+    };
+
+    ObjInfo {
+        ty,
+        features: obj.features(),
+        koid: koid.map_or(0, Koid::raw),
+        related_koid: related.map_or(0, Koid::raw),
+        mem_usage,
+    }
+}
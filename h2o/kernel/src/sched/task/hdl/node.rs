@@ -171,7 +171,7 @@ impl Ref {
 
     pub fn try_clone(&self) -> Result<Ref> {
         let feat = self.features();
-        if feat.contains(Feature::SEND | Feature::SYNC) {
+        if feat.contains(Feature::SEND | Feature::SYNC | Feature::DUPLICATE) {
             // SAFETY: The underlying object is `send` and `sync`.
             Ok(unsafe { self.clone_unchecked() })
         } else {
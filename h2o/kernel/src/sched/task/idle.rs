@@ -19,16 +19,19 @@ pub(super) static CTX_DROPPER: Lazy<SegQueue<alloc::boxed::Box<Context>>> =
 pub(super) static IDLE: Lazy<Tid> = Lazy::new(|| {
     let cpu = unsafe { crate::cpu::id() };
 
+    let space = super::Space::new_current();
     let ti = TaskInfo::builder()
         .from(Default::default())
         .excep_chan(Arsc::try_new(Default::default()).expect("Failed to create task info"))
         .name(format!("IDLE{cpu}"))
         .ty(Type::Kernel)
+        .job(Arc::clone(super::Job::root()))
+        .space(Arc::downgrade(&space))
         .affinity(crate::cpu::current_mask())
+        .priority(Priority::Idle)
         .build()
         .unwrap();
 
-    let space = super::Space::new_current();
     let stack = space::init_stack(space.mem(), DEFAULT_STACK_SIZE)
         .expect("Failed to initialize stack for IDLE");
 
@@ -42,7 +45,7 @@ pub(super) static IDLE: Lazy<Tid> = Lazy::new(|| {
     let tid = tid::allocate(ti).expect("Tid exhausted");
     space.set_main(&tid);
 
-    let init = Init::new(tid.clone(), space, kstack, ctx::ExtFrame::zeroed());
+    let init = Init::new(tid.clone(), space, kstack, ctx::ExtFrame::zeroed(), None);
     crate::sched::SCHED.unblock(init, true);
 
     tid
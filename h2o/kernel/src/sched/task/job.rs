@@ -0,0 +1,334 @@
+//! Jobs, which group processes together to limit their resources and to kill
+//! them all at once.
+//!
+//! Every process (task [`Space`]) belongs to a job, and every job except the
+//! root one belongs to its parent. Resources consumed in a job are charged to
+//! all of its ancestors, so a child can never use more than its parent allows.
+
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    iter, mem, ptr,
+    sync::atomic::{AtomicUsize, Ordering::SeqCst},
+};
+
+use archop::Azy;
+use spin::Mutex;
+use sv_call::{task::JobRes, Feature, Result, EAGAIN, EINVAL, EKILLED, EMFILE, ENOMEM};
+
+use super::{
+    hdl::{DefaultFeature, Koid},
+    Space,
+};
+use crate::{cpu::CpuMask, sched::PREEMPT};
+
+static ROOT: Azy<Arc<Job>> =
+    Azy::new(|| Arc::new(Job::new(None, JobRes::UNLIMITED, crate::cpu::all_mask())));
+
+/// The kinds of resources limited by jobs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Limit {
+    /// The number of handles in the handle maps of the processes.
+    Handles,
+    /// The bytes of memory committed for physical objects.
+    Memory,
+    /// The number of running tasks.
+    Tasks,
+}
+
+impl Limit {
+    const COUNT: usize = 3;
+
+    fn error(self) -> sv_call::Error {
+        match self {
+            Limit::Handles => EMFILE,
+            Limit::Memory => ENOMEM,
+            Limit::Tasks => EAGAIN,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Members {
+    killed: bool,
+    spaces: Vec<Weak<Space>>,
+    children: Vec<Weak<Job>>,
+}
+
+#[derive(Debug)]
+pub struct Job {
+    koid: Koid,
+    parent: Option<Arc<Job>>,
+    limits: [usize; Limit::COUNT],
+    usage: [AtomicUsize; Limit::COUNT],
+    affinity: CpuMask,
+    members: Mutex<Members>,
+}
+
+impl Job {
+    fn new(parent: Option<Arc<Job>>, limits: JobRes, affinity: CpuMask) -> Self {
+        Job {
+            koid: Koid::new(),
+            parent,
+            limits: [limits.handles, limits.memory, limits.tasks],
+            usage: Default::default(),
+            affinity,
+            members: Mutex::new(Members::default()),
+        }
+    }
+
+    /// The job of the processes created by the kernel, which has no limits.
+    #[inline]
+    pub fn root() -> &'static Arc<Job> {
+        &ROOT
+    }
+
+    /// Create a child job.
+    ///
+    /// The affinity of the child is limited in that of this job, and the
+    /// resources of the child are also charged to this job.
+    pub fn new_child(
+        self: &Arc<Self>,
+        limits: JobRes,
+        affinity: Option<CpuMask>,
+    ) -> Result<Arc<Job>> {
+        let affinity = affinity.map_or(self.affinity, |affinity| affinity & self.affinity);
+        if affinity.not_any() {
+            return Err(EINVAL);
+        }
+        let child = Arc::try_new(Job::new(Some(Arc::clone(self)), limits, affinity))?;
+        self.with_members(|members| {
+            if members.killed {
+                return Err(EKILLED);
+            }
+            members.children.retain(|child| child.strong_count() > 0);
+            members.children.push(Arc::downgrade(&child));
+            Ok(())
+        })?;
+        Ok(child)
+    }
+
+    fn with_members<F, R>(&self, func: F) -> R
+    where
+        F: FnOnce(&mut Members) -> R,
+    {
+        PREEMPT.scope(|| func(&mut self.members.lock()))
+    }
+
+    fn ancestors(&self) -> impl Iterator<Item = &Job> {
+        iter::successors(Some(self), |job| job.parent.as_deref())
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<&Arc<Job>> {
+        self.parent.as_ref()
+    }
+
+    #[inline]
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+
+    #[inline]
+    pub fn affinity(&self) -> CpuMask {
+        self.affinity
+    }
+
+    /// Limit the CPU affinity of a task in the job, which must not be empty
+    /// afterwards.
+    pub fn restrict(&self, affinity: CpuMask) -> Result<CpuMask> {
+        let affinity = affinity & self.affinity;
+        if affinity.not_any() {
+            Err(EINVAL)
+        } else {
+            Ok(affinity)
+        }
+    }
+
+    /// Take `amount` of the resource from the job and all its ancestors.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if any of their limits is exceeded, in which case nothing
+    /// is taken.
+    pub fn acquire(&self, limit: Limit, amount: usize) -> Result {
+        for job in self.ancestors() {
+            let max = job.limits[limit as usize];
+            let res = job.usage[limit as usize].fetch_update(SeqCst, SeqCst, |usage| {
+                usage.checked_add(amount).filter(|&usage| usage <= max)
+            });
+            if res.is_err() {
+                self.ancestors()
+                    .take_while(|&charged| !ptr::eq(charged, job))
+                    .for_each(|charged| {
+                        charged.usage[limit as usize].fetch_sub(amount, SeqCst);
+                    });
+                return Err(limit.error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Take the resource regardless of the limits, for the resources that
+    /// can't be refused, such as the handles received from channels.
+    pub fn acquire_force(&self, limit: Limit, amount: usize) {
+        for job in self.ancestors() {
+            job.usage[limit as usize].fetch_add(amount, SeqCst);
+        }
+    }
+
+    /// Return `amount` of the resource taken with [`Job::acquire`].
+    pub fn release(&self, limit: Limit, amount: usize) {
+        for job in self.ancestors() {
+            job.usage[limit as usize].fetch_sub(amount, SeqCst);
+        }
+    }
+
+    /// The same as [`Job::acquire`], but the resource is returned when the
+    /// result is dropped.
+    pub fn charge(self: &Arc<Self>, limit: Limit, amount: usize) -> Result<Charge> {
+        self.acquire(limit, amount)?;
+        Ok(Charge {
+            job: Arc::clone(self),
+            limit,
+            amount,
+        })
+    }
+
+    pub fn usage(&self) -> JobRes {
+        let usage = |limit: Limit| self.usage[limit as usize].load(SeqCst);
+        JobRes {
+            handles: usage(Limit::Handles),
+            memory: usage(Limit::Memory),
+            tasks: usage(Limit::Tasks),
+        }
+    }
+
+    /// Record a new process in the job.
+    pub(super) fn add_space(&self, space: &Arc<Space>) -> Result {
+        self.with_members(|members| {
+            if members.killed {
+                return Err(EKILLED);
+            }
+            members.spaces.retain(|space| space.strong_count() > 0);
+            members.spaces.push(Arc::downgrade(space));
+            Ok(())
+        })
+    }
+
+    /// Kill every process in the job and its descendants. No more processes
+    /// or child jobs can be created in them afterwards.
+    pub fn kill(&self) {
+        let (spaces, children) = self.with_members(|members| {
+            members.killed = true;
+            (
+                mem::take(&mut members.spaces),
+                mem::take(&mut members.children),
+            )
+        });
+        spaces
+            .iter()
+            .filter_map(Weak::upgrade)
+            .for_each(|space| space.kill());
+        children
+            .iter()
+            .filter_map(Weak::upgrade)
+            .for_each(|child| child.kill());
+    }
+}
+
+unsafe impl DefaultFeature for Job {
+    fn default_features() -> Feature {
+        Feature::SEND
+            | Feature::SYNC
+            | Feature::READ
+            | Feature::WRITE
+            | Feature::EXECUTE
+            | Feature::DUPLICATE
+            | Feature::DERIVE
+    }
+}
+
+/// Some resource charged to a job, which is returned when dropped.
+#[derive(Debug)]
+pub struct Charge {
+    job: Arc<Job>,
+    limit: Limit,
+    amount: usize,
+}
+
+impl Charge {
+    #[inline]
+    pub fn job(&self) -> &Arc<Job> {
+        &self.job
+    }
+
+    /// Change the charged amount of the resource.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the job can't afford the growth.
+    pub fn resize(&mut self, amount: usize) -> Result {
+        if amount > self.amount {
+            self.job.acquire(self.limit, amount - self.amount)?;
+        } else {
+            self.job.release(self.limit, self.amount - amount);
+        }
+        self.amount = amount;
+        Ok(())
+    }
+}
+
+impl Drop for Charge {
+    fn drop(&mut self) {
+        self.job.release(self.limit, self.amount);
+    }
+}
+
+mod syscall {
+    use sv_call::{
+        task::{CpuSet, JobRes},
+        *,
+    };
+
+    use crate::{
+        sched::SCHED,
+        syscall::{In, Out, UserPtr},
+    };
+
+    #[syscall]
+    fn job_new(
+        parent: Handle,
+        limits: UserPtr<In, JobRes>,
+        affinity: UserPtr<In, CpuSet>,
+    ) -> Result<Handle> {
+        let limits = unsafe { limits.read() }?;
+        let affinity = if affinity.as_ptr().is_null() {
+            None
+        } else {
+            Some(super::super::syscall::get_affinity(affinity)?)
+        };
+        SCHED.with_current(|cur| {
+            let parent = cur.space().job_of(parent, Feature::DERIVE)?;
+            let child = parent.new_child(limits, affinity)?;
+            cur.space().handles().insert_raw(child, None)
+        })
+    }
+
+    #[syscall]
+    fn job_kill(hdl: Handle) -> Result {
+        hdl.check_null()?;
+        let job = SCHED.with_current(|cur| cur.space().job_of(hdl, Feature::EXECUTE))?;
+        job.kill();
+        Ok(())
+    }
+
+    #[syscall]
+    fn job_usage(hdl: Handle, usage: UserPtr<Out, JobRes>) -> Result {
+        usage.check()?;
+        let job = SCHED.with_current(|cur| cur.space().job_of(hdl, Feature::READ))?;
+        usage.write(job.usage())
+    }
+}
@@ -1,7 +1,11 @@
-use alloc::{boxed::Box, string::String, sync::Arc};
+use alloc::{
+    boxed::Box,
+    string::String,
+    sync::{Arc, Weak},
+};
 use core::{
     fmt,
-    mem::ManuallyDrop,
+    mem::{self, ManuallyDrop},
     ops::{Deref, DerefMut},
     time::Duration,
 };
@@ -9,35 +13,60 @@ use core::{
 use bitvec::prelude::BitVec;
 use derive_builder::Builder;
 use spin::Mutex;
+use sv_call::task::ExitInfo;
 
 use super::{
-    ctx, idle,
+    ctx,
+    hdl::Koid,
+    idle,
     sig::Signal,
     tid::{self, WeakTid},
-    Space, Tid, Type,
+    Charge, Job, Priority, Space, Tid, Type,
 };
 use crate::{
-    cpu::{time::Instant, CpuMask},
-    sched::{ipc::Channel, Arsc, BasicEvent, Event, PREEMPT, SIG_READ},
+    cpu::{
+        time::{Instant, Timer},
+        CpuMask,
+    },
+    sched::{
+        ipc::{Channel, Packet},
+        Arsc, BasicEvent, Event, PREEMPT, SIG_READ,
+    },
 };
 
 #[derive(Debug, Builder)]
 #[builder(no_std, pattern = "owned")]
 pub struct TaskInfo {
+    #[builder(setter(skip))]
+    koid: Koid,
     from: WeakTid,
     #[builder(setter(skip))]
-    ret_cell: Mutex<Option<usize>>,
+    exit_info: Mutex<Option<ExitInfo>>,
+    /// The channel to which the exit record is sent.
+    #[builder(setter(skip))]
+    watcher: Mutex<Option<Channel>>,
     #[builder(setter(skip))]
     pub(super) event: Arc<BasicEvent>,
     excep_chan: Arsc<Mutex<Option<Channel>>>,
 
     name: String,
     ty: Type,
+    job: Arc<Job>,
+    /// The space the task runs in, kept weak since spaces own their tasks'
+    /// handles.
+    space: Weak<Space>,
 
-    affinity: CpuMask,
+    #[builder(setter(into))]
+    affinity: Mutex<CpuMask>,
+    #[builder(setter(into))]
+    priority: Mutex<Priority>,
 
     #[builder(setter(skip))]
     signal: Mutex<Option<Signal>>,
+    /// The timer of the last blocking of the task, canceled to wake it up
+    /// when it's killed.
+    #[builder(setter(skip))]
+    blocker: Mutex<Option<Arsc<Timer>>>,
 }
 
 impl TaskInfo {
@@ -51,6 +80,11 @@ impl TaskInfo {
         self.from.clone()
     }
 
+    #[inline]
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+
     #[inline]
     pub fn name(&self) -> &str {
         &self.name
@@ -61,14 +95,72 @@ impl TaskInfo {
         self.ty
     }
 
+    /// The job of the space the task is created in.
     #[inline]
-    pub fn affinity(&self) -> crate::cpu::CpuMask {
-        self.affinity
+    pub fn job(&self) -> &Arc<Job> {
+        &self.job
     }
 
+    /// The space of the task, or `None` if it has been destroyed.
     #[inline]
-    pub fn ret_cell(&self) -> &Mutex<Option<usize>> {
-        &self.ret_cell
+    pub fn space(&self) -> Option<Arc<Space>> {
+        self.space.upgrade()
+    }
+
+    #[inline]
+    pub fn affinity(&self) -> CpuMask {
+        PREEMPT.scope(|| *self.affinity.lock())
+    }
+
+    /// Returns the previous affinity.
+    #[inline]
+    pub fn set_affinity(&self, affinity: CpuMask) -> CpuMask {
+        PREEMPT.scope(|| mem::replace(&mut *self.affinity.lock(), affinity))
+    }
+
+    #[inline]
+    pub fn priority(&self) -> Priority {
+        PREEMPT.scope(|| *self.priority.lock())
+    }
+
+    #[inline]
+    pub fn set_priority(&self, priority: Priority) {
+        PREEMPT.scope(|| *self.priority.lock() = priority)
+    }
+
+    /// The exit record of the task, or `None` if it's still alive.
+    #[inline]
+    pub fn exit_info(&self) -> Option<ExitInfo> {
+        PREEMPT.scope(|| *self.exit_info.lock())
+    }
+
+    /// Create a channel to which the exit record will be sent. The record is
+    /// sent at once if the task has already exited.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the task is already being watched.
+    pub fn watch(&self) -> sv_call::Result<Channel> {
+        PREEMPT.scope(|| {
+            let mut watcher = self.watcher.lock();
+            if watcher.is_some() {
+                return Err(sv_call::EEXIST);
+            }
+            let (usr, krl) = Channel::new();
+            match *self.exit_info.lock() {
+                Some(info) => send_exit_info(&krl, info),
+                None => *watcher = Some(krl),
+            }
+            Ok(usr)
+        })
+    }
+
+    fn set_exit_info(&self, info: ExitInfo) {
+        PREEMPT.scope(|| *self.exit_info.lock() = Some(info));
+        let watcher = PREEMPT.scope(|| self.watcher.lock().take());
+        if let Some(watcher) = watcher {
+            send_exit_info(&watcher, info);
+        }
     }
 
     #[inline]
@@ -79,12 +171,45 @@ impl TaskInfo {
         PREEMPT.scope(|| func(&mut self.signal.lock()))
     }
 
+    /// Whether the task is killed, either on its own or along with its space.
+    pub fn is_killed(&self) -> bool {
+        self.with_signal(|sig| matches!(sig, Some(Signal::Kill)))
+            || self.space().map_or(false, |space| space.is_killed())
+    }
+
+    /// Record the timer on which the task is going to block, so that it can
+    /// be woken up by [`TaskInfo::interrupt`].
+    ///
+    /// Returns `false` if the task is already killed and shouldn't stay
+    /// blocked.
+    pub(in crate::sched) fn set_blocker(&self, timer: &Arsc<Timer>) -> bool {
+        PREEMPT.scope(|| {
+            *self.blocker.lock() = Some(Arsc::clone(timer));
+            !self.is_killed()
+        })
+    }
+
+    /// Wake the task up if it's blocked, so that it notices being killed.
+    pub fn interrupt(&self) {
+        if let Some(timer) = PREEMPT.scope(|| self.blocker.lock().take()) {
+            timer.cancel(true);
+        }
+    }
+
     #[inline]
     pub fn excep_chan(&self) -> Arsc<Mutex<Option<Channel>>> {
         Arsc::clone(&self.excep_chan)
     }
 }
 
+fn send_exit_info(chan: &Channel, info: ExitInfo) {
+    // SAFETY: `ExitInfo` is a plain C struct.
+    let data: [u8; mem::size_of::<ExitInfo>()] = unsafe { mem::transmute(info) };
+    let mut packet = Packet::new(0, Default::default(), &data);
+    // The watcher may have been dropped, which is fine.
+    let _ = chan.send(&mut packet);
+}
+
 #[derive(Debug)]
 pub struct Context {
     pub(in crate::sched) tid: ManuallyDrop<Tid>,
@@ -92,7 +217,11 @@ pub struct Context {
     pub(in crate::sched) space: Arc<Space>,
     pub(in crate::sched) kstack: ctx::Kstack,
     pub(in crate::sched) ext_frame: ctx::ExtFrame,
+    pub(in crate::sched) debug_regs: ctx::arch::DebugRegs,
     pub(in crate::sched) io_bitmap: Option<BitVec>,
+    /// The task counted in its job, which is returned when the context is
+    /// dropped after exiting.
+    pub(in crate::sched) charge: Option<Charge>,
 
     pub(in crate::sched) cpu: usize,
     pub(in crate::sched) runtime: Duration,
@@ -114,6 +243,16 @@ impl Context {
         &mut self.kstack
     }
 
+    #[inline]
+    pub fn debug_regs(&self) -> &ctx::arch::DebugRegs {
+        &self.debug_regs
+    }
+
+    #[inline]
+    pub fn debug_regs_mut(&mut self) -> &mut ctx::arch::DebugRegs {
+        &mut self.debug_regs
+    }
+
     #[inline]
     pub fn io_bitmap_mut(&mut self) -> &mut Option<BitVec> {
         &mut self.io_bitmap
@@ -196,26 +335,40 @@ impl IntoReady for Init {
     }
 
     #[inline]
-    fn into_ready(this: Self, cpu: usize, time_slice: Duration) -> Ready {
+    fn priority(&self) -> Priority {
+        self.ctx.tid.priority()
+    }
+
+    #[inline]
+    fn into_ready(this: Self, cpu: usize, priority: Priority) -> Ready {
         let mut ctx = this.ctx;
         ctx.cpu = cpu;
         Ready {
             ctx,
             running_state: RunningState::NOT_RUNNING,
-            time_slice,
+            priority,
+            time_slice: priority.time_slice(),
         }
     }
 }
 
 impl Init {
-    pub fn new(tid: Tid, space: Arc<Space>, kstack: ctx::Kstack, ext_frame: ctx::ExtFrame) -> Self {
+    pub fn new(
+        tid: Tid,
+        space: Arc<Space>,
+        kstack: ctx::Kstack,
+        ext_frame: ctx::ExtFrame,
+        charge: Option<Charge>,
+    ) -> Self {
         Init {
             ctx: Box::new(Context {
                 tid: ManuallyDrop::new(tid),
                 space,
                 kstack,
                 ext_frame,
+                debug_regs: Default::default(),
                 io_bitmap: None,
+                charge,
                 cpu: 0,
                 runtime: Duration::new(0, 0),
             }),
@@ -233,6 +386,9 @@ pub struct Ready {
     ctx: Box<Context>,
 
     pub(in crate::sched) running_state: RunningState,
+    /// The priority the task is queued with, which may lag behind the one in
+    /// its [`TaskInfo`] until it's queued again.
+    pub(in crate::sched) priority: Priority,
     pub(in crate::sched) time_slice: Duration,
 }
 
@@ -241,7 +397,9 @@ pub trait IntoReady {
 
     fn affinity(&self) -> CpuMask;
 
-    fn into_ready(this: Self, cpu: usize, time_slice: Duration) -> Ready;
+    fn priority(&self) -> Priority;
+
+    fn into_ready(this: Self, cpu: usize, priority: Priority) -> Ready;
 }
 
 impl Ready {
@@ -253,10 +411,14 @@ impl Ready {
         }
     }
 
-    pub fn exit(mut this: Self, retval: usize) {
+    pub fn exit(mut this: Self, info: ExitInfo) {
+        let info = ExitInfo {
+            cpu_time: this.ctx.runtime.as_nanos() as u64,
+            ..info
+        };
+        this.ctx.tid.set_exit_info(info);
         // SAFETY: The context won't be dropped twice.
         tid::deallocate(unsafe { ManuallyDrop::take(&mut this.ctx.tid) });
-        *this.ctx.tid.ret_cell.lock() = Some(retval);
         this.ctx.tid.event.notify(0, SIG_READ);
         idle::CTX_DROPPER.push(this.ctx);
     }
@@ -296,13 +458,19 @@ impl IntoReady for Blocked {
     }
 
     #[inline]
-    fn into_ready(this: Self, cpu: usize, time_slice: Duration) -> Ready {
+    fn priority(&self) -> Priority {
+        self.ctx.tid.priority()
+    }
+
+    #[inline]
+    fn into_ready(this: Self, cpu: usize, priority: Priority) -> Ready {
         let mut ctx = this.ctx;
         ctx.cpu = cpu;
         Ready {
             ctx,
             running_state: RunningState::NOT_RUNNING,
-            time_slice,
+            priority,
+            time_slice: priority.time_slice(),
         }
     }
 }
@@ -342,4 +510,14 @@ impl Blocked {
     pub fn ext_frame_mut(&mut self) -> &mut ctx::ExtFrame {
         &mut self.ctx.ext_frame
     }
+
+    #[inline]
+    pub fn debug_regs(&self) -> &ctx::arch::DebugRegs {
+        &self.ctx.debug_regs
+    }
+
+    #[inline]
+    pub fn debug_regs_mut(&mut self) -> &mut ctx::arch::DebugRegs {
+        &mut self.ctx.debug_regs
+    }
 }
@@ -1,11 +1,13 @@
-use alloc::sync::Arc;
-use core::sync::atomic::{AtomicU64, Ordering::*};
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering::*};
 
+use spin::Mutex;
 use sv_call::Feature;
 
 use super::{
-    hdl::{DefaultFeature, HandleMap},
-    Tid,
+    hdl::{DefaultFeature, HandleMap, Koid},
+    tid::WeakTid,
+    Job, Tid,
 };
 use crate::{
     mem,
@@ -14,32 +16,48 @@ use crate::{
 
 #[derive(Debug)]
 pub struct Space {
+    koid: Koid,
     mem: Arc<mem::space::Space>,
     handles: HandleMap,
     futexes: Futexes,
     main: AtomicU64,
+    /// All the tasks ever created in the space, for debuggers to enumerate.
+    tasks: Mutex<Vec<WeakTid>>,
+    job: Arc<Job>,
+    killed: AtomicBool,
 }
 
 unsafe impl Send for Space {}
 unsafe impl Sync for Space {}
 
 impl Space {
-    pub fn new() -> sv_call::Result<Arc<Self>> {
+    /// Create a process in `job`, whose handles are charged to it.
+    pub fn new(job: &Arc<Job>) -> sv_call::Result<Arc<Self>> {
         let mem = mem::space::Space::try_new(super::Type::User)?;
-        Ok(Arc::try_new(Space {
+        let space = Arc::try_new(Space {
+            koid: Koid::new(),
             mem,
-            handles: HandleMap::new(),
+            handles: HandleMap::with_job(Arc::clone(job)),
             futexes: Default::default(),
             main: AtomicU64::new(0),
-        })?)
+            tasks: Mutex::new(Vec::new()),
+            job: Arc::clone(job),
+            killed: AtomicBool::new(false),
+        })?;
+        job.add_space(&space)?;
+        Ok(space)
     }
 
     pub fn new_current() -> Arc<Self> {
         Arc::new(Space {
+            koid: Koid::new(),
             mem: mem::space::with_current(Arc::clone),
             handles: HandleMap::new(),
             futexes: Default::default(),
             main: AtomicU64::new(0),
+            tasks: Mutex::new(Vec::new()),
+            job: Arc::clone(Job::root()),
+            killed: AtomicBool::new(false),
         })
     }
 
@@ -48,11 +66,46 @@ impl Space {
         &self.mem
     }
 
+    #[inline]
+    pub fn job(&self) -> &Arc<Job> {
+        &self.job
+    }
+
+    #[inline]
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
+
     #[inline]
     pub fn set_main(&self, tid: &Tid) {
         let _ = self.main.compare_exchange(0, tid.raw(), AcqRel, Acquire);
     }
 
+    /// Record a new task in the space.
+    pub fn add_task(&self, tid: &Tid) {
+        super::PREEMPT.scope(|| {
+            let mut tasks = self.tasks.lock();
+            tasks.retain(|task| {
+                task.upgrade()
+                    .map_or(false, |tid| tid.exit_info().is_none())
+            });
+            tasks.push(tid.downgrade());
+        })
+    }
+
+    /// The tasks in the space that haven't exited yet, in the order of
+    /// creation.
+    pub fn tasks(&self) -> Vec<Tid> {
+        super::PREEMPT.scope(|| {
+            let tasks = self.tasks.lock();
+            tasks
+                .iter()
+                .filter_map(WeakTid::upgrade)
+                .filter(|tid| tid.exit_info().is_none())
+                .collect()
+        })
+    }
+
     #[inline]
     pub fn try_stop(&self, tid: &Tid) {
         let _ = self.main.compare_exchange(tid.raw(), 0, AcqRel, Acquire);
@@ -60,7 +113,19 @@ impl Space {
 
     #[inline]
     pub fn has_to_stop(&self) -> bool {
-        self.main.load(Acquire) == 0
+        self.main.load(Acquire) == 0 || self.is_killed()
+    }
+
+    /// Stop all the tasks in the space for good, as its job is killed.
+    #[inline]
+    pub fn kill(&self) {
+        self.killed.store(true, Release);
+        self.tasks().iter().for_each(|tid| tid.interrupt());
+    }
+
+    #[inline]
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Acquire)
     }
 
     #[inline]
@@ -82,10 +147,10 @@ impl Space {
         let _ = self.futexes.remove_if(&key, |futex| futex.is_empty());
     }
 
-    pub fn child(&self, hdl: sv_call::Handle) -> sv_call::Result<Tid> {
+    pub fn child(&self, hdl: sv_call::Handle, require: Feature) -> sv_call::Result<Tid> {
         super::PREEMPT.scope(|| {
             self.handles().get::<Tid>(hdl).and_then(|obj| {
-                if obj.features().contains(Feature::EXECUTE) {
+                if obj.features().contains(require) {
                     Ok(Tid::clone(&obj))
                 } else {
                     Err(sv_call::EPERM)
@@ -93,6 +158,22 @@ impl Space {
             })
         })
     }
+
+    /// Get the job referred to by `hdl`, or the job of the space if it's null.
+    pub fn job_of(&self, hdl: sv_call::Handle, require: Feature) -> sv_call::Result<Arc<Job>> {
+        if hdl == sv_call::Handle::NULL {
+            return Ok(Arc::clone(&self.job));
+        }
+        super::PREEMPT.scope(|| {
+            self.handles().get::<Job>(hdl).and_then(|obj| {
+                if obj.features().contains(require) {
+                    Ok(Arc::clone(&obj))
+                } else {
+                    Err(sv_call::EPERM)
+                }
+            })
+        })
+    }
 }
 
 unsafe impl DefaultFeature for Space {
@@ -1,22 +1,24 @@
 use alloc::{string::String, sync::Arc, vec::Vec};
 use core::{hint, slice, time::Duration};
 
+use archop::reg::rflags;
 use paging::LAddr;
 use spin::Mutex;
 use sv_call::*;
 
 use super::{
-    hdl::{DefaultFeature, Ref},
-    Blocked, RunningState, Signal, Space, Tid,
+    hdl::{DefaultFeature, HandleMap, Koid, Ref},
+    Blocked, Priority, RunningState, Signal, Space, Tid,
 };
 use crate::{
-    cpu::time::Instant,
-    sched::{imp::MIN_TIME_GRAN, Arsc, PREEMPT, SCHED},
+    cpu::{time::Instant, CpuMask},
+    sched::{ipc::Channel, Arsc, PREEMPT, SCHED},
     syscall::{In, InOut, Out, UserPtr},
 };
 
 #[derive(Debug)]
-struct SuspendToken {
+pub(super) struct SuspendToken {
+    koid: Koid,
     slot: Arsc<Mutex<Option<super::Blocked>>>,
     tid: Tid,
 }
@@ -26,6 +28,16 @@ impl SuspendToken {
     pub fn signal(&self) -> Signal {
         Signal::Suspend(Arsc::clone(&self.slot))
     }
+
+    #[inline]
+    pub fn tid(&self) -> &Tid {
+        &self.tid
+    }
+
+    #[inline]
+    pub fn koid(&self) -> Koid {
+        self.koid
+    }
 }
 
 impl Drop for SuspendToken {
@@ -49,7 +61,24 @@ unsafe impl DefaultFeature for SuspendToken {
 
 #[syscall]
 fn task_exit(retval: usize, kill_all: bool) -> Result {
-    SCHED.exit_current(retval, kill_all);
+    let info = task::ExitInfo {
+        reason: task::EXIT_NORMAL,
+        retval,
+        ..Default::default()
+    };
+    SCHED.exit_current(info, kill_all);
+    #[allow(unreachable_code)]
+    Err(EKILLED)
+}
+
+#[syscall]
+fn task_abort() -> Result {
+    let info = task::ExitInfo {
+        reason: task::EXIT_ABORTED,
+        retval: EKILLED.into_retval(),
+        ..Default::default()
+    };
+    SCHED.exit_current(info, true);
     #[allow(unreachable_code)]
     Err(EKILLED)
 }
@@ -84,24 +113,61 @@ fn get_name(ptr: UserPtr<In>, len: usize) -> Result<Option<String>> {
     }
 }
 
+/// Convert the set into a mask of present CPUs, which must not be empty.
+pub(super) fn get_affinity(ptr: UserPtr<In, task::CpuSet>) -> Result<CpuMask> {
+    let set = unsafe { ptr.read()? };
+    let mut mask = CpuMask::ZERO;
+    for cpu in (0..crate::cpu::count()).filter(|&cpu| set.contains(cpu)) {
+        mask.set(cpu, true);
+    }
+    if mask.not_any() {
+        Err(EINVAL)
+    } else {
+        Ok(mask)
+    }
+}
+
+/// The initial channel is moved into the new task, so it must be transferable.
+fn take_init_chan(handles: &HandleMap, hdl: Handle) -> Result<Ref<Channel>> {
+    let obj = handles.get::<Channel>(hdl)?;
+    if !obj.features().contains(Feature::SEND) {
+        return Err(EPERM);
+    }
+    drop(obj);
+    handles.remove::<Channel>(hdl)
+}
+
+fn take_space(handles: &HandleMap, hdl: Handle) -> Result<Arc<Space>> {
+    let obj = handles.get::<Space>(hdl)?;
+    if !obj.features().contains(Feature::WRITE) {
+        return Err(EPERM);
+    }
+    drop(obj);
+    handles.remove::<Space>(hdl).map(Ref::into_raw)
+}
+
 #[syscall]
 fn task_exec(ci: UserPtr<In, task::ExecInfo>) -> Result<Handle> {
     let ci = unsafe { ci.read()? };
 
     let name = get_name(UserPtr::<In, _>::new(ci.name as *mut u8), ci.name_len)?;
+    let affinity = if ci.affinity.is_null() {
+        None
+    } else {
+        Some(get_affinity(UserPtr::new(ci.affinity as *mut _))?)
+    };
 
     let (init_chan, space) = SCHED.with_current(|cur| {
         let handles = cur.space().handles();
         let init_chan = if ci.init_chan == Handle::NULL {
             None
         } else {
-            Some(handles.remove::<crate::sched::ipc::Channel>(ci.init_chan)?)
+            Some(take_init_chan(handles, ci.init_chan)?)
         };
         if ci.space == Handle::NULL {
             Ok((init_chan, Arc::clone(cur.space())))
         } else {
-            let space = handles.remove::<Space>(ci.space)?;
-            Ok((init_chan, Ref::into_raw(space)))
+            Ok((init_chan, take_space(handles, ci.space)?))
         }
     })?;
 
@@ -118,7 +184,8 @@ fn task_exec(ci: UserPtr<In, task::ExecInfo>) -> Result<Handle> {
         stack: LAddr::new(ci.stack),
         arg: ci.arg,
     };
-    let (task, hdl) = super::exec(name, space, init_chan, &starter)?;
+    let priority = Priority::from_raw(ci.priority).ok_or(EINVAL)?;
+    let (task, hdl) = super::exec(name, affinity, priority, space, init_chan, &starter)?;
 
     SCHED.unblock(task, true);
 
@@ -140,13 +207,12 @@ fn task_new(
         let init_chan = if init_chan == Handle::NULL {
             None
         } else {
-            Some(handles.remove::<crate::sched::ipc::Channel>(init_chan)?)
+            Some(take_init_chan(handles, init_chan)?)
         };
         if space == Handle::NULL {
             Ok((init_chan, Arc::clone(cur.space())))
         } else {
-            let space = handles.remove::<Space>(space)?;
-            Ok((init_chan, Ref::into_raw(space)))
+            Ok((init_chan, take_space(handles, space)?))
         }
     })?;
     let init_chan = match init_chan {
@@ -159,7 +225,7 @@ fn task_new(
     let (task, hdl) = super::create(name, space, init_chan)?;
 
     let task = super::Ready::block(
-        super::IntoReady::into_ready(task, unsafe { crate::cpu::id() }, MIN_TIME_GRAN),
+        super::IntoReady::into_ready(task, unsafe { crate::cpu::id() }, Priority::Normal),
         "task_ctl_suspend",
     );
 
@@ -167,6 +233,7 @@ fn task_new(
     let st_data = unsafe {
         Arsc::get_mut_unchecked(&mut sus_slot).write(Mutex::new(Some(task)));
         SuspendToken {
+            koid: Koid::new(),
             slot: Arsc::assume_init(sus_slot),
             tid,
         }
@@ -177,18 +244,20 @@ fn task_new(
 }
 
 #[syscall]
-fn task_join(hdl: Handle, retval: UserPtr<Out, usize>) -> Result {
+fn task_join(hdl: Handle, info: UserPtr<Out, task::ExitInfo>) -> Result {
     hdl.check_null()?;
+    info.check()?;
 
     SCHED.with_current(|cur| {
         let handles = cur.space().handles();
         let val = match handles.get::<Tid>(hdl) {
-            Ok(tid) => tid.ret_cell().lock().ok_or(ENOENT)?,
+            Ok(tid) if !tid.features().contains(Feature::READ) => return Err(EPERM),
+            Ok(tid) => tid.exit_info().ok_or(ENOENT)?,
             Err(e) => return Err(e),
         };
 
         drop(handles.remove::<Tid>(hdl));
-        unsafe { retval.write(val) }
+        info.write(val)
     })
 }
 
@@ -200,17 +269,19 @@ fn task_ctl(hdl: Handle, op: u32, data: UserPtr<InOut, Handle>) -> Result {
 
     match op {
         task::TASK_CTL_KILL => {
-            let child = cur.child(hdl)?;
+            let child = cur.child(hdl, Feature::EXECUTE)?;
             child.with_signal(|sig| *sig = Some(Signal::Kill));
+            child.interrupt();
 
             Ok(())
         }
         task::TASK_CTL_SUSPEND => {
             data.check()?;
 
-            let child = cur.child(hdl)?;
+            let child = cur.child(hdl, Feature::EXECUTE)?;
 
             let st = SuspendToken {
+                koid: Koid::new(),
                 slot: Arsc::try_new(Mutex::new(None))?,
                 tid: child,
             };
@@ -229,10 +300,101 @@ fn task_ctl(hdl: Handle, op: u32, data: UserPtr<InOut, Handle>) -> Result {
 
             Ok(())
         }
+        task::TASK_CTL_SET_PRIO => {
+            let raw = unsafe { data.cast::<u32>().read()? };
+            let priority = Priority::from_raw(raw).ok_or(EINVAL)?;
+
+            let child = cur.child(hdl, Feature::EXECUTE)?;
+            let priority = SCHED.with_current(|cur| Priority::pass(priority, cur.tid()))?;
+            child.set_priority(priority);
+
+            Ok(())
+        }
+        task::TASK_CTL_GET_PRIO => {
+            let child = cur.child(hdl, Feature::READ)?;
+            data.cast::<u32>().write(child.priority().raw())
+        }
+        task::TASK_CTL_SET_AFFINITY => {
+            let affinity = get_affinity(data.cast::<task::CpuSet>().r#in())?;
+
+            let child = cur.child(hdl, Feature::EXECUTE)?;
+            let affinity = child.job().restrict(affinity)?;
+            let old = child.set_affinity(affinity);
+            // Move the task off the CPUs it has lost at once.
+            crate::sched::imp::evict(old & !affinity);
+
+            Ok(())
+        }
+        task::TASK_CTL_GET_AFFINITY => {
+            let child = cur.child(hdl, Feature::READ)?;
+            let set = child.affinity().iter_ones().collect::<task::CpuSet>();
+            data.cast::<task::CpuSet>().write(set)
+        }
+        task::TASK_CTL_WATCH => {
+            data.check()?;
+
+            let child = cur.child(hdl, Feature::READ)?;
+            let chan = child.watch()?;
+            let event = Arc::downgrade(chan.event()) as _;
+            let out = super::PREEMPT.scope(|| cur.handles().insert(chan, Some(event)))?;
+            data.write(out)
+        }
+        task::TASK_CTL_DEBUG => {
+            data.check()?;
+
+            let child = cur.child(hdl, Feature::EXECUTE)?;
+            let chan = create_excep_chan(&child)?;
+            let event = Arc::downgrade(chan.event()) as _;
+            let out = super::PREEMPT.scope(|| cur.handles().insert(chan, Some(event)))?;
+            data.write(out)
+        }
         _ => Err(EINVAL),
     }
 }
 
+#[syscall]
+fn task_list(hdl: Handle, tasks: UserPtr<Out, Handle>, count: usize) -> Result<usize> {
+    tasks.check_slice(count)?;
+
+    let cur = SCHED.with_current(|cur| Ok(Arc::clone(cur.space())))?;
+    let (space, feat) = if hdl == Handle::NULL {
+        (Arc::clone(&cur), Tid::default_features())
+    } else {
+        let (child, feat) = PREEMPT.scope(|| {
+            let obj = cur.handles().get::<Tid>(hdl)?;
+            if !obj.features().contains(Feature::READ) {
+                return Err(EPERM);
+            }
+            Ok((Tid::clone(&obj), obj.features()))
+        })?;
+        // The tasks are reached through `hdl`, so they get no more rights than
+        // it.
+        let space = child.space().ok_or(ENOENT)?;
+        (space, feat & Tid::default_features())
+    };
+
+    let list = space.tasks();
+    let mut out = Vec::new();
+    out.try_reserve(count.min(list.len())).map_err(|_| ENOMEM)?;
+    let ret = list.iter().take(count).try_for_each(|tid| {
+        let event = Arc::downgrade(&tid.event) as _;
+        // SAFETY: `feat` is no more than the default features of tasks.
+        let hdl = PREEMPT.scope(|| unsafe {
+            cur.handles()
+                .insert_unchecked(tid.clone(), feat, Some(event))
+        })?;
+        out.push(hdl);
+        Ok(())
+    });
+    if let Err(err) = ret.and_then(|_| tasks.write_slice(&out)) {
+        for hdl in out {
+            let _ = super::PREEMPT.scope(|| cur.handles().remove::<Tid>(hdl));
+        }
+        return Err(err);
+    }
+    Ok(list.len())
+}
+
 fn read_regs(
     task: &Blocked,
     feat: Feature,
@@ -259,6 +421,13 @@ fn read_regs(
                 unsafe { data.write_slice(&task.ext_frame()[..size]) }
             }
         }
+        task::TASK_DBGADDR_DEBUG => {
+            if len < task::ctx::DEBUG_REGS_SIZE {
+                Err(EBUFFER)
+            } else {
+                data.cast().write(task.debug_regs().get())
+            }
+        }
         _ => Err(EINVAL),
     }
 }
@@ -291,15 +460,20 @@ fn write_regs(
                 unsafe { data.read_slice(ptr, size) }
             }
         }
+        task::TASK_DBGADDR_DEBUG => {
+            if len < task::ctx::DEBUG_REGS_SIZE {
+                Err(EBUFFER)
+            } else {
+                let regs = unsafe { data.cast().read()? };
+                task.debug_regs_mut().set(&regs)
+            }
+        }
         _ => Err(EINVAL),
     }
 }
 
-fn create_excep_chan(task: &Blocked, feat: Feature) -> Result<crate::sched::ipc::Channel> {
-    if !feat.contains(Feature::READ) {
-        return Err(EPERM);
-    }
-    let slot = task.tid().excep_chan();
+fn create_excep_chan(tid: &Tid) -> Result<crate::sched::ipc::Channel> {
+    let slot = tid.excep_chan();
     let chan = match slot.lock() {
         mut g if g.is_none() => {
             let (usr, krl) = crate::sched::ipc::Channel::new();
@@ -353,15 +527,25 @@ fn task_debug(hdl: Handle, op: u32, addr: usize, data: UserPtr<InOut, u8>, len:
         task::TASK_DBG_EXCEP_HDL => {
             if len < core::mem::size_of::<Handle>() {
                 Err(EBUFFER)
+            } else if !feat.contains(Feature::READ) {
+                Err(EPERM)
             } else {
-                let hdl = SCHED.with_current(|cur| {
-                    create_excep_chan(&task, feat).and_then(|chan| {
-                        let event = Arc::downgrade(chan.event()) as _;
-                        cur.space().handles().insert(chan, Some(event))
+                SCHED
+                    .with_current(|cur| {
+                        create_excep_chan(task.tid()).and_then(|chan| {
+                            let event = Arc::downgrade(chan.event()) as _;
+                            cur.space().handles().insert(chan, Some(event))
+                        })
                     })
-                })?;
-
-                unsafe { data.cast::<Handle>().write(hdl) }
+                    .and_then(|hdl| unsafe { data.cast::<Handle>().write(hdl) })
+            }
+        }
+        task::TASK_DBG_SINGLE_STEP => {
+            if feat.contains(Feature::WRITE) {
+                task.kstack_mut().task_frame_mut().rflags |= rflags::TF;
+                Ok(())
+            } else {
+                Err(EPERM)
             }
         }
         _ => Err(EINVAL),
@@ -91,7 +91,7 @@ impl PartialEq for Tid {
 
 unsafe impl DefaultFeature for Tid {
     fn default_features() -> Feature {
-        Feature::SEND | Feature::EXECUTE | Feature::WAIT
+        Feature::SEND | Feature::READ | Feature::EXECUTE | Feature::WAIT
     }
 }
 
@@ -135,6 +135,18 @@ pub fn deallocate(tid: Tid) -> bool {
         .is_some()
 }
 
+/// Call `func` on every task that hasn't exited.
+pub fn for_each_task<F: FnMut(&Tid)>(mut func: F) {
+    TI_MAP.for_each(|&raw, ti| {
+        if let Some(raw) = NonZeroU64::new(raw) {
+            func(&Tid {
+                raw,
+                ti: Arc::clone(ti),
+            })
+        }
+    })
+}
+
 #[inline]
 pub fn init() {
     Azy::force(&TI_MAP);
@@ -65,7 +65,7 @@ mod syscall {
         SCHED.with_current(|cur| unsafe {
             cur.space().handles().insert_unchecked(
                 value,
-                Feature::SEND | Feature::SYNC | Feature::READ,
+                Feature::SEND | Feature::SYNC | Feature::READ | Feature::DUPLICATE,
                 None,
             )
         })
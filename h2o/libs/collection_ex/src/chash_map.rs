@@ -370,6 +370,18 @@ impl<K, V, S: BuildHasher + Default> CHashMap<K, V, S> {
     {
         self.retain_mut(|key, value| predicate(key, value))
     }
+
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &V),
+    {
+        let buckets = self.inner.read();
+        for ent in buckets.as_inner() {
+            if let inner::Entry::Data((ref key, ref value)) = *ent.read() {
+                f(key, value)
+            }
+        }
+    }
 }
 
 impl<K, V, S: BuildHasher + Default> fmt::Debug for CHashMap<K, V, S> {
@@ -4,7 +4,7 @@ use alloc::collections::{
 };
 use core::{
     borrow::Borrow,
-    ops::{Add, Bound, Range, RangeBounds, Sub},
+    ops::{Add, Bound, Range, RangeBounds, Rem, Sub},
 };
 
 #[derive(Debug)]
@@ -59,6 +59,56 @@ impl<K, V> RangeMap<K, V> {
         }
     }
 
+    /// The same as [`RangeMap::allocate_with`], but the start of the allocated
+    /// range is a multiple of `align`.
+    pub fn allocate_aligned_with<F, E>(
+        &mut self,
+        size: K,
+        align: K,
+        value: F,
+        no_fit: E,
+    ) -> Result<K, E>
+    where
+        K: Ord + Sub<Output = K> + Add<Output = K> + Rem<Output = K> + Copy,
+        F: FnOnce(Range<K>) -> Result<V, E>,
+    {
+        let end = self.range.end;
+        // `None` if the aligned start is out of range.
+        let align_up = |start: K| {
+            let padding = (align - start % align) % align;
+            (end - start >= padding).then(|| start + padding)
+        };
+
+        let mut range = None;
+
+        let mut start = align_up(self.range.start);
+
+        for (_, (r, _)) in self.inner.iter() {
+            let Some(s) = start else { break };
+            if r.start >= s && r.start - s >= size {
+                range = Some(s..(s + size));
+                break;
+            }
+            if r.end > s {
+                start = align_up(r.end);
+            }
+        }
+        if let (None, Some(s)) = (&range, start) {
+            if end - s >= size {
+                range = Some(s..(s + size));
+            }
+        }
+
+        if let Some(range) = range {
+            let start = range.start;
+            let value = value(range.clone())?;
+            self.inner.entry(start).or_insert((range, value));
+            Ok(start)
+        } else {
+            Err(no_fit)
+        }
+    }
+
     pub fn try_insert_with<F, E, R>(
         &mut self,
         range: Range<K>,
@@ -32,8 +32,38 @@ pub const KERNEL_ALLOCABLE_RANGE: core::ops::Range<usize> =
 
 pub const ID_OFFSET: usize = KERNEL_SPACE_START;
 
+/// The offset of the virtual addresses of the memory used by EFI runtime
+/// services from their physical ones.
+pub const EFI_RUNTIME_OFFSET: usize = 0xFFFF_B000_0000_0000;
+
 // Kernel args
 
+/// The maximum length of the kernel command line.
+pub const CMDLINE_MAX: usize = 2048;
+
+/// The layouts of the 32-bit pixels in framebuffers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PixelFormat {
+    /// Red, green and blue from the lowest byte, with the highest one reserved.
+    Rgb = 0,
+    /// Blue, green and red from the lowest byte, with the highest one reserved.
+    Bgr = 1,
+}
+
+/// The linear framebuffer set up by the boot loader.
+#[derive(Debug, Copy, Clone)]
+pub struct Framebuffer {
+    pub phys: paging::PAddr,
+    pub size: usize,
+    pub width: usize,
+    pub height: usize,
+    /// The number of pixels in a scan line, which may be greater than the
+    /// width.
+    pub stride: usize,
+    pub format: PixelFormat,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct KernelArgs {
     pub rsdp: paging::PAddr,
@@ -50,4 +80,14 @@ pub struct KernelArgs {
 
     pub bootfs_phys: paging::PAddr,
     pub bootfs_len: usize,
+
+    /// The kernel command line in UTF-8, or empty if not given.
+    pub cmdline_phys: paging::PAddr,
+    pub cmdline_len: usize,
+
+    pub framebuffer: Option<Framebuffer>,
+
+    /// The physical address of the table of EFI runtime services, present if
+    /// they are switched to the virtual mode.
+    pub efi_runtime: Option<paging::PAddr>,
 }
@@ -100,6 +100,16 @@ impl AttrBuilder {
         self
     }
 
+    /// Select the upper half of the page attribute table, whose entries are
+    /// programmed by the kernel.
+    #[inline]
+    pub fn pat(mut self, pat: bool) -> Self {
+        if pat {
+            self.attr |= Attr::PAT;
+        }
+        self
+    }
+
     #[inline]
     pub fn cache(mut self, write_thru: bool, disable: bool) -> Self {
         if write_thru {
@@ -15,7 +15,14 @@ pub struct Syscall {
 
 #[cfg(all(not(feature = "stub"), feature = "call"))]
 use crate::{
-    c_ty::*, ipc::RawPacket, mem::*, res::IntrConfig, task::ExecInfo, Feature, Handle, SerdeReg,
+    c_ty::*,
+    ipc::RawPacket,
+    mem::*,
+    obj::ObjInfo,
+    res::{IntrConfig, MsiVector},
+    task::{CpuSet, ExecInfo, ExitInfo, JobRes},
+    time::UtcTime,
+    Feature, Handle, SerdeReg,
 };
 
 #[cfg(feature = "vdso")]
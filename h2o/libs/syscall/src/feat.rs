@@ -1,14 +1,31 @@
 use crate::SerdeReg;
 
 bitflags::bitflags! {
+    /// The rights of a handle, which can only be reduced with `sv_obj_feat`.
+    ///
+    /// The meanings of some rights depend on the type of the object:
+    ///
+    /// | Right     | `Phys`              | `Virt`                | `Channel` | Task           | Job          |
+    /// |-----------|---------------------|-----------------------|-----------|----------------|--------------|
+    /// | `READ`    | read, pin           | map readable          | receive   | join, get info | get usage    |
+    /// | `WRITE`   | write, pin writable | map writable, drop    | send      | -              | add spaces   |
+    /// | `EXECUTE` | map executable      | map executable        | -         | control        | kill         |
+    /// | `MAP`     | be mapped, pin      | map, unmap, reprotect | -         | -              | -            |
+    /// | `DERIVE`  | create sub-objects  | allocate sub-regions  | -         | -              | add children |
     #[repr(transparent)]
     pub struct Feature: u64 {
+        /// The handle can be transferred to other tasks through channels.
         const SEND = 1 << 0;
         const SYNC = 1 << 1;
         const READ = 1 << 2;
         const WRITE = 1 << 3;
         const EXECUTE = 1 << 4;
         const WAIT = 1 << 5;
+        /// The handle can be cloned with `sv_obj_clone`, which also requires
+        /// `SEND` and `SYNC`.
+        const DUPLICATE = 1 << 6;
+        const MAP = 1 << 7;
+        const DERIVE = 1 << 8;
     }
 }
 
@@ -10,12 +10,32 @@ pub struct RawPacket {
     pub buffer: *mut u8,
     pub buffer_size: usize,
     pub buffer_cap: usize,
+    /// The physical object carrying the data following `buffer` out of line,
+    /// or [`Handle::NULL`] if there is none.
+    ///
+    /// When sending, the first `phys_len` bytes of the object are shared with
+    /// the packet copy-on-write instead of being copied, so the sender keeps
+    /// the object and the later writes of either side are not seen by the
+    /// other. When receiving, a new handle to the shared copy is written here
+    /// and is owned by the receiver.
+    pub phys: Handle,
+    pub phys_len: usize,
 }
 
 pub const MAX_HANDLE_COUNT: usize = 256;
+/// The maximum size of the inline buffers of packets. Larger data should be
+/// carried out of line in [`RawPacket::phys`].
 pub const MAX_BUFFER_SIZE: usize = crate::mem::PAGE_SIZE;
 
+/// The number of packets a channel can hold by default before the sender gets
+/// `ENOSPC`.
+pub const DEFAULT_CAPACITY: usize = 2048;
+pub const MAX_CAPACITY: usize = 1 << 16;
+
 pub const SIG_GENERIC: usize = 0b0000_0001;
 pub const SIG_READ: usize = 0b0000_0010;
 pub const SIG_WRITE: usize = 0b0000_0100;
 pub const SIG_TIMER: usize = 0b0000_1000;
+/// Asserted on a channel when its peer is closed, along with `SIG_READ` and
+/// `SIG_WRITE` so that waiters for them observe `EPIPE` instead of hanging.
+pub const SIG_PEER_CLOSED: usize = 0b0001_0000;
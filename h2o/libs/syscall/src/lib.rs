@@ -12,10 +12,12 @@ pub mod error;
 pub mod feat;
 pub mod ipc;
 pub mod mem;
+pub mod obj;
 pub mod res;
 #[cfg(feature = "stub")]
 pub mod stub;
 pub mod task;
+pub mod time;
 
 pub use sv_gen::*;
 
@@ -11,6 +11,12 @@ bitflags! {
         const WRITABLE    = 1 << 2;
         const EXECUTABLE  = 1 << 3;
         const UNCACHED    = 1 << 4;
+        /// Commit the pages of the mapping on first access instead of at
+        /// the mapping time.
+        const LAZY        = 1 << 5;
+        /// Combine the writes to the mapping instead of caching it, which
+        /// suits framebuffers. Ignored if `UNCACHED` is also set.
+        const WRITE_COMBINING = 1 << 6;
     }
 
     #[derive(Default)]
@@ -18,6 +24,14 @@ bitflags! {
     pub struct PhysOptions: u32 {
         const RESIZABLE = 1 << 0;
         const ZEROED = 1 << 1;
+        /// Allocate physically contiguous memory, which can't be resizable.
+        const CONTIGUOUS = 1 << 2;
+        /// Allocate the memory below 4GB for devices only capable of 32-bit
+        /// DMA. Requires `CONTIGUOUS`.
+        const BELOW_4G = 1 << 3;
+        /// Map the memory uncached wherever it is mapped. Requires
+        /// `CONTIGUOUS`.
+        const UNCACHED = 1 << 4;
     }
 }
 
@@ -45,6 +59,15 @@ impl SerdeReg for PhysOptions {
     }
 }
 
+/// A range of physical memory of a pinned physical object, as seen by the
+/// devices.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct PhysRange {
+    pub base: usize,
+    pub len: usize,
+}
+
 #[derive(Debug, Default)]
 #[repr(C)]
 pub struct MemInfo {
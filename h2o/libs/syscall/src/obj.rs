@@ -0,0 +1,24 @@
+use crate::Feature;
+
+/// The information of the object referred to by a handle, returned by
+/// `sv_obj_info`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct ObjInfo {
+    /// The type of the object, one of the `SV_*` type constants, or
+    /// `usize::MAX` if it's unknown.
+    pub ty: usize,
+    /// The features of the handle, not the object.
+    pub features: Feature,
+    /// The kernel object ID, which is shared by all the handles to the same
+    /// object and never reused, or 0 for integers and dead virtual regions.
+    pub koid: u64,
+    /// The ID of the related object, or 0 if there's none: the peer of a
+    /// channel, the parent of a job, a physical object, a virtual region or
+    /// a resource, the physical object of a pinned range, or the task of a
+    /// suspend token.
+    pub related_koid: u64,
+    /// The size of the object in bytes: the length of a physical object, a
+    /// pinned range or a virtual region, or the data queued in a channel.
+    pub mem_usage: usize,
+}
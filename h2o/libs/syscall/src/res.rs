@@ -1,11 +1,18 @@
 use bitflags::bitflags;
 
-use crate::SerdeReg;
+use crate::{Handle, SerdeReg};
 
 pub const RES_MEM: u32 = 0;
 pub const RES_PIO: u32 = 1;
 pub const RES_GSI: u32 = 2;
 
+/// Reset the whole platform as if it is power-cycled.
+pub const RESET_COLD: u32 = 0;
+/// Reset the processors only.
+pub const RESET_WARM: u32 = 1;
+/// Power off the platform.
+pub const RESET_SHUTDOWN: u32 = 2;
+
 bitflags! {
     #[repr(transparent)]
     pub struct IntrConfig: u32 {
@@ -23,3 +30,14 @@ impl SerdeReg for IntrConfig {
         Self::from_bits_truncate(val as u32)
     }
 }
+
+/// An MSI vector allocated by `sv_intr_msi`, whose address and data are to be
+/// programmed into the MSI or MSI-X capability of a device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct MsiVector {
+    /// The interrupt object that receives the vector.
+    pub intr: Handle,
+    pub address: u32,
+    pub data: u32,
+}
@@ -1,5 +1,12 @@
 use crate::{
-    c_ty::*, ipc::RawPacket, mem::*, res::IntrConfig, task::ExecInfo, Feature, Handle, Syscall,
+    c_ty::*,
+    ipc::RawPacket,
+    mem::*,
+    obj::ObjInfo,
+    res::{IntrConfig, MsiVector},
+    task::{CpuSet, ExecInfo, ExitInfo, JobRes},
+    time::UtcTime,
+    Feature, Handle, Syscall,
 };
 
 include!(concat!(env!("CARGO_MANIFEST_DIR"), "/target/stub.rs"));
@@ -1,21 +1,103 @@
 pub mod ctx;
 pub mod excep;
 
+use core::fmt;
+
 use crate::Handle;
 
 pub const DEFAULT_STACK_SIZE: usize = 256 * 1024;
 
 pub const TASK_CTL_KILL: u32 = 1;
 pub const TASK_CTL_SUSPEND: u32 = 2;
+pub const TASK_CTL_SET_PRIO: u32 = 3;
+pub const TASK_CTL_GET_PRIO: u32 = 4;
+pub const TASK_CTL_SET_AFFINITY: u32 = 5;
+pub const TASK_CTL_GET_AFFINITY: u32 = 6;
+/// Get a channel which receives an [`ExitInfo`] packet once the task exits.
+pub const TASK_CTL_WATCH: u32 = 7;
+/// Get a channel receiving the exceptions of the task, as `TASK_DBG_EXCEP_HDL`
+/// does, without suspending it first.
+pub const TASK_CTL_DEBUG: u32 = 8;
+
+/// The default priority, so that a zeroed [`ExecInfo`] gets it.
+pub const TASK_PRIO_NORMAL: u32 = 0;
+/// Only kernel tasks or the tasks in the root job can grant this priority.
+pub const TASK_PRIO_REALTIME: u32 = 1;
+pub const TASK_PRIO_IDLE: u32 = 2;
 
 pub const TASK_DBG_READ_REG: u32 = 1;
 pub const TASK_DBG_WRITE_REG: u32 = 2;
 pub const TASK_DBG_READ_MEM: u32 = 3;
 pub const TASK_DBG_WRITE_MEM: u32 = 4;
 pub const TASK_DBG_EXCEP_HDL: u32 = 5;
+/// Make the suspended task raise a debug exception after it executes one
+/// instruction.
+pub const TASK_DBG_SINGLE_STEP: u32 = 6;
 
 pub const TASK_DBGADDR_GPR: usize = 0x1000;
 pub const TASK_DBGADDR_FPU: usize = 0x2000;
+/// The hardware breakpoints in [`ctx::DebugRegs`].
+pub const TASK_DBGADDR_DEBUG: usize = 0x3000;
+
+/// The maximum number of CPUs a [`CpuSet`] can hold.
+pub const CPU_SET_SIZE: usize = 256;
+
+/// A set of CPUs that a task is allowed to run on.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct CpuSet {
+    bits: [u64; CPU_SET_SIZE / 64],
+}
+
+impl CpuSet {
+    #[inline]
+    pub const fn new() -> Self {
+        CpuSet {
+            bits: [0; CPU_SET_SIZE / 64],
+        }
+    }
+
+    #[inline]
+    pub fn single(cpu: usize) -> Self {
+        let mut ret = Self::new();
+        ret.insert(cpu);
+        ret
+    }
+
+    #[inline]
+    pub fn contains(&self, cpu: usize) -> bool {
+        cpu < CPU_SET_SIZE && self.bits[cpu / 64] & (1 << (cpu % 64)) != 0
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `cpu` is not less than [`CPU_SET_SIZE`].
+    #[inline]
+    pub fn insert(&mut self, cpu: usize) {
+        assert!(cpu < CPU_SET_SIZE, "CPU #{cpu} out of range");
+        self.bits[cpu / 64] |= 1 << (cpu % 64);
+    }
+
+    #[inline]
+    pub fn remove(&mut self, cpu: usize) {
+        if cpu < CPU_SET_SIZE {
+            self.bits[cpu / 64] &= !(1 << (cpu % 64));
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+}
+
+impl FromIterator<usize> for CpuSet {
+    fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
+        let mut ret = Self::new();
+        iter.into_iter().for_each(|cpu| ret.insert(cpu));
+        ret
+    }
+}
 
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
@@ -27,4 +109,82 @@ pub struct ExecInfo {
     pub stack: *mut u8,
     pub init_chan: Handle,
     pub arg: u64,
+    pub priority: u32,
+    /// Inherits the affinity of the current task if null.
+    pub affinity: *const CpuSet,
+}
+
+/// The resource limits or usage of a job.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct JobRes {
+    /// The number of handles held by the processes.
+    pub handles: usize,
+    /// The bytes of memory committed for the processes.
+    pub memory: usize,
+    /// The number of running tasks.
+    pub tasks: usize,
+}
+
+impl JobRes {
+    pub const UNLIMITED: JobRes = JobRes {
+        handles: usize::MAX,
+        memory: usize::MAX,
+        tasks: usize::MAX,
+    };
+}
+
+/// The task returned by itself with `sv_task_exit`.
+pub const EXIT_NORMAL: u32 = 0;
+/// The task was killed with [`TASK_CTL_KILL`], by its job, or along with the
+/// main task of its process.
+pub const EXIT_KILLED: u32 = 1;
+/// The task raised an exception which no handler recovered from.
+pub const EXIT_EXCEPTION: u32 = 2;
+/// The task gave up with `sv_task_abort`, such as on panics.
+pub const EXIT_ABORTED: u32 = 3;
+
+/// How a task exited, returned by `sv_task_join` and sent to the channels
+/// watching the task.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct ExitInfo {
+    /// One of `EXIT_*`.
+    pub reason: u32,
+    /// The exception vector if the reason is [`EXIT_EXCEPTION`].
+    pub vec: u32,
+    /// The value passed to `sv_task_exit`, or the error code of the other
+    /// reasons.
+    pub retval: usize,
+    /// The error code pushed by the exception.
+    pub errc: u64,
+    /// The faulting address of page faults.
+    pub fault_addr: u64,
+    /// The CPU time consumed by the task in nanoseconds.
+    pub cpu_time: u64,
+}
+
+impl ExitInfo {
+    /// Whether the task exited by itself with a zero return value.
+    #[inline]
+    pub fn success(&self) -> bool {
+        self.reason == EXIT_NORMAL && self.retval == 0
+    }
+}
+
+impl fmt::Display for ExitInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            EXIT_NORMAL => write!(f, "exited with {:#x}", self.retval)?,
+            EXIT_KILLED => f.write_str("killed")?,
+            EXIT_EXCEPTION => write!(
+                f,
+                "exception #{} (error code {:#x}, address {:#x})",
+                self.vec, self.errc, self.fault_addr
+            )?,
+            EXIT_ABORTED => f.write_str("aborted")?,
+            reason => write!(f, "unknown reason {reason}")?,
+        }
+        write!(f, " after {}us of CPU time", self.cpu_time / 1000)
+    }
 }
@@ -25,3 +25,71 @@ pub struct Gpr {
     pub gs_base: u64,
 }
 pub const GPR_SIZE: usize = mem::size_of::<Gpr>();
+
+/// The number of hardware breakpoints of a task.
+pub const NR_BREAKPOINT: usize = 4;
+
+/// Break on executing the instruction at the address.
+pub const BP_EXEC: u64 = 0b00;
+/// Break after writing to the address. Not supported by the kernel yet.
+pub const BP_WRITE: u64 = 0b01;
+/// Break after reading from or writing to the address. Not supported by the
+/// kernel yet.
+pub const BP_ACCESS: u64 = 0b11;
+
+/// The hardware breakpoints of a task, in the layout of x86 debug registers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct DebugRegs {
+    /// The addresses of the breakpoints (DR0 to DR3).
+    pub addr: [u64; NR_BREAKPOINT],
+    /// The status of the last debug exception (DR6).
+    pub status: u64,
+    /// The enabled breakpoints and their conditions (DR7). Only the local
+    /// enable, condition and length fields may be set.
+    pub control: u64,
+}
+pub const DEBUG_REGS_SIZE: usize = mem::size_of::<DebugRegs>();
+
+impl DebugRegs {
+    const STATUS_STEP: u64 = 1 << 14;
+
+    /// Enable breakpoint `index` on `len` bytes at `addr`, where `cond` is one
+    /// of `BP_*`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` or `len` is invalid. Breakpoints of [`BP_EXEC`] must
+    /// have a length of 1, and others 1, 2, 4 or 8.
+    pub fn set(&mut self, index: usize, addr: u64, cond: u64, len: usize) {
+        assert!(index < NR_BREAKPOINT, "Breakpoint #{index} out of range");
+        let len = match (cond, len) {
+            (BP_EXEC | BP_WRITE | BP_ACCESS, 1) => 0b00,
+            (BP_WRITE | BP_ACCESS, 2) => 0b01,
+            (BP_WRITE | BP_ACCESS, 8) => 0b10,
+            (BP_WRITE | BP_ACCESS, 4) => 0b11,
+            _ => panic!("Invalid breakpoint condition {cond} of length {len}"),
+        };
+        self.addr[index] = addr;
+        self.control &= !(0b1111 << (16 + index * 4));
+        self.control |= ((cond | (len << 2)) << (16 + index * 4)) | (1 << (index * 2));
+    }
+
+    pub fn clear(&mut self, index: usize) {
+        if index < NR_BREAKPOINT {
+            self.control &= !(1 << (index * 2));
+        }
+    }
+
+    /// Whether breakpoint `index` triggered the last debug exception.
+    #[inline]
+    pub fn hit(&self, index: usize) -> bool {
+        index < NR_BREAKPOINT && self.status & (1 << index) != 0
+    }
+
+    /// Whether the last debug exception is raised by single-stepping.
+    #[inline]
+    pub fn stepped(&self) -> bool {
+        self.status & Self::STATUS_STEP != 0
+    }
+}
@@ -0,0 +1,8 @@
+/// The wall-clock time in UTC, as the time elapsed since the UNIX epoch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub struct UtcTime {
+    pub secs: u64,
+    /// The fraction of the second, less than 1,000,000,000.
+    pub nanos: u32,
+}
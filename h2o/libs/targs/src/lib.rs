@@ -9,14 +9,33 @@ pub enum HandleIndex {
     Vdso = 3,
     Bootfs = 4,
     RootVirt = 5,
+    /// Present only if the boot loader set up a framebuffer.
+    Framebuffer = 6,
 
     Len,
 }
 
+/// The maximum size of the arguments following [`Targs`] in the initial packet
+/// of TINIT.
+pub const ARGS_MAX: usize = 2048;
+
+/// The initial data of TINIT, followed by the command-line arguments forwarded
+/// by the kernel, each terminated with a NUL.
 #[derive(Debug, Copy, Clone, Default)]
 pub struct Targs {
     pub rsdp: usize,
     pub smbios: usize,
+    /// The physical base address of the PCI ECAM window, or 0 if absent.
+    pub pci_ecam: usize,
+    pub pci_bus_start: usize,
+    pub pci_bus_end: usize,
+    /// The size of the framebuffer in pixels, or 0 if absent.
+    pub fb_width: usize,
+    pub fb_height: usize,
+    /// The number of pixels in a scan line of the framebuffer.
+    pub fb_stride: usize,
+    /// The pixel format of the framebuffer, 0 for RGB and 1 for BGR.
+    pub fb_format: usize,
 }
 
 unsafe impl plain::Plain for Targs {}
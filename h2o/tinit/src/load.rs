@@ -30,8 +30,9 @@ fn load_segs(
             let last = interp.pop();
             assert_eq!(last, Some(0), "Not a valid c string");
 
-            let data = bootfs.find(&interp, b'/').ok_or(ENOENT).inspect_err(|_| {
-                log::error!("Failed to find the interpreter for the executable")
+            let data = bootfs.find(&interp, b'/').map_err(|err| {
+                log::error!("Failed to find the interpreter for the executable: {err:?}");
+                ENOENT
             })?;
 
             crate::sub_phys(data, bootfs, bootfs_phys)?
@@ -1,13 +1,15 @@
-use solvent::prelude::Virt;
+use solvent::prelude::{GsiRes, MemRes, Virt};
 
+mod intr;
 mod ipc;
 mod mem;
 mod task;
 mod time;
 
-pub unsafe fn test_syscall(virt: &Virt) {
+pub unsafe fn test_syscall(virt: &Virt, mem_res: &MemRes, gsi_res: &GsiRes) {
     let stack = task::test(virt);
     ipc::test(virt, stack);
-    mem::test(virt);
+    mem::test(virt, mem_res);
     time::test();
+    intr::test(gsi_res);
 }
@@ -0,0 +1,31 @@
+use alloc::vec::Vec;
+
+use solvent::prelude::{GsiRes, Interrupt, EINVAL};
+
+pub fn test(gsi_res: &GsiRes) {
+    // The vectors of a block are aligned to their number rounded up to a power
+    // of 2, since devices put the index of a vector in the lowest bits of the
+    // data. Keep the blocks alive so that later ones can't reuse the space.
+    let mut blocks = Vec::new();
+    for count in [1, 2, 3, 8, 1, 32] {
+        let msi =
+            Interrupt::allocate_msi(gsi_res, 0, count).expect("Failed to allocate MSI vectors");
+        assert_eq!(msi.len(), count);
+
+        let base = msi[0].data;
+        assert_eq!(base % count.next_power_of_two() as u32, 0);
+        for (index, vec) in msi.iter().enumerate() {
+            assert_eq!(vec.address, msi[0].address);
+            assert_eq!(vec.data, base + index as u32);
+        }
+        blocks.push(msi);
+    }
+    drop(blocks);
+
+    let res = Interrupt::allocate_msi(gsi_res, 0, 0);
+    assert_eq!(res.unwrap_err(), EINVAL);
+    let res = Interrupt::allocate_msi(gsi_res, 0, 33);
+    assert_eq!(res.unwrap_err(), EINVAL);
+    let res = Interrupt::allocate_msi(gsi_res, u32::MAX as usize, 1);
+    assert_eq!(res.unwrap_err(), EINVAL);
+}
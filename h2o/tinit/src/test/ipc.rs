@@ -1,4 +1,8 @@
-use core::ptr::{self, NonNull};
+use alloc::vec;
+use core::{
+    mem,
+    ptr::{self, NonNull},
+};
 
 use solvent::prelude::Virt;
 use sv_call::{ipc::*, task::DEFAULT_STACK_SIZE, *};
@@ -14,16 +18,41 @@ pub unsafe fn test(virt: &Virt, stack: (*mut u8, *mut u8, Handle)) {
             buffer: buf.as_mut_ptr(),
             buffer_size: buf.len(),
             buffer_cap: buf.len(),
+            phys: Handle::NULL,
+            phys_len: 0,
         }
     }
 
     let mut c1 = Handle::NULL;
     let mut c2 = Handle::NULL;
-    sv_chan_new(&mut c1, &mut c2)
+    sv_chan_new(0, &mut c1, &mut c2)
         .into_res()
         .expect("Failed to create a channel");
     let (c1, c2) = (c1, c2);
 
+    // Peers refer to each other by their object IDs.
+    {
+        let (mut i1, mut i2) = (mem::zeroed(), mem::zeroed());
+        sv_obj_info(c1, &mut i1)
+            .into_res()
+            .expect("Failed to get the object info");
+        sv_obj_info(c2, &mut i2)
+            .into_res()
+            .expect("Failed to get the object info");
+        assert_eq!(i1.ty, SV_CHANNEL);
+        assert_eq!(i1.related_koid, i2.koid);
+        assert_eq!(i2.related_koid, i1.koid);
+
+        let count = sv_obj_list(ptr::null_mut(), 0)
+            .into_res()
+            .expect("Failed to list the handles") as usize;
+        let mut handles = vec![Handle::NULL; count];
+        sv_obj_list(handles.as_mut_ptr(), count)
+            .into_res()
+            .expect("Failed to list the handles");
+        assert!(handles.contains(&c1) && handles.contains(&c2));
+    }
+
     // Test in 1 task (transfering to myself).
     let e = {
         let e = sv_int_new(12345)
@@ -90,6 +119,120 @@ pub unsafe fn test(virt: &Virt, stack: (*mut u8, *mut u8, Handle)) {
         let ret = sv_chan_recv(c2, &mut receivee);
         assert_eq!(ret.into_res(), Err(ENOENT));
 
+        // Calls must have IDs to match the replies.
+        let mut caller = rp(0, &mut [], &mut buf);
+        let ret = sv_chan_call(c1, &mut caller, 0);
+        assert_eq!(ret.into_res(), Err(EINVAL));
+
+        // Calls time out without replies, leaving the requests in the channel.
+        caller = rp(300, &mut [], &mut buf);
+        let ret = sv_chan_call(c1, &mut caller, 0);
+        assert_eq!(ret.into_res(), Err(ETIME));
+        receivee = rp(0, &mut [], &mut buf);
+        sv_chan_recv(c2, &mut receivee)
+            .into_res()
+            .expect("Failed to receive the request");
+        assert_eq!(receivee.id, 300);
+
+        // Inline buffers larger than the limit are refused.
+        let mut large = vec![0u8; MAX_BUFFER_SIZE + 1];
+        let sendee = rp(200, &mut [], &mut large);
+        let ret = sv_chan_send(c1, &sendee);
+        assert_eq!(ret.into_res(), Err(ENOMEM));
+
+        // Larger data is carried out of line in physical objects, shared
+        // copy-on-write with the receiver.
+        let mut large = vec![0u8; MAX_BUFFER_SIZE * 3 + 5];
+        large
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, byte)| *byte = index as u8);
+        let phys = sv_phys_alloc(large.len() + 7, Default::default())
+            .into_res()
+            .expect("Failed to allocate a physical object");
+        sv_phys_write(phys, 0, large.len(), large.as_ptr())
+            .into_res()
+            .expect("Failed to write the physical object");
+
+        let mut sendee = rp(200, &mut [], &mut buf);
+        sendee.phys = phys;
+        sendee.phys_len = large.len() + 8;
+        let ret = sv_chan_send(c1, &sendee);
+        assert_eq!(ret.into_res(), Err(ERANGE));
+
+        sendee.phys_len = large.len();
+        sv_chan_send(c1, &sendee)
+            .into_res()
+            .expect("Failed to send a packet with out-of-line data");
+
+        // Later writes of the sender are not seen by the receiver.
+        let zeros = vec![0u8; large.len()];
+        sv_phys_write(phys, 0, zeros.len(), zeros.as_ptr())
+            .into_res()
+            .expect("Failed to write the physical object");
+        sv_obj_drop(phys)
+            .into_res()
+            .expect("Failed to drop the physical object");
+
+        receivee = rp(0, &mut [], &mut buf);
+        sv_chan_recv(c2, &mut receivee)
+            .into_res()
+            .expect("Failed to receive a packet with out-of-line data");
+        assert_eq!(receivee.id, 200);
+        assert!(!receivee.phys.is_null());
+        assert_eq!(receivee.phys_len, large.len());
+
+        let mut received = vec![0u8; large.len()];
+        sv_phys_read(receivee.phys, 0, received.len(), received.as_mut_ptr())
+            .into_res()
+            .expect("Failed to read the received physical object");
+        assert_eq!(received, large);
+        sv_obj_drop(receivee.phys)
+            .into_res()
+            .expect("Failed to drop the physical object");
+
+        // Full channels refuse packets until the peer receives some of them.
+        let (mut d1, mut d2) = (Handle::NULL, Handle::NULL);
+        sv_chan_new(1, &mut d1, &mut d2)
+            .into_res()
+            .expect("Failed to create a channel");
+        let sendee = rp(0, &mut [], &mut buf);
+        sv_chan_send(d1, &sendee)
+            .into_res()
+            .expect("Failed to send a packet into the channel");
+        let ret = sv_chan_send(d1, &sendee);
+        assert_eq!(ret.into_res(), Err(ENOSPC));
+
+        receivee = rp(0, &mut [], &mut buf);
+        sv_chan_recv(d2, &mut receivee)
+            .into_res()
+            .expect("Failed to receive a packet from the channel");
+        sv_obj_wait(d1, u64::MAX, true, false, SIG_WRITE)
+            .into_res()
+            .expect("Failed to wait for the channel");
+        sv_chan_send(d1, &sendee)
+            .into_res()
+            .expect("Failed to send a packet into the channel");
+        sv_obj_drop(d1)
+            .into_res()
+            .expect("Failed to drop the channel");
+
+        // Closing a side wakes up its peer, which can still receive the pending
+        // packets.
+        sv_obj_wait(d2, u64::MAX, true, false, SIG_READ | SIG_PEER_CLOSED)
+            .into_res()
+            .expect("Failed to wait for the channel");
+        receivee = rp(0, &mut [], &mut buf);
+        sv_chan_recv(d2, &mut receivee)
+            .into_res()
+            .expect("Failed to receive a packet from the channel");
+        receivee = rp(0, &mut [], &mut buf);
+        let ret = sv_chan_recv(d2, &mut receivee);
+        assert_eq!(ret.into_res(), Err(EPIPE));
+        sv_obj_drop(d2)
+            .into_res()
+            .expect("Failed to drop the channel");
+
         e
     };
 
@@ -134,6 +277,8 @@ pub unsafe fn test(virt: &Virt, stack: (*mut u8, *mut u8, Handle)) {
                 stack: stack.0,
                 init_chan: c2,
                 arg: 0,
+                priority: sv_call::task::TASK_PRIO_NORMAL,
+                affinity: ptr::null(),
             };
 
             sv_task_exec(&ci)
@@ -144,22 +289,13 @@ pub unsafe fn test(virt: &Virt, stack: (*mut u8, *mut u8, Handle)) {
         let mut buf = [1u8, 2, 3, 4, 5, 6, 7];
         let mut hdl = [e];
 
-        ::log::trace!("Sending the initial packet");
+        ::log::trace!("Calling with the initial packet");
         let mut p = rp(MSG_ID, &mut hdl, &mut buf);
-        sv_chan_send(c1, &p)
-            .into_res()
-            .expect("Failed to send init packet");
-
-        p.id = 0;
-        ::log::trace!("Waiting for the initial response");
-        sv_obj_wait(c1, u64::MAX, true, false, SIG_READ)
-            .into_res()
-            .expect("Failed to wait for the channel");
-        ::log::trace!("Receiving the response");
-        sv_chan_recv(c1, &mut p)
+        sv_chan_call(c1, &mut p, u64::MAX)
             .into_res()
-            .expect("Failed to receive the response");
+            .expect("Failed to call with the init packet");
         assert_eq!(p.id, MSG_ID);
+        assert_eq!(p.handle_count, 1);
         assert_eq!(buf, [6, 7, 8, 9, 10, 11, 12]);
 
         ::log::trace!("Finished");
@@ -169,11 +305,11 @@ pub unsafe fn test(virt: &Virt, stack: (*mut u8, *mut u8, Handle)) {
             .into_res()
             .expect("Failed to drop the event in master");
 
-        let mut retval = Default::default();
+        let mut info = Default::default();
         sv_obj_wait(other, u64::MAX, true, false, SIG_READ)
             .into_res()
             .expect("Failed to wait for the task");
-        sv_task_join(other, &mut retval)
+        sv_task_join(other, &mut info)
             .into_res()
             .expect("Failed to join the task");
     }
@@ -1,6 +1,13 @@
-use solvent::prelude::{Flags, Phys, PhysOptions, Virt, PAGE_LAYOUT, PAGE_SIZE};
+use alloc::vec;
+use core::slice;
 
-pub unsafe fn test(virt: &Virt) {
+use solvent::prelude::{
+    DmaBuf, Feature, Flags, MemRes, Object, Phys, PhysOptions, Pinned, Virt, EINVAL, EPERM,
+    PAGE_LAYOUT, PAGE_SIZE,
+};
+use sv_call::{sv_job_usage, task::JobRes, Handle};
+
+pub unsafe fn test(virt: &Virt, mem_res: &MemRes) {
     let sub = virt
         .allocate(None, PAGE_LAYOUT)
         .expect("Failed to allocate sub-virt");
@@ -25,4 +32,200 @@ pub unsafe fn test(virt: &Virt) {
     phys.resize(4, true).expect("Failed to resize the phys");
     let buf = phys.read(1, 10).expect("Failed to read from phys");
     assert_eq!(&buf, &[0, 1, 2]);
+
+    // Shrinking objects drops their pages and releases the memory charged to
+    // the job.
+    let memory = || {
+        let mut usage = JobRes::default();
+        sv_job_usage(Handle::NULL, &mut usage)
+            .into_res()
+            .expect("Failed to get the usage");
+        usage.memory
+    };
+    let phys =
+        Phys::allocate(PAGE_SIZE * 4, PhysOptions::RESIZABLE).expect("Failed to allocate memory");
+    unsafe { phys.write(0, &vec![1; PAGE_SIZE * 4]) }.expect("Failed to write to phys");
+    let committed = memory();
+    phys.resize(PAGE_SIZE, false)
+        .expect("Failed to resize the phys");
+    assert_eq!(memory(), committed - PAGE_SIZE * 3);
+    phys.resize(PAGE_SIZE * 4, false)
+        .expect("Failed to resize the phys");
+    assert_eq!(memory(), committed);
+    let buf = phys
+        .read(PAGE_SIZE * 3, 1)
+        .expect("Failed to read from phys");
+    assert_eq!(&buf, &[0]);
+
+    let size = PAGE_SIZE * 16;
+    let layout = unsafe { Virt::page_aligned(size) };
+    let sub = virt
+        .allocate(None, layout)
+        .expect("Failed to allocate sub-virt");
+    let phys = Phys::allocate(size, Default::default()).expect("Failed to allocate memory");
+    let ptr = sub
+        .map(
+            None,
+            phys.clone(),
+            0,
+            layout,
+            Flags::READABLE | Flags::WRITABLE | Flags::USER_ACCESS | Flags::LAZY,
+        )
+        .expect("Failed to map memory lazily");
+    unsafe {
+        let page = ptr.as_mut_ptr().add(PAGE_SIZE * 3);
+        assert_eq!(page.read(), 0);
+        page.write(0x37);
+    }
+    sub.destroy().expect("Failed to destroy sub-virt");
+    let buf = phys.read(PAGE_SIZE * 3, 1).expect("Failed to read memory");
+    assert_eq!(&buf, &[0x37]);
+
+    // Syscalls can access the uncommitted pages of lazy mappings, even ones of
+    // the object being accessed.
+    let sub = virt
+        .allocate(None, layout)
+        .expect("Failed to allocate sub-virt");
+    let ptr = sub
+        .map(
+            None,
+            phys.clone(),
+            0,
+            layout,
+            Flags::READABLE | Flags::WRITABLE | Flags::USER_ACCESS | Flags::LAZY,
+        )
+        .expect("Failed to map memory lazily");
+    unsafe {
+        let lazy = slice::from_raw_parts_mut(ptr.as_mut_ptr().add(PAGE_SIZE * 7), 2);
+        let len = phys
+            .read_into(PAGE_SIZE * 3, lazy)
+            .expect("Failed to read into a lazy buffer");
+        assert_eq!(len, 2);
+        assert_eq!(&lazy[..], &[0x37, 0]);
+
+        let lazy = slice::from_raw_parts(ptr.as_mut_ptr().add(PAGE_SIZE * 9), 1);
+        let other =
+            Phys::allocate(PAGE_SIZE, PhysOptions::empty()).expect("Failed to allocate memory");
+        other.write(0, &[0xff]).expect("Failed to write to phys");
+        let len = other
+            .write(0, lazy)
+            .expect("Failed to write from a lazy buffer");
+        assert_eq!(len, 1);
+        let buf = other.read(0, 1).expect("Failed to read memory");
+        assert_eq!(&buf, &[0]);
+    }
+    sub.destroy().expect("Failed to destroy sub-virt");
+    let buf = phys.read(PAGE_SIZE * 7, 1).expect("Failed to read memory");
+    assert_eq!(&buf, &[0x37]);
+
+    let cow = phys
+        .create_sub(0, size, true)
+        .expect("Failed to create copy-on-write phys");
+    let sub = virt
+        .allocate(None, layout)
+        .expect("Failed to allocate sub-virt");
+    let ptr = sub
+        .map(
+            None,
+            cow.clone(),
+            0,
+            layout,
+            Flags::READABLE | Flags::WRITABLE | Flags::USER_ACCESS,
+        )
+        .expect("Failed to map memory");
+    unsafe {
+        let page = ptr.as_mut_ptr().add(PAGE_SIZE * 3);
+        assert_eq!(page.read(), 0x37);
+        page.write(0x59);
+    }
+    sub.destroy().expect("Failed to destroy sub-virt");
+    let buf = cow.read(PAGE_SIZE * 3, 1).expect("Failed to read memory");
+    assert_eq!(&buf, &[0x59]);
+    let buf = phys.read(PAGE_SIZE * 3, 1).expect("Failed to read memory");
+    assert_eq!(&buf, &[0x37]);
+
+    test_dma(virt, mem_res);
+    test_rights(virt);
+}
+
+unsafe fn test_rights(virt: &Virt) {
+    let phys = Phys::allocate(PAGE_SIZE, PhysOptions::ZEROED).expect("Failed to allocate memory");
+    let phys = phys
+        .reduce_rights(Feature::SEND | Feature::SYNC | Feature::READ | Feature::MAP)
+        .expect("Failed to reduce the rights");
+
+    // Rights can only be reduced.
+    let res = Phys::try_clone(&phys);
+    assert_eq!(res.unwrap_err(), EPERM);
+    let res = unsafe { phys.write(0, &[1]) };
+    assert_eq!(res.unwrap_err(), EPERM);
+
+    let sub = virt
+        .allocate(None, PAGE_LAYOUT)
+        .expect("Failed to allocate sub-virt");
+    let res = sub.map(
+        None,
+        phys,
+        0,
+        PAGE_LAYOUT,
+        Flags::READABLE | Flags::WRITABLE | Flags::USER_ACCESS,
+    );
+    assert_eq!(res.unwrap_err(), EPERM);
+    sub.destroy().expect("Failed to destroy sub-virt");
+
+    // Sharing the memory of contiguous objects requires the right to derive.
+    let options = PhysOptions::CONTIGUOUS | PhysOptions::ZEROED;
+    let cont = Phys::allocate(PAGE_SIZE * 2, options).expect("Failed to allocate memory");
+    cont.create_sub(PAGE_SIZE, PAGE_SIZE, false)
+        .expect("Failed to create a sub-object");
+    let cont = cont
+        .reduce_rights(Feature::SEND | Feature::SYNC | Feature::READ | Feature::MAP)
+        .expect("Failed to reduce the rights");
+    let res = cont.create_sub(PAGE_SIZE, PAGE_SIZE, false);
+    assert_eq!(res.unwrap_err(), EPERM);
+}
+
+unsafe fn test_dma(virt: &Virt, mem_res: &MemRes) {
+    let res = Phys::allocate(PAGE_SIZE, PhysOptions::BELOW_4G);
+    assert_eq!(res.unwrap_err(), EINVAL);
+
+    let size = PAGE_SIZE * 4;
+    let options = PhysOptions::CONTIGUOUS | PhysOptions::BELOW_4G | PhysOptions::ZEROED;
+    let buf = DmaBuf::allocate(mem_res, size, options).expect("Failed to allocate DMA buffer");
+    let base = buf
+        .contiguous_base()
+        .expect("The DMA buffer is not contiguous");
+    assert!(base + size <= 1 << 32);
+    assert_eq!(buf.ranges()[0].len, size);
+
+    let phys = Phys::allocate(size, PhysOptions::ZEROED).expect("Failed to allocate memory");
+    let pinned = Pinned::new(mem_res, &phys, 0, size, true).expect("Failed to pin memory");
+    let ranges = pinned.ranges().expect("Failed to get the ranges");
+    assert_eq!(ranges.iter().map(|range| range.len).sum::<usize>(), size);
+    let res = Pinned::new(mem_res, &phys, PAGE_SIZE, size, false);
+    assert!(res.is_err());
+    drop(pinned);
+
+    let phys = Phys::allocate(PAGE_SIZE, PhysOptions::CONTIGUOUS | PhysOptions::UNCACHED)
+        .expect("Failed to allocate uncached memory");
+    let sub = virt
+        .allocate(None, PAGE_LAYOUT)
+        .expect("Failed to allocate sub-virt");
+    let ptr = sub
+        .map(
+            None,
+            phys,
+            0,
+            PAGE_LAYOUT,
+            Flags::READABLE | Flags::WRITABLE | Flags::USER_ACCESS,
+        )
+        .expect("Failed to map memory");
+    // The mapping stays uncached without asking for it again.
+    sub.reprotect(
+        ptr.as_non_null_ptr(),
+        PAGE_SIZE,
+        Flags::READABLE | Flags::USER_ACCESS,
+    )
+    .expect("Failed to reprotect uncached memory");
+    sub.destroy().expect("Failed to destroy sub-virt");
 }
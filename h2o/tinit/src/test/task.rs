@@ -1,7 +1,8 @@
+use alloc::vec;
 use core::{
     arch::asm,
     mem::{size_of, MaybeUninit},
-    ptr::null_mut,
+    ptr::{null, null_mut},
 };
 
 use solvent::prelude::{Object, Phys, Virt};
@@ -9,8 +10,8 @@ use sv_call::{
     ipc::{RawPacket, SIG_READ},
     mem::Flags,
     task::{
-        ctx::{Gpr, GPR_SIZE},
-        excep::{Exception, ExceptionResult},
+        ctx::{DebugRegs, Gpr, BP_EXEC, BP_WRITE, DEBUG_REGS_SIZE, GPR_SIZE},
+        excep::{Exception, ExceptionResult, EXRES_CODE_RECOVERED},
         *,
     },
     *,
@@ -34,6 +35,7 @@ unsafe extern "C" fn func(_: Handle, arg: u32) {
             let ptr = PF_ADDR as *mut u64;
             *ptr = 1;
         },
+        2 => breakpoint_target(),
         _ => {}
     }
     sv_task_exit(12345, false)
@@ -41,25 +43,34 @@ unsafe extern "C" fn func(_: Handle, arg: u32) {
         .expect("Failed to exit the task");
 }
 
+#[inline(never)]
+extern "C" fn breakpoint_target() {
+    unsafe { asm!("nop; nop") };
+}
+
 unsafe fn join(normal: Handle, fault: Handle) {
     log::trace!("join: normal = {:?}, fault = {:?}", normal, fault);
-    let mut ret = Default::default();
+    let mut info = ExitInfo::default();
 
     sv_obj_wait(normal, u64::MAX, true, false, SIG_READ)
         .into_res()
         .expect("Failed to wait for the task");
-    sv_task_join(normal, &mut ret)
+    sv_task_join(normal, &mut info)
         .into_res()
         .expect("Failed to join the task");
-    assert_eq!(ret, 12345);
+    assert_eq!(info.reason, EXIT_NORMAL);
+    assert_eq!(info.retval, 12345);
+    assert!(info.cpu_time > 0);
 
     sv_obj_wait(fault, u64::MAX, true, false, SIG_READ)
         .into_res()
         .expect("Failed to wait for the task");
-    sv_task_join(fault, &mut ret)
+    sv_task_join(fault, &mut info)
         .into_res()
         .expect("Failed to join the task");
-    assert_eq!(Error::try_from_retval(ret), Some(EFAULT));
+    assert_eq!(info.reason, EXIT_EXCEPTION);
+    assert_eq!(info.fault_addr, PF_ADDR as u64);
+    assert_eq!(Error::try_from_retval(info.retval), Some(EFAULT));
 }
 
 unsafe fn sleep() {
@@ -161,6 +172,9 @@ unsafe fn debug_excep(task: Handle, st: Handle) {
         buffer: excep.as_mut_ptr().cast(),
         buffer_size: size_of::<Exception>(),
         buffer_cap: size_of::<Exception>(),
+
+        phys: Handle::NULL,
+        phys_len: 0,
     };
     sv_obj_wait(chan, u64::MAX, true, false, SIG_READ)
         .into_res()
@@ -182,11 +196,228 @@ unsafe fn debug_excep(task: Handle, st: Handle) {
     sv_obj_wait(task, u64::MAX, true, false, SIG_READ)
         .into_res()
         .expect("Failed to wait for the task");
-    let mut ret = Default::default();
-    sv_task_join(task, &mut ret)
+    let mut info = ExitInfo::default();
+    sv_task_join(task, &mut info)
+        .into_res()
+        .expect("Failed to join the task");
+    assert_eq!(info.reason, EXIT_EXCEPTION);
+    assert_eq!(info.vec, u32::from(excep.vec));
+    assert_eq!(Error::try_from_retval(info.retval), Some(EFAULT));
+}
+
+unsafe fn recv_excep(chan: Handle) -> Exception {
+    let mut excep = MaybeUninit::<Exception>::uninit();
+    let mut packet = RawPacket {
+        id: 0,
+        handles: null_mut(),
+        handle_count: 0,
+        handle_cap: 0,
+        buffer: excep.as_mut_ptr().cast(),
+        buffer_size: size_of::<Exception>(),
+        buffer_cap: size_of::<Exception>(),
+
+        phys: Handle::NULL,
+        phys_len: 0,
+    };
+    sv_obj_wait(chan, u64::MAX, true, false, SIG_READ)
+        .into_res()
+        .expect("Failed to wait for the channel");
+    sv_chan_recv(chan, &mut packet)
+        .into_res()
+        .expect("Failed to receive exception");
+    excep.assume_init()
+}
+
+/// Suspend the task stopped at an exception, and then let it go on.
+unsafe fn recover(task: Handle, chan: Handle) -> Handle {
+    let mut st = Handle::NULL;
+    sv_task_ctl(task, TASK_CTL_SUSPEND, &mut st)
+        .into_res()
+        .expect("Failed to suspend the task");
+
+    let mut exres = ExceptionResult {
+        code: EXRES_CODE_RECOVERED,
+    };
+    let packet = RawPacket {
+        id: 0,
+        handles: null_mut(),
+        handle_count: 0,
+        handle_cap: 0,
+        buffer: (&mut exres as *mut ExceptionResult).cast(),
+        buffer_size: size_of::<ExceptionResult>(),
+        buffer_cap: size_of::<ExceptionResult>(),
+
+        phys: Handle::NULL,
+        phys_len: 0,
+    };
+    sv_chan_send(chan, &packet)
+        .into_res()
+        .expect("Failed to send exception result");
+    st
+}
+
+unsafe fn debug_regs(st: Handle, regs: Option<&DebugRegs>) -> DebugRegs {
+    let mut buf = regs.copied().unwrap_or_default();
+    let op = if regs.is_some() {
+        TASK_DBG_WRITE_REG
+    } else {
+        TASK_DBG_READ_REG
+    };
+    sv_task_debug(
+        st,
+        op,
+        TASK_DBGADDR_DEBUG,
+        (&mut buf as *mut DebugRegs).cast(),
+        DEBUG_REGS_SIZE,
+    )
+    .into_res()
+    .expect("Failed to access debug registers");
+    buf
+}
+
+unsafe fn debug_break(task: Handle, st: Handle) {
+    log::trace!("debug_break: task = {:?}, st = {:?}", task, st);
+
+    // Breakpoints must be in the user space.
+    let mut regs = DebugRegs::default();
+    regs.set(0, 0xFFFF_8000_0000_0000, BP_EXEC, 1);
+    let ret = sv_task_debug(
+        st,
+        TASK_DBG_WRITE_REG,
+        TASK_DBGADDR_DEBUG,
+        (&mut regs as *mut DebugRegs).cast(),
+        DEBUG_REGS_SIZE,
+    );
+    assert_eq!(ret.into_res(), Err(EPERM));
+
+    // Data breakpoints are not allowed.
+    let mut data = 0u64;
+    regs.set(0, &mut data as *mut u64 as u64, BP_WRITE, 8);
+    let ret = sv_task_debug(
+        st,
+        TASK_DBG_WRITE_REG,
+        TASK_DBGADDR_DEBUG,
+        (&mut regs as *mut DebugRegs).cast(),
+        DEBUG_REGS_SIZE,
+    );
+    assert_eq!(ret.into_res(), Err(EPERM));
+
+    regs.set(0, breakpoint_target as usize as u64, BP_EXEC, 1);
+    debug_regs(st, Some(&regs));
+
+    // Attaching doesn't need the task to be suspended.
+    let mut chan = Handle::NULL;
+    sv_task_ctl(task, TASK_CTL_DEBUG, &mut chan)
+        .into_res()
+        .expect("Failed to attach to the task");
+    let mut other = Handle::NULL;
+    let ret = sv_task_ctl(task, TASK_CTL_DEBUG, &mut other);
+    assert_eq!(ret.into_res(), Err(EEXIST));
+
+    // The task is listed along with the current one.
+    {
+        let mut info = MaybeUninit::uninit();
+        sv_obj_info(task, info.as_mut_ptr())
+            .into_res()
+            .expect("Failed to get the object info");
+        let koid = info.assume_init().koid;
+
+        let count = sv_task_list(Handle::NULL, null_mut(), 0)
+            .into_res()
+            .expect("Failed to list the tasks") as usize;
+        assert!(count >= 2);
+        let mut tasks = vec![Handle::NULL; count];
+        sv_task_list(task, tasks.as_mut_ptr(), count)
+            .into_res()
+            .expect("Failed to list the tasks");
+        let mut found = false;
+        for hdl in tasks {
+            sv_obj_info(hdl, info.as_mut_ptr())
+                .into_res()
+                .expect("Failed to get the object info");
+            found |= info.assume_init().koid == koid;
+            sv_obj_drop(hdl)
+                .into_res()
+                .expect("Failed to drop the task");
+        }
+        assert!(found);
+
+        // Tasks listed through a handle get no more rights than it.
+        let mut reduced = sv_obj_clone(task)
+            .into_res()
+            .expect("Failed to clone the task");
+        sv_obj_feat(&mut reduced, Feature::SEND | Feature::READ | Feature::WAIT)
+            .into_res()
+            .expect("Failed to reduce the features");
+        let mut listed = Handle::NULL;
+        sv_task_list(reduced, &mut listed, 1)
+            .into_res()
+            .expect("Failed to list the tasks");
+        sv_obj_info(listed, info.as_mut_ptr())
+            .into_res()
+            .expect("Failed to get the object info");
+        assert!(!info.assume_init().features.contains(Feature::EXECUTE));
+        sv_obj_drop(listed)
+            .into_res()
+            .expect("Failed to drop the task");
+        sv_obj_drop(reduced)
+            .into_res()
+            .expect("Failed to drop the task");
+    }
+
+    sv_obj_drop(st)
+        .into_res()
+        .expect("Failed to resume the task");
+
+    // Stop at the breakpoint.
+    let excep = recv_excep(chan);
+    assert_eq!(excep.vec, 1);
+    let st = recover(task, chan);
+    let mut gpr = MaybeUninit::<Gpr>::uninit();
+    sv_task_debug(
+        st,
+        TASK_DBG_READ_REG,
+        TASK_DBGADDR_GPR,
+        gpr.as_mut_ptr().cast(),
+        GPR_SIZE,
+    )
+    .into_res()
+    .expect("Failed to read general registers");
+    assert_eq!(gpr.assume_init().rip, breakpoint_target as usize as u64);
+    let mut regs = debug_regs(st, None);
+    assert!(regs.hit(0));
+
+    // Step over 1 instruction.
+    regs.clear(0);
+    debug_regs(st, Some(&regs));
+    sv_task_debug(st, TASK_DBG_SINGLE_STEP, 0, null_mut(), 0)
+        .into_res()
+        .expect("Failed to single-step the task");
+    sv_obj_drop(st)
+        .into_res()
+        .expect("Failed to resume the task");
+
+    let excep = recv_excep(chan);
+    assert_eq!(excep.vec, 1);
+    let st = recover(task, chan);
+    let regs = debug_regs(st, None);
+    assert!(regs.stepped() && !regs.hit(0));
+    sv_obj_drop(st)
+        .into_res()
+        .expect("Failed to resume the task");
+
+    sv_obj_wait(task, u64::MAX, true, false, SIG_READ)
+        .into_res()
+        .expect("Failed to wait for the task");
+    let mut info = ExitInfo::default();
+    sv_task_join(task, &mut info)
         .into_res()
         .expect("Failed to join the task");
-    assert_eq!(Error::try_from_retval(ret), Some(EFAULT));
+    assert_eq!(info.reason, EXIT_NORMAL);
+    assert_eq!(info.retval, 12345);
+    sv_obj_drop(chan)
+        .into_res()
+        .expect("Failed to drop the channel");
 }
 
 unsafe fn suspend(task: Handle) {
@@ -211,6 +442,14 @@ unsafe fn suspend(task: Handle) {
 unsafe fn kill(task: Handle) {
     log::trace!("kill: task = {:?}", task);
 
+    let mut watcher = Handle::NULL;
+    sv_task_ctl(task, TASK_CTL_WATCH, &mut watcher)
+        .into_res()
+        .expect("Failed to watch the task");
+    let mut other = Handle::NULL;
+    let ret = sv_task_ctl(task, TASK_CTL_WATCH, &mut other);
+    assert_eq!(ret.into_res(), Err(EEXIST));
+
     sv_task_ctl(task, TASK_CTL_KILL, null_mut())
         .into_res()
         .expect("Failed to kill a task");
@@ -218,20 +457,251 @@ unsafe fn kill(task: Handle) {
     sv_obj_wait(task, u64::MAX, true, false, SIG_READ)
         .into_res()
         .expect("Failed to wait for the task");
-    let mut ret = Default::default();
-    sv_task_join(task, &mut ret)
+    let mut info = ExitInfo::default();
+    sv_task_join(task, &mut info)
         .into_res()
         .expect("Failed to join the task");
-    assert_eq!(Error::try_from_retval(ret), Some(EKILLED));
+    assert_eq!(info.reason, EXIT_KILLED);
+    assert_eq!(Error::try_from_retval(info.retval), Some(EKILLED));
+
+    // The watcher receives the same record.
+    let mut record = MaybeUninit::<ExitInfo>::uninit();
+    let mut packet = RawPacket {
+        id: 0,
+        handles: null_mut(),
+        handle_count: 0,
+        handle_cap: 0,
+        buffer: record.as_mut_ptr().cast(),
+        buffer_size: size_of::<ExitInfo>(),
+        buffer_cap: size_of::<ExitInfo>(),
+
+        phys: Handle::NULL,
+        phys_len: 0,
+    };
+    sv_obj_wait(watcher, u64::MAX, true, false, SIG_READ)
+        .into_res()
+        .expect("Failed to wait for the watcher");
+    sv_chan_recv(watcher, &mut packet)
+        .into_res()
+        .expect("Failed to receive the exit record");
+    assert_eq!(record.assume_init(), info);
+    sv_obj_drop(watcher)
+        .into_res()
+        .expect("Failed to drop the watcher");
+}
+
+unsafe fn priority(task: Handle) {
+    log::trace!("priority: task = {:?}", task);
+
+    let get = || {
+        let mut prio = u32::MAX;
+        sv_task_ctl(task, TASK_CTL_GET_PRIO, (&mut prio as *mut u32).cast())
+            .into_res()
+            .expect("Failed to get the priority");
+        prio
+    };
+    assert_eq!(get(), TASK_PRIO_NORMAL);
+
+    for mut prio in [TASK_PRIO_IDLE, TASK_PRIO_REALTIME, TASK_PRIO_NORMAL] {
+        sv_task_ctl(task, TASK_CTL_SET_PRIO, (&mut prio as *mut u32).cast())
+            .into_res()
+            .expect("Failed to set the priority");
+        assert_eq!(get(), prio);
+    }
+
+    let mut invalid = 100u32;
+    let ret = sv_task_ctl(task, TASK_CTL_SET_PRIO, (&mut invalid as *mut u32).cast());
+    assert_eq!(ret.into_res(), Err(EINVAL));
+}
+
+unsafe fn affinity(task: Handle) {
+    log::trace!("affinity: task = {:?}", task);
+
+    let get = || {
+        let mut set = CpuSet::new();
+        sv_task_ctl(
+            task,
+            TASK_CTL_GET_AFFINITY,
+            (&mut set as *mut CpuSet).cast(),
+        )
+        .into_res()
+        .expect("Failed to get the affinity");
+        set
+    };
+    let mut all = get();
+    assert!(all.contains(0));
+
+    // The task is still busy running, and should be moved to CPU #0 at once.
+    let mut single = CpuSet::single(0);
+    sv_task_ctl(
+        task,
+        TASK_CTL_SET_AFFINITY,
+        (&mut single as *mut CpuSet).cast(),
+    )
+    .into_res()
+    .expect("Failed to set the affinity");
+    assert_eq!(get(), single);
+
+    let mut empty = CpuSet::new();
+    let ret = sv_task_ctl(
+        task,
+        TASK_CTL_SET_AFFINITY,
+        (&mut empty as *mut CpuSet).cast(),
+    );
+    assert_eq!(ret.into_res(), Err(EINVAL));
+
+    sv_task_ctl(
+        task,
+        TASK_CTL_SET_AFFINITY,
+        (&mut all as *mut CpuSet).cast(),
+    )
+    .into_res()
+    .expect("Failed to restore the affinity");
 }
 
 unsafe fn ctl(task: Handle) {
     log::trace!("ctl: task = {:?}", task);
+    priority(task);
+    affinity(task);
     suspend(task);
     sleep();
     kill(task);
 }
 
+unsafe fn job(stack: *mut u8) {
+    log::trace!("job");
+
+    let create = |parent: Handle, limits: JobRes| {
+        sv_job_new(parent, &limits, null())
+            .into_res()
+            .expect("Failed to create a job")
+    };
+    let exec = |job: Handle, init_chan: Handle| {
+        let mut virt = Handle::NULL;
+        let space = sv_space_new(job, &mut virt)
+            .into_res()
+            .expect("Failed to create a space");
+        sv_obj_drop(virt).into_res().expect("Failed to drop virt");
+        let ci = ExecInfo {
+            name: null_mut(),
+            name_len: 0,
+            space,
+            entry: func as *mut u8,
+            stack,
+            init_chan,
+            arg: 0,
+            priority: TASK_PRIO_NORMAL,
+            affinity: null(),
+        };
+        sv_task_exec(&ci)
+    };
+
+    // The initial channel can't be moved into a process out of handles.
+    let job = create(
+        Handle::NULL,
+        JobRes {
+            handles: 0,
+            ..JobRes::UNLIMITED
+        },
+    );
+    let (mut c1, mut c2) = (Handle::NULL, Handle::NULL);
+    sv_chan_new(0, &mut c1, &mut c2)
+        .into_res()
+        .expect("Failed to create channel");
+    assert_eq!(exec(job, c2).into_res(), Err(EMFILE));
+    sv_obj_drop(c1).into_res().expect("Failed to drop channel");
+    sv_obj_drop(job).into_res().expect("Failed to drop the job");
+
+    // Tasks in child jobs count in their parents.
+    let job = create(
+        Handle::NULL,
+        JobRes {
+            tasks: 0,
+            ..JobRes::UNLIMITED
+        },
+    );
+    let child = create(job, JobRes::UNLIMITED);
+    assert_eq!(exec(child, Handle::NULL).into_res(), Err(EAGAIN));
+    let mut usage = JobRes::default();
+    sv_job_usage(job, &mut usage)
+        .into_res()
+        .expect("Failed to get the usage");
+    assert_eq!(usage.tasks, 0);
+
+    // Killed jobs accept no more processes or children.
+    sv_job_kill(job).into_res().expect("Failed to kill the job");
+    let mut virt = Handle::NULL;
+    let ret = sv_space_new(child, &mut virt);
+    assert_eq!(ret.into_res(), Err(EKILLED));
+    let ret = sv_job_new(job, &JobRes::UNLIMITED, null());
+    assert_eq!(ret.into_res(), Err(EKILLED));
+
+    sv_obj_drop(child)
+        .into_res()
+        .expect("Failed to drop the job");
+    sv_obj_drop(job).into_res().expect("Failed to drop the job");
+
+    // Killing a job wakes up its blocked tasks, here one waiting for its
+    // exception to be handled.
+    let job = create(Handle::NULL, JobRes::UNLIMITED);
+    let mut virt = Handle::NULL;
+    let space = sv_space_new(job, &mut virt)
+        .into_res()
+        .expect("Failed to create a space");
+    sv_obj_drop(virt).into_res().expect("Failed to drop virt");
+    let mut st = Handle::NULL;
+    let task = sv_task_new(null_mut(), 0, space, Handle::NULL, &mut st)
+        .into_res()
+        .expect("Failed to create task");
+    let frame = Gpr {
+        rip: PF_ADDR as u64,
+        rsp: stack as u64,
+        rflags: 1 << 9,
+        ..Default::default()
+    };
+    sv_task_debug(
+        st,
+        TASK_DBG_WRITE_REG,
+        TASK_DBGADDR_GPR,
+        (&frame as *const Gpr) as *mut u8,
+        size_of::<Gpr>(),
+    )
+    .into_res()
+    .expect("Failed to write task's data");
+    let mut chan = Handle::NULL;
+    sv_task_debug(
+        st,
+        TASK_DBG_EXCEP_HDL,
+        0,
+        (&mut chan as *mut Handle).cast(),
+        size_of::<Handle>(),
+    )
+    .into_res()
+    .expect("Failed to create exception channel");
+    sv_obj_drop(st)
+        .into_res()
+        .expect("Failed to resume the task");
+
+    let excep = recv_excep(chan);
+    assert_eq!(excep.cr2, PF_ADDR as u64);
+    sv_job_kill(job).into_res().expect("Failed to kill the job");
+
+    sv_obj_wait(task, u64::MAX, true, false, SIG_READ)
+        .into_res()
+        .expect("Failed to wait for the task");
+    let mut info = ExitInfo::default();
+    sv_task_join(task, &mut info)
+        .into_res()
+        .expect("Failed to join the task");
+    assert_eq!(info.reason, EXIT_KILLED);
+    assert_eq!(Error::try_from_retval(info.retval), Some(EKILLED));
+
+    sv_obj_drop(chan)
+        .into_res()
+        .expect("Failed to drop the channel");
+    sv_obj_drop(job).into_res().expect("Failed to drop the job");
+}
+
 pub unsafe fn test(virt: &Virt) -> (*mut u8, *mut u8, Handle) {
     // Test the defence of invalid user pointer access.
     let ret = sv_task_exec(0x100000000 as *const ExecInfo);
@@ -253,7 +723,7 @@ pub unsafe fn test(virt: &Virt) -> (*mut u8, *mut u8, Handle) {
     let creator = |arg: u32| {
         let mut c1 = Handle::NULL;
         let mut c2 = Handle::NULL;
-        sv_chan_new(&mut c1, &mut c2)
+        sv_chan_new(0, &mut c1, &mut c2)
             .into_res()
             .expect("Failed to create channel");
         sv_obj_drop(c1).into_res().expect("Failed to drop channel");
@@ -265,6 +735,8 @@ pub unsafe fn test(virt: &Virt) -> (*mut u8, *mut u8, Handle) {
             stack: stack_ptr,
             init_chan: c2,
             arg: arg.into(),
+            priority: TASK_PRIO_NORMAL,
+            affinity: null(),
         };
         sv_task_exec(&ci)
     };
@@ -302,5 +774,33 @@ pub unsafe fn test(virt: &Virt) -> (*mut u8, *mut u8, Handle) {
     };
     debug_excep(task, st);
 
+    let mut st = Handle::NULL;
+    let task = {
+        let t = sv_task_new(null_mut(), 0, Handle::NULL, Handle::NULL, &mut st)
+            .into_res()
+            .expect("Failed to create task");
+        let frame = Gpr {
+            rip: func as usize as u64,
+            rsp: stack_ptr as u64,
+            rflags: 1 << 9,
+            rdi: 0,
+            rsi: 2,
+            ..Default::default()
+        };
+        sv_task_debug(
+            st,
+            TASK_DBG_WRITE_REG,
+            TASK_DBGADDR_GPR,
+            (&frame as *const Gpr) as *mut u8,
+            core::mem::size_of::<Gpr>(),
+        )
+        .into_res()
+        .expect("Failed to write task's data");
+        t
+    };
+    debug_break(task, st);
+
+    job(stack_ptr);
+
     (stack_ptr, stack_base, stack_phys2)
 }
@@ -19,10 +19,10 @@ mod mem;
 mod rxx;
 mod test;
 
-use alloc::{ffi::CString, vec, vec::Vec};
+use alloc::{ffi::CString, format, vec, vec::Vec};
 use core::{hint, mem::MaybeUninit, time::Duration};
 
-use bootfs::parse::Directory;
+use bootfs::parse::{Compressed, Directory, File};
 use solvent::prelude::*;
 use solvent_rpc::{loader::GET_OBJECT, packet};
 use sv_call::ipc::SIG_READ;
@@ -48,9 +48,52 @@ fn offset_sub<T>(slice: &[T], parent: &[T]) -> Option<usize> {
     })
 }
 
-fn sub_phys(bin_data: &[u8], bootfs: Directory, bootfs_phys: &Phys) -> Result<Phys> {
-    let offset = offset_sub(bin_data, bootfs.image()).ok_or(ERANGE)?;
-    bootfs_phys.create_sub(offset, bin_data.len().next_multiple_of(PAGE_SIZE), false)
+fn sub_phys(file: File, bootfs: Directory, bootfs_phys: &Phys) -> Result<Phys> {
+    match file {
+        File::Raw(data) => {
+            let offset = offset_sub(data, bootfs.image()).ok_or(ERANGE)?;
+            bootfs_phys.create_sub(offset, data.len().next_multiple_of(PAGE_SIZE), false)
+        }
+        File::Compressed(compressed) => decompress(compressed),
+    }
+}
+
+fn decompress(compressed: Compressed) -> Result<Phys> {
+    let phys = Phys::allocate(
+        compressed.len().next_multiple_of(PAGE_SIZE),
+        PhysOptions::ZEROED,
+    )?;
+    let root = unsafe { ROOT_VIRT.assume_init_ref() };
+    let ptr = root.map_phys(
+        None,
+        Phys::clone(&phys),
+        Flags::READABLE | Flags::WRITABLE | Flags::USER_ACCESS,
+    )?;
+    let ret = compressed.decompress_into(unsafe { &mut *ptr.as_ptr() });
+    root.unmap(ptr.as_non_null_ptr(), ptr.len(), true)?;
+    ret.ok_or(EIO).map(|_| phys)
+}
+
+/// Parse the public key in hex given by `BOOTFS_PUBLIC_KEY` at compile time.
+fn bootfs_key() -> Option<[u8; bootfs::PUBLIC_KEY_LEN]> {
+    let hex = option_env!("BOOTFS_PUBLIC_KEY")?.as_bytes();
+    assert_eq!(
+        hex.len(),
+        bootfs::PUBLIC_KEY_LEN * 2,
+        "Invalid length of the boot FS public key"
+    );
+
+    let digit = |c: u8| match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'f' => c - b'a' + 10,
+        b'A'..=b'F' => c - b'A' + 10,
+        _ => panic!("Invalid digit in the boot FS public key"),
+    };
+    let mut key = [0; bootfs::PUBLIC_KEY_LEN];
+    for (byte, pair) in key.iter_mut().zip(hex.chunks(2)) {
+        *byte = (digit(pair[0]) << 4) | digit(pair[1]);
+    }
+    Some(key)
 }
 
 fn map_bootfs(phys: &Phys, root: &Virt) -> Directory<'static> {
@@ -61,7 +104,16 @@ fn map_bootfs(phys: &Phys, root: &Virt) -> Directory<'static> {
             Flags::READABLE | Flags::EXECUTABLE | Flags::USER_ACCESS,
         )
         .expect("Failed to map boot FS");
-    Directory::root(unsafe { ptr.as_ref() }).expect("Failed to parse boot filesystem")
+    let bootfs = Directory::root(unsafe { ptr.as_ref() }).expect("Failed to parse boot filesystem");
+
+    // Entries are checked against their hashes when found, so only the header
+    // needs to be checked here.
+    let key = bootfs_key();
+    assert!(
+        bootfs.verify(key.as_ref()),
+        "Failed to verify the boot filesystem"
+    );
+    bootfs
 }
 
 fn serve_load(load_rpc: Channel, bootfs: Directory, bootfs_phys: &Phys) -> Error {
@@ -75,9 +127,13 @@ fn serve_load(load_rpc: Channel, bootfs: Directory, bootfs_phys: &Phys) -> Error
                 for (i, path) in paths.into_iter().enumerate() {
                     let mut root = Vec::from(b"lib/" as &[u8]);
                     root.append(&mut path.into_bytes());
-                    let obj = bootfs
-                        .find(&root, b'/')
-                        .and_then(|bin| sub_phys(bin, bootfs, bootfs_phys).ok());
+                    let obj = match bootfs.find(&root, b'/') {
+                        Ok(bin) => sub_phys(bin, bootfs, bootfs_phys).ok(),
+                        Err(err) => {
+                            log::error!("Failed to find {}: {err:?}", root.escape_ascii());
+                            None
+                        }
+                    };
                     match obj {
                         Some(obj) => objs.push(obj),
                         None => {
@@ -106,23 +162,51 @@ fn serve_load(load_rpc: Channel, bootfs: Directory, bootfs_phys: &Phys) -> Error
     }
 }
 
+/// Describe the PCI ECAM window for the device manager, which can't parse the
+/// ACPI tables itself, and the geometry of the framebuffer.
+fn startup_env(targs: &Targs) -> Vec<u8> {
+    let mut env = Vec::new();
+    if targs.pci_ecam != 0 {
+        env.extend_from_slice(
+            format!(
+                "PCI_ECAM={:#x},{},{}\0",
+                targs.pci_ecam, targs.pci_bus_start, targs.pci_bus_end
+            )
+            .as_bytes(),
+        );
+    }
+    if targs.fb_width != 0 {
+        let format = if targs.fb_format == 0 { "rgb" } else { "bgr" };
+        env.extend_from_slice(
+            format!(
+                "FRAMEBUFFER={}x{},{},{}\0",
+                targs.fb_width, targs.fb_height, targs.fb_stride, format
+            )
+            .as_bytes(),
+        );
+    }
+    if env.is_empty() {
+        env.push(0);
+    }
+    env
+}
+
 #[no_mangle]
 extern "C" fn tmain(init_chan: sv_call::Handle) {
     dbglog::init(log::Level::Debug);
     log::info!("Starting initialization");
 
     let init_chan = unsafe { Channel::from_raw(init_chan) };
-    let mut buffer = [0; core::mem::size_of::<Targs>()];
+    let mut buffer = [0; core::mem::size_of::<Targs>() + targs::ARGS_MAX];
     let mut handles = [MaybeUninit::uninit(); HandleIndex::Len as usize];
-    init_chan
-        .receive_raw(&mut buffer, &mut handles)
-        .0
-        .expect("Failed to receive the initial packet");
+    let (res, len, handle_count) = init_chan.receive_raw(&mut buffer, &mut handles);
+    res.expect("Failed to receive the initial packet");
 
-    let _targs = {
+    let (targs, args) = {
+        let (targs_buf, args) = buffer[..len].split_at(core::mem::size_of::<Targs>());
         let mut targs = Targs::default();
-        plain::copy_from_bytes(&mut targs, &buffer).expect("Failed to get TINIT args");
-        targs
+        plain::copy_from_bytes(&mut targs, targs_buf).expect("Failed to get TINIT args");
+        (targs, args)
     };
 
     let root_virt = unsafe {
@@ -133,7 +217,11 @@ extern "C" fn tmain(init_chan: sv_call::Handle) {
 
     mem::init();
 
-    unsafe { test::test_syscall(root_virt) };
+    let mem_res =
+        unsafe { Ref::<MemRes>::from_raw(handles[HandleIndex::MemRes as usize].assume_init()) };
+    let gsi_res =
+        unsafe { Ref::<GsiRes>::from_raw(handles[HandleIndex::GsiRes as usize].assume_init()) };
+    unsafe { test::test_syscall(root_virt, &mem_res, &gsi_res) };
 
     let vdso_phys = unsafe { Phys::from_raw(handles[HandleIndex::Vdso as usize].assume_init()) };
 
@@ -160,10 +248,10 @@ extern "C" fn tmain(init_chan: sv_call::Handle) {
 
     let (me, child) = Channel::new();
     let child = child
-        .reduce_features(Feature::SEND | Feature::READ)
+        .reduce_rights(Feature::SEND | Feature::READ)
         .expect("Failed to reduce features for read");
     let me = me
-        .reduce_features(Feature::SEND | Feature::WRITE)
+        .reduce_rights(Feature::SEND | Feature::WRITE)
         .expect("Failed to reduce features for write");
 
     let load_rpc = Channel::new();
@@ -197,11 +285,25 @@ extern "C" fn tmain(init_chan: sv_call::Handle) {
                 HandleType::BootfsPhys.into(),
                 Phys::into_raw(bootfs_phys.clone()),
             ),
+            (HandleType::MemRes.into(), unsafe {
+                handles[HandleIndex::MemRes as usize].assume_init()
+            }),
+            (HandleType::PioRes.into(), unsafe {
+                handles[HandleIndex::PioRes as usize].assume_init()
+            }),
+            (HandleType::GsiRes.into(), unsafe {
+                handles[HandleIndex::GsiRes as usize].assume_init()
+            }),
         ]
         .into_iter()
+        .chain((handle_count > HandleIndex::Framebuffer as usize).then(|| {
+            (HandleType::FramebufferPhys.into(), unsafe {
+                handles[HandleIndex::Framebuffer as usize].assume_init()
+            })
+        }))
         .collect(),
-        args: Vec::from(b"progm\0" as &[u8]),
-        env: vec![0],
+        args: [b"progm\0" as &[u8], args].concat(),
+        env: startup_env(&targs),
     };
 
     exe_args
@@ -216,6 +318,7 @@ extern "C" fn tmain(init_chan: sv_call::Handle) {
         stack,
         Some(child),
         vdso_base.as_mut_ptr() as u64,
+        None,
     )
     .expect("Failed to create the task");
 
@@ -225,8 +328,8 @@ extern "C" fn tmain(init_chan: sv_call::Handle) {
 
     log::debug!("Waiting for the task");
 
-    let retval = task.join().expect("Failed to join the task");
-    log::debug!("{:x} {:?}", retval, Error::try_from_retval(retval));
+    let info = task.join().expect("Failed to join the task");
+    log::debug!("{info} {:?}", Error::try_from_retval(info.retval));
 
     log::debug!("Reaching end of TINIT");
 }
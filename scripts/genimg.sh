@@ -3,7 +3,9 @@
 mkdir -p target/img/mnt
 cd target
 
-tar vcf H2O.k KERNEL TINIT BOOT.fs
+# The optional CMDLINE file holds the kernel command line, which is used when
+# the boot loader is started without load options.
+tar vcf H2O.k KERNEL TINIT BOOT.fs $(ls CMDLINE 2>/dev/null)
 
 cd img
 
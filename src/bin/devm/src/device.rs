@@ -1,10 +1,333 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::mem;
+
 use futures_lite::StreamExt;
+use solvent::prelude::{Channel, Object};
+use solvent_async::{
+    ipc::Channel as AsyncChannel,
+    sync::channel::{self, Receiver, Sender},
+};
+use solvent_fs::Spawner;
 use solvent_rpc::{
-    ddk::driver::{DriverRequest, DriverServer},
+    ddk::{
+        driver::{Binding, DriverRequest, DriverServer, Platform, Property},
+        host::{self, DriverHostClient},
+        Error,
+    },
     Server,
 };
+use solvent_std::sync::{Arsc, Mutex};
+
+use crate::{manifest::Manifest, tree::Node};
+
+/// How many times a driver is restarted after its host crashes before it's
+/// given up.
+const MAX_RESTARTS: usize = 3;
+
+/// A running driver host process.
+pub struct Host {
+    control: DriverHostClient,
+    /// The drivers loaded into the host and not yet unloaded.
+    drivers: Mutex<Vec<Arsc<Driver>>>,
+}
+
+impl Host {
+    pub fn new(control: DriverHostClient) -> Self {
+        Host {
+            control,
+            drivers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Load `driver` into the host and serve its connection to the device
+    /// manager.
+    pub async fn load(
+        self: &Arsc<Self>,
+        manager: &Arsc<DeviceManager>,
+        driver: &Arsc<Driver>,
+    ) -> Result<(), host::Error> {
+        let (instance, server) = Channel::new();
+        let id = self.control.load(driver.path.clone(), instance).await??;
+        *driver.loaded.lock() = Some((self.clone(), id));
+        self.drivers.lock().push(driver.clone());
+        log::debug!("Loaded {}", driver.path);
+
+        let spawner = solvent_fs::spawner();
+        let server = DriverServer::from(AsyncChannel::with_disp(server, spawner.dispatch()));
+        let task = handle_driver(manager.clone(), driver.clone(), spawner.clone(), server);
+        spawner.spawn(task);
+        Ok(())
+    }
+}
+
+/// A driver, to which the devices matching its manifest are bound.
+pub struct Driver {
+    path: String,
+    /// The host the driver is loaded into, and its ID there.
+    loaded: Mutex<Option<(Arsc<Host>, u64)>>,
+    /// How many times the driver has been restarted.
+    restarts: usize,
+    tx: Sender<Binding>,
+    rx: Receiver<Binding>,
+}
+
+impl Driver {
+    fn new(path: String) -> Self {
+        let (tx, rx) = channel::unbounded();
+        Driver {
+            path,
+            loaded: Mutex::new(None),
+            restarts: 0,
+            tx,
+            rx,
+        }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn host(&self) -> Option<Arsc<Host>> {
+        self.loaded.lock().as_ref().map(|(host, _)| host.clone())
+    }
+}
+
+/// The requests to driver hosts, which are carried out by the main task.
+pub enum Request {
+    /// Load the driver into a new host, so that a crashing driver takes no
+    /// others down with it.
+    Load(Arsc<Driver>),
+    Unload(Arsc<Driver>),
+}
+
+pub struct DeviceManager {
+    platform: Option<Platform>,
+    root: Arsc<Node>,
+    manifests: Vec<Manifest>,
+    drivers: Mutex<BTreeMap<String, Arsc<Driver>>>,
+    requests: Sender<Request>,
+}
+
+impl DeviceManager {
+    pub fn new(
+        platform: Option<Platform>,
+        manifests: Vec<Manifest>,
+        requests: Sender<Request>,
+    ) -> Self {
+        DeviceManager {
+            platform,
+            root: Arsc::new(Node::root()),
+            manifests,
+            drivers: Mutex::new(BTreeMap::new()),
+            requests,
+        }
+    }
+
+    #[inline]
+    pub fn root(&self) -> Arsc<Node> {
+        self.root.clone()
+    }
+
+    /// Bind the root of the device tree to `driver`, which is to be loaded
+    /// by the caller.
+    pub fn bind_root(&self, driver: &str) -> Arsc<Driver> {
+        self.root.bind_root(driver.into());
+        let driver = Arsc::new(Driver::new(driver.into()));
+        let mut drivers = self.drivers.lock();
+        drivers.insert(driver.path.clone(), driver.clone());
+        driver
+    }
+
+    /// Get the instance of `driver`, requesting to load a new one if it's not
+    /// loaded.
+    fn driver(&self, driver: &str) -> Arsc<Driver> {
+        let mut drivers = self.drivers.lock();
+        if let Some(driver) = drivers.get(driver) {
+            return driver.clone();
+        }
+        let driver = Arsc::new(Driver::new(driver.into()));
+        drivers.insert(driver.path.clone(), driver.clone());
+        self.request(Request::Load(driver.clone()));
+        driver
+    }
+
+    fn request(&self, request: Request) {
+        if self.requests.try_send(request).is_err() {
+            log::warn!("Failed to send the request to driver hosts");
+        }
+    }
+
+    /// Forget the driver so that its devices will be bound to a new instance.
+    pub fn remove_driver(&self, driver: &Arsc<Driver>) {
+        let mut drivers = self.drivers.lock();
+        if drivers
+            .get(&driver.path)
+            .map_or(false, |d| Arsc::ptr_eq(d, driver))
+        {
+            drivers.remove(&driver.path);
+        }
+    }
 
-pub async fn handle_driver(server: DriverServer) {
+    /// Forget all the drivers loaded into `host` after it exits.
+    pub fn remove_host(&self, host: &Arsc<Host>) {
+        let mut drivers = self.drivers.lock();
+        drivers.retain(|_, driver| !driver.host().map_or(false, |h| Arsc::ptr_eq(&h, host)));
+    }
+
+    /// Load the drivers of a crashed host into new hosts.
+    ///
+    /// The devices the crashed drivers published went down with them, so they
+    /// are removed from the tree, and the devices bound to the crashed drivers
+    /// are bound to the new instances over new connections.
+    pub async fn restart_host(&self, host: &Arsc<Host>) {
+        let crashed = mem::take(&mut *host.drivers.lock());
+        for driver in crashed {
+            // The root has no device to connect to again, so only the devices
+            // published under it are removed.
+            if self.root.driver().as_deref() == Some(driver.path()) {
+                self.root.unbind();
+                self.root.bind_root(driver.path.clone());
+            }
+            let mut nodes = Vec::new();
+            self.root.bound_to(&driver.path, &mut nodes);
+            nodes.iter().for_each(|node| node.unbind());
+            // The connections not taken yet are replaced by the new ones.
+            while driver.rx.try_recv().is_ok() {}
+
+            if driver.restarts >= MAX_RESTARTS {
+                log::warn!(
+                    "Giving up {} after {} restarts",
+                    driver.path,
+                    driver.restarts
+                );
+                continue;
+            }
+            let new = Arsc::new(Driver {
+                restarts: driver.restarts + 1,
+                ..Driver::new(driver.path.clone())
+            });
+            for node in nodes {
+                match node.bind(new.path.clone()).await {
+                    Ok(device) => Self::hand_over(&new, &node, device),
+                    Err(err) => log::warn!("Failed to bind {} again: {err}", node.path()),
+                }
+            }
+            log::debug!("Restarting {}", new.path);
+            self.drivers.lock().insert(new.path.clone(), new.clone());
+            self.request(Request::Load(new));
+        }
+    }
+
+    /// Shut the driver down and unload it from its host.
+    pub async fn unload(&self, driver: &Arsc<Driver>) {
+        self.remove_driver(driver);
+        let loaded = driver.loaded.lock().take();
+        if let Some((host, id)) = loaded {
+            match host.control.unload(id).await {
+                Ok(Ok(())) => {
+                    host.drivers.lock().retain(|d| !Arsc::ptr_eq(d, driver));
+                    log::debug!("Unloaded {}", driver.path)
+                }
+                Ok(Err(err)) => log::warn!("Failed to unload {}: {err}", driver.path),
+                Err(err) => log::warn!("RPC error: {err}"),
+            }
+        }
+    }
+
+    fn find(&self, path: &str) -> Option<Arsc<Node>> {
+        path.split('/')
+            .try_fold(self.root.clone(), |node, name| node.child(name))
+    }
+
+    async fn publish(
+        &self,
+        driver: &Driver,
+        name: String,
+        props: Vec<Property>,
+        device: Channel,
+    ) -> Result<(), Error> {
+        let (parent, leaf) = match name.rsplit_once('/') {
+            Some((parent, leaf)) => (self.find(parent).ok_or(Error::InvalidArgument)?, leaf),
+            None => (self.root.clone(), &*name),
+        };
+        // Names starting with dots are reserved for the device tree itself.
+        if leaf.is_empty() || leaf.starts_with('.') {
+            return Err(Error::InvalidArgument);
+        }
+        if parent.driver().as_deref() != Some(driver.path()) {
+            return Err(Error::InvalidArgument);
+        }
+
+        let node = Arsc::new(Node::new(name.clone(), props, Some(device)));
+        if !parent.insert(leaf.into(), node.clone()) {
+            return Err(Error::InvalidArgument);
+        }
+        log::debug!("Published device {name}");
+
+        self.bind(&node).await;
+        Ok(())
+    }
+
+    /// Hand the platform resources to `driver` if it's bound to the root of
+    /// the device tree.
+    ///
+    /// The resources are cloned so that they can be handed to the driver again
+    /// after it's restarted.
+    fn platform(&self, driver: &Driver) -> Result<Platform, Error> {
+        let platform = self.platform.as_ref().ok_or(Error::Unavailable)?;
+        if self.root.driver().as_deref() != Some(driver.path()) {
+            return Err(Error::Unavailable);
+        }
+        Ok(Platform {
+            mem: Object::try_clone(&platform.mem)?,
+            pio: Object::try_clone(&platform.pio)?,
+            gsi: Object::try_clone(&platform.gsi)?,
+            pci_ecam: platform.pci_ecam,
+        })
+    }
+
+    /// Bind a device to the first driver whose manifest matches it.
+    async fn bind(&self, node: &Node) {
+        let manifest = match self.manifests.iter().find(|m| m.matches(node.props())) {
+            Some(manifest) => manifest,
+            None => {
+                log::debug!("No driver found for {}", node.path());
+                return;
+            }
+        };
+        let device = match node.bind(manifest.driver().into()).await {
+            Ok(device) => device,
+            Err(err) => {
+                log::warn!("Failed to bind {}: {err}", node.path());
+                return;
+            }
+        };
+        let driver = self.driver(manifest.driver());
+        Self::hand_over(&driver, node, device);
+    }
+
+    /// Send the connection to the device bound to `driver`.
+    fn hand_over(driver: &Driver, node: &Node, device: Channel) {
+        let binding = Binding {
+            name: node.path().into(),
+            props: node.props().into(),
+            device,
+        };
+        if driver.tx.try_send(binding).is_err() {
+            log::warn!("Failed to bind {} to {}", node.path(), driver.path);
+        } else {
+            log::debug!("Bound {} to {}", node.path(), driver.path);
+        }
+    }
+}
+
+pub async fn handle_driver(
+    manager: Arsc<DeviceManager>,
+    driver: Arsc<Driver>,
+    spawner: Spawner,
+    server: DriverServer,
+) {
     let (mut stream, _) = server.serve();
     while let Some(request) = stream.next().await {
         let request = match request {
@@ -16,6 +339,25 @@ pub async fn handle_driver(server: DriverServer) {
         };
 
         let res = match request {
+            DriverRequest::Platform { responder } => responder.send(manager.platform(&driver)),
+            DriverRequest::Publish {
+                name,
+                props,
+                device,
+                responder,
+            } => responder.send(manager.publish(&driver, name, props, device).await),
+            DriverRequest::Bind { responder } => {
+                // Wait for the binding in another task so that the other
+                // requests are not blocked.
+                let rx = driver.rx.clone();
+                spawner.spawn(async move {
+                    let binding = rx.recv().await.map_err(|_| Error::Unavailable);
+                    if let Err(err) = responder.send(binding) {
+                        log::warn!("RPC send error: {err}")
+                    }
+                });
+                continue;
+            }
             DriverRequest::CloseConnection { responder } => responder.send(()),
             DriverRequest::Unknown(_) => {
                 log::warn!("unknown request received");
@@ -27,4 +369,7 @@ pub async fn handle_driver(server: DriverServer) {
             log::warn!("RPC send error: {err}")
         }
     }
+
+    // The driver has quit, so unload it from its host.
+    manager.request(Request::Unload(driver));
 }
@@ -2,23 +2,129 @@
 #![no_main]
 
 mod device;
+mod manifest;
+mod tree;
 
 use alloc::vec;
 
-use solvent::prelude::{Channel, Phys};
-use solvent_fs::{process::Process, rpc::RpcNode, spawner};
+use solvent::{
+    prelude::{Channel, GsiRes, MemRes, Object, Phys, PioRes},
+    task::{Job, JobRes},
+};
+use solvent_async::{
+    ipc::Channel as AsyncChannel,
+    sync::channel::{self, Receiver},
+};
+use solvent_fs::{
+    entry::Entry,
+    process::{BuildError, Process},
+    spawner,
+};
 use solvent_rpc::{
-    io::{self, file::PhysOptions, OpenOptions},
+    ddk::{
+        driver::{PciEcam, Platform},
+        host::DriverHostClient,
+    },
+    io::{self, dir::Directory, file::PhysOptions, OpenOptions},
     sync::Client,
+    Protocol,
+};
+use solvent_std::{env, path::Path, sync::Arsc};
+use svrt::HandleType;
+
+use self::{
+    device::{DeviceManager, Driver, Host, Request},
+    tree::Node,
 };
 
 extern crate alloc;
 
+const DRIVER_DIR: &str = "boot/drv";
+const ROOT_DRIVER: &str = "boot/drv/libpc.so";
+
+/// The resources each driver host is allowed to use, so that a misbehaving
+/// driver can't exhaust those of the whole system.
+const HOST_LIMITS: JobRes = JobRes {
+    handles: 4096,
+    memory: 256 * 1024 * 1024,
+    tasks: 64,
+};
+
 async fn main() {
     let drvhost = driver_host().expect("Failed to get driver host");
 
-    let root_driver = "boot/drv/libpc.so";
+    let (tx, rx) = channel::unbounded();
+    let manifests = manifest::load(DRIVER_DIR);
+    let manager = Arsc::new(DeviceManager::new(platform(), manifests, tx));
+    mount_tree(manager.root());
+
+    log::debug!("Starting the root driver");
+    let root = manager.bind_root(ROOT_DRIVER);
+    start_host(&manager, &drvhost, root).await;
+
+    serve_requests(manager, drvhost, rx).await
+}
 
+/// Expose the device tree to the local FS, and thus to the drivers.
+fn mount_tree(root: Arsc<Node>) {
+    let (client, server) = Directory::sync_channel();
+    root.open(
+        spawner(),
+        Default::default(),
+        Path::new(""),
+        OpenOptions::READ,
+        server.try_into().unwrap(),
+    )
+    .expect("Failed to open the device tree");
+    solvent_fs::fs::local()
+        .mount("dev", client.into())
+        .expect("Failed to mount the device tree");
+}
+
+/// Carry out the requests of the device manager to driver hosts.
+async fn serve_requests(manager: Arsc<DeviceManager>, drvhost: Phys, rx: Receiver<Request>) {
+    while let Ok(request) = rx.recv().await {
+        match request {
+            Request::Load(driver) => start_host(&manager, &drvhost, driver).await,
+            Request::Unload(driver) => manager.unload(&driver).await,
+        }
+    }
+}
+
+/// Load `driver` into a new driver host, and restart the drivers in it if it
+/// crashes.
+async fn start_host(manager: &Arsc<DeviceManager>, drvhost: &Phys, driver: Arsc<Driver>) {
+    log::debug!("Starting a driver host for {}", driver.path());
+    let (host, mut task) = match spawn_host(drvhost).await {
+        Ok(ret) => ret,
+        Err(err) => {
+            log::warn!("Failed to start a driver host: {err:?}");
+            manager.remove_driver(&driver);
+            return;
+        }
+    };
+    if let Err(err) = host.load(manager, &driver).await {
+        log::warn!("Failed to load {}: {err}", driver.path());
+        manager.remove_driver(&driver);
+    }
+
+    let manager = manager.clone();
+    let wait = async move {
+        let info = task.ajoin().await;
+        manager.remove_host(&host);
+        match info {
+            Ok(info) if info.success() => log::debug!("A driver host {info}"),
+            Ok(info) => {
+                log::warn!("A driver host died: {info}");
+                manager.restart_host(&host).await;
+            }
+            Err(err) => log::warn!("Failed to join a driver host: {err:?}"),
+        }
+    };
+    solvent_async::spawn_local(wait).detach();
+}
+
+async fn spawn_host(drvhost: &Phys) -> Result<(Arsc<Host>, Process), BuildError> {
     let bootfs = solvent_fs::open_dir("/boot", OpenOptions::READ).expect("Failed to open bootfs");
     let bootfs = bootfs.into_async().expect("Failed to get loader");
 
@@ -26,27 +132,60 @@ async fn main() {
     solvent_fs::fs::local()
         .export(&mut vfs)
         .expect("Failed to export vfs");
-    let (instance, server) = Channel::new();
-
-    vfs.push(("use/devm".into(), instance.into()));
+    let (control, server) = Channel::new();
+    let job = Job::new(None, &HOST_LIMITS, None).expect("Failed to create a job");
 
-    let mut task = Process::builder()
-        .executable(drvhost, "drvhost")
+    let mut builder = Process::builder();
+    builder
+        .executable(drvhost.clone(), "drvhost")
         .expect("Failed to set executable")
-        .arg(root_driver)
         .load_dirs(vec![bootfs])
         .expect("Failed to set load dirs")
         .local_fs(vfs)
-        .build()
-        .await
-        .expect("Failed to build the process");
-    log::debug!("Starting the root driver");
+        .job(job);
+    // SAFETY: The handle is the control channel of the driver host.
+    let control_handle = (HandleType::DriverHost.into(), Channel::into_raw(server));
+    unsafe { builder.handles([control_handle].into_iter()) };
+    let task = builder.build().await?;
 
-    let node = RpcNode::new(|server, _| async move { device::handle_driver(server).await });
-    node.open_conn(spawner(), Default::default(), server);
+    let control =
+        DriverHostClient::from(AsyncChannel::with_disp(control, solvent_async::dispatch()));
+    Ok((Arsc::new(Host::new(control)), task))
+}
+
+/// Collect the root resources of the platform passed down from the program
+/// manager, which will be handed to the root driver.
+fn platform() -> Option<Platform> {
+    let take = |ty: HandleType| svrt::try_take_startup_handle(ty.into()).ok();
+    let (mem, pio, gsi) = (
+        take(HandleType::MemRes)?,
+        take(HandleType::PioRes)?,
+        take(HandleType::GsiRes)?,
+    );
+    let pci_ecam = env::vars()
+        .find(|(key, _)| key == "PCI_ECAM")
+        .and_then(|(_, value)| parse_pci_ecam(&value))
+        .unwrap_or_default();
+    // SAFETY: The handles are of their types respectively.
+    Some(unsafe {
+        Platform {
+            mem: MemRes::from_raw(mem),
+            pio: PioRes::from_raw(pio),
+            gsi: GsiRes::from_raw(gsi),
+            pci_ecam,
+        }
+    })
+}
 
-    let ret = task.ajoin().await.expect("Failed to join the process");
-    assert_eq!(ret, 0);
+/// Parse the ECAM window in the format of `base,bus_start,bus_end`.
+fn parse_pci_ecam(value: &str) -> Option<PciEcam> {
+    let mut iter = value.split(',');
+    let base = iter.next()?.strip_prefix("0x")?;
+    Some(PciEcam {
+        base: usize::from_str_radix(base, 16).ok()?,
+        bus_start: iter.next()?.parse().ok()?,
+        bus_end: iter.next()?.parse().ok()?,
+    })
 }
 
 fn driver_host() -> Result<Phys, io::Error> {
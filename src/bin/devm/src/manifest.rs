@@ -0,0 +1,188 @@
+//! Driver manifests.
+//!
+//! Every driver `lib<name>.so` in the driver directory may come with a
+//! manifest `lib<name>.manifest` describing the devices it binds to. Each
+//! non-empty line of a manifest is a rule of space-separated `key=value`
+//! fields, all of which must match the properties of a device:
+//!
+//! ```text
+//! # Comments start with `#`.
+//! pci-id=8086:2922
+//! pci-class=01:06 pci-id=8086:2922
+//! pci-class=0c:03:30
+//! acpi-hid=PNP0501
+//! ```
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use solvent_rpc::ddk::driver::Property;
+
+const EXTENSION: &str = ".manifest";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+    PciId {
+        vendor: u16,
+        device: u16,
+    },
+    PciClass {
+        class: u8,
+        subclass: Option<u8>,
+        prog_if: Option<u8>,
+    },
+    AcpiHid(String),
+}
+
+impl Field {
+    fn parse(field: &str) -> Option<Self> {
+        let (key, value) = field.split_once('=')?;
+        match key {
+            "pci-id" => {
+                let (vendor, device) = value.split_once(':')?;
+                Some(Field::PciId {
+                    vendor: u16::from_str_radix(vendor, 16).ok()?,
+                    device: u16::from_str_radix(device, 16).ok()?,
+                })
+            }
+            "pci-class" => {
+                let mut iter = value.split(':').map(|s| u8::from_str_radix(s, 16));
+                let class = iter.next()?.ok()?;
+                let subclass = iter.next().transpose().ok()?;
+                let prog_if = iter.next().transpose().ok()?;
+                if iter.next().is_some() || (subclass.is_none() && prog_if.is_some()) {
+                    return None;
+                }
+                Some(Field::PciClass {
+                    class,
+                    subclass,
+                    prog_if,
+                })
+            }
+            "acpi-hid" if !value.is_empty() => Some(Field::AcpiHid(value.into())),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, prop: &Property) -> bool {
+        match (self, prop) {
+            (
+                Field::PciId { vendor, device },
+                Property::PciId {
+                    vendor: v,
+                    device: d,
+                },
+            ) => vendor == v && device == d,
+            (
+                Field::PciClass {
+                    class,
+                    subclass,
+                    prog_if,
+                },
+                Property::PciClass {
+                    class: c,
+                    subclass: s,
+                    prog_if: p,
+                },
+            ) => {
+                class == c
+                    && subclass.map_or(true, |subclass| subclass == *s)
+                    && prog_if.map_or(true, |prog_if| prog_if == *p)
+            }
+            (Field::AcpiHid(hid), Property::AcpiHid(h)) => hid == h,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Manifest {
+    /// The path of the driver DSO.
+    driver: String,
+    rules: Vec<Vec<Field>>,
+}
+
+impl Manifest {
+    fn parse(driver: String, text: &str) -> Result<Self, usize> {
+        let rules = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index, line.split('#').next().unwrap().trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(index, line)| {
+                line.split_whitespace()
+                    .map(Field::parse)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(index + 1)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Manifest { driver, rules })
+    }
+
+    #[inline]
+    pub fn driver(&self) -> &str {
+        &self.driver
+    }
+
+    /// Check if any rule of the manifest matches the properties of a device.
+    pub fn matches(&self, props: &[Property]) -> bool {
+        let rule_matches = |rule: &Vec<Field>| {
+            rule.iter()
+                .all(|field| props.iter().any(|prop| field.matches(prop)))
+        };
+        self.rules.iter().any(rule_matches)
+    }
+}
+
+/// Load all the driver manifests in `dir`.
+pub fn load(dir: &str) -> Vec<Manifest> {
+    let entries = match solvent_fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::warn!("Failed to read the driver directory {dir}: {err}");
+            return Vec::new();
+        }
+    };
+    let names = entries.filter_map(|entry| match entry {
+        Ok(entry) => entry.name.strip_suffix(EXTENSION).map(ToString::to_string),
+        Err(err) => {
+            log::warn!("Failed to read the driver directory {dir}: {err}");
+            None
+        }
+    });
+    names
+        .filter_map(|name| {
+            let path = format!("{dir}/{name}{EXTENSION}");
+            let text = match solvent_fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(err) => {
+                    log::warn!("Failed to read {path}: {err}");
+                    return None;
+                }
+            };
+            match Manifest::parse(format!("{dir}/{name}.so"), &text) {
+                Ok(manifest) => Some(manifest),
+                Err(line) => {
+                    log::warn!("{path}:{line}: invalid rule");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Format a property in the syntax of manifest fields.
+pub fn format_property(prop: &Property) -> String {
+    match prop {
+        Property::PciId { vendor, device } => format!("pci-id={vendor:04x}:{device:04x}"),
+        Property::PciClass {
+            class,
+            subclass,
+            prog_if,
+        } => format!("pci-class={class:02x}:{subclass:02x}:{prog_if:02x}"),
+        Property::AcpiHid(hid) => format!("acpi-hid={hid}"),
+    }
+}
@@ -0,0 +1,230 @@
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+use core::fmt::Write;
+
+use async_trait::async_trait;
+use solvent::prelude::{Channel, Phys, PhysOptions};
+use solvent_async::ipc::Channel as AsyncChannel;
+use solvent_fs::{
+    dir::{handle, Directory, EventTokens},
+    entry::Entry,
+    mem::file::MemFile,
+    Spawner,
+};
+use solvent_rpc::{
+    ddk::{device::DeviceClient, driver::Property, Error as DdkError},
+    io::{
+        dir::{DirEntry, DirectoryServer},
+        Error, FileType, Metadata, OpenOptions, Permission,
+    },
+};
+use solvent_std::{
+    path::{Component, Path},
+    sync::{Arsc, Mutex},
+};
+
+use crate::manifest;
+
+/// The name of the file in every node describing the device.
+const INFO: &str = ".info";
+
+/// A device in the device tree.
+pub struct Node {
+    /// The path of the device in the tree.
+    path: String,
+    props: Vec<Property>,
+    /// The driver bound to the device.
+    driver: Mutex<Option<String>>,
+    /// The connection to the device published by the parent's driver, which
+    /// is cloned for every driver bound to the node.
+    device: Option<DeviceClient>,
+    children: Mutex<BTreeMap<String, Arsc<Node>>>,
+}
+
+impl Node {
+    pub fn root() -> Self {
+        Self::new(String::new(), Vec::new(), None)
+    }
+
+    pub fn new(path: String, props: Vec<Property>, device: Option<Channel>) -> Self {
+        Node {
+            path,
+            props,
+            driver: Mutex::new(None),
+            device: device.map(|device| {
+                DeviceClient::from(AsyncChannel::with_disp(device, solvent_async::dispatch()))
+            }),
+            children: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    #[inline]
+    pub fn props(&self) -> &[Property] {
+        &self.props
+    }
+
+    pub fn driver(&self) -> Option<String> {
+        self.driver.lock().clone()
+    }
+
+    /// Bind the node to `driver`, returning a new connection to the device to
+    /// be handed to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the node has already been bound or the device is
+    /// unreachable.
+    pub async fn bind(&self, driver: String) -> Result<Channel, DdkError> {
+        let device = self.device.as_ref().ok_or(DdkError::Unavailable)?;
+        if self.driver.lock().is_some() {
+            return Err(DdkError::InvalidArgument);
+        }
+        let (conn, server) = Channel::new();
+        device.clone_connection(server).await?;
+
+        let mut bound = self.driver.lock();
+        if bound.is_some() {
+            return Err(DdkError::InvalidArgument);
+        }
+        *bound = Some(driver);
+        Ok(conn)
+    }
+
+    /// Unbind the node from its driver, removing the devices published by it.
+    pub fn unbind(&self) {
+        *self.driver.lock() = None;
+        self.children.lock().clear();
+    }
+
+    /// Collect the descendants bound to `driver`, without looking into their
+    /// own descendants.
+    pub fn bound_to(&self, driver: &str, output: &mut Vec<Arsc<Node>>) {
+        let children = self.children.lock().values().cloned().collect::<Vec<_>>();
+        for child in children {
+            if child.driver().as_deref() == Some(driver) {
+                output.push(child);
+            } else {
+                child.bound_to(driver, output);
+            }
+        }
+    }
+
+    /// Bind the root node, which has no device channel, to `driver`.
+    pub fn bind_root(&self, driver: String) {
+        *self.driver.lock() = Some(driver);
+    }
+
+    pub fn child(&self, name: &str) -> Option<Arsc<Node>> {
+        self.children.lock().get(name).cloned()
+    }
+
+    /// Insert a child node, returning `false` if the name is already taken.
+    pub fn insert(&self, name: String, node: Arsc<Node>) -> bool {
+        let mut children = self.children.lock();
+        if children.contains_key(&name) {
+            return false;
+        }
+        children.insert(name, node);
+        true
+    }
+
+    fn info(&self) -> Result<Arsc<MemFile>, Error> {
+        let mut text = String::new();
+        let _ = writeln!(text, "path=/{}", self.path);
+        if let Some(driver) = self.driver() {
+            let _ = writeln!(text, "driver={driver}");
+        }
+        for prop in &self.props {
+            let _ = writeln!(text, "{}", manifest::format_property(prop));
+        }
+
+        let phys = Phys::allocate(text.len(), PhysOptions::RESIZABLE).map_err(Error::Other)?;
+        // SAFETY: The object is not shared with anyone else yet.
+        unsafe { phys.write(0, text.as_bytes()) }.map_err(Error::Other)?;
+        Ok(Arsc::new(MemFile::new(phys, Permission::READ)))
+    }
+
+    fn get(&self, name: &str) -> Result<Arsc<dyn Entry>, Error> {
+        if name == INFO {
+            return Ok(self.info()?);
+        }
+        match self.child(name) {
+            Some(node) => Ok(node),
+            None => Err(Error::NotFound),
+        }
+    }
+}
+
+impl Entry for Node {
+    fn open(
+        self: Arsc<Self>,
+        spawner: Spawner,
+        tokens: EventTokens,
+        path: &Path,
+        options: OpenOptions,
+        conn: Channel,
+    ) -> Result<bool, Error> {
+        if options.intersects(OpenOptions::CREATE | OpenOptions::CREATE_NEW) {
+            return Err(Error::PermissionDenied(Permission::WRITE));
+        }
+        match path.components().next() {
+            Some(Component::Normal(name)) => {
+                let name = name
+                    .to_str()
+                    .ok_or_else(|| Error::InvalidPath(path.into()))?;
+                let path = path.strip_prefix(name).unwrap();
+                let entry = self.get(name)?;
+                entry.open(spawner, tokens, path, options, conn)
+            }
+            Some(_) => Err(Error::InvalidPath(path.into())),
+            None => {
+                if options.intersects(OpenOptions::EXPECT_FILE | OpenOptions::EXPECT_RPC) {
+                    return Err(Error::InvalidType(FileType::Directory));
+                }
+                let require = options.require();
+                if !Permission::READ.contains(require) {
+                    return Err(Error::PermissionDenied(require - Permission::READ));
+                }
+                let server =
+                    DirectoryServer::new(AsyncChannel::with_disp(conn, spawner.dispatch()));
+                let task = handle(self, spawner.clone(), tokens, server, options);
+                spawner.spawn(task);
+                Ok(false)
+            }
+        }
+    }
+
+    #[inline]
+    fn metadata(&self) -> Result<Metadata, Error> {
+        Ok(Metadata {
+            file_type: FileType::Directory,
+            perm: Permission::READ,
+            len: self.children.lock().len() + 1,
+        })
+    }
+}
+
+#[async_trait]
+impl Directory for Node {
+    async fn next_dirent(&self, last: Option<String>) -> Result<DirEntry, Error> {
+        let (name, entry) = match last.as_deref() {
+            None => (String::from(INFO), self.info()? as Arsc<dyn Entry>),
+            Some(last) => {
+                let children = self.children.lock();
+                let next = if last == INFO {
+                    children.iter().next()
+                } else {
+                    children.range::<str, _>(last..).nth(1)
+                };
+                let (name, node) = next.ok_or(Error::IterEnd)?;
+                (name.clone(), node.clone() as _)
+            }
+        };
+        let metadata = entry.metadata()?;
+        Ok(DirEntry { name, metadata })
+    }
+}
@@ -4,9 +4,13 @@ use core::{
     ptr::NonNull,
 };
 
-use solvent_async::{global_executor, local_executor};
+use solvent::obj::Ref;
+use solvent_async::{disp::DispSender, global_executor, local_executor};
 use solvent_ddk::ffi::VTable;
 use solvent_fs::fs;
+use solvent_std::sync::Lazy;
+
+static DISP: Lazy<DispSender> = Lazy::new(solvent_async::dispatch);
 
 #[no_mangle]
 unsafe extern "C" fn __h2o_ddk_alloc(size: usize, align: usize) -> *mut () {
@@ -31,6 +35,8 @@ pub fn vtable() -> VTable {
         global_exe: global_executor() as _,
         local_exe: local_executor(|exe| exe as *const _),
         local_fs: fs::local() as *const _,
+        disp: &*DISP as *const _,
+        root_virt: Ref::into_raw(svrt::root_virt()),
 
         alloc: __h2o_ddk_alloc,
         dealloc: __h2o_ddk_dealloc,
@@ -1,66 +1,201 @@
-use alloc::{boxed::Box, ffi::CString};
+use alloc::{boxed::Box, collections::BTreeMap, ffi::CString};
 use core::{
-    error::Error,
     ffi::{c_char, c_void, CStr},
-    future::Future,
     ptr,
 };
 
 use async_task::Task;
-use solvent::prelude::{Channel, Handle, Object, Phys};
+use futures_lite::{future, StreamExt};
+use solvent::{
+    c_ty::Status,
+    prelude::{Channel, Handle, Object, Phys},
+};
 use solvent_fs::fs;
-use solvent_rpc::io::{
-    file::{FileSyncClient, PhysOptions},
-    OpenOptions,
+use solvent_rpc::{
+    ddk::host::{DriverHostRequest, DriverHostServer, Error},
+    io::{
+        file::{FileSyncClient, PhysOptions},
+        OpenOptions,
+    },
+    Server,
 };
-use solvent_std::{c_str, path::Path};
-
-pub fn bootstrap(file_path: &Path) -> Result<impl Future<Output = ()>, Box<dyn Error>> {
-    let (driver, dserver) = Channel::new();
-    fs::local().open("use/devm", OpenOptions::READ | OpenOptions::WRITE, dserver)?;
-
-    let (file, fserver) = Channel::new();
-    fs::local().open(
-        file_path,
-        OpenOptions::READ | OpenOptions::EXECUTE | OpenOptions::EXPECT_FILE,
-        fserver,
-    )?;
-    let file = FileSyncClient::from(file);
-    let phys = file.phys(PhysOptions::Shared)??;
-
-    let name = CString::new(file_path.to_str().unwrap())?;
-    create(driver, phys, &name)
+use solvent_std::c_str;
+
+/// A driver loaded into the host.
+struct Instance {
+    dso: *const c_void,
+    exit: Exit,
+    tasks: Tasks,
+    /// The main task of the driver, which is `None` once the driver is
+    /// stopped.
+    task: Option<Task<()>>,
 }
 
-fn create(
-    driver: Channel,
-    phys: Phys,
-    name: &CStr,
-) -> Result<impl Future<Output = ()>, Box<dyn Error>> {
-    // Load the DSO.
-    let dso = {
-        let phys = Phys::into_raw(phys);
-        unsafe { dlphys(phys, name.as_ptr()) }
-    };
-
-    // Get `__h2o_ddk_enter` function.
-    let ddk_enter =
-        unsafe { ddk_fn::<Enter>(dso, c_str!("__h2o_ddk_enter")) }.ok_or("ddk_enter not found")?;
-
-    // And `__h2o_ddk_exit`.
-    let ddk_exit =
-        unsafe { ddk_fn::<Exit>(dso, c_str!("__h2o_ddk_exit")) }.ok_or("ddk_exit not found")?;
-
-    // Initialize the driver environment.
-    let task = unsafe {
+impl Instance {
+    fn load(path: &str, driver: Channel) -> Result<Self, Error> {
+        let (file, fserver) = Channel::new();
+        fs::local().open(
+            path,
+            OpenOptions::READ | OpenOptions::EXECUTE | OpenOptions::EXPECT_FILE,
+            fserver,
+        )?;
+        let file = FileSyncClient::from(file);
+        let phys = file.phys(PhysOptions::Shared)??;
+
+        let name = CString::new(path).map_err(|_| Error::Load)?;
+        // SAFETY: The DSO is a driver.
+        unsafe { Self::create(driver, phys, &name) }
+    }
+
+    /// # Safety
+    ///
+    /// `phys` must be a driver DSO built with the DDK.
+    unsafe fn create(driver: Channel, phys: Phys, name: &CStr) -> Result<Self, Error> {
+        // Load the DSO.
+        let dso = dlphys(Phys::into_raw(phys), name.as_ptr());
+        if dso.is_null() {
+            return Err(Error::Load);
+        }
+
+        let ret = Self::enter(dso, driver);
+        if ret.is_err() {
+            let _ = dlclose(dso);
+        }
+        ret
+    }
+
+    unsafe fn enter(dso: *const c_void, driver: Channel) -> Result<Self, Error> {
+        let symbol_not_found =
+            |name: &CStr| Error::SymbolNotFound(name.to_string_lossy().into_owned());
+
+        // Get `__h2o_ddk_enter` function.
+        let enter_name = c_str!("__h2o_ddk_enter");
+        let ddk_enter =
+            ddk_fn::<Enter>(dso, enter_name).ok_or_else(|| symbol_not_found(enter_name))?;
+
+        // And `__h2o_ddk_exit`.
+        let exit_name = c_str!("__h2o_ddk_exit");
+        let exit = ddk_fn::<Exit>(dso, exit_name).ok_or_else(|| symbol_not_found(exit_name))?;
+
+        // And `__h2o_ddk_tasks`.
+        let tasks_name = c_str!("__h2o_ddk_tasks");
+        let tasks = ddk_fn::<Tasks>(dso, tasks_name).ok_or_else(|| symbol_not_found(tasks_name))?;
+
+        // Initialize the driver environment.
         let ptr = ddk_enter(&crate::ffi::vtable() as _, Channel::into_raw(driver));
-        Box::from_raw(ptr.cast::<Task<()>>())
-    };
+        let task = *Box::from_raw(ptr.cast::<Task<()>>());
+
+        Ok(Instance {
+            dso,
+            exit,
+            tasks,
+            task: Some(task),
+        })
+    }
 
-    Ok(async move {
-        task.await;
-        unsafe { ddk_exit() };
-    })
+    /// Shut the driver down by cancelling its main task, and wait for the
+    /// other tasks owned by it to be dropped.
+    ///
+    /// Fails if any task of the driver is still alive afterwards, e.g. a
+    /// detached one, because its code may still run.
+    async fn stop(&mut self) -> Result<(), Error> {
+        if let Some(task) = self.task.take() {
+            task.cancel().await;
+        }
+        // Cancelled tasks are dropped by the executor, so let it run them until
+        // no more progress is made.
+        let mut count = unsafe { (self.tasks)() };
+        while count > 0 {
+            future::yield_now().await;
+            let new = unsafe { (self.tasks)() };
+            if new == count {
+                return Err(Error::Busy(count));
+            }
+            count = new;
+        }
+        Ok(())
+    }
+
+    /// Unload the driver stopped by [`Instance::stop`].
+    fn unload(self) -> Result<(), Error> {
+        assert!(self.task.is_none(), "the driver is not stopped");
+        unsafe {
+            (self.exit)();
+            dlclose(self.dso).into_res().map_err(Error::Unload)
+        }
+    }
+}
+
+pub async fn serve(server: DriverHostServer) {
+    let mut instances = BTreeMap::new();
+    let mut next_id = 0;
+
+    let (mut stream, _) = server.serve();
+    while let Some(request) = stream.next().await {
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                log::warn!("RPC receive error: {err}");
+                continue;
+            }
+        };
+
+        let mut unloaded = false;
+        let res = match request {
+            DriverHostRequest::Load {
+                path,
+                instance,
+                responder,
+            } => {
+                let res = Instance::load(&path, instance).map(|instance| {
+                    let id = next_id;
+                    next_id += 1;
+                    instances.insert(id, instance);
+                    log::debug!("Loaded driver {path} as #{id}");
+                    id
+                });
+                responder.send(res)
+            }
+            DriverHostRequest::Unload { id, responder } => {
+                let res = match instances.get_mut(&id) {
+                    // The driver stays loaded if it can't be stopped.
+                    Some(instance) => match instance.stop().await {
+                        Ok(()) => {
+                            unloaded = true;
+                            instances.remove(&id).unwrap().unload()
+                        }
+                        Err(err) => Err(err),
+                    },
+                    None => Err(Error::NotFound(id)),
+                };
+                responder.send(res)
+            }
+            DriverHostRequest::CloseConnection { responder } => responder.send(()),
+            DriverHostRequest::Unknown(_) => {
+                log::warn!("unknown request received");
+                continue;
+            }
+        };
+
+        if let Err(err) = res {
+            log::warn!("RPC send error: {err}")
+        }
+        if unloaded && instances.is_empty() {
+            log::debug!("The last driver is unloaded, exiting");
+            return;
+        }
+    }
+
+    // The device manager is gone, so shut all the drivers down.
+    for (id, mut instance) in instances {
+        let res = match instance.stop().await {
+            Ok(()) => instance.unload(),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = res {
+            log::warn!("Failed to unload driver #{id}: {err}");
+        }
+    }
 }
 
 #[link(name = "ldso")]
@@ -68,6 +203,8 @@ extern "C" {
     fn dlphys(phys: Handle, name: *const c_char) -> *const c_void;
 
     fn dlsym(handle: *const c_void, name: *const c_char) -> *mut c_void;
+
+    fn dlclose(handle: *const c_void) -> Status;
 }
 
 /// # Safety
@@ -88,3 +225,5 @@ type Enter = unsafe extern "C" fn(
 ) -> *mut ();
 
 type Exit = unsafe extern "C" fn();
+
+type Tasks = unsafe extern "C" fn() -> usize;
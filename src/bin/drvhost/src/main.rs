@@ -9,14 +9,21 @@ mod instance;
 use alloc::boxed::Box;
 use core::error::Error;
 
-use solvent_std::env;
+use solvent::prelude::{Channel, Object};
+use solvent_async::ipc::Channel as AsyncChannel;
+use solvent_rpc::ddk::host::DriverHostServer;
+use svrt::HandleType;
 
 extern crate alloc;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let driver = env::args().nth(1).expect("Failed to get the driver path");
-    let task = instance::bootstrap(driver.as_ref())?;
-    solvent_async::block_on(Some(1), task);
+    let control = svrt::try_take_startup_handle(HandleType::DriverHost.into())
+        .map_err(|_| "Failed to get the control channel")?;
+    // SAFETY: The handle is the control channel from the device manager.
+    let control = unsafe { Channel::from_raw(control) };
+    let server =
+        DriverHostServer::from(AsyncChannel::with_disp(control, solvent_async::dispatch()));
+    solvent_async::block_on(Some(1), instance::serve(server));
     Ok(())
 }
 
@@ -1,26 +1,101 @@
-use alloc::{borrow::ToOwned, vec::Vec};
+use alloc::{borrow::ToOwned, string::ToString, vec::Vec};
 use core::{ffi::CStr, ptr::NonNull};
 
+use bootfs::parse::{Compressed, File};
 use either::Either;
-use solvent::prelude::{Flags, Object, Phys, PAGE_MASK};
+use solvent::prelude::{Channel, Flags, Object, Phys, PhysOptions, PAGE_MASK};
 use solvent_fs::{
+    dir::EventTokens,
     entry::Entry,
     fs,
     mem::{
         dir::{RecursiveBuild, RecursiveBuilder},
         file::MemFile,
     },
+    Spawner,
 };
 use solvent_rpc::{
-    io::{dir::Directory, OpenOptions, Permission},
+    io::{dir::Directory, Error, FileType, Metadata, OpenOptions, Permission},
     Protocol,
 };
 use solvent_std::{
     path::Path,
-    sync::{Arsc, Once},
+    sync::{Arsc, Once, OnceCell},
 };
 use svrt::HandleType;
 
+/// A compressed file in the bootfs, decompressed into a fresh physical object
+/// when first opened.
+struct CompressedFile {
+    data: Phys,
+    len: usize,
+    perm: Permission,
+    file: OnceCell<Arsc<MemFile>>,
+}
+
+impl CompressedFile {
+    fn decompress(&self) -> Result<Phys, Error> {
+        let phys = Phys::allocate((self.len + PAGE_MASK) & !PAGE_MASK, PhysOptions::ZEROED)
+            .map_err(Error::Other)?;
+
+        let root = svrt::root_virt();
+        let data = root
+            .map_phys(
+                None,
+                self.data.clone(),
+                Flags::READABLE | Flags::USER_ACCESS,
+            )
+            .map_err(Error::Other)?;
+        let output = root
+            .map_phys(
+                None,
+                phys.clone(),
+                Flags::READABLE | Flags::WRITABLE | Flags::USER_ACCESS,
+            )
+            .map_err(Error::Other);
+
+        let ret = output.map(|output| unsafe {
+            let ret = Compressed::parse(data.as_ref())
+                .and_then(|compressed| compressed.decompress_into(&mut *output.as_ptr()));
+            let _ = root.unmap(output.as_non_null_ptr(), output.len(), true);
+            ret
+        });
+        let _ = root.unmap(data.as_non_null_ptr(), data.len(), true);
+
+        match ret? {
+            Some(len) if len == self.len => Ok(phys),
+            _ => Err(Error::InvalidData("corrupted compressed file".to_string())),
+        }
+    }
+
+    fn file(&self) -> Result<&Arsc<MemFile>, Error> {
+        self.file
+            .get_or_try_init(|| Ok(Arsc::new(MemFile::new(self.decompress()?, self.perm))))
+    }
+}
+
+impl Entry for CompressedFile {
+    fn open(
+        self: Arsc<Self>,
+        spawner: Spawner,
+        tokens: EventTokens,
+        path: &Path,
+        options: OpenOptions,
+        conn: Channel,
+    ) -> Result<bool, Error> {
+        let file = Arsc::clone(self.file()?);
+        file.open(spawner, tokens, path, options, conn)
+    }
+
+    fn metadata(&self) -> Result<Metadata, Error> {
+        Ok(Metadata {
+            file_type: FileType::File,
+            perm: self.perm,
+            len: self.len,
+        })
+    }
+}
+
 /// # Safety
 ///
 /// The caller must ensure `dir` is the sub slice of `root_virt` and
@@ -40,8 +115,15 @@ unsafe fn build_inner(
             .to_str()
             .unwrap()
             .to_owned();
+        let content = match dir_entry.content() {
+            Some(content) if dir_entry.verify() => content,
+            _ => {
+                log::error!("Bootfs entry {name:?} is corrupted, skipping");
+                continue;
+            }
+        };
 
-        match dir_entry.content() {
+        match content {
             Either::Right(dir_slice) => {
                 ret.push(RecursiveBuild::Down(
                     name,
@@ -50,7 +132,8 @@ unsafe fn build_inner(
                 ret.append(&mut build_inner(root_phys, base, dir_slice));
                 ret.push(RecursiveBuild::Up);
             }
-            Either::Left(data) => {
+            Either::Left(file) => {
+                let data = file.data();
                 let offset = unsafe { data.as_ptr().offset_from(base.as_ptr()) as usize };
                 assert!(
                     offset & PAGE_MASK == 0,
@@ -60,8 +143,22 @@ unsafe fn build_inner(
                 let data = root_phys
                     .create_sub(offset, (len + PAGE_MASK) & !PAGE_MASK, false)
                     .expect("Failed to create sub phys");
-                let file = MemFile::new(data, Permission::READ | Permission::EXECUTE);
-                ret.push(RecursiveBuild::Entry(name, Arsc::new(file)));
+                let perm = Permission::READ | Permission::EXECUTE;
+                match file {
+                    File::Raw(_) => {
+                        let file = MemFile::new(data, perm);
+                        ret.push(RecursiveBuild::Entry(name, Arsc::new(file)));
+                    }
+                    File::Compressed(compressed) => {
+                        let file = CompressedFile {
+                            data,
+                            len: compressed.len(),
+                            perm,
+                            file: OnceCell::new(),
+                        };
+                        ret.push(RecursiveBuild::Entry(name, Arsc::new(file)));
+                    }
+                }
             }
         };
     }
@@ -6,8 +6,10 @@ mod boot;
 
 use alloc::vec;
 
+use solvent::prelude::Handle;
 use solvent_fs::process::Process;
 use solvent_rpc::{io::OpenOptions, sync::Client};
+use svrt::{HandleInfo, HandleType};
 
 extern crate alloc;
 
@@ -34,23 +36,42 @@ async fn main() {
         .export(&mut vfs)
         .expect("Failed to export vfs");
 
-    let mut task = Process::builder()
+    let mut builder = Process::builder();
+    builder
         .executable(devm, "devm")
         .expect("Failed to add executable")
         .load_dirs(vec![bootfs])
         .expect("Failed to add loader client")
         .local_fs(vfs)
-        .build()
-        .await
-        .expect("Failed to build a process");
+        .environs(
+            solvent_std::env::vars().filter(|(key, _)| key == "PCI_ECAM" || key == "FRAMEBUFFER"),
+        );
+    // SAFETY: The resources are taken from our own startup handles.
+    unsafe { builder.handles(platform_resources()) };
+    let mut task = builder.build().await.expect("Failed to build a process");
 
     log::debug!("Waiting for devm");
-    let retval = task.ajoin().await.expect("Failed to wait for devm");
-    assert_eq!(retval, 0, "The process failed: {retval:#x}");
+    let info = task.ajoin().await.expect("Failed to wait for devm");
+    assert!(info.success(), "The process failed: {info}");
 
     log::debug!("Goodbye!");
 }
 
+/// The root resources of the platform, handed down to the device manager.
+fn platform_resources() -> impl Iterator<Item = (HandleInfo, Handle)> {
+    [
+        HandleType::MemRes,
+        HandleType::PioRes,
+        HandleType::GsiRes,
+        HandleType::FramebufferPhys,
+    ]
+    .into_iter()
+    .filter_map(|ty| {
+        let handle = svrt::try_take_startup_handle(ty.into()).ok()?;
+        Some((ty.into(), handle))
+    })
+}
+
 solvent_async::entry!(main, solvent_std, None);
 
 #[link(name = "ldso")]
@@ -0,0 +1,212 @@
+use core::{arch::asm, mem, num::NonZeroUsize, ops::RangeInclusive, ptr::NonNull};
+
+use solvent::prelude::{Flags, MemRes, Phys, PioRes, PortIo};
+use solvent_core::sync::Mutex;
+use solvent_rpc::ddk::{device::PciAddress, driver::PciEcam, Error};
+
+const CONFIG_ADDRESS: u16 = 0xcf8;
+const CONFIG_DATA: u16 = 0xcfc;
+
+/// The access method to the configuration space of PCI functions.
+pub enum ConfigSpace {
+    /// PCIe enhanced configuration access mechanism, with the window mapped
+    /// into our address space.
+    Ecam {
+        base: NonNull<u8>,
+        bus_start: u8,
+        bus_end: u8,
+    },
+    /// The legacy `0xcf8`/`0xcfc` port pair, which must be accessed
+    /// atomically.
+    Legacy(Mutex<()>),
+}
+
+// SAFETY: The ECAM window is mapped for the lifetime of the driver.
+unsafe impl Send for ConfigSpace {}
+unsafe impl Sync for ConfigSpace {}
+
+impl ConfigSpace {
+    pub fn new(mem: &MemRes, pio: &PioRes, ecam: PciEcam) -> Result<Self, Error> {
+        if ecam.base != 0 {
+            match Self::map_ecam(mem, ecam) {
+                Ok(config) => return Ok(config),
+                Err(err) => log::warn!("Failed to map ECAM, falling back to port I/O: {err:?}"),
+            }
+        }
+        // The ports are kept for the lifetime of the driver.
+        mem::forget(PortIo::acquire(pio, CONFIG_ADDRESS..(CONFIG_DATA + 4))?);
+        Ok(ConfigSpace::Legacy(Mutex::new(())))
+    }
+
+    fn map_ecam(mem: &MemRes, ecam: PciEcam) -> solvent::error::Result<Self> {
+        let size = (ecam.bus_end as usize - ecam.bus_start as usize + 1) << 20;
+        let phys = Phys::acquire(mem, NonZeroUsize::new(ecam.base), size)?;
+        let ptr = solvent_ddk::ffi::root_virt().map_phys(
+            None,
+            phys,
+            Flags::READABLE | Flags::WRITABLE | Flags::UNCACHED | Flags::USER_ACCESS,
+        )?;
+        Ok(ConfigSpace::Ecam {
+            base: ptr.cast(),
+            bus_start: ecam.bus_start,
+            bus_end: ecam.bus_end,
+        })
+    }
+
+    pub fn buses(&self) -> RangeInclusive<u8> {
+        match *self {
+            ConfigSpace::Ecam {
+                bus_start, bus_end, ..
+            } => bus_start..=bus_end,
+            ConfigSpace::Legacy(_) => 0..=u8::MAX,
+        }
+    }
+
+    /// Whether the PCIe extended configuration space is accessible.
+    #[inline]
+    pub fn is_extended(&self) -> bool {
+        matches!(self, ConfigSpace::Ecam { .. })
+    }
+
+    fn check(&self, addr: PciAddress, offset: u16, width: u8) -> Result<(), Error> {
+        let size: u32 = if self.is_extended() { 4096 } else { 256 };
+        let valid = addr.segment == 0
+            && self.buses().contains(&addr.bus)
+            && addr.device < 32
+            && addr.function < 8
+            && matches!(width, 1 | 2 | 4)
+            && offset % width as u16 == 0
+            && offset as u32 + width as u32 <= size;
+        valid.then_some(()).ok_or(Error::InvalidArgument)
+    }
+
+    pub fn read(&self, addr: PciAddress, offset: u16, width: u8) -> Result<u32, Error> {
+        self.check(addr, offset, width)?;
+        Ok(match self {
+            ConfigSpace::Ecam {
+                base, bus_start, ..
+            } => unsafe {
+                // SAFETY: The offset is checked to be inside the window.
+                let ptr = base.as_ptr().add(ecam_offset(addr, *bus_start, offset));
+                match width {
+                    1 => ptr.read_volatile() as u32,
+                    2 => ptr.cast::<u16>().read_volatile() as u32,
+                    _ => ptr.cast::<u32>().read_volatile(),
+                }
+            },
+            ConfigSpace::Legacy(lock) => unsafe {
+                let _lock = lock.lock();
+                out32(CONFIG_ADDRESS, legacy_address(addr, offset));
+                let port = CONFIG_DATA + (offset & 3);
+                match width {
+                    1 => in8(port) as u32,
+                    2 => in16(port) as u32,
+                    _ => in32(port),
+                }
+            },
+        })
+    }
+
+    pub fn write(&self, addr: PciAddress, offset: u16, width: u8, value: u32) -> Result<(), Error> {
+        self.check(addr, offset, width)?;
+        match self {
+            ConfigSpace::Ecam {
+                base, bus_start, ..
+            } => unsafe {
+                // SAFETY: The offset is checked to be inside the window.
+                let ptr = base.as_ptr().add(ecam_offset(addr, *bus_start, offset));
+                match width {
+                    1 => ptr.write_volatile(value as u8),
+                    2 => ptr.cast::<u16>().write_volatile(value as u16),
+                    _ => ptr.cast::<u32>().write_volatile(value),
+                }
+            },
+            ConfigSpace::Legacy(lock) => unsafe {
+                let _lock = lock.lock();
+                out32(CONFIG_ADDRESS, legacy_address(addr, offset));
+                let port = CONFIG_DATA + (offset & 3);
+                match width {
+                    1 => out8(port, value as u8),
+                    2 => out16(port, value as u16),
+                    _ => out32(port, value),
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // The helpers below are used with the fixed offsets of the configuration
+    // header, and return all ones on invalid accesses as absent functions do.
+
+    #[inline]
+    pub fn read_u8(&self, addr: PciAddress, offset: u16) -> u8 {
+        self.read(addr, offset, 1)
+            .map_or(u8::MAX, |value| value as u8)
+    }
+
+    #[inline]
+    pub fn read_u16(&self, addr: PciAddress, offset: u16) -> u16 {
+        self.read(addr, offset, 2)
+            .map_or(u16::MAX, |value| value as u16)
+    }
+
+    #[inline]
+    pub fn read_u32(&self, addr: PciAddress, offset: u16) -> u32 {
+        self.read(addr, offset, 4).unwrap_or(u32::MAX)
+    }
+
+    #[inline]
+    pub fn write_u16(&self, addr: PciAddress, offset: u16, value: u16) {
+        let _ = self.write(addr, offset, 2, value as u32);
+    }
+
+    #[inline]
+    pub fn write_u32(&self, addr: PciAddress, offset: u16, value: u32) {
+        let _ = self.write(addr, offset, 4, value);
+    }
+}
+
+fn ecam_offset(addr: PciAddress, bus_start: u8, offset: u16) -> usize {
+    ((addr.bus - bus_start) as usize) << 20
+        | (addr.device as usize) << 15
+        | (addr.function as usize) << 12
+        | offset as usize
+}
+
+fn legacy_address(addr: PciAddress, offset: u16) -> u32 {
+    1 << 31
+        | (addr.bus as u32) << 16
+        | (addr.device as u32) << 11
+        | (addr.function as u32) << 8
+        | (offset & 0xfc) as u32
+}
+
+unsafe fn in8(port: u16) -> u8 {
+    let value;
+    asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn in16(port: u16) -> u16 {
+    let value;
+    asm!("in ax, dx", out("ax") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn in32(port: u16) -> u32 {
+    let value;
+    asm!("in eax, dx", out("eax") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn out8(port: u16, value: u8) {
+    asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
+
+unsafe fn out16(port: u16, value: u16) {
+    asm!("out dx, ax", in("dx") port, in("ax") value, options(nomem, nostack, preserves_flags));
+}
+
+unsafe fn out32(port: u16, value: u32) {
+    asm!("out dx, eax", in("dx") port, in("eax") value, options(nomem, nostack, preserves_flags));
+}
@@ -0,0 +1,70 @@
+use futures_lite::StreamExt;
+use solvent::prelude::Channel;
+use solvent_async::ipc::Channel as AsyncChannel;
+use solvent_core::sync::Arsc;
+use solvent_ddk::ffi::{dispatch, spawn_local};
+use solvent_rpc::{
+    ddk::device::{DeviceRequest, DeviceServer},
+    Server,
+};
+
+use crate::pci::{Function, Pci};
+
+/// Serve another connection to the function in the background, such as the
+/// one cloned for a restarted driver.
+fn serve_clone(pci: Arsc<Pci>, function: Arsc<Function>, conn: Channel) {
+    let server = DeviceServer::from(AsyncChannel::with_disp(conn, dispatch()));
+    spawn_local(serve(pci, function, server)).detach();
+}
+
+pub async fn serve(pci: Arsc<Pci>, function: Arsc<Function>, server: DeviceServer) {
+    let addr = function.info.address;
+    let (mut stream, _) = server.serve();
+    while let Some(request) = stream.next().await {
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                log::warn!("RPC receive error: {err}");
+                continue;
+            }
+        };
+
+        let res = match request {
+            DeviceRequest::Info { responder } => responder.send(function.info),
+            DeviceRequest::Bars { responder } => responder.send(function.bars.clone()),
+            DeviceRequest::Capabilities { responder } => {
+                responder.send(function.capabilities.clone())
+            }
+            DeviceRequest::ReadConfig {
+                offset,
+                width,
+                responder,
+            } => responder.send(pci.config().read(addr, offset, width)),
+            DeviceRequest::WriteConfig {
+                offset,
+                width,
+                value,
+                responder,
+            } => responder.send(pci.config().write(addr, offset, width, value)),
+            DeviceRequest::Bar { index, responder } => responder.send(pci.bar(&function, index)),
+            DeviceRequest::AllocateMsi {
+                count,
+                cpu,
+                responder,
+            } => responder.send(pci.allocate_msi(&function, count, cpu)),
+            DeviceRequest::CloneConnection { conn, responder } => {
+                serve_clone(pci.clone(), function.clone(), conn);
+                responder.send(())
+            }
+            DeviceRequest::CloseConnection { responder } => responder.send(()),
+            DeviceRequest::Unknown(_) => {
+                log::warn!("unknown request received");
+                continue;
+            }
+        };
+
+        if let Err(err) = res {
+            log::warn!("RPC send error: {err}")
+        }
+    }
+}
@@ -1,11 +1,90 @@
 #![no_std]
 
+mod config;
+mod device;
+mod pci;
+
+use alloc::{format, string::String, vec, vec::Vec};
+
 use solvent::prelude::Channel;
+use solvent_async::ipc::Channel as AsyncChannel;
+use solvent_core::sync::Arsc;
+use solvent_ddk::ffi::{dispatch, spawn_local};
+use solvent_rpc::ddk::{
+    device::{DeviceServer, PciAddress, PciInfo},
+    driver::{DriverClient, Property},
+    Error,
+};
+
+use self::pci::Pci;
 
 extern crate alloc;
 
-async fn init(_driver_instance: Channel) {
-    log::debug!("Hello from driver");
+async fn init(driver_instance: Channel) {
+    let driver = DriverClient::from(AsyncChannel::with_disp(driver_instance, dispatch()));
+
+    let platform = match driver.platform().await {
+        Ok(res) => res,
+        Err(err) => Err(Error::from(err)),
+    };
+    let pci = match platform.and_then(Pci::new) {
+        Ok(pci) => Arsc::new(pci),
+        Err(err) => {
+            log::error!("Failed to initialize the PCI bus: {err}");
+            return;
+        }
+    };
+
+    let mut tasks = Vec::new();
+    for function in pci.enumerate() {
+        let info = function.info;
+        let name = device_name(info.address);
+        log::debug!(
+            "{name}: {:04x}:{:04x}, class {:02x}:{:02x}:{:02x}",
+            info.vendor_id,
+            info.device_id,
+            info.class,
+            info.subclass,
+            info.prog_if
+        );
+
+        let (client, server) = Channel::new();
+        let server = DeviceServer::from(AsyncChannel::with_disp(server, dispatch()));
+        let task = device::serve(pci.clone(), Arsc::new(function), server);
+        tasks.push(spawn_local(task));
+
+        match driver.publish(name, properties(&info), client).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => log::warn!("Failed to publish the device: {err}"),
+            Err(err) => log::warn!("RPC error: {err}"),
+        }
+    }
+
+    // Keep the driver alive while any of the devices is being served.
+    for task in tasks {
+        task.await
+    }
+}
+
+fn properties(info: &PciInfo) -> Vec<Property> {
+    vec![
+        Property::PciId {
+            vendor: info.vendor_id,
+            device: info.device_id,
+        },
+        Property::PciClass {
+            class: info.class,
+            subclass: info.subclass,
+            prog_if: info.prog_if,
+        },
+    ]
+}
+
+fn device_name(addr: PciAddress) -> String {
+    format!(
+        "pci-{:04x}:{:02x}:{:02x}.{}",
+        addr.segment, addr.bus, addr.device, addr.function
+    )
 }
 
 solvent_ddk::entry!(init);
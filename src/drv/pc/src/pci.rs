@@ -0,0 +1,314 @@
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+
+use solvent::prelude::{GsiRes, Interrupt, MemRes, Phys, PioRes, PAGE_MASK, PAGE_SIZE};
+use solvent_rpc::ddk::{
+    device::{Bar, BarInfo, BarKind, Capability, PciAddress, PciInfo},
+    driver::Platform,
+    Error,
+};
+
+use crate::config::ConfigSpace;
+
+const VENDOR_ID: u16 = 0x00;
+const DEVICE_ID: u16 = 0x02;
+const COMMAND: u16 = 0x04;
+const STATUS: u16 = 0x06;
+const REVISION: u16 = 0x08;
+const PROG_IF: u16 = 0x09;
+const SUBCLASS: u16 = 0x0a;
+const CLASS: u16 = 0x0b;
+const HEADER_TYPE: u16 = 0x0e;
+const BAR0: u16 = 0x10;
+const SUBSYSTEM_VENDOR_ID: u16 = 0x2c;
+const SUBSYSTEM_ID: u16 = 0x2e;
+const CAP_PTR: u16 = 0x34;
+const INTERRUPT_LINE: u16 = 0x3c;
+const INTERRUPT_PIN: u16 = 0x3d;
+
+const INVALID_VENDOR: u16 = 0xffff;
+const HEADER_MULTIFUNCTION: u8 = 0x80;
+
+const COMMAND_IO: u16 = 1 << 0;
+const COMMAND_MEMORY: u16 = 1 << 1;
+const COMMAND_INTX_DISABLE: u16 = 1 << 10;
+const STATUS_CAP_LIST: u16 = 1 << 4;
+
+const BAR_IO: u32 = 1 << 0;
+const BAR_TYPE_64: u32 = 0b10 << 1;
+const BAR_PREFETCHABLE: u32 = 1 << 3;
+
+const EXT_CAP_START: u16 = 0x100;
+/// Bounds the walk of capability lists in case of a loop.
+const MAX_CAPS: usize = 64;
+
+const CAP_MSI: u16 = 0x05;
+const MSI_ENABLE: u16 = 1 << 0;
+const MSI_64BIT: u16 = 1 << 7;
+const MSI_MME_MASK: u16 = 0b111 << 4;
+
+/// A PCI function found at enumeration.
+pub struct Function {
+    pub info: PciInfo,
+    pub bars: Vec<BarInfo>,
+    pub capabilities: Vec<Capability>,
+}
+
+pub struct Pci {
+    config: ConfigSpace,
+    mem: MemRes,
+    pio: PioRes,
+    gsi: GsiRes,
+}
+
+impl Pci {
+    pub fn new(platform: Platform) -> Result<Self, Error> {
+        let config = ConfigSpace::new(&platform.mem, &platform.pio, platform.pci_ecam)?;
+        Ok(Pci {
+            config,
+            mem: platform.mem,
+            pio: platform.pio,
+            gsi: platform.gsi,
+        })
+    }
+
+    #[inline]
+    pub fn config(&self) -> &ConfigSpace {
+        &self.config
+    }
+
+    /// Scan every bus reachable through the configuration space for present
+    /// functions.
+    pub fn enumerate(&self) -> Vec<Function> {
+        let mut ret = Vec::new();
+        for bus in self.config.buses() {
+            for device in 0..32 {
+                let addr = PciAddress {
+                    segment: 0,
+                    bus,
+                    device,
+                    function: 0,
+                };
+                if self.config.read_u16(addr, VENDOR_ID) == INVALID_VENDOR {
+                    continue;
+                }
+                let header_type = self.config.read_u8(addr, HEADER_TYPE);
+                let count = if header_type & HEADER_MULTIFUNCTION != 0 {
+                    8
+                } else {
+                    1
+                };
+                ret.extend(
+                    (0..count).filter_map(|function| self.probe(PciAddress { function, ..addr })),
+                );
+            }
+        }
+        ret
+    }
+
+    fn probe(&self, addr: PciAddress) -> Option<Function> {
+        let config = &self.config;
+        let vendor_id = config.read_u16(addr, VENDOR_ID);
+        if vendor_id == INVALID_VENDOR {
+            return None;
+        }
+        // Only general devices (type 0) have 6 BARs and subsystem IDs;
+        // PCI-to-PCI bridges (type 1) have 2 BARs.
+        let (bar_count, has_subsystem) =
+            match config.read_u8(addr, HEADER_TYPE) & !HEADER_MULTIFUNCTION {
+                0 => (6, true),
+                1 => (2, false),
+                _ => (0, false),
+            };
+        let subsystem = |offset| {
+            if has_subsystem {
+                config.read_u16(addr, offset)
+            } else {
+                0
+            }
+        };
+        let info = PciInfo {
+            address: addr,
+            vendor_id,
+            device_id: config.read_u16(addr, DEVICE_ID),
+            subsystem_vendor_id: subsystem(SUBSYSTEM_VENDOR_ID),
+            subsystem_id: subsystem(SUBSYSTEM_ID),
+            class: config.read_u8(addr, CLASS),
+            subclass: config.read_u8(addr, SUBCLASS),
+            prog_if: config.read_u8(addr, PROG_IF),
+            revision: config.read_u8(addr, REVISION),
+            interrupt_pin: config.read_u8(addr, INTERRUPT_PIN),
+            interrupt_line: config.read_u8(addr, INTERRUPT_LINE),
+        };
+        Some(Function {
+            info,
+            bars: self.decode_bars(addr, bar_count),
+            capabilities: self.capabilities(addr),
+        })
+    }
+
+    /// Size the BARs by writing all ones to them with decoding disabled.
+    fn decode_bars(&self, addr: PciAddress, count: u8) -> Vec<BarInfo> {
+        let config = &self.config;
+        let command = config.read_u16(addr, COMMAND);
+        config.write_u16(addr, COMMAND, command & !(COMMAND_IO | COMMAND_MEMORY));
+
+        let probe = |offset: u16| {
+            let orig = config.read_u32(addr, offset);
+            config.write_u32(addr, offset, u32::MAX);
+            let mask = config.read_u32(addr, offset);
+            config.write_u32(addr, offset, orig);
+            (orig, mask)
+        };
+
+        let mut ret = Vec::new();
+        let mut index = 0;
+        while index < count {
+            let offset = BAR0 + index as u16 * 4;
+            let (orig, mask) = probe(offset);
+            if mask == 0 {
+                // Unimplemented.
+                index += 1;
+                continue;
+            }
+
+            let (kind, base, mask, len) = if orig & BAR_IO != 0 {
+                let mask = (mask & !0x3) as u64 | !0xffff;
+                (BarKind::Io, (orig & !0x3) as u64, mask, 1)
+            } else if orig & BAR_TYPE_64 != 0 && index + 1 < count {
+                let (orig_high, mask_high) = probe(offset + 4);
+                let base = (orig_high as u64) << 32 | (orig & !0xf) as u64;
+                let mask = (mask_high as u64) << 32 | (mask & !0xf) as u64;
+                (BarKind::Memory64, base, mask, 2)
+            } else {
+                let mask = (mask & !0xf) as u64 | !(u32::MAX as u64);
+                (BarKind::Memory32, (orig & !0xf) as u64, mask, 1)
+            };
+
+            let size = (!mask).wrapping_add(1);
+            if size != 0 {
+                ret.push(BarInfo {
+                    index,
+                    kind,
+                    prefetchable: kind != BarKind::Io && orig & BAR_PREFETCHABLE != 0,
+                    base,
+                    size,
+                });
+            }
+            index += len;
+        }
+
+        config.write_u16(addr, COMMAND, command);
+        ret
+    }
+
+    fn capabilities(&self, addr: PciAddress) -> Vec<Capability> {
+        let config = &self.config;
+        let mut ret = Vec::new();
+
+        if config.read_u16(addr, STATUS) & STATUS_CAP_LIST != 0 {
+            let mut offset = (config.read_u8(addr, CAP_PTR) & !0x3) as u16;
+            while offset != 0 && ret.len() < MAX_CAPS {
+                ret.push(Capability {
+                    id: config.read_u8(addr, offset) as u16,
+                    offset,
+                    extended: false,
+                });
+                offset = (config.read_u8(addr, offset + 1) & !0x3) as u16;
+            }
+        }
+
+        if config.is_extended() {
+            let mut offset = EXT_CAP_START;
+            for _ in 0..MAX_CAPS {
+                let header = config.read_u32(addr, offset);
+                if header == 0 || header == u32::MAX {
+                    break;
+                }
+                ret.push(Capability {
+                    id: header as u16,
+                    offset,
+                    extended: true,
+                });
+                offset = (header >> 20) as u16 & !0x3;
+                if offset < EXT_CAP_START {
+                    break;
+                }
+            }
+        }
+
+        ret
+    }
+
+    pub fn bar(&self, function: &Function, index: u8) -> Result<Bar, Error> {
+        let bar = { function.bars.iter() }
+            .find(|bar| bar.index == index)
+            .ok_or(Error::InvalidArgument)?;
+        match bar.kind {
+            BarKind::Io => {
+                let base = u16::try_from(bar.base).map_err(|_| Error::InvalidArgument)?;
+                let size = u16::try_from(bar.size).map_err(|_| Error::InvalidArgument)?;
+                let end = base.checked_add(size).ok_or(Error::InvalidArgument)?;
+                let res = self
+                    .pio
+                    .allocate(base..end)
+                    .map_err(|_| Error::Unavailable)?;
+                Ok(Bar::Io { res, base, size })
+            }
+            BarKind::Memory32 | BarKind::Memory64 => {
+                // Physical objects must span whole pages, while BARs can be
+                // smaller than a page, such as the ABAR of AHCI.
+                let start = bar.base as usize & !PAGE_MASK;
+                let end = ((bar.base + bar.size) as usize).next_multiple_of(PAGE_SIZE);
+                // An unassigned BAR would make the kernel allocate new memory
+                // instead.
+                let addr = NonZeroUsize::new(start).ok_or(Error::Unavailable)?;
+                let phys = Phys::acquire(&self.mem, Some(addr), end - start)?;
+                Ok(Bar::Memory {
+                    phys,
+                    offset: bar.base as usize & PAGE_MASK,
+                })
+            }
+        }
+    }
+
+    /// Only MSI is supported, see `Device::allocate_msi`.
+    pub fn allocate_msi(
+        &self,
+        function: &Function,
+        count: u8,
+        cpu: u32,
+    ) -> Result<Vec<Interrupt>, Error> {
+        let cap = { function.capabilities.iter() }
+            .find(|cap| !cap.extended && cap.id == CAP_MSI)
+            .ok_or(Error::NotSupported)?;
+        let (config, addr) = (&self.config, function.info.address);
+
+        let control = config.read_u16(addr, cap.offset + 2);
+        let capable = 1 << ((control >> 1) & 0x7);
+        if !count.is_power_of_two() || count > capable {
+            return Err(Error::InvalidArgument);
+        }
+
+        let msi = Interrupt::allocate_msi(&self.gsi, cpu as usize, count as usize)?;
+        let (address, data) = (msi[0].address, msi[0].data);
+
+        config.write_u32(addr, cap.offset + 4, address);
+        let data_offset = if control & MSI_64BIT != 0 {
+            config.write_u32(addr, cap.offset + 8, 0);
+            cap.offset + 12
+        } else {
+            cap.offset + 8
+        };
+        config.write_u16(addr, data_offset, data as u16);
+
+        let mme = count.trailing_zeros() as u16;
+        let control = (control & !MSI_MME_MASK) | (mme << 4) | MSI_ENABLE;
+        config.write_u16(addr, cap.offset + 2, control);
+
+        let command = config.read_u16(addr, COMMAND);
+        config.write_u16(addr, COMMAND, command | COMMAND_INTX_DISABLE);
+
+        Ok(msi.into_iter().map(|msi| msi.intr).collect())
+    }
+}
@@ -1,13 +1,20 @@
 use std::{collections::VecDeque, io::Write, mem, vec::Vec};
 
+use ed25519_compact::{KeyPair, Seed};
 use plain::Plain;
+use sha2::{Digest, Sha256};
 
 use crate::{
-    BootfsHeader, EntryType, ENTRY_LAYOUT, HEADER_SIZE, MAX_NAME_LEN, PAGE_LAYOUT, VERSION,
+    parse::header_hash, BootfsHeader, CompressedHeader, EntryType, BLOCK_SIZE, ENTRY_LAYOUT,
+    HASH_LEN, HEADER_SIZE, MAX_NAME_LEN, PAGE_LAYOUT, PUBLIC_KEY_LEN, SEED_LEN, SIGNATURE_LEN,
+    VERSION,
 };
 
 pub enum Content {
     File(Vec<u8>),
+    /// The file is stored compressed if it takes fewer pages that way, or as
+    /// is otherwise.
+    CompressedFile(Vec<u8>),
     Directory(Vec<Entry>),
 }
 
@@ -27,25 +34,37 @@ fn split(input: &Entry, entries: &mut Vec<super::Entry>, contents: &mut Vec<Vec<
         name.resize(MAX_NAME_LEN, 0);
 
         match &entry.content {
-            Content::File(content) => {
+            Content::File(content) | Content::CompressedFile(content) => {
+                let compressed = matches!(entry.content, Content::CompressedFile(_))
+                    .then(|| compress(content))
+                    .filter(|compressed| {
+                        compressed.len().next_multiple_of(PAGE_LAYOUT.align())
+                            < content.len().next_multiple_of(PAGE_LAYOUT.align())
+                    });
+                let (ty, content) = match compressed {
+                    Some(compressed) => (EntryType::CompressedFile, compressed),
+                    None => (EntryType::File, content.clone()),
+                };
                 let entry = super::Entry {
                     version: VERSION,
                     name: name.try_into().expect("Name too long"),
-                    ty: crate::EntryType::File,
+                    ty: ty as u8,
                     offset: 0,
                     len: content.len(),
+                    hash: [0; HASH_LEN],
                 };
                 ent_index += 1;
                 entries.push(entry);
-                contents.push(content.clone());
+                contents.push(content);
             }
             Content::Directory(ent) => {
                 let entry = super::Entry {
                     version: VERSION,
                     name: name.try_into().expect("Name too long"),
-                    ty: crate::EntryType::Directory,
+                    ty: crate::EntryType::Directory as u8,
                     offset: HEADER_SIZE + (ent_index + q.len() + 1) * mem::size_of::<usize>(),
                     len: ent.len() * mem::size_of::<usize>(),
+                    hash: [0; HASH_LEN],
                 };
                 ent_index += 1;
                 entries.push(entry);
@@ -55,6 +74,11 @@ fn split(input: &Entry, entries: &mut Vec<super::Entry>, contents: &mut Vec<Vec<
     }
 }
 
+/// Get the public key for verifying the images signed with `seed`.
+pub fn public_key(seed: &[u8; SEED_LEN]) -> [u8; PUBLIC_KEY_LEN] {
+    *KeyPair::from_seed(Seed::new(*seed)).pk
+}
+
 fn write_typed<T: ?Sized + Plain>(
     data: &T,
     size: usize,
@@ -72,23 +96,44 @@ fn write_typed<T: ?Sized + Plain>(
     Ok(())
 }
 
-pub fn generate(input: &Entry, output: &mut impl Write) -> anyhow::Result<()> {
+fn compress(content: &[u8]) -> Vec<u8> {
+    let blocks = content
+        .chunks(BLOCK_SIZE)
+        .map(lz4_flex::block::compress)
+        .collect::<Vec<_>>();
+
+    let header = CompressedHeader {
+        raw_len: content.len(),
+        block_size: BLOCK_SIZE,
+        num_blocks: blocks.len(),
+    };
+    let mut output = Vec::new();
+    let _ = write_typed(&header, mem::size_of::<CompressedHeader>(), &mut output);
+
+    let mut offset = 0;
+    let _ = write_typed(&offset, mem::size_of::<usize>(), &mut output);
+    for block in &blocks {
+        offset += block.len();
+        let _ = write_typed(&offset, mem::size_of::<usize>(), &mut output);
+    }
+    for block in &blocks {
+        output.extend_from_slice(block);
+    }
+    output
+}
+
+pub fn generate(
+    input: &Entry,
+    key: Option<&[u8; SEED_LEN]>,
+    output: &mut impl Write,
+) -> anyhow::Result<()> {
     let mut entries = Vec::new();
     let mut contents = Vec::new();
     split(input, &mut entries, &mut contents);
 
-    let mut len = 0;
-    // Generate the header.
-    {
-        let bootfs_header = BootfsHeader {
-            version: VERSION,
-            num_entries: entries.len(),
-            root_dir_offset: HEADER_SIZE + mem::size_of::<usize>(),
-            root_dir_len: entries[0].len,
-        };
-        write_typed(&bootfs_header, HEADER_SIZE, output)?;
-        len += HEADER_SIZE;
-    }
+    // The entry table is generated before the header, which contains its hash.
+    let mut table = Vec::new();
+    let mut len = HEADER_SIZE;
 
     // Generate the entry offset array.
     let ent_size = {
@@ -98,14 +143,16 @@ pub fn generate(input: &Entry, output: &mut impl Write) -> anyhow::Result<()> {
 
         let ent_size = ENTRY_LAYOUT.pad_to_align().size();
         for i in 0..entries.len() {
-            write_typed(&(ent_start + i * ent_size), mem::size_of::<usize>(), output)?;
+            write_typed(
+                &(ent_start + i * ent_size),
+                mem::size_of::<usize>(),
+                &mut table,
+            )?;
             len += mem::size_of::<usize>();
         }
 
         let aligned_len = len.next_multiple_of(PAGE_LAYOUT.align());
-        for _ in len..aligned_len {
-            output.write_all(&[0])?;
-        }
+        table.resize(aligned_len - HEADER_SIZE, 0);
         len = aligned_len;
         ent_size
     };
@@ -113,23 +160,49 @@ pub fn generate(input: &Entry, output: &mut impl Write) -> anyhow::Result<()> {
     // Generate the entry metadata array.
     let file_offsets = {
         let mut file_offsets = Vec::new();
+        let mut files = contents.iter();
 
         let mut offset = len + (entries.len() * ent_size).next_multiple_of(PAGE_LAYOUT.align());
 
         for entry in entries.iter_mut() {
-            if entry.ty == EntryType::File {
+            let content = if entry.ty != EntryType::Directory as u8 {
                 entry.offset = offset;
                 file_offsets.push(offset);
                 offset += entry.len.next_multiple_of(PAGE_LAYOUT.align());
-            }
+                files.next().unwrap().as_slice()
+            } else {
+                &table[(entry.offset - HEADER_SIZE)..][..entry.len]
+            };
+            entry.hash = Sha256::digest(content).into();
+        }
 
-            write_typed(entry, ent_size, output)?;
+        for entry in entries.iter() {
+            write_typed(entry, ent_size, &mut table)?;
             len += ent_size;
         }
 
         file_offsets
     };
 
+    // Generate the header.
+    {
+        let mut bootfs_header = BootfsHeader {
+            version: VERSION,
+            num_entries: entries.len(),
+            root_dir_offset: HEADER_SIZE + mem::size_of::<usize>(),
+            root_dir_len: entries[0].len,
+            hash: [0; HASH_LEN],
+            signature: [0; SIGNATURE_LEN],
+        };
+        bootfs_header.hash = header_hash(&bootfs_header, &table);
+        if let Some(seed) = key {
+            let key_pair = KeyPair::from_seed(Seed::new(*seed));
+            bootfs_header.signature = *key_pair.sk.sign(bootfs_header.hash, None);
+        }
+        write_typed(&bootfs_header, HEADER_SIZE, output)?;
+        output.write_all(&table)?;
+    }
+
     // Copy file contents.
     if !contents.is_empty() {
         assert_eq!(file_offsets.len(), contents.len());
@@ -159,3 +232,209 @@ pub fn generate(input: &Entry, output: &mut impl Write) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::{mem, vec, vec::Vec};
+
+    use plain::Plain;
+
+    use super::{compress, generate, public_key, Content, Entry};
+    use crate::{
+        parse::{Compressed, Directory, Error},
+        BootfsHeader, BLOCK_SIZE, HEADER_SIZE, PAGE_LAYOUT, SEED_LEN,
+    };
+
+    const SEED: [u8; SEED_LEN] = [0x5a; SEED_LEN];
+
+    fn round_trip(content: &[u8]) {
+        let compressed = compress(content);
+        let file = Compressed::parse(&compressed).expect("Failed to parse the compressed file");
+        assert_eq!(file.len(), content.len());
+
+        let mut output = vec![0; content.len()];
+        assert_eq!(file.decompress_into(&mut output), Some(content.len()));
+        assert_eq!(output, content);
+
+        if !content.is_empty() {
+            let len = content.len() - 1;
+            assert_eq!(file.decompress_into(&mut output[..len]), None);
+        }
+    }
+
+    #[test]
+    fn test_compress_empty() {
+        round_trip(&[]);
+    }
+
+    #[test]
+    fn test_compress_blocks() {
+        let content = (0..BLOCK_SIZE * 3 + 123)
+            .map(|i| (i % 251) as u8 ^ (i / 4096) as u8)
+            .collect::<Vec<_>>();
+        // A partial block at the end.
+        round_trip(&content);
+        // Full blocks only.
+        round_trip(&content[..BLOCK_SIZE * 2]);
+        // Less than a block.
+        round_trip(&content[..123]);
+    }
+
+    #[derive(Clone)]
+    #[repr(C, align(4096))]
+    struct Page([u8; PAGE_LAYOUT.size()]);
+
+    /// The image copied to pages, since the parser requires the alignment of
+    /// the header and the entries.
+    #[derive(Clone)]
+    struct Image {
+        pages: Vec<Page>,
+        len: usize,
+    }
+
+    impl Image {
+        fn generate() -> Self {
+            let entry = |name: &[u8], content| Entry {
+                name: name.to_vec(),
+                content,
+            };
+            let root = entry(
+                b"bootfs",
+                Content::Directory(vec![
+                    entry(b"file", Content::File(b"Hello, bootfs!".to_vec())),
+                    entry(
+                        b"lib",
+                        Content::Directory(vec![entry(
+                            b"compressed",
+                            Content::CompressedFile(vec![0x42; 0x5000]),
+                        )]),
+                    ),
+                ]),
+            );
+            let mut image = Vec::new();
+            generate(&root, Some(&SEED), &mut image).expect("Failed to generate the image");
+
+            let mut pages =
+                vec![Page([0; PAGE_LAYOUT.size()]); image.len().div_ceil(PAGE_LAYOUT.size())];
+            for (page, chunk) in pages.iter_mut().zip(image.chunks(PAGE_LAYOUT.size())) {
+                page.0[..chunk.len()].copy_from_slice(chunk);
+            }
+            Image {
+                pages,
+                len: image.len(),
+            }
+        }
+
+        fn bytes(&self) -> &[u8] {
+            let ptr = self.pages.as_ptr().cast::<u8>();
+            // SAFETY: The pages are contiguous and at least `len` bytes long.
+            unsafe { core::slice::from_raw_parts(ptr, self.len) }
+        }
+
+        fn flip(&self, offset: usize) -> Self {
+            let mut ret = self.clone();
+            let page = &mut ret.pages[offset / PAGE_LAYOUT.size()];
+            page.0[offset % PAGE_LAYOUT.size()] ^= 1;
+            ret
+        }
+
+        fn write(&self, offset: usize, data: &[u8]) -> Self {
+            let mut ret = self.clone();
+            for (offset, &byte) in (offset..).zip(data) {
+                let page = &mut ret.pages[offset / PAGE_LAYOUT.size()];
+                page.0[offset % PAGE_LAYOUT.size()] = byte;
+            }
+            ret
+        }
+
+        fn check(&self) -> Result<(), Error> {
+            let root = Directory::root(self.bytes()).expect("Failed to parse the header");
+            if !root.verify(Some(&public_key(&SEED))) {
+                return Err(Error::Corrupted);
+            }
+            if root.iter().any(|entry| !entry.verify()) {
+                return Err(Error::Corrupted);
+            }
+            let file = root.find(b"file", b'/')?;
+            assert_eq!(file.data(), b"Hello, bootfs!");
+            let file = root.find(b"lib/compressed", b'/')?;
+            let mut output = vec![0; file.len()];
+            file.read_into(&mut output).ok_or(Error::Corrupted)?;
+            assert_eq!(output, [0x42; 0x5000]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let image = Image::generate();
+        assert_eq!(image.check(), Ok(()));
+
+        let root = Directory::root(image.bytes()).unwrap();
+        assert!(root.verify(None));
+        assert!(!root.verify(Some(&public_key(&[0xa5; SEED_LEN]))));
+    }
+
+    #[test]
+    fn test_tampered_file() {
+        let image = Image::generate();
+        let root = Directory::root(image.bytes()).unwrap();
+        let entry = root.get(b"file").unwrap();
+        let image = image.flip(entry.metadata().offset);
+
+        let root = Directory::root(image.bytes()).unwrap();
+        assert!(!root.get(b"file").unwrap().verify());
+        assert_eq!(root.find(b"file", b'/').err(), Some(Error::Corrupted));
+        assert_eq!(image.check(), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn test_tampered_entry_table() {
+        let image = Image::generate();
+        // The name of the first entry in the table.
+        let ent_start = &image.bytes()[HEADER_SIZE..][..mem::size_of::<usize>()];
+        let ent_start = usize::from_ne_bytes(ent_start.try_into().unwrap());
+        let image = image.flip(ent_start + mem::size_of::<u32>());
+
+        let root = Directory::root(image.bytes()).unwrap();
+        assert!(!root.verify(None));
+        assert_eq!(image.check(), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn test_tampered_signature() {
+        let image = Image::generate();
+        let image = image.flip(mem::offset_of!(BootfsHeader, signature));
+
+        let root = Directory::root(image.bytes()).unwrap();
+        assert!(root.verify(None));
+        assert_eq!(image.check(), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn test_malformed_image() {
+        let image = Image::generate();
+        assert!(Directory::root(&image.bytes()[..2]).is_none());
+        assert!(Directory::root(&image.bytes()[..HEADER_SIZE / 2]).is_none());
+
+        let header = &image.bytes()[..mem::size_of::<BootfsHeader>()];
+        let root_dir_offset = BootfsHeader::from_bytes(header).unwrap().root_dir_offset;
+
+        // The root directory out of the image.
+        let offset = mem::offset_of!(BootfsHeader, root_dir_offset);
+        let bad = image.write(offset, &usize::MAX.to_ne_bytes());
+        assert!(Directory::root(bad.bytes()).is_none());
+
+        // An entry out of the image.
+        let bad = image.write(root_dir_offset, &usize::MAX.to_ne_bytes());
+        let root = Directory::root(bad.bytes()).unwrap();
+        assert_eq!(root.iter().count(), 0);
+
+        // An entry of an unknown type.
+        let ent = &image.bytes()[root_dir_offset..][..mem::size_of::<usize>()];
+        let ent = usize::from_ne_bytes(ent.try_into().unwrap());
+        let bad = image.write(ent + mem::offset_of!(crate::Entry, ty), &[0xff]);
+        let root = Directory::root(bad.bytes()).unwrap();
+        assert!(root.iter().next().unwrap().content().is_none());
+    }
+}
@@ -1,5 +1,6 @@
 //! The bootfs.
 //!
+//! ```text
 //!     |   Header   |
 //!     |------------|
 //!     |   Entries  |
@@ -8,6 +9,11 @@
 //!     |  Dir/File  |
 //!     |  Content   |
 //!     |  ...       |
+//! ```
+//!
+//! Every file content is page-aligned so that it can be used directly from the
+//! image, unless it is compressed, in which case it must be decompressed
+//! before used.
 
 #![no_std]
 #![feature(int_roundings)]
@@ -1,9 +1,34 @@
 use core::mem;
 
+use ed25519_compact::{PublicKey, Signature};
 use either::Either;
 use plain::Plain;
+use sha2::{Digest, Sha256};
 
-use crate::{MAX_NAME_LEN, VERSION};
+use crate::{
+    BootfsHeader, CompressedHeader, ENTRY_LAYOUT, HASH_LEN, HEADER_SIZE, MAX_NAME_LEN,
+    PUBLIC_KEY_LEN, VERSION,
+};
+
+/// Hash the fields of the header except the hash and the signature
+/// themselves, and the entry table that follows it.
+pub fn header_hash(header: &BootfsHeader, table: &[u8]) -> [u8; HASH_LEN] {
+    Sha256::new()
+        .chain_update(header.num_entries.to_ne_bytes())
+        .chain_update(header.root_dir_offset.to_ne_bytes())
+        .chain_update(header.root_dir_len.to_ne_bytes())
+        .chain_update(table)
+        .finalize()
+        .into()
+}
+
+/// The reason why a file can't be found in the bootfs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    NotFound,
+    /// The entry is found, but its content is corrupted.
+    Corrupted,
+}
 
 #[derive(Debug, Copy, Clone)]
 pub struct Directory<'a> {
@@ -13,12 +38,14 @@ pub struct Directory<'a> {
 
 impl<'a> Directory<'a> {
     pub fn root(image: &'a [u8]) -> Option<Self> {
-        let header = if image[..4] == VERSION.to_ne_bytes() {
-            crate::BootfsHeader::from_bytes(&image[..mem::size_of::<crate::BootfsHeader>()]).ok()?
-        } else {
+        if image.get(..4)? != VERSION.to_ne_bytes() {
             return None;
-        };
-        let root_dir = &image[header.root_dir_offset..][..header.root_dir_len];
+        }
+        let header = image.get(..mem::size_of::<crate::BootfsHeader>())?;
+        let header = crate::BootfsHeader::from_bytes(header).ok()?;
+        let root_dir = image
+            .get(header.root_dir_offset..)?
+            .get(..header.root_dir_len)?;
         Some(Directory {
             image,
             dir: root_dir,
@@ -36,17 +63,59 @@ impl<'a> Directory<'a> {
         self.image
     }
 
+    /// The header and the entry table following it.
+    fn header_table(&self) -> Option<(&'a BootfsHeader, &'a [u8])> {
+        let header = self.image.get(..mem::size_of::<BootfsHeader>())?;
+        let header = BootfsHeader::from_bytes(header).ok()?;
+
+        let table_end = if header.num_entries > 0 {
+            let ent_start = self
+                .image
+                .get(HEADER_SIZE..HEADER_SIZE + mem::size_of::<usize>())?;
+            let ent_start = usize::from_ne_bytes(ent_start.try_into().unwrap());
+            let ent_size = ENTRY_LAYOUT.pad_to_align().size();
+            ent_start.checked_add(header.num_entries.checked_mul(ent_size)?)?
+        } else {
+            HEADER_SIZE
+        };
+        Some((header, self.image.get(HEADER_SIZE..table_end)?))
+    }
+
+    /// Check the hash of the entry table in the header, and its signature if
+    /// `key` is given.
+    ///
+    /// The contents of the entries are checked separately by
+    /// [`Entry::verify`].
+    pub fn verify(&self, key: Option<&[u8; PUBLIC_KEY_LEN]>) -> bool {
+        let (header, table) = match self.header_table() {
+            Some(ret) => ret,
+            None => return false,
+        };
+        if header_hash(header, table) != header.hash {
+            return false;
+        }
+
+        key.is_none_or(|key| {
+            let signature = Signature::new(header.signature);
+            PublicKey::new(*key).verify(header.hash, &signature).is_ok()
+        })
+    }
+
     pub fn get(self, name: &[u8]) -> Option<Entry<'a>> {
         self.iter().find(|ent| ent.name_eq(name))
     }
 
-    pub fn find(self, path: &[u8], separator: u8) -> Option<&'a [u8]> {
+    pub fn find(self, path: &[u8], separator: u8) -> Result<File<'a>, Error> {
         let mut dir = self;
         let mut names = path.split(|&b| b == separator);
         loop {
-            let entry: Entry<'a> = dir.get(names.next()?)?;
-            dir = match entry.content() {
-                Either::Left(content) => break Some(content),
+            let name = names.next().ok_or(Error::NotFound)?;
+            let entry: Entry<'a> = dir.get(name).ok_or(Error::NotFound)?;
+            if !entry.verify() {
+                return Err(Error::Corrupted);
+            }
+            dir = match entry.content().ok_or(Error::Corrupted)? {
+                Either::Left(content) => break Ok(content),
                 Either::Right(dir) => dir,
             };
         }
@@ -70,7 +139,10 @@ impl<'a> Iterator for DirIter<'a> {
         (offset, self.rem) = self.rem.split_at(mem::size_of::<usize>());
         let offset = usize::from_ne_bytes(offset.try_into().unwrap());
 
-        let entry = &self.image[offset..][..mem::size_of::<super::Entry>()];
+        let entry = self
+            .image
+            .get(offset..)?
+            .get(..mem::size_of::<super::Entry>())?;
         if entry[..4] != VERSION.to_ne_bytes() {
             return None;
         }
@@ -99,17 +171,146 @@ impl<'a> Entry<'a> {
         &self.metadata
     }
 
-    pub fn content(self) -> Either<&'a [u8], Directory<'a>> {
-        let content = &self.image[self.metadata.offset..][..self.metadata.len];
-        match self.metadata.ty {
-            crate::EntryType::File => Either::Left(content),
+    /// Check the content of the entry against its hash.
+    pub fn verify(&self) -> bool {
+        let content = self
+            .image
+            .get(self.metadata.offset..)
+            .and_then(|content| content.get(..self.metadata.len));
+        content.is_some_and(|content| {
+            <[u8; HASH_LEN]>::from(Sha256::digest(content)) == self.metadata.hash
+        })
+    }
+
+    /// The content of the entry, either a file or a directory.
+    ///
+    /// Returns `None` if the content is out of the image or malformed.
+    pub fn content(self) -> Option<Either<File<'a>, Directory<'a>>> {
+        let content = self
+            .image
+            .get(self.metadata.offset..)?
+            .get(..self.metadata.len)?;
+        Some(match crate::EntryType::try_from(self.metadata.ty).ok()? {
+            crate::EntryType::File => Either::Left(File::Raw(content)),
+            crate::EntryType::CompressedFile => {
+                Either::Left(File::Compressed(Compressed::parse(content)?))
+            }
             crate::EntryType::Directory => {
-                assert!(content.len() & 7 == 0);
+                if content.len() % mem::size_of::<usize>() != 0 {
+                    return None;
+                }
                 Either::Right(Directory {
                     image: self.image,
                     dir: content,
                 })
             }
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum File<'a> {
+    /// The content stored as is, which can be used directly from the image.
+    Raw(&'a [u8]),
+    Compressed(Compressed<'a>),
+}
+
+impl<'a> File<'a> {
+    /// The bytes of the file stored in the image.
+    pub fn data(&self) -> &'a [u8] {
+        match self {
+            File::Raw(data) => data,
+            File::Compressed(compressed) => compressed.data,
+        }
+    }
+
+    /// The length of the file after decompressed.
+    pub fn len(&self) -> usize {
+        match self {
+            File::Raw(data) => data.len(),
+            File::Compressed(compressed) => compressed.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copy the content of the file to `output`, which must be at least
+    /// [`File::len`] long, decompressing it if necessary.
+    ///
+    /// Returns `None` if the content is corrupted.
+    pub fn read_into(&self, output: &mut [u8]) -> Option<usize> {
+        match self {
+            File::Raw(data) => {
+                output.get_mut(..data.len())?.copy_from_slice(data);
+                Some(data.len())
+            }
+            File::Compressed(compressed) => compressed.decompress_into(output),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Compressed<'a> {
+    data: &'a [u8],
+    header: CompressedHeader,
+    offsets: &'a [u8],
+    blocks: &'a [u8],
+}
+
+impl<'a> Compressed<'a> {
+    /// Parse the content of a compressed file.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let header_size = mem::size_of::<CompressedHeader>();
+        let header = *CompressedHeader::from_bytes(data.get(..header_size)?).ok()?;
+        let offsets_len = header
+            .num_blocks
+            .checked_add(1)?
+            .checked_mul(mem::size_of::<usize>())?;
+        let rest = data.get(header_size..)?;
+        if rest.len() < offsets_len {
+            return None;
+        }
+        let (offsets, blocks) = rest.split_at(offsets_len);
+        Some(Compressed {
+            data,
+            header,
+            offsets,
+            blocks,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.header.raw_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn offset(&self, index: usize) -> usize {
+        let offset = &self.offsets[index * mem::size_of::<usize>()..][..mem::size_of::<usize>()];
+        usize::from_ne_bytes(offset.try_into().unwrap())
+    }
+
+    /// Decompress the file into `output`, which must be at least
+    /// [`Compressed::len`] long.
+    ///
+    /// Returns `None` if the content is corrupted.
+    pub fn decompress_into(&self, output: &mut [u8]) -> Option<usize> {
+        let output = output.get_mut(..self.header.raw_len)?;
+        let mut chunks = output.chunks_mut(self.header.block_size.max(1));
+        for index in 0..self.header.num_blocks {
+            let block = self
+                .blocks
+                .get(self.offset(index)..self.offset(index + 1))?;
+            let chunk = chunks.next()?;
+            let len = lz4_flex::block::decompress_into(block, chunk).ok()?;
+            if len != chunk.len() {
+                return None;
+            }
         }
+        chunks.next().is_none().then_some(self.header.raw_len)
     }
 }
@@ -12,6 +12,22 @@ pub enum EntryType {
     /// The content of the directory only contains an array of offsets of other
     /// entries, pointing to the global entry table.
     Directory,
+    /// The content starts with a [`CompressedHeader`], followed by the offsets
+    /// of the LZ4 blocks and the blocks themselves.
+    CompressedFile,
+}
+
+impl TryFrom<u8> for EntryType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => EntryType::File,
+            1 => EntryType::Directory,
+            2 => EntryType::CompressedFile,
+            _ => return Err(value),
+        })
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -19,9 +35,13 @@ pub enum EntryType {
 pub struct Entry {
     pub version: u32,
     pub name: [u8; 64],
-    pub ty: EntryType,
+    /// The [`EntryType`] of the entry, stored as a raw byte so that any value
+    /// read from an image is valid.
+    pub ty: u8,
     pub offset: usize,
     pub len: usize,
+    /// The SHA-256 hash of the content as stored in the image.
+    pub hash: [u8; HASH_LEN],
 }
 const_assert!(mem::size_of::<Entry>() <= 128);
 
@@ -39,11 +59,42 @@ pub struct BootfsHeader {
     pub num_entries: usize,
     pub root_dir_offset: usize,
     pub root_dir_len: usize,
+    /// The SHA-256 hash of the fields above and the entry table, which
+    /// includes the hashes of all the entries.
+    pub hash: [u8; HASH_LEN],
+    /// The Ed25519 signature of `hash`, or zeros if the image is not signed.
+    pub signature: [u8; SIGNATURE_LEN],
 }
+const_assert!(mem::size_of::<BootfsHeader>() <= 128);
 
 unsafe impl Plain for BootfsHeader {}
 
-pub const VERSION: u32 = u32::from_ne_bytes([0xbb, 0xff, 0xee, 0xaa]);
+pub const HASH_LEN: usize = 32;
+pub const SIGNATURE_LEN: usize = 64;
+pub const PUBLIC_KEY_LEN: usize = 32;
+pub const SEED_LEN: usize = 32;
+
+/// The header of the content of a compressed file.
+///
+/// It is followed by `num_blocks + 1` offsets of type `usize`, relative to the
+/// end of the offset array, of which the block `i` spans from the `i`th to the
+/// `(i + 1)`th. Every block except the last one decompresses to exactly
+/// `block_size` bytes.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct CompressedHeader {
+    pub raw_len: usize,
+    pub block_size: usize,
+    pub num_blocks: usize,
+}
+
+unsafe impl Plain for CompressedHeader {}
+
+pub const BLOCK_SIZE: usize = 0x10000;
+
+/// The magic number of the header and the entries, changed along with their
+/// layout so that images of other layouts are rejected.
+pub const VERSION: u32 = u32::from_ne_bytes([0xbb, 0xff, 0xee, 0xab]);
 
 pub const HEADER_SIZE: usize =
     mem::size_of::<BootfsHeader>().next_multiple_of(mem::size_of::<usize>());
@@ -8,13 +8,15 @@ use core::{
 };
 
 use solvent::prelude::{
-    Handle, PackRecv, Packet, Result, SerdeReg, Syscall, EBUFFER, ENOENT, EPIPE, SIG_READ,
+    Handle, PackRecv, Packet, Phys, Result, SerdeReg, Syscall, EBUFFER, ENOENT, ENOSPC, EPIPE,
+    SIG_PEER_CLOSED, SIG_READ, SIG_WRITE,
 };
 use solvent_core::{
     sync::channel::{oneshot, TryRecvError},
     thread::Backoff,
 };
 
+use super::AsyncObject;
 use crate::disp::{DispError, DispSender, PackedSyscall};
 
 type Inner = solvent::ipc::Channel;
@@ -68,15 +70,33 @@ impl Channel {
         self.disp = disp
     }
 
+    /// Send a packet without waiting, failing with `ENOSPC` if the channel is
+    /// full.
     #[inline]
     pub fn send_raw(&self, id: Option<NonZeroUsize>, buffer: &[u8], handles: &[Handle]) -> Result {
         self.inner.send_raw(id, buffer, handles)
     }
 
+    /// Send a packet without waiting, failing with `ENOSPC` if the channel is
+    /// full.
     #[inline]
-    pub fn send(&self, packet: &mut Packet) -> Result {
-        self.send_raw(packet.id, &packet.buffer, &packet.handles)
-            .map(|_| *packet = Default::default())
+    pub fn try_send(&self, packet: &mut Packet) -> Result {
+        self.inner.send(packet)
+    }
+
+    /// Send a packet, waiting for the peer to make room for it if the channel
+    /// is full.
+    pub async fn send(&self, packet: &mut Packet) -> Result {
+        loop {
+            match self.try_send(packet) {
+                Err(ENOSPC) => {
+                    self.inner
+                        .try_wait_with(&self.disp, true, SIG_WRITE)
+                        .await?;
+                }
+                res => break res,
+            }
+        }
     }
 
     #[inline]
@@ -95,10 +115,19 @@ impl Channel {
         *packet = temp;
         Ok(())
     }
+
+    /// Wait until the peer is closed. Pending packets can still be received
+    /// afterwards until `EPIPE` is returned.
+    pub async fn closed(&self) -> Result {
+        self.inner
+            .try_wait_with(&self.disp, true, SIG_PEER_CLOSED)
+            .await?;
+        Ok(())
+    }
 }
 
 pub(crate) struct SendData {
-    pub id: Result<usize>,
+    pub id: Result<(usize, Option<(Phys, usize)>)>,
     pub buffer_size: usize,
     pub handle_count: usize,
     pub packet: Packet,
@@ -138,10 +167,18 @@ impl<'a> Receive<'a> {
             ($send_data:ident) => {
                 match $send_data.id {
                     // Packet transferring successful, return it
-                    Ok(id) => {
+                    Ok((id, phys)) => {
                         let mut packet = $send_data.packet;
                         packet.id = NonZeroUsize::new(id);
-                        return ControlFlow::Break(Poll::Ready(Ok(packet)));
+                        // Append the out-of-line data to the inline one.
+                        let res = match phys {
+                            Some((phys, len)) => phys.read(0, len).map(|data| {
+                                packet.buffer.extend(data);
+                                packet
+                            }),
+                            None => Ok(packet),
+                        };
+                        return ControlFlow::Break(Poll::Ready(res));
                     }
 
                     // Packet buffer too small, reserve enough memory and restart polling
@@ -63,7 +63,7 @@ pub mod test {
                     .buffer
                     .extend(core::iter::repeat_with(|| random() as u8).take(199));
                 // log::debug!("Send #{index}");
-                i1.send(&mut packet).expect("Failed to send packet");
+                i1.send(&mut packet).await.expect("Failed to send packet");
                 if index % 10 == 5 {
                     yield_now().await
                 }
@@ -0,0 +1,87 @@
+//! DMA buffers mapped into the driver host.
+
+use core::{ptr::NonNull, slice};
+
+use solvent::{
+    dev::MemRes,
+    error::Result,
+    mem::{DmaBuf, Flags, PhysOptions, PhysRange},
+};
+
+use crate::ffi::root_virt;
+
+/// A DMA buffer mapped into the address space of the driver host, which is
+/// unmapped and unpinned when dropped.
+#[derive(Debug)]
+pub struct Buffer {
+    buf: DmaBuf,
+    ptr: NonNull<[u8]>,
+}
+
+// SAFETY: The mapping is owned by the buffer exclusively.
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    /// Allocate a DMA buffer and map it. See [`DmaBuf::allocate`] for the
+    /// options.
+    pub fn allocate(res: &MemRes, size: usize, options: PhysOptions) -> Result<Self> {
+        let buf = DmaBuf::allocate(res, size, options)?;
+        Self::map(buf, false)
+    }
+
+    /// Map a DMA buffer readable and writable. If `uncached` is set, the
+    /// mapping bypasses the CPU caches so that no explicit flushing is needed
+    /// for devices that don't snoop them.
+    pub fn map(buf: DmaBuf, uncached: bool) -> Result<Self> {
+        let mut flags = Flags::READABLE | Flags::WRITABLE | Flags::USER_ACCESS;
+        if uncached {
+            flags |= Flags::UNCACHED;
+        }
+        let ptr = root_virt().map_phys(None, buf.phys().clone(), flags)?;
+        Ok(Buffer { buf, ptr })
+    }
+
+    #[inline]
+    pub fn dma(&self) -> &DmaBuf {
+        &self.buf
+    }
+
+    /// The physical address ranges of the buffer. See [`DmaBuf::ranges`].
+    #[inline]
+    pub fn ranges(&self) -> &[PhysRange] {
+        self.buf.ranges()
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> NonNull<[u8]> {
+        self.ptr
+    }
+
+    /// # Safety
+    ///
+    /// The caller must ensure that no device is writing to the buffer while
+    /// the returned slice is alive.
+    #[inline]
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        slice::from_raw_parts(self.ptr.as_ptr().cast(), self.ptr.len())
+    }
+
+    /// # Safety
+    ///
+    /// The caller must ensure that no device is accessing the buffer while
+    /// the returned slice is alive.
+    #[inline]
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        slice::from_raw_parts_mut(self.ptr.as_ptr().cast(), self.ptr.len())
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        let ret = root_virt().unmap(self.ptr.as_non_null_ptr(), self.ptr.len(), true);
+        if let Err(err) = ret {
+            log::warn!("Failed to unmap the DMA buffer: {err:?}");
+        }
+    }
+}
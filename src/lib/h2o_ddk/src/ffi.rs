@@ -7,6 +7,9 @@ pub struct VTable {
     /// one thread can execute thread-local tasks.
     pub local_exe: *const solvent_async::exe::LocalExecutor,
     pub local_fs: *const solvent_fs::fs::LocalFs,
+    pub disp: *const solvent_async::disp::DispSender,
+    /// The root virt of the host process, whose ownership is not transferred.
+    pub root_virt: solvent::obj::Handle,
 
     pub alloc: unsafe extern "C" fn(usize, usize) -> *mut (),
     pub dealloc: unsafe extern "C" fn(*mut (), usize, usize),
@@ -14,31 +17,98 @@ pub struct VTable {
 
 #[cfg(feature = "ddk")]
 mod ddk {
-    use core::sync::atomic;
-
-    use solvent_async::exe::{Executor, LocalExecutor};
+    use core::{
+        future::Future,
+        pin::Pin,
+        sync::atomic::{self, AtomicUsize, Ordering::SeqCst},
+        task::{Context, Poll},
+    };
+
+    use async_task::Task;
+    use solvent::{
+        mem::Virt,
+        obj::{Object, Ref},
+    };
+    use solvent_async::{
+        disp::DispSender,
+        exe::{Executor, LocalExecutor},
+    };
     use solvent_fs::fs::LocalFs;
 
     use super::*;
 
     static mut VTABLE: Option<VTable> = None;
 
+    /// The number of the alive tasks spawned by the driver, including the main
+    /// one.
+    static TASKS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A future counted in [`TASKS`] until it is dropped.
+    struct Tracked<F>(F);
+
+    impl<F> Tracked<F> {
+        fn new(fut: F) -> Self {
+            TASKS.fetch_add(1, SeqCst);
+            Tracked(fut)
+        }
+    }
+
+    impl<F: Future> Future for Tracked<F> {
+        type Output = F::Output;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            // SAFETY: The inner future is never moved out.
+            unsafe { self.map_unchecked_mut(|this| &mut this.0) }.poll(cx)
+        }
+    }
+
+    impl<F> Drop for Tracked<F> {
+        fn drop(&mut self) {
+            TASKS.fetch_sub(1, SeqCst);
+        }
+    }
+
     pub(crate) fn vtable() -> &'static VTable {
         unsafe { VTABLE.as_ref().expect("DDK vtable uninitialized") }
     }
 
-    pub fn global_executor() -> &'static Executor {
+    fn global_executor() -> &'static Executor {
         unsafe { &*vtable().global_exe }
     }
 
-    pub fn local_executor<T, F: FnOnce(&LocalExecutor) -> T>(f: F) -> T {
+    fn local_executor<T, F: FnOnce(&LocalExecutor) -> T>(f: F) -> T {
         f(unsafe { &*vtable().local_exe })
     }
 
+    /// Spawn a task of the driver on the global executor of the host.
+    ///
+    /// The host refuses to unload the driver while any of its tasks is alive,
+    /// so every task must be spawned with this function or [`spawn_local`].
+    pub fn spawn<T: Send + 'static>(fut: impl Future<Output = T> + Send + 'static) -> Task<T> {
+        global_executor().spawn(Tracked::new(fut))
+    }
+
+    /// Spawn a task of the driver on the local executor of the host.
+    ///
+    /// See [`spawn`] for more information.
+    pub fn spawn_local<T: 'static>(fut: impl Future<Output = T> + 'static) -> Task<T> {
+        local_executor(|exe| exe.spawn(Tracked::new(fut)))
+    }
+
     pub fn local_fs() -> &'static LocalFs {
         unsafe { &*vtable().local_fs }
     }
 
+    /// Get the dispatcher of the host for asynchronous I/O.
+    pub fn dispatch() -> DispSender {
+        unsafe { (*vtable().disp).clone() }
+    }
+
+    pub fn root_virt() -> Ref<'static, Virt> {
+        // SAFETY: The host keeps its root virt alive as long as the driver.
+        unsafe { Ref::from_raw(vtable().root_virt) }
+    }
+
     /// # Safety
     ///
     /// This function must be called from `__h2o_ddk_enter` only once before
@@ -58,6 +128,12 @@ mod ddk {
         VTABLE = None;
     }
 
+    /// Get the number of the alive tasks of the driver.
+    #[no_mangle]
+    extern "C" fn __h2o_ddk_tasks() -> usize {
+        TASKS.load(SeqCst)
+    }
+
     /// Set the entry of the driver.
     ///
     /// The init function should be signatured `async fn(Channel)`.
@@ -77,7 +153,7 @@ mod ddk {
                 struct AssertInit<F: core::future::Future<Output = ()> + 'static>(F);
                 let assert_init = AssertInit(($init)(instance));
 
-                let task = $crate::ffi::local_executor(|exe| exe.spawn(assert_init.0));
+                let task = $crate::ffi::spawn_local(assert_init.0);
                 let task = alloc::boxed::Box::new(task);
                 alloc::boxed::Box::into_raw(task).cast()
             }
@@ -88,9 +164,7 @@ mod ddk {
     fn rust_begin_unwind(info: &core::panic::PanicInfo) -> ! {
         log::error!("{}", info);
 
-        loop {
-            unsafe { core::arch::asm!("pause; ud2") }
-        }
+        solvent::task::abort()
     }
 }
 
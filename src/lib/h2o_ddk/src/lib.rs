@@ -2,9 +2,13 @@
 #![no_std]
 #![feature(allocator_api)]
 #![feature(nonnull_slice_from_raw_parts)]
+#![feature(slice_ptr_get)]
+#![feature(slice_ptr_len)]
 
 #[cfg(feature = "ddk")]
 mod alloc2;
+#[cfg(feature = "ddk")]
+pub mod dma;
 pub mod ffi;
 
 #[cfg(feature = "ddk")]
@@ -89,14 +89,15 @@ mod std_local {
     }
 
     pub fn write<P: AsRef<Path>, B: AsRef<[u8]>>(path: P, buf: B) -> Result<(), Error> {
-        const CAP: usize = 64;
+        // Large packets are carried out of line, so the chunks can be big.
+        const CAP: usize = 1 << 20;
         let file = open(
             path,
             OpenOptions::READ | OpenOptions::WRITE | OpenOptions::CREATE | OpenOptions::TRUNCATE,
         )?;
         for buf in buf.as_ref().chunks(CAP) {
             let mut written = 0;
-            loop {
+            while written < buf.len() {
                 let buf = Vec::from(&buf[written..]);
                 written += file.write(buf)??;
             }
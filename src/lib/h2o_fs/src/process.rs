@@ -2,14 +2,14 @@ mod builder;
 
 use core::{mem, ops::Deref, ptr::NonNull};
 
-use solvent::task::{SuspendToken, Task};
+use solvent::task::{ExitInfo, SuspendToken, Task};
 use solvent_rpc::SerdePacket;
 
 pub use self::builder::{Builder, Error as BuildError};
 
 #[derive(Debug)]
 pub enum Error {
-    Exited(usize),
+    Exited(ExitInfo),
     Started,
     Start(solvent::error::Error),
     Join(solvent::error::Error),
@@ -59,7 +59,7 @@ impl InitProcess {
 
 enum ProcessState {
     Started(Task),
-    Exited(usize),
+    Exited(ExitInfo),
 }
 
 impl ProcessState {
@@ -70,16 +70,17 @@ impl ProcessState {
         }
     }
 
-    fn join(&mut self) -> Result<usize, Error> {
-        let status = match mem::replace(self, ProcessState::Exited(0)) {
+    fn join(&mut self) -> Result<ExitInfo, Error> {
+        let status = match mem::replace(self, ProcessState::Exited(Default::default())) {
             ProcessState::Started(task) => task.join().map_err(Error::Join)?,
             ProcessState::Exited(status) => status,
         };
+        *self = ProcessState::Exited(status);
         Ok(status)
     }
 
-    fn try_join(&mut self) -> Result<usize, Error> {
-        let status = match mem::replace(self, ProcessState::Exited(0)) {
+    fn try_join(&mut self) -> Result<ExitInfo, Error> {
+        let status = match mem::replace(self, ProcessState::Exited(Default::default())) {
             ProcessState::Started(task) => match task.try_join() {
                 Ok(status) => status,
                 Err((err, task)) => {
@@ -122,12 +123,12 @@ impl Process {
     }
 
     #[inline]
-    pub fn join(&mut self) -> Result<usize, Error> {
+    pub fn join(&mut self) -> Result<ExitInfo, Error> {
         self.0.join()
     }
 
     #[inline]
-    pub fn try_join(&mut self) -> Result<usize, Error> {
+    pub fn try_join(&mut self) -> Result<ExitInfo, Error> {
         self.0.try_join()
     }
 }
@@ -145,18 +146,18 @@ mod runtime {
     impl Process {
         #[inline]
         #[cfg(feature = "runtime")]
-        pub async fn ajoin(&mut self) -> Result<usize, Error> {
+        pub async fn ajoin(&mut self) -> Result<ExitInfo, Error> {
             self.ajoin_with(&solvent_async::dispatch()).await
         }
 
-        pub async fn ajoin_with(&mut self, disp: &DispSender) -> Result<usize, Error> {
+        pub async fn ajoin_with(&mut self, disp: &DispSender) -> Result<ExitInfo, Error> {
             // log::debug!("Polling");
             let status = match &self.0 {
                 ProcessState::Started(task) => {
                     task.try_wait_with(disp, true, SIG_READ)
                         .await
                         .map_err(Error::Wait)?;
-                    match mem::replace(&mut self.0, ProcessState::Exited(0)) {
+                    match mem::replace(&mut self.0, ProcessState::Exited(Default::default())) {
                         ProcessState::Started(task) => task.join().map_err(Error::Join)?,
                         ProcessState::Exited(_) => {
                             unreachable!("Inner handle secretly stealed")
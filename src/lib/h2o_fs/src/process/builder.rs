@@ -9,7 +9,7 @@ use core::{mem, num::NonZeroUsize, ptr::NonNull};
 
 use solvent::{
     prelude::{drop_raw, Channel, Feature, Flags, Handle, Object, Phys, Space, Virt, PAGE_SIZE},
-    task::{Task, DEFAULT_STACK_SIZE},
+    task::{CpuSet, Job, Task, DEFAULT_STACK_SIZE},
 };
 use solvent_async::disp::DispSender;
 use solvent_core::{path::PathBuf, sync::Lazy};
@@ -34,10 +34,12 @@ pub enum Error {
     InvalidCStr(FromVecWithNulError),
     DepNotFound(CString),
     Rpc(solvent_rpc::Error),
+    SpaceNew(solvent::error::Error),
     VdsoMap(solvent::error::Error),
     StackAlloc(solvent::error::Error),
     SendStartupArgs(solvent::error::Error),
     TaskExec(solvent::error::Error),
+    SetAffinity(solvent::error::Error),
 }
 
 impl From<elfload::Error> for Error {
@@ -56,6 +58,8 @@ pub struct Builder {
     vdso: Option<Phys>,
     args: Vec<String>,
     environ: BTreeMap<String, String>,
+    affinity: Option<CpuSet>,
+    job: Option<Job>,
 }
 
 impl Builder {
@@ -95,7 +99,13 @@ impl Builder {
                 return Err(executable);
             }
             let executable = executable
-                .reduce_features(Feature::SEND | Feature::READ | Feature::EXECUTE)
+                .reduce_rights(
+                    Feature::SEND
+                        | Feature::READ
+                        | Feature::EXECUTE
+                        | Feature::MAP
+                        | Feature::DERIVE,
+                )
                 .expect("Failed to adjust features for executable");
             this.executable = Some((executable, name.clone()));
             this.args.insert(0, name);
@@ -141,6 +151,21 @@ impl Builder {
         self
     }
 
+    /// Restrict the main task of the process to the CPUs in `affinity`.
+    #[inline]
+    pub fn affinity(&mut self, affinity: CpuSet) -> &mut Self {
+        self.affinity = Some(affinity);
+        self
+    }
+
+    /// Create the process in `job` instead of the current one, so that it's
+    /// constrained by the limits of the job.
+    #[inline]
+    pub fn job(&mut self, job: Job) -> &mut Self {
+        self.job = Some(job);
+        self
+    }
+
     #[inline]
     pub fn args<S, I>(&mut self, args: I) -> &mut Self
     where
@@ -213,6 +238,8 @@ impl Builder {
             vdso,
             args,
             environ,
+            affinity,
+            job,
         } = mem::take(self);
         let (executable, name) = executable.ok_or_else(|| Error::FieldMissing("executable"))?;
         let loader = loader.ok_or_else(|| Error::FieldMissing("loader"))?;
@@ -232,7 +259,7 @@ impl Builder {
             .unwrap();
 
         build_end(
-            interp, executable, vdso, loader, handles, local_fs, args, environ, name,
+            interp, executable, vdso, loader, handles, local_fs, args, environ, name, affinity, job,
         )
     }
 
@@ -245,6 +272,8 @@ impl Builder {
             vdso,
             args,
             environ,
+            affinity,
+            job,
         } = mem::take(self);
         let (executable, name) = executable.ok_or_else(|| Error::FieldMissing("executable"))?;
         let loader = loader
@@ -269,7 +298,7 @@ impl Builder {
 
         let loader = solvent_rpc::Client::into_sync(loader).unwrap();
         build_end(
-            interp, executable, vdso, loader, handles, local_fs, args, environ, name,
+            interp, executable, vdso, loader, handles, local_fs, args, environ, name, affinity, job,
         )
     }
 
@@ -284,6 +313,7 @@ impl Builder {
                 build_args.stack,
                 Some(build_args.init_chan),
                 build_args.vdso_base.as_ptr() as _,
+                build_args.affinity.as_ref(),
             )
             .map_err(Error::TaskExec)?,
         );
@@ -299,6 +329,9 @@ impl Builder {
             Some(build_args.space),
             Some(build_args.init_chan),
         );
+        if let Some(affinity) = &build_args.affinity {
+            task.set_affinity(affinity).map_err(Error::SetAffinity)?;
+        }
         let proc = InitProcess {
             task,
             entry: build_args.entry,
@@ -321,6 +354,7 @@ impl Builder {
                 build_args.stack,
                 Some(build_args.init_chan),
                 build_args.vdso_base.as_ptr() as _,
+                build_args.affinity.as_ref(),
             )
             .map_err(Error::TaskExec)?,
         );
@@ -339,6 +373,9 @@ impl Builder {
             Some(build_args.space),
             Some(build_args.init_chan),
         );
+        if let Some(affinity) = &build_args.affinity {
+            task.set_affinity(affinity).map_err(Error::SetAffinity)?;
+        }
         let proc = InitProcess {
             task,
             entry: build_args.entry,
@@ -369,6 +406,7 @@ struct BuildArgs {
     stack: NonNull<u8>,
     init_chan: Channel,
     vdso_base: NonNull<u8>,
+    affinity: Option<CpuSet>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -382,8 +420,13 @@ fn build_end(
     args: Vec<String>,
     environ: BTreeMap<String, String>,
     name: String,
+    affinity: Option<CpuSet>,
+    job: Option<Job>,
 ) -> Result<BuildArgs, Error> {
-    let (space, root_virt) = Space::new();
+    let (space, root_virt) = match job {
+        Some(job) => Space::try_new_in(&job).map_err(Error::SpaceNew)?,
+        None => Space::new(),
+    };
 
     let loaded = elfload::load(&interp, true, &root_virt)?;
     elfload::load(&executable, true, &root_virt)?;
@@ -399,10 +442,10 @@ fn build_end(
     let (me, child) = Channel::new();
 
     let child = child
-        .reduce_features(Feature::SEND | Feature::READ)
+        .reduce_rights(Feature::SEND | Feature::READ)
         .expect("Failed to reduce features for read");
     let me = me
-        .reduce_features(Feature::SEND | Feature::WRITE)
+        .reduce_rights(Feature::SEND | Feature::WRITE)
         .expect("Failed to reduce features for write");
 
     let dl_args = StartupArgs {
@@ -440,6 +483,7 @@ fn build_end(
         stack,
         init_chan: child,
         vdso_base: vdso_base.as_non_null_ptr(),
+        affinity,
     })
 }
 
@@ -1,3 +1,44 @@
+pub mod device;
 pub mod driver;
+pub mod host;
+
+use alloc::string::{String, ToString};
+use core as std;
+
+use solvent::error::Error as RawError;
+use solvent_rpc_core::SerdePacket;
+use thiserror_impl::Error;
 
 use crate as solvent_rpc;
+use crate::thiserror;
+
+#[derive(SerdePacket, Debug, Error)]
+pub enum Error {
+    #[error("the resource is not available")]
+    Unavailable,
+
+    #[error("invalid argument")]
+    InvalidArgument,
+
+    #[error("the operation is not supported by the device")]
+    NotSupported,
+
+    #[error("RPC error: {0}")]
+    RpcError(String),
+
+    #[error("unknown error: {0}")]
+    Other(#[source] RawError),
+}
+
+impl From<solvent_rpc_core::Error> for Error {
+    fn from(value: solvent_rpc_core::Error) -> Self {
+        Error::RpcError(value.to_string())
+    }
+}
+
+impl From<RawError> for Error {
+    #[inline]
+    fn from(value: RawError) -> Self {
+        Error::Other(value)
+    }
+}
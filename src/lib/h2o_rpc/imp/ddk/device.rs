@@ -0,0 +1,110 @@
+use alloc::vec::Vec;
+
+use solvent::{
+    dev::{Interrupt, PioRes},
+    mem::Phys,
+};
+use solvent_rpc_core::SerdePacket;
+
+use super::Error;
+use crate as solvent_rpc;
+
+/// The location of a PCI function.
+#[derive(SerdePacket, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PciAddress {
+    pub segment: u16,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+/// The identification of a PCI function, read from its configuration header.
+#[derive(SerdePacket, Debug, Copy, Clone)]
+pub struct PciInfo {
+    pub address: PciAddress,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub subsystem_vendor_id: u16,
+    pub subsystem_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub prog_if: u8,
+    pub revision: u8,
+    pub interrupt_pin: u8,
+    pub interrupt_line: u8,
+}
+
+#[derive(SerdePacket, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BarKind {
+    Memory32,
+    Memory64,
+    Io,
+}
+
+/// A base address register decoded at enumeration.
+#[derive(SerdePacket, Debug, Copy, Clone)]
+pub struct BarInfo {
+    pub index: u8,
+    pub kind: BarKind,
+    pub prefetchable: bool,
+    pub base: u64,
+    pub size: u64,
+}
+
+/// A capability found in the configuration space.
+#[derive(SerdePacket, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Capability {
+    pub id: u16,
+    pub offset: u16,
+    /// Whether it is a PCIe extended capability.
+    pub extended: bool,
+}
+
+/// The resource decoded by a BAR.
+#[derive(SerdePacket, Debug)]
+pub enum Bar {
+    /// The pages covering the BAR, whose registers start at `offset` in
+    /// `phys`.
+    Memory { phys: Phys, offset: usize },
+    /// The port range `base..base + size`, to be acquired with `PortIo`.
+    Io {
+        res: PioRes,
+        base: u16,
+        size: u16,
+    },
+}
+
+#[protocol]
+pub trait Device: crate::core::Cloneable + crate::core::Closeable {
+    fn info() -> PciInfo;
+
+    fn bars() -> Vec<BarInfo>;
+
+    fn capabilities() -> Vec<Capability>;
+
+    /// Read `width` (1, 2 or 4) bytes at `offset` in the configuration space.
+    fn read_config(offset: u16, width: u8) -> Result<u32, Error>;
+
+    /// Write `width` (1, 2 or 4) bytes at `offset` in the configuration space.
+    fn write_config(offset: u16, width: u8, value: u32) -> Result<(), Error>;
+
+    /// Get the resource decoded by the BAR at `index`.
+    ///
+    /// A memory BAR is rounded out to whole pages, and the offset of its
+    /// registers in the returned object is returned along with it.
+    fn bar(index: u8) -> Result<Bar, Error>;
+
+    /// Allocate `count` MSI vectors targeting `cpu` and program them into the
+    /// MSI capability of the device, which is enabled afterwards.
+    ///
+    /// `count` must be a power of 2 no greater than what the device supports.
+    /// The vectors of an MSI capability share one address, so they all target
+    /// the same CPU; callers wanting to spread the load should pick different
+    /// CPUs for different devices.
+    ///
+    /// # Limitations
+    ///
+    /// MSI-X is not supported yet, so `Error::NotSupported` is returned for
+    /// devices without an MSI capability even if they have an MSI-X one.
+    fn allocate_msi(count: u8, cpu: u32) -> Result<Vec<Interrupt>, Error>;
+}
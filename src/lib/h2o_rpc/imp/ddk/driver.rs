@@ -1,6 +1,73 @@
-use solvent::mem::Phys;
+use alloc::{string::String, vec::Vec};
 
+use solvent::{
+    dev::{GsiRes, MemRes, PioRes},
+    ipc::Channel,
+};
+use solvent_rpc_core::SerdePacket;
+
+use super::Error;
 use crate as solvent_rpc;
 
+/// The ECAM window of PCI segment group 0.
+#[derive(SerdePacket, Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PciEcam {
+    /// The physical base address of the window, or 0 if the platform has no
+    /// MCFG table.
+    pub base: usize,
+    pub bus_start: u8,
+    pub bus_end: u8,
+}
+
+/// The root resources of the platform.
+#[derive(SerdePacket, Debug)]
+pub struct Platform {
+    pub mem: MemRes,
+    pub pio: PioRes,
+    pub gsi: GsiRes,
+    pub pci_ecam: PciEcam,
+}
+
+/// A property of a device, against which the device manager matches the
+/// driver manifests.
+#[derive(SerdePacket, Debug, Clone, PartialEq, Eq)]
+pub enum Property {
+    PciId {
+        vendor: u16,
+        device: u16,
+    },
+    PciClass {
+        class: u8,
+        subclass: u8,
+        prog_if: u8,
+    },
+    AcpiHid(String),
+}
+
+/// A device bound to a driver by the device manager.
+#[derive(SerdePacket, Debug)]
+pub struct Binding {
+    /// The path of the device in the device tree.
+    pub name: String,
+    pub props: Vec<Property>,
+    /// The channel on which the protocol of the device is served.
+    pub device: Channel,
+}
+
 #[protocol]
-pub trait Driver: crate::core::Closeable {}
+pub trait Driver: crate::core::Closeable {
+    /// Take the root resources of the platform.
+    ///
+    /// The resources are handed out only once, to the root driver.
+    fn platform() -> Result<Platform, Error>;
+
+    /// Publish a child device whose protocol is served on the other end of
+    /// `device`.
+    ///
+    /// `name` is the path of the new device in the device tree, whose parent
+    /// must be the root or a device bound to the calling driver.
+    fn publish(name: String, props: Vec<Property>, device: Channel) -> Result<(), Error>;
+
+    /// Wait for the next device bound to the calling driver.
+    fn bind() -> Result<Binding, Error>;
+}
@@ -0,0 +1,64 @@
+use alloc::string::{String, ToString};
+use core as std;
+
+use solvent::{error::Error as RawError, ipc::Channel};
+use solvent_rpc_core::SerdePacket;
+use thiserror_impl::Error;
+
+use crate as solvent_rpc;
+use crate::thiserror;
+
+/// The errors reported by a driver host.
+#[derive(SerdePacket, Debug, Error)]
+pub enum Error {
+    #[error("failed to open the driver: {0}")]
+    Open(#[source] crate::io::Error),
+
+    #[error("failed to load the driver DSO")]
+    Load,
+
+    #[error("symbol `{0}` not found in the driver")]
+    SymbolNotFound(String),
+
+    #[error("driver {0} not found in the host")]
+    NotFound(u64),
+
+    #[error("failed to unload the driver DSO: {0}")]
+    Unload(#[source] RawError),
+
+    #[error("the driver still has {0} tasks running")]
+    Busy(usize),
+
+    #[error("RPC error: {0}")]
+    RpcError(String),
+
+    #[error("unknown error: {0}")]
+    Other(#[source] RawError),
+}
+
+impl From<solvent_rpc_core::Error> for Error {
+    fn from(value: solvent_rpc_core::Error) -> Self {
+        Error::RpcError(value.to_string())
+    }
+}
+
+impl From<crate::io::Error> for Error {
+    #[inline]
+    fn from(value: crate::io::Error) -> Self {
+        Error::Open(value)
+    }
+}
+
+/// The control protocol of a driver host, served to the device manager.
+///
+/// The host exits after the last of its drivers is unloaded.
+#[protocol]
+pub trait DriverHost: crate::core::Closeable {
+    /// Load the driver at `path` and start it with `instance` as its
+    /// connection to the device manager, returning the ID of the driver in
+    /// the host.
+    fn load(path: String, instance: Channel) -> Result<u64, Error>;
+
+    /// Shut the driver down and unload it from the host.
+    fn unload(id: u64) -> Result<(), Error>;
+}
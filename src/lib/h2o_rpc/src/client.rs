@@ -60,10 +60,13 @@ impl ClientImpl {
     }
 
     pub async fn call(&self, mut packet: Packet) -> Result<Packet, Error> {
+        if self.inner.stop.load(Acquire) {
+            return Err(Error::Disconnected);
+        }
         let id = self.inner.register();
         packet.id = NonZeroUsize::new(id);
 
-        match self.inner.channel.send(&mut packet) {
+        match self.inner.channel.send(&mut packet).await {
             Err(EPIPE) => self.inner.receive().await?,
             res => res.map_err(Error::ClientSend)?,
         };
@@ -318,7 +321,7 @@ impl Inner {
         let res = self.channel.receive(&mut packet).await;
         res.map_err(|err| {
             if matches!(err, EPIPE) {
-                self.stop.store(true, Release);
+                self.disconnect();
                 Error::Disconnected
             } else {
                 Error::ClientReceive(err)
@@ -337,6 +340,18 @@ impl Inner {
         Ok(())
     }
 
+    /// Mark the client as stopped and wake up all the callers and the event
+    /// receiver to let them finish.
+    fn disconnect(&self) {
+        self.stop.store(true, Release);
+        for entry in self.wakers.lock().values() {
+            if let WakerEntry::Waiting(waker) = entry {
+                waker.wake_by_ref();
+            }
+        }
+        self.event.waker.lock().wake();
+    }
+
     async fn receive_for_caller(&self, id: usize, waker: &Waker) -> Poll<Result<Packet, Error>> {
         {
             let mut wakers = self.wakers.lock();
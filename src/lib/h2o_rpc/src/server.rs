@@ -142,6 +142,22 @@ impl EventSenderImpl {
         self.inner.send(packet)
     }
 
+    /// Send a packet, waiting for the client to receive the pending ones if
+    /// the channel is full, instead of failing.
+    pub async fn send_async(&self, packet: Packet) -> Result<(), Error> {
+        if self.inner.stop.load(Acquire) {
+            return Err(Error::Disconnected);
+        }
+        self.inner.send_async(packet).await
+    }
+
+    /// Wait until the client is closed, after which no more events can be
+    /// sent.
+    pub async fn closed(&self) -> Result<(), Error> {
+        let res = self.inner.channel.closed().await;
+        res.map_err(Error::ServerReceive)
+    }
+
     #[inline]
     pub fn as_raw(&self) -> Handle {
         // SAFETY: `solvent` marks unsafe use for `Object::from_raw`
@@ -204,16 +220,22 @@ impl Inner {
     }
 
     fn send(&self, mut packet: Packet) -> Result<(), Error> {
-        let res = self.channel.send(&mut packet);
-        res.map_err(|err| {
-            if err == EPIPE {
-                self.stop.store(true, Release);
-                Error::Disconnected
-            } else {
-                Error::ServerSend(err)
-            }
-        })?;
-        Ok(())
+        let res = self.channel.try_send(&mut packet);
+        res.map_err(|err| self.send_error(err))
+    }
+
+    async fn send_async(&self, mut packet: Packet) -> Result<(), Error> {
+        let res = self.channel.send(&mut packet).await;
+        res.map_err(|err| self.send_error(err))
+    }
+
+    fn send_error(&self, err: solvent::error::Error) -> Error {
+        if err == EPIPE {
+            self.stop.store(true, Release);
+            Error::Disconnected
+        } else {
+            Error::ServerSend(err)
+        }
     }
 }
 
@@ -9,7 +9,7 @@ use core::{
 
 use crossbeam::queue::SegQueue;
 use solvent::{
-    error::{ENOENT, EPIPE, ETIME},
+    error::{EBUFFER, ENOENT, EPIPE, ETIME},
     ipc::{Channel, Packet, SIG_READ},
     prelude::Object,
     time::Instant,
@@ -134,7 +134,7 @@ struct Inner {
 
 impl Inner {
     fn call(&self, packet: Packet) -> Result<Packet, Error> {
-        self.call_inner(packet, |_| {
+        self.call_inner(packet, Duration::MAX, |_| {
             self.channel
                 .try_wait(Duration::MAX, true, false, SIG_READ)
                 .map_err(Error::ClientReceive)?;
@@ -143,7 +143,7 @@ impl Inner {
     }
 
     fn call_timeout(&self, packet: Packet, timeout: Duration) -> Result<Packet, Error> {
-        self.call_inner(packet, |instant| {
+        self.call_inner(packet, timeout, |instant| {
             let elapsed = instant.elapsed();
             if elapsed >= timeout {
                 return Err(Error::ClientReceive(ETIME));
@@ -156,22 +156,31 @@ impl Inner {
     }
 
     #[inline]
-    fn call_inner<F>(&self, mut packet: Packet, mut wait: F) -> Result<Packet, Error>
+    fn call_inner<F>(
+        &self,
+        mut packet: Packet,
+        timeout: Duration,
+        mut wait: F,
+    ) -> Result<Packet, Error>
     where
         F: FnMut(Instant) -> Result<(), Error>,
     {
         let self_id = self.next_id.fetch_add(1, SeqCst);
         packet.id = NonZeroUsize::new(self_id);
-        self.channel.send(&mut packet).map_err(|err| {
-            if err == EPIPE {
+
+        let instant = Instant::now();
+        match self.channel.call(&mut packet, timeout) {
+            Ok(()) => return Ok(packet),
+            // The reply is too large and left in the channel, so receive it
+            // the usual way.
+            Err(EBUFFER) => {}
+            Err(EPIPE) => {
                 self.stop.store(true, Release);
-                Error::Disconnected
-            } else {
-                Error::ClientReceive(err)
+                return Err(Error::Disconnected);
             }
-        })?;
+            Err(err) => return Err(Error::ClientReceive(err)),
+        }
 
-        let instant = Instant::now();
         loop {
             match self.channel.receive(&mut packet) {
                 Ok(()) => {
@@ -3,7 +3,7 @@ mod pio;
 mod res;
 
 pub use self::{
-    intr::{Interrupt, PackIntrWait},
+    intr::{Interrupt, Msi, PackIntrWait},
     pio::PortIo,
-    res::{GsiRes, MemRes, PioRes},
+    res::{GsiRes, MemRes, PioRes, ResetType},
 };
@@ -1,4 +1,7 @@
-pub use sv_call::res::IntrConfig;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+pub use sv_call::res::{IntrConfig, MsiVector};
 use sv_call::{c_ty::Status, Syscall, ETIME, SV_INTERRUPT};
 
 use super::GsiRes;
@@ -22,6 +25,34 @@ impl Interrupt {
         }
     }
 
+    /// Allocate `count` MSI vectors targeting `cpu`, returning the interrupt
+    /// objects along with the address and data of each vector.
+    #[cfg(feature = "alloc")]
+    pub fn allocate_msi(res: &GsiRes, cpu: usize, count: usize) -> Result<Vec<Msi>> {
+        let mut vecs = Vec::with_capacity(count);
+        unsafe {
+            // SAFETY: We don't move the ownership of the resource handle, and
+            // the buffer is large enough for `count` vectors.
+            sv_call::sv_intr_msi(
+                unsafe { res.raw() },
+                cpu.try_into()?,
+                vecs.as_mut_ptr(),
+                count,
+            )
+            .into_res()?;
+            vecs.set_len(count);
+        }
+        Ok(vecs
+            .into_iter()
+            .map(|vec: MsiVector| Msi {
+                // SAFETY: The handles are freshly allocated.
+                intr: unsafe { Self::from_raw(vec.intr) },
+                address: vec.address,
+                data: vec.data,
+            })
+            .collect())
+    }
+
     pub fn last_time(&self) -> Result<Instant> {
         let mut ins = 0u128;
         unsafe {
@@ -42,6 +73,13 @@ impl Interrupt {
     }
 }
 
+#[derive(Debug)]
+pub struct Msi {
+    pub intr: Interrupt,
+    pub address: u32,
+    pub data: u32,
+}
+
 pub struct PackIntrWait {
     pub ins: u128,
     pub syscall: Syscall,
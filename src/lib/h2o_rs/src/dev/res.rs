@@ -59,3 +59,25 @@ macro_rules! impl_resource {
 impl_resource!(MemRes, usize, RES_MEM, SV_MEMRES);
 impl_resource!(PioRes, u16, RES_PIO, SV_PIORES);
 impl_resource!(GsiRes, u32, RES_GSI, SV_GSIRES);
+
+/// The ways to reset the platform.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResetType {
+    Cold,
+    Warm,
+    Shutdown,
+}
+
+impl MemRes {
+    /// Reset the platform, which requires the root memory resource.
+    ///
+    /// Returns only if the reset fails.
+    pub fn reset(&self, ty: ResetType) -> Result {
+        let ty = match ty {
+            ResetType::Cold => RESET_COLD,
+            ResetType::Warm => RESET_WARM,
+            ResetType::Shutdown => RESET_SHUTDOWN,
+        };
+        unsafe { sv_call::sv_sys_reset(unsafe { self.raw() }, ty).into_res() }
+    }
+}
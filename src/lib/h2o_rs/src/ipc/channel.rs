@@ -1,12 +1,16 @@
 #[cfg(feature = "alloc")]
 use alloc::{boxed::Box, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::time::Duration;
 use core::{mem::MaybeUninit, num::NonZeroUsize};
 
+#[cfg(feature = "alloc")]
+use sv_call::{ipc::MAX_BUFFER_SIZE, mem::PhysOptions};
 use sv_call::{c_ty::Status, ipc::RawPacket, Syscall, SV_CHANNEL};
 
 #[cfg(feature = "alloc")]
 use super::Packet;
-use crate::{error::*, obj::Object};
+use crate::{error::*, mem::Phys, obj::Object};
 
 #[repr(transparent)]
 #[derive(Debug)]
@@ -16,23 +20,38 @@ crate::impl_obj!(Channel, SV_CHANNEL);
 crate::impl_obj!(@DROP, Channel);
 
 impl Channel {
+    #[inline]
     pub fn try_new() -> Result<(Channel, Channel)> {
+        Self::try_with_capacity(0)
+    }
+
+    pub fn new() -> (Channel, Channel) {
+        Self::try_new().expect("Failed to create a pair of channels")
+    }
+
+    /// Create a pair of channels, each of which holds at most `capacity`
+    /// packets not yet received, or `DEFAULT_CAPACITY` if it's 0. Sending to
+    /// a full channel fails with `ENOSPC`, and `SIG_WRITE` is asserted on the
+    /// sender whenever there is space left.
+    pub fn try_with_capacity(capacity: usize) -> Result<(Channel, Channel)> {
         let (mut h1, mut h2) = (sv_call::Handle::NULL, sv_call::Handle::NULL);
-        unsafe { sv_call::sv_chan_new(&mut h1, &mut h2).into_res()? };
+        unsafe { sv_call::sv_chan_new(capacity, &mut h1, &mut h2).into_res()? };
 
         // SAFETY: The handles are freshly allocated.
         Ok(unsafe { (Channel::from_raw(h1), Channel::from_raw(h2)) })
     }
 
-    pub fn new() -> (Channel, Channel) {
-        Self::try_new().expect("Failed to create a pair of channels")
+    pub fn with_capacity(capacity: usize) -> (Channel, Channel) {
+        Self::try_with_capacity(capacity).expect("Failed to create a pair of channels")
     }
 
-    pub fn send_raw(
+    fn send_raw_impl(
         &self,
         id: Option<NonZeroUsize>,
         buffer: &[u8],
         handles: &[sv_call::Handle],
+        phys: sv_call::Handle,
+        phys_len: usize,
     ) -> Result {
         let packet = RawPacket {
             id: id.map_or(0, |id| id.get()),
@@ -42,22 +61,59 @@ impl Channel {
             buffer: buffer.as_ptr() as *mut _,
             buffer_size: buffer.len(),
             buffer_cap: buffer.len(),
+            phys,
+            phys_len,
         };
         // SAFETY: We don't move the ownership of the handle.
         unsafe { sv_call::sv_chan_send(unsafe { self.raw() }, &packet).into_res() }
     }
 
+    pub fn send_raw(
+        &self,
+        id: Option<NonZeroUsize>,
+        buffer: &[u8],
+        handles: &[sv_call::Handle],
+    ) -> Result {
+        self.send_raw_impl(id, buffer, handles, sv_call::Handle::NULL, 0)
+    }
+
+    /// Send a packet whose data is followed by the first `phys_len` bytes of
+    /// `phys`, which are shared with the receiver copy-on-write instead of
+    /// being copied.
+    ///
+    /// The object must not be mapped, or the sending fails with `EBUSY`.
+    pub fn send_raw_with_phys(
+        &self,
+        id: Option<NonZeroUsize>,
+        buffer: &[u8],
+        handles: &[sv_call::Handle],
+        phys: &Phys,
+        phys_len: usize,
+    ) -> Result {
+        // SAFETY: We don't move the ownership of the handle.
+        self.send_raw_impl(id, buffer, handles, unsafe { phys.raw() }, phys_len)
+    }
+
+    /// Send the packet, carrying the part of its buffer beyond
+    /// `MAX_BUFFER_SIZE` out of line in a physical object.
     #[cfg(feature = "alloc")]
     pub fn send(&self, packet: &mut Packet) -> Result {
-        self.send_raw(packet.id, &packet.buffer, &packet.handles)
-            .map(|_| *packet = Default::default())
+        match split_buffer(&packet.buffer)? {
+            (buffer, Some((phys, len))) => {
+                self.send_raw_with_phys(packet.id, buffer, &packet.handles, &phys, len)
+            }
+            (buffer, None) => self.send_raw(packet.id, buffer, &packet.handles),
+        }
+        .map(|_| *packet = Default::default())
     }
 
+    /// Receive a packet into the buffers, returning its ID and its out-of-line
+    /// data along with the length if any.
     pub fn receive_raw(
         &self,
         buffer: &mut [u8],
         handles: &mut [MaybeUninit<sv_call::Handle>],
-    ) -> (Result<usize>, usize, usize) {
+    ) -> (Result<(usize, Option<(Phys, usize)>)>, usize, usize) {
         let mut packet = RawPacket {
             id: 0,
             handles: handles.as_mut_ptr().cast(),
@@ -66,11 +122,13 @@ impl Channel {
             buffer: buffer.as_mut_ptr(),
             buffer_size: buffer.len(),
             buffer_cap: buffer.len(),
+            phys: sv_call::Handle::NULL,
+            phys_len: 0,
         };
         // SAFETY: We don't move the ownership of the handle.
         let res = unsafe { sv_call::sv_chan_recv(unsafe { self.raw() }, &mut packet).into_res() };
         (
-            res.map(|_| packet.id),
+            res.map(|_| (packet.id, take_phys(&mut packet))),
             packet.buffer_size,
             packet.handle_count,
         )
@@ -88,6 +146,8 @@ impl Channel {
             buffer: buffer.as_mut_ptr(),
             buffer_size: buffer.len(),
             buffer_cap: buffer.len(),
+            phys: sv_call::Handle::NULL,
+            phys_len: 0,
         });
         let syscall =
             unsafe { sv_call::sv_pack_chan_recv(unsafe { self.raw() }, &mut *raw_packet) };
@@ -104,7 +164,9 @@ impl Channel {
         buffer: &mut Vec<u8>,
         handles: &mut Vec<sv_call::Handle>,
     ) -> Result<usize> {
-        receive_into_impl(|buf, hdl| self.receive_raw(buf, hdl), buffer, handles)
+        let (id, phys) = receive_into_impl(|buf, hdl| self.receive_raw(buf, hdl), buffer, handles)?;
+        append_phys(buffer, phys)?;
+        Ok(id)
     }
 
     #[cfg(feature = "alloc")]
@@ -114,6 +176,75 @@ impl Channel {
         Ok(())
     }
 
+    /// Send the packet and wait for the reply with the same ID from the peer,
+    /// which is received into `packet` directly instead of being queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EBUFFER` if the reply is larger than the reserved capacities
+    /// of `packet`, in which case the packet is cleared and the reply is left
+    /// in the channel to be received later. If the request is sent but no
+    /// reply is received, the handles of `packet` are cleared since they have
+    /// been taken by the kernel.
+    #[cfg(feature = "alloc")]
+    pub fn call(&self, packet: &mut Packet, timeout: Duration) -> Result {
+        let id = packet.id.ok_or(EINVAL)?;
+        let timeout = crate::time::try_into_us(timeout)?;
+
+        let buffer_len = packet.buffer.len();
+        let (buffer, phys) = split_buffer(&packet.buffer)?;
+        let buffer_size = buffer.len();
+        let handle_count = packet.handles.len();
+        packet
+            .buffer
+            .reserve(MAX_BUFFER_SIZE.saturating_sub(buffer_len));
+        packet.handles.reserve(4);
+        // SAFETY: u8 doesn't implement `Drop` so we always consider it valid.
+        unsafe { packet.buffer.set_len(packet.buffer.capacity()) };
+
+        let mut raw = RawPacket {
+            id: id.get(),
+            handles: packet.handles.as_mut_ptr(),
+            handle_count,
+            handle_cap: packet.handles.capacity(),
+            buffer: packet.buffer.as_mut_ptr(),
+            buffer_size,
+            buffer_cap: packet.buffer.capacity(),
+            // SAFETY: We don't move the ownership of the handle.
+            phys: phys
+                .as_ref()
+                .map_or(sv_call::Handle::NULL, |(phys, _)| unsafe { phys.raw() }),
+            phys_len: phys.as_ref().map_or(0, |&(_, len)| len),
+        };
+        // SAFETY: We don't move the ownership of the handle.
+        let res = unsafe { sv_call::sv_chan_call(unsafe { self.raw() }, &mut raw, timeout) };
+        drop(phys);
+        match res.into_res() {
+            Ok(()) => {
+                // SAFETY: The reply is written into the buffers.
+                unsafe {
+                    packet.buffer.set_len(raw.buffer_size);
+                    packet.handles.set_len(raw.handle_count);
+                }
+                packet.id = NonZeroUsize::new(raw.id);
+                append_phys(&mut packet.buffer, take_phys(&mut raw))
+            }
+            Err(EBUFFER) => {
+                *packet = Default::default();
+                Err(EBUFFER)
+            }
+            Err(err) => {
+                // The kernel clears the count once it has taken the handles,
+                // whose values may already refer to other objects afterwards.
+                if raw.handle_count == 0 {
+                    packet.handles.clear();
+                }
+                packet.buffer.truncate(buffer_len);
+                Err(err)
+            }
+        }
+    }
+
     #[cfg(feature = "alloc")]
     pub fn handle<F, R>(&self, handler: F) -> Result<R>
     where
@@ -129,6 +260,36 @@ impl Channel {
     }
 }
 
+/// Take the ownership of the out-of-line data received in `raw`.
+fn take_phys(raw: &mut RawPacket) -> Option<(Phys, usize)> {
+    let phys = core::mem::replace(&mut raw.phys, sv_call::Handle::NULL);
+    // SAFETY: The handle is freshly received and owned by us.
+    (!phys.is_null()).then(|| (unsafe { Phys::from_raw(phys) }, raw.phys_len))
+}
+
+/// Split the buffer at `MAX_BUFFER_SIZE`, copying the rest into a new physical
+/// object to be carried out of line.
+#[cfg(feature = "alloc")]
+fn split_buffer(buffer: &[u8]) -> Result<(&[u8], Option<(Phys, usize)>)> {
+    if buffer.len() <= MAX_BUFFER_SIZE {
+        return Ok((buffer, None));
+    }
+    let (buffer, rest) = buffer.split_at(MAX_BUFFER_SIZE);
+    let phys = Phys::allocate(rest.len(), PhysOptions::empty())?;
+    // SAFETY: The object is freshly allocated and not mapped anywhere.
+    unsafe { phys.write(0, rest) }?;
+    Ok((buffer, Some((phys, rest.len()))))
+}
+
+/// Append the out-of-line data of a received packet to its buffer.
+#[cfg(feature = "alloc")]
+fn append_phys(buffer: &mut Vec<u8>, phys: Option<(Phys, usize)>) -> Result {
+    if let Some((phys, len)) = phys {
+        buffer.extend(phys.read(0, len)?);
+    }
+    Ok(())
+}
+
 #[cfg(feature = "alloc")]
 fn receive_into_impl<F, R>(
     mut receiver: F,
@@ -206,10 +367,14 @@ unsafe impl Send for PackRecv {}
 
 #[cfg(feature = "alloc")]
 impl PackRecv {
-    pub fn receive(&self, res: Status, canceled: bool) -> (Result<usize>, usize, usize) {
+    pub fn receive(
+        &mut self,
+        res: Status,
+        canceled: bool,
+    ) -> (Result<(usize, Option<(Phys, usize)>)>, usize, usize) {
         let res = res.into_res().and((!canceled).then_some(()).ok_or(ETIME));
         (
-            res.map(|_| self.raw_packet.id),
+            res.map(|_| (self.raw_packet.id, take_phys(&mut self.raw_packet))),
             self.raw_packet.buffer_size,
             self.raw_packet.handle_count,
         )
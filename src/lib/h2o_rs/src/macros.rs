@@ -11,6 +11,7 @@ macro_rules! impl_obj_for {
         $macro!($crate::mem::Space);
         $macro!($crate::mem::Virt);
         $macro!($crate::mem::Phys);
+        $macro!($crate::mem::Pinned);
         $macro!($crate::dev::Interrupt);
         $macro!($crate::dev::MemRes);
         $macro!($crate::dev::GsiRes);
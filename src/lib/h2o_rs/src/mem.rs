@@ -1,3 +1,4 @@
+mod dma;
 mod phys;
 mod space;
 mod virt;
@@ -13,7 +14,7 @@ use core::{
 pub use sv_call::mem::Flags;
 use sv_call::mem::IoVec;
 
-pub use self::{phys::*, space::Space, virt::Virt};
+pub use self::{dma::*, phys::*, space::Space, virt::Virt};
 
 cfg_if::cfg_if! { if #[cfg(target_arch = "x86_64")] {
 
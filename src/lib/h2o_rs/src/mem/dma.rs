@@ -0,0 +1,134 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::ptr;
+
+pub use sv_call::mem::PhysRange;
+use sv_call::SV_PINNED;
+
+#[cfg(feature = "alloc")]
+use super::PhysOptions;
+use super::{Phys, PAGE_MASK};
+use crate::{
+    dev::MemRes,
+    error::{Result, EINVAL},
+    obj::Object,
+};
+
+/// A range of a physical object pinned in memory, whose physical addresses
+/// stay valid for devices until the handle is dropped.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct Pinned(sv_call::Handle);
+crate::impl_obj!(Pinned, SV_PINNED);
+crate::impl_obj!(@DROP, Pinned);
+
+impl Pinned {
+    /// Pin `len` bytes of `phys` from the page-aligned `offset`. If `write` is
+    /// set, devices are allowed to write to the memory, and the handle of
+    /// `phys` must be writable.
+    pub fn new(res: &MemRes, phys: &Phys, offset: usize, len: usize, write: bool) -> Result<Self> {
+        if offset & PAGE_MASK != 0 || len == 0 {
+            return Err(EINVAL);
+        }
+        let handle = unsafe {
+            sv_call::sv_phys_pin(
+                // SAFETY: We don't move the ownership of the handles.
+                unsafe { res.raw() },
+                unsafe { phys.raw() },
+                offset,
+                len,
+                write,
+            )
+            .into_res()?
+        };
+        // SAFETY: The handle is freshly allocated.
+        Ok(unsafe { Self::from_raw(handle) })
+    }
+
+    /// Get the scatter-gather list of the pinned memory, in which adjacent
+    /// pages are merged into one range.
+    #[cfg(feature = "alloc")]
+    pub fn ranges(&self) -> Result<Vec<PhysRange>> {
+        // SAFETY: We don't move the ownership of the handle.
+        let count = unsafe { sv_call::sv_pinned_ranges(unsafe { self.raw() }, ptr::null_mut(), 0) }
+            .into_res()? as usize;
+
+        let mut ranges = Vec::with_capacity(count);
+        unsafe {
+            // SAFETY: We don't move the ownership of the handle, and the
+            // buffer is large enough for `count` ranges.
+            sv_call::sv_pinned_ranges(unsafe { self.raw() }, ranges.as_mut_ptr(), count)
+                .into_res()?;
+            ranges.set_len(count);
+        }
+        Ok(ranges)
+    }
+}
+
+/// A buffer for devices to access directly, pinned for its whole lifetime.
+///
+/// The cacheability of the buffer is decided by the options on allocation:
+/// `PhysOptions::UNCACHED` forces every mapping of the buffer to bypass the
+/// CPU caches. Otherwise, mappings are cached unless they're created with
+/// `Flags::UNCACHED`.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct DmaBuf {
+    phys: Phys,
+    pinned: Pinned,
+    ranges: Vec<PhysRange>,
+}
+
+#[cfg(feature = "alloc")]
+impl DmaBuf {
+    /// Allocate a buffer of `size` bytes, writable by devices.
+    ///
+    /// `options` may contain `PhysOptions::CONTIGUOUS`, `BELOW_4G` and
+    /// `UNCACHED`, the last two of which require the first. Pinned objects
+    /// can't be resized.
+    pub fn allocate(res: &MemRes, size: usize, options: PhysOptions) -> Result<Self> {
+        if options.contains(PhysOptions::RESIZABLE) {
+            return Err(EINVAL);
+        }
+        let phys = Phys::allocate(size, options)?;
+        Self::pin(res, phys, 0, size, true)
+    }
+
+    /// Pin an existing physical object for devices. See [`Pinned::new`] for
+    /// more information.
+    pub fn pin(res: &MemRes, phys: Phys, offset: usize, len: usize, write: bool) -> Result<Self> {
+        let pinned = Pinned::new(res, &phys, offset, len, write)?;
+        let ranges = pinned.ranges()?;
+        Ok(DmaBuf {
+            phys,
+            pinned,
+            ranges,
+        })
+    }
+
+    #[inline]
+    pub fn phys(&self) -> &Phys {
+        &self.phys
+    }
+
+    #[inline]
+    pub fn pinned(&self) -> &Pinned {
+        &self.pinned
+    }
+
+    /// The physical address ranges of the buffer in order, to be programmed
+    /// into the descriptors of devices.
+    #[inline]
+    pub fn ranges(&self) -> &[PhysRange] {
+        &self.ranges
+    }
+
+    /// The physical address of the buffer if it is contiguous.
+    pub fn contiguous_base(&self) -> Option<usize> {
+        match *self.ranges {
+            [range] => Some(range.base),
+            _ => None,
+        }
+    }
+}
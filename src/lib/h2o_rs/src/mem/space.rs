@@ -1,7 +1,7 @@
 use sv_call::SV_SPACE;
 
 use super::Virt;
-use crate::{error::Result, obj::Object};
+use crate::{error::Result, obj::Object, task::Job};
 
 #[repr(transparent)]
 #[derive(Debug)]
@@ -11,13 +11,24 @@ crate::impl_obj!(Space, SV_SPACE);
 crate::impl_obj!(@DROP, Space);
 
 impl Space {
-    pub fn try_new() -> Result<(Self, Virt)> {
+    fn new_impl(job: sv_call::Handle) -> Result<(Self, Virt)> {
         let mut root_virt = sv_call::Handle::NULL;
-        let handle = unsafe { sv_call::sv_space_new(&mut root_virt).into_res() }?;
+        let handle = unsafe { sv_call::sv_space_new(job, &mut root_virt).into_res() }?;
         // SAFETY: The handle is freshly allocated.
         Ok(unsafe { (Self::from_raw(handle), Virt::from_raw(root_virt)) })
     }
 
+    /// Create a task space in the current job.
+    pub fn try_new() -> Result<(Self, Virt)> {
+        Self::new_impl(sv_call::Handle::NULL)
+    }
+
+    /// Create a task space in `job`, whose resources are charged to it.
+    pub fn try_new_in(job: &Job) -> Result<(Self, Virt)> {
+        // SAFETY: We don't move the ownership of the handle.
+        Self::new_impl(unsafe { job.raw() })
+    }
+
     pub fn new() -> (Self, Virt) {
         Self::try_new().expect("Failed to create task space")
     }
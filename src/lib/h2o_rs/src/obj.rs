@@ -1,7 +1,16 @@
-use core::{fmt, marker::PhantomData, mem, mem::ManuallyDrop, ops::Deref, ptr, time::Duration};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop, MaybeUninit},
+    ops::Deref,
+    ptr,
+    time::Duration,
+};
 
 use sv_call::SV_DISPATCHER;
-pub use sv_call::{Feature, Handle, SerdeReg, Syscall};
+pub use sv_call::{obj::ObjInfo, Feature, Handle, SerdeReg, Syscall};
 
 use crate::error::Result;
 
@@ -77,12 +86,25 @@ pub trait Object: private::Sealed + fmt::Debug {
         }
     }
 
-    fn reduce_features(self, features: Feature) -> Result<Self>
+    /// Get the information of the object, including its type and kernel
+    /// object ID. See [`ObjInfo`] for more information.
+    fn info(&self) -> Result<ObjInfo> {
+        let mut info = MaybeUninit::uninit();
+        unsafe {
+            // SAFETY: We don't move the ownership of the handle.
+            sv_call::sv_obj_info(unsafe { self.raw() }, info.as_mut_ptr()).into_res()?;
+            Ok(info.assume_init())
+        }
+    }
+
+    /// Reduce the rights of the handle to `rights`, which must be a subset of
+    /// the current ones. See [`Feature`] for their meanings.
+    fn reduce_rights(self, rights: Feature) -> Result<Self>
     where
         Self: Sized,
     {
         let mut handle = Self::into_raw(self);
-        unsafe { sv_call::sv_obj_feat(&mut handle, features) }.into_res()?;
+        unsafe { sv_call::sv_obj_feat(&mut handle, rights) }.into_res()?;
         // SAFETY: The handle is freshly allocated.
         Ok(unsafe { Self::from_raw(handle) })
     }
@@ -190,6 +212,24 @@ impl<'a, T: ?Sized> Deref for Ref<'a, T> {
     }
 }
 
+/// Get all the handles of the current task, which may be changed by other
+/// threads at any time.
+#[cfg(feature = "alloc")]
+pub fn handles() -> Result<Vec<Handle>> {
+    let mut ret = Vec::new();
+    loop {
+        let count =
+            unsafe { sv_call::sv_obj_list(ret.as_mut_ptr(), ret.capacity()) }.into_res()? as usize;
+        if count <= ret.capacity() {
+            // SAFETY: The first `count` handles are written by the kernel.
+            unsafe { ret.set_len(count) };
+            break Ok(ret);
+        }
+        // Leave some room for the handles created in the meantime.
+        ret.reserve(count + count / 4);
+    }
+}
+
 #[repr(transparent)]
 #[derive(Debug)]
 pub struct Dispatcher(sv_call::Handle);
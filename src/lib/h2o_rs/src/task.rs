@@ -1,3 +1,5 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 #[cfg(feature = "stub")]
 use core::num::NonZeroUsize;
 use core::{
@@ -7,8 +9,11 @@ use core::{
     time::Duration,
 };
 
-pub use sv_call::task::{ctx::Gpr, *};
-use sv_call::{ipc::SIG_READ, Error, Handle, SV_SUSPENDTOKEN, SV_TASK};
+pub use sv_call::task::{
+    ctx::{DebugRegs, Gpr},
+    *,
+};
+use sv_call::{ipc::SIG_READ, Error, Handle, SV_JOB, SV_SUSPENDTOKEN, SV_TASK};
 
 use crate::{error::Result, ipc::Channel, mem::Space, obj::Object};
 
@@ -55,6 +60,7 @@ impl Task {
         stack: NonNull<u8>,
         init_chan: Option<Channel>,
         arg2: u64,
+        affinity: Option<&CpuSet>,
     ) -> Result<Self> {
         let name = name.map(|name| name.as_bytes());
         let ci = ExecInfo {
@@ -65,13 +71,17 @@ impl Task {
             stack: stack.as_ptr(),
             init_chan: init_chan.map_or(Handle::NULL, Channel::into_raw),
             arg: arg2,
+            priority: TASK_PRIO_NORMAL,
+            affinity: affinity.map_or(null(), |affinity| affinity as *const _),
         };
         let handle = unsafe { sv_call::sv_task_exec(&ci).into_res()? };
         // SAFETY: The handle is freshly allocated.
         Ok(unsafe { Self::from_raw(handle) })
     }
 
-    pub fn try_join(self) -> core::result::Result<usize, (Error, Self)> {
+    /// Get the exit record of the task if it has exited, consuming the
+    /// handle; or return the handle otherwise.
+    pub fn try_join(self) -> core::result::Result<ExitInfo, (Error, Self)> {
         // SAFETY: We don't move the ownership of the handle...
         let mut ret = Default::default();
         let res = unsafe { sv_call::sv_task_join(unsafe { self.raw() }, &mut ret).into_res() };
@@ -85,7 +95,8 @@ impl Task {
         }
     }
 
-    pub fn join(self) -> Result<usize> {
+    /// Wait for the task to exit and get its exit record.
+    pub fn join(self) -> Result<ExitInfo> {
         self.try_wait(Duration::MAX, true, false, SIG_READ)?;
         self.try_join().map_err(|(err, _)| err)
     }
@@ -97,6 +108,41 @@ impl Task {
         }
     }
 
+    /// Get a channel which receives the [`ExitInfo`] of the task as a packet
+    /// once it exits. A task can only be watched by one channel.
+    pub fn watch(&self) -> Result<Channel> {
+        let mut chan = Handle::NULL;
+        unsafe {
+            // SAFETY: We don't move the ownership of the handle.
+            sv_call::sv_task_ctl(unsafe { self.raw() }, TASK_CTL_WATCH, &mut chan).into_res()?
+        };
+        // SAFETY: The handle is freshly allocated.
+        Ok(unsafe { Channel::from_raw(chan) })
+    }
+
+    /// Attach to the task as a debugger without suspending it, getting the
+    /// channel that receives its exceptions.
+    ///
+    /// Breakpoints, single steps and other exceptions stop the task until the
+    /// channel replies. The task can be suspended with [`Task::suspend`]
+    /// before the reply to inspect it at the very point.
+    pub fn debug(&self) -> Result<Channel> {
+        let mut chan = Handle::NULL;
+        unsafe {
+            // SAFETY: We don't move the ownership of the handle.
+            sv_call::sv_task_ctl(unsafe { self.raw() }, TASK_CTL_DEBUG, &mut chan).into_res()?
+        };
+        // SAFETY: The handle is freshly allocated.
+        Ok(unsafe { Channel::from_raw(chan) })
+    }
+
+    /// Get all the running tasks in the space of this task, including itself.
+    #[cfg(feature = "alloc")]
+    pub fn threads(&self) -> Result<Vec<Task>> {
+        // SAFETY: We don't move the ownership of the handle.
+        list_tasks(unsafe { self.raw() })
+    }
+
     pub fn suspend(&self) -> Result<SuspendToken> {
         let mut st = Handle::NULL;
         unsafe {
@@ -106,6 +152,59 @@ impl Task {
         // SAFETY: The handles are freshly allocated.
         Ok(unsafe { SuspendToken::from_raw(st) })
     }
+
+    /// Set the priority of the task to one of `TASK_PRIO_*`.
+    pub fn set_priority(&self, mut priority: u32) -> Result {
+        unsafe {
+            // SAFETY: We don't move the ownership of the handle.
+            sv_call::sv_task_ctl(
+                unsafe { self.raw() },
+                TASK_CTL_SET_PRIO,
+                (&mut priority as *mut u32).cast(),
+            )
+            .into_res()
+        }
+    }
+
+    pub fn priority(&self) -> Result<u32> {
+        let mut priority = TASK_PRIO_NORMAL;
+        unsafe {
+            // SAFETY: We don't move the ownership of the handle.
+            sv_call::sv_task_ctl(
+                unsafe { self.raw() },
+                TASK_CTL_GET_PRIO,
+                (&mut priority as *mut u32).cast(),
+            )
+            .into_res()?
+        };
+        Ok(priority)
+    }
+
+    pub fn set_affinity(&self, affinity: &CpuSet) -> Result {
+        unsafe {
+            // SAFETY: We don't move the ownership of the handle.
+            sv_call::sv_task_ctl(
+                unsafe { self.raw() },
+                TASK_CTL_SET_AFFINITY,
+                (affinity as *const CpuSet as *mut CpuSet).cast(),
+            )
+            .into_res()
+        }
+    }
+
+    pub fn affinity(&self) -> Result<CpuSet> {
+        let mut affinity = CpuSet::new();
+        unsafe {
+            // SAFETY: We don't move the ownership of the handle.
+            sv_call::sv_task_ctl(
+                unsafe { self.raw() },
+                TASK_CTL_GET_AFFINITY,
+                (&mut affinity as *mut CpuSet).cast(),
+            )
+            .into_res()?
+        };
+        Ok(affinity)
+    }
 }
 
 #[repr(transparent)]
@@ -178,12 +277,101 @@ impl SuspendToken {
         }
     }
 
+    pub fn read_debug_regs(&self) -> Result<DebugRegs> {
+        let mut regs = DebugRegs::default();
+        unsafe {
+            sv_call::sv_task_debug(
+                // SAFETY: We don't move the ownership of the handle.
+                unsafe { self.raw() },
+                TASK_DBG_READ_REG,
+                TASK_DBGADDR_DEBUG,
+                &mut regs as *mut _ as *mut _,
+                mem::size_of::<DebugRegs>(),
+            )
+            .into_res()?
+        };
+        Ok(regs)
+    }
+
+    /// Set the hardware breakpoints of the task, which take effect once it's
+    /// woken up.
+    pub fn write_debug_regs(&self, regs: &DebugRegs) -> Result {
+        unsafe {
+            sv_call::sv_task_debug(
+                // SAFETY: We don't move the ownership of the handle.
+                unsafe { self.raw() },
+                TASK_DBG_WRITE_REG,
+                TASK_DBGADDR_DEBUG,
+                regs as *const _ as *mut u8,
+                mem::size_of::<DebugRegs>(),
+            )
+            .into_res()
+        }
+    }
+
+    /// Make the task raise a debug exception after executing one instruction
+    /// once it's woken up.
+    pub fn single_step(&self) -> Result {
+        unsafe {
+            sv_call::sv_task_debug(
+                // SAFETY: We don't move the ownership of the handle.
+                unsafe { self.raw() },
+                TASK_DBG_SINGLE_STEP,
+                0,
+                null_mut(),
+                0,
+            )
+            .into_res()
+        }
+    }
+
     #[inline]
     pub fn wake(self) {
         let _ = self;
     }
 }
 
+/// A group of processes sharing the limits of resources, which can be killed
+/// all at once.
+#[repr(transparent)]
+#[derive(Debug)]
+pub struct Job(sv_call::Handle);
+crate::impl_obj!(Job, SV_JOB);
+crate::impl_obj!(@DROP, Job);
+
+impl Job {
+    /// Create a child job of `parent`, or of the current job if it's `None`.
+    ///
+    /// The resources used in the child job are also counted in its ancestors,
+    /// and its affinity is limited in theirs.
+    pub fn new(parent: Option<&Job>, limits: &JobRes, affinity: Option<&CpuSet>) -> Result<Self> {
+        let handle = unsafe {
+            sv_call::sv_job_new(
+                // SAFETY: We don't move the ownership of the handle.
+                parent.map_or(Handle::NULL, |parent| unsafe { parent.raw() }),
+                limits,
+                affinity.map_or(null(), |affinity| affinity as *const _),
+            )
+            .into_res()?
+        };
+        // SAFETY: The handle is freshly allocated.
+        Ok(unsafe { Self::from_raw(handle) })
+    }
+
+    /// Kill all the processes in the job and its descendants.
+    pub fn kill(&self) -> Result {
+        // SAFETY: We don't move the ownership of the handle.
+        unsafe { sv_call::sv_job_kill(unsafe { self.raw() }).into_res() }
+    }
+
+    pub fn usage(&self) -> Result<JobRes> {
+        let mut usage = JobRes::default();
+        // SAFETY: We don't move the ownership of the handle.
+        unsafe { sv_call::sv_job_usage(unsafe { self.raw() }, &mut usage).into_res()? };
+        Ok(usage)
+    }
+}
+
 /// # Safety
 ///
 /// This function doesn't clean up the current self-maintained context, and the
@@ -193,6 +381,44 @@ pub unsafe fn exit(retval: usize, kill_all: bool) -> ! {
     unreachable!("The task failed to exit");
 }
 
+/// Terminate the current process abnormally, such as on panics, so that its
+/// exit record tells [`EXIT_ABORTED`].
+pub fn abort() -> ! {
+    let _ = unsafe { sv_call::sv_task_abort() };
+    unreachable!("The task failed to abort");
+}
+
+/// Get all the running tasks in the current space.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn threads() -> Result<Vec<Task>> {
+    list_tasks(Handle::NULL)
+}
+
+#[cfg(feature = "alloc")]
+fn list_tasks(hdl: Handle) -> Result<Vec<Task>> {
+    let mut raw = Vec::new();
+    loop {
+        let count = unsafe { sv_call::sv_task_list(hdl, raw.as_mut_ptr(), raw.capacity()) }
+            .into_res()? as usize;
+        // SAFETY: The first handles are written by the kernel, and are freshly
+        // allocated.
+        let tasks = unsafe {
+            raw.set_len(count.min(raw.capacity()));
+            raw.drain(..)
+                .map(|hdl| Task::from_raw(hdl))
+                .collect::<Vec<_>>()
+        };
+        if tasks.len() == count {
+            break Ok(tasks);
+        }
+        // Drop the partial list and try again with some room for the tasks
+        // created in the meantime.
+        drop(tasks);
+        raw.reserve(count + count / 4);
+    }
+}
+
 pub fn sleep(duration: Duration) -> Result {
     let millis = duration.as_millis().try_into()?;
     unsafe { sv_call::sv_task_sleep(millis).into_res() }
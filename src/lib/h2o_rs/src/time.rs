@@ -86,6 +86,13 @@ impl Sub<Instant> for Instant {
     }
 }
 
+/// Get the wall-clock time as the duration since the UNIX epoch.
+pub fn utc_now() -> Result<Duration> {
+    let mut time = sv_call::time::UtcTime::default();
+    unsafe { sv_call::sv_time_utc(&mut time).into_res()? };
+    Ok(Duration::new(time.secs, time.nanos))
+}
+
 #[inline]
 pub fn from_us(us: u64) -> Duration {
     if us == u64::MAX {
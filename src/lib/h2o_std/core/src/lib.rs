@@ -29,3 +29,4 @@ pub mod io;
 pub mod path;
 pub mod sync;
 pub mod thread;
+pub mod time;
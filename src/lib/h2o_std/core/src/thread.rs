@@ -13,7 +13,7 @@ use core::{
     time::Duration,
 };
 
-use solvent::error::Result;
+use solvent::{error::Result, task::CpuSet};
 
 pub use self::{backoff::Backoff, scope::scope};
 use crate::sync::{imp::Parker, Arsc};
@@ -22,6 +22,7 @@ use crate::sync::{imp::Parker, Arsc};
 pub struct Builder {
     stack: usize,
     name: Option<String>,
+    affinity: Option<CpuSet>,
 }
 
 impl Default for Builder {
@@ -51,6 +52,7 @@ impl Builder {
         Builder {
             stack: 0,
             name: None,
+            affinity: None,
         }
     }
 
@@ -66,6 +68,12 @@ impl Builder {
         self
     }
 
+    #[inline]
+    pub fn affinity(mut self, affinity: CpuSet) -> Self {
+        self.affinity = Some(affinity);
+        self
+    }
+
     pub fn spawn<F, T>(self, f: F) -> Result<JoinHandle<T>>
     where
         F: FnOnce() -> T,
@@ -113,9 +121,16 @@ impl Builder {
             unsafe { *p2.result.get() = Some(f()) };
         };
 
-        let native = imp::Thread::new(thread.inner.name.as_deref(), self.stack, unsafe {
-            mem::transmute::<Box<dyn FnOnce() + 'a>, Box<dyn FnOnce() + 'static>>(Box::new(main))
-        })?;
+        let native = imp::Thread::new(
+            thread.inner.name.as_deref(),
+            self.stack,
+            self.affinity.as_ref(),
+            unsafe {
+                mem::transmute::<Box<dyn FnOnce() + 'a>, Box<dyn FnOnce() + 'static>>(Box::new(
+                    main,
+                ))
+            },
+        )?;
 
         if let Some(scope_data) = scope_data {
             scope_data.increment_num_running_threads();
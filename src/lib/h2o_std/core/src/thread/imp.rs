@@ -4,7 +4,7 @@ use core::{mem, ptr::NonNull, time::Duration};
 use solvent::{
     error::Result,
     prelude::{Flags, Phys, Virt, PAGE_SIZE},
-    task::{exit, sleep, Task},
+    task::{exit, sleep, CpuSet, Task},
 };
 
 pub const DEFAULT_STACK_SIZE: usize = 256 * 1024;
@@ -21,7 +21,12 @@ impl Thread {
     /// # Safety
     ///
     /// `func` must implements `Send` and has its lifetime checked.
-    pub unsafe fn new(name: Option<&str>, stack: usize, func: Box<dyn FnOnce()>) -> Result<Self> {
+    pub unsafe fn new(
+        name: Option<&str>,
+        stack: usize,
+        affinity: Option<&CpuSet>,
+        func: Box<dyn FnOnce()>,
+    ) -> Result<Self> {
         let stack = stack.max(DEFAULT_STACK_SIZE);
 
         let virt = svrt::root_virt().allocate(None, Virt::page_aligned(stack + 2 * PAGE_SIZE))?;
@@ -45,9 +50,11 @@ impl Thread {
         let entry = NonNull::new_unchecked(thread_func as _);
 
         let func = Box::into_raw(Box::new(func));
-        let task = Task::exec(name, None, entry, stack, None, func as u64).inspect_err(|_| {
-            let _ = Box::from_raw(func);
-        })?;
+        let task = Task::exec(name, None, entry, stack, None, func as u64, affinity).inspect_err(
+            |_| {
+                let _ = Box::from_raw(func);
+            },
+        )?;
 
         mem::forget(guard);
         return Ok(Thread {
@@ -77,7 +84,10 @@ impl Thread {
 
     pub fn join(self) {
         let res = self.inner.join();
-        assert_eq!(res, Ok(0), "Failed to join thread");
+        assert!(
+            matches!(res, Ok(info) if info.success()),
+            "Failed to join thread: {res:?}"
+        );
         let _ = self.stack.destroy();
     }
 }
@@ -83,7 +83,5 @@ unsafe impl GlobalAlloc for Alloc {
 fn rust_oom(layout: core::alloc::Layout) -> ! {
     log::error!("Allocation error for {:?}", layout);
 
-    loop {
-        unsafe { core::arch::asm!("pause; ud2") }
-    }
+    solvent::task::abort()
 }
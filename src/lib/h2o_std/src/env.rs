@@ -38,7 +38,5 @@ pub fn vars() -> impl Iterator<Item = (String, String)> {
 fn rust_begin_unwind(info: &core::panic::PanicInfo) -> ! {
     log::error!("{}", info);
 
-    loop {
-        unsafe { core::arch::asm!("pause; ud2") }
-    }
+    solvent::task::abort()
 }
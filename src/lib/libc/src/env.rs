@@ -47,9 +47,7 @@ pub extern "C" fn __cxa_atexit(
 #[no_mangle]
 pub(crate) extern "C" fn __libc_panic(info: &PanicInfo) -> ! {
     log::error!("{}", info);
-    loop {
-        unsafe { core::arch::asm!("pause; ud2") }
-    }
+    solvent::task::abort()
 }
 
 #[link(name = "ldso")]
@@ -1 +1,66 @@
+use core::{ffi::*, time::Duration};
 
+use solvent::{error::Result, time::Instant};
+
+pub type time_t = i64;
+pub type clockid_t = c_int;
+
+pub const CLOCK_REALTIME: clockid_t = 0;
+pub const CLOCK_MONOTONIC: clockid_t = 1;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct timespec {
+    pub tv_sec: time_t,
+    pub tv_nsec: c_long,
+}
+
+fn clock_time(clock: clockid_t) -> Result<Duration> {
+    match clock {
+        CLOCK_REALTIME => solvent::time::utc_now(),
+        // SAFETY: The timestamp is only used as the time since boot.
+        CLOCK_MONOTONIC => {
+            let nanos = unsafe { Instant::try_now()?.raw() };
+            Ok(Duration::from_nanos(nanos as u64))
+        }
+        _ => Err(solvent::error::EINVAL),
+    }
+}
+
+/// # Safety
+///
+/// `ptr` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn time(ptr: *mut time_t) -> time_t {
+    let ret = match solvent::time::utc_now() {
+        Ok(now) => now.as_secs() as time_t,
+        Err(err) => {
+            *crate::ffi::errno::__libc_errno() = -err.raw();
+            return -1;
+        }
+    };
+    if !ptr.is_null() {
+        ptr.write(ret);
+    }
+    ret
+}
+
+/// # Safety
+///
+/// `tp` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn clock_gettime(clock: clockid_t, tp: *mut timespec) -> c_int {
+    match clock_time(clock) {
+        Ok(time) => {
+            tp.write(timespec {
+                tv_sec: time.as_secs() as time_t,
+                tv_nsec: time.subsec_nanos() as c_long,
+            });
+            0
+        }
+        Err(err) => {
+            *crate::ffi::errno::__libc_errno() = -err.raw();
+            -1
+        }
+    }
+}
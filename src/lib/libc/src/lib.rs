@@ -30,9 +30,7 @@ extern "C" fn rust_begin_unwind(info: &core::panic::PanicInfo) -> ! {
 fn rust_oom(layout: core::alloc::Layout) -> ! {
     log::error!("Allocation error for {:?}", layout);
 
-    loop {
-        unsafe { core::arch::asm!("pause; ud2") }
-    }
+    solvent::task::abort()
 }
 
 #[global_allocator]
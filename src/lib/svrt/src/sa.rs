@@ -19,6 +19,11 @@ pub enum HandleType {
     LoadRpc,
     BootfsPhys,
     LocalFs,
+    MemRes,
+    PioRes,
+    GsiRes,
+    DriverHost,
+    FramebufferPhys,
 }
 
 #[derive(Copy, Clone)]
@@ -38,6 +38,10 @@ pub struct Dist {
     ty: Type,
     #[structopt(long = "--release", parse(from_flag))]
     release: bool,
+    /// The file containing the 32-byte seed of the Ed25519 key to sign the
+    /// boot FS with.
+    #[structopt(long = "--bootfs-key", parse(from_os_str))]
+    bootfs_key: Option<PathBuf>,
 }
 
 impl Dist {
@@ -49,8 +53,33 @@ impl Dist {
         }
     }
 
+    fn bootfs_key(&self) -> anyhow::Result<Option<[u8; bootfs::SEED_LEN]>> {
+        let path = match &self.bootfs_key {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let seed = fs::read(path)?;
+        let seed = seed
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("the key must be {} bytes long", bootfs::SEED_LEN))?;
+        Ok(Some(seed))
+    }
+
     pub fn build(self) -> Result<(), anyhow::Error> {
         let src_root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        let bootfs_key = self
+            .bootfs_key()
+            .context("failed to read the boot FS signing key")?;
+        if let Some(seed) = &bootfs_key {
+            // TINIT verifies the boot FS with it.
+            let public_key = bootfs::gen::public_key(seed)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>();
+            env::set_var("BOOTFS_PUBLIC_KEY", public_key);
+        } else {
+            env::remove_var("BOOTFS_PUBLIC_KEY");
+        }
         let target_root = env::var("CARGO_TARGET_DIR")
             .unwrap_or_else(|_| src_root.join("target").to_string_lossy().to_string());
 
@@ -105,7 +134,7 @@ impl Dist {
         self.build_bin(src_root, &target_root)
             .context("failed to build binaries or drivers")?;
 
-        crate::gen::gen_bootfs(Path::new(BOOTFS).join("../BOOT.fs"))
+        crate::gen::gen_bootfs(Path::new(BOOTFS).join("../BOOT.fs"), bootfs_key.as_ref())
             .context("failed to generate BOOTFS")?;
 
         match &self.ty {
@@ -252,6 +281,14 @@ impl Dist {
                         name
                     };
                     self.build_impl(&dst_name, &dst_name, ent.path(), &bin_dir, &dst_root)?;
+                    if is_dylib {
+                        // The manifest of the devices that the driver binds to.
+                        let manifest = ent.path().join("manifest");
+                        if manifest.exists() {
+                            let dst_name = Path::new(&dst_name).with_extension("manifest");
+                            fs::copy(manifest, dst_root.join(dst_name))?;
+                        }
+                    }
                     for dep in fs::read_dir(bin_dir.join(self.profile()).join("deps"))?.flatten() {
                         let name = dep.file_name();
                         match name.to_str() {
@@ -33,9 +33,12 @@ pub fn gen_syscall(
     Ok(())
 }
 
-pub fn gen_bootfs(output: impl AsRef<Path>) -> anyhow::Result<()> {
+pub fn gen_bootfs(
+    output: impl AsRef<Path>,
+    key: Option<&[u8; ::bootfs::SEED_LEN]>,
+) -> anyhow::Result<()> {
     let data = bootfs::parse(crate::BOOTFS)?;
     let mut file = BufWriter::new(fs::File::create(output)?);
-    ::bootfs::gen::generate(&data, &mut file)?;
+    ::bootfs::gen::generate(&data, key, &mut file)?;
     Ok(())
 }
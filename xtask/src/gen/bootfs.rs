@@ -7,7 +7,7 @@ fn parse_file(path: impl AsRef<Path>, name: Vec<u8>) -> anyhow::Result<Entry> {
     fs::File::open(path)?.read_to_end(&mut content)?;
     Ok(Entry {
         name,
-        content: Content::File(content),
+        content: Content::CompressedFile(content),
     })
 }
 